@@ -0,0 +1,344 @@
+//! Hot-path parsing benchmarks
+//!
+//! Run with `cargo bench --bench message_processing`. Add
+//! `--no-default-features` to measure the hot path with metrics compiled out,
+//! or `--features simd-json` to run `parse_message` on simd-json.
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use flow_rs_backend::exchanges::timestamp::{fast_parse_rfc3339_millis, parse_rfc3339_millis};
+use flow_rs_backend::exchanges::utils::fast_parse_u64_inner;
+use flow_rs_backend::exchanges::{
+    BinanceConn, BybitConn, CoinbaseConn, Exchange, ExchangeConnector, KrakenConn, KuCoinConn,
+    OkxConn,
+};
+use flow_rs_backend::metrics::MetricsCollector;
+use flow_rs_backend::orderbook::{OrderBook, TopOfBookChange, PRICE_FACTOR};
+use serde::de::IgnoredAny;
+use std::time::Instant;
+
+/// Coinbase and Kraken trade timestamps
+const TIMESTAMPS: &[&str] = &["2019-08-14T20:42:27.265Z", "2023-09-25T07:49:37.708706Z"];
+
+fn bench_timestamps(c: &mut Criterion) {
+    let mut group = c.benchmark_group("timestamp");
+    group.bench_function("chrono_rfc3339", |b| {
+        b.iter(|| {
+            for s in TIMESTAMPS {
+                black_box(
+                    chrono::DateTime::parse_from_rfc3339(black_box(s))
+                        .map(|dt| dt.timestamp_millis())
+                        .ok(),
+                );
+            }
+        })
+    });
+    group.bench_function("fast_path", |b| {
+        b.iter(|| {
+            for s in TIMESTAMPS {
+                black_box(fast_parse_rfc3339_millis(black_box(s)));
+            }
+        })
+    });
+    group.bench_function("with_fallback", |b| {
+        b.iter(|| {
+            for s in TIMESTAMPS {
+                black_box(parse_rfc3339_millis(black_box(s)));
+            }
+        })
+    });
+    group.finish();
+}
+
+/// Bid and ask level changes of one depth update
+type Delta = (Vec<(u64, u64)>, Vec<(u64, u64)>);
+
+/// Delta updates on a full book: stored depth must not slow the hot path down
+fn bench_apply_update_depth(c: &mut Criterion) {
+    const ONE: u64 = PRICE_FACTOR;
+    let mut group = c.benchmark_group("apply_update");
+
+    for depth in [25u64, 100] {
+        let mut book = OrderBook::new("BTCUSDT", "Binance").with_max_depth(depth as usize);
+        book.initialize_from_snapshot(
+            (1..=depth).map(|i| ((10_000 - i) * ONE, ONE)).collect(),
+            (1..=depth).map(|i| ((10_000 + i) * ONE, ONE)).collect(),
+            1,
+        );
+
+        // Resize near the top, pull a mid-book level and put it back: the book
+        // ends each iteration in its initial state
+        let deltas: Vec<Delta> = vec![
+            (vec![(9_999 * ONE, 2 * ONE)], vec![(10_001 * ONE, 2 * ONE)]),
+            (vec![(9_990 * ONE, 0)], vec![(10_010 * ONE, 0)]),
+            (vec![(9_990 * ONE, ONE)], vec![(10_010 * ONE, ONE)]),
+            (vec![(9_999 * ONE, ONE)], vec![(10_001 * ONE, ONE)]),
+        ];
+
+        group.bench_with_input(BenchmarkId::from_parameter(depth), &deltas, |b, deltas| {
+            b.iter_batched(
+                || deltas.clone(),
+                |deltas| {
+                    for (bids, asks) in deltas {
+                        black_box(book.apply_update(bids, asks, 0, 2));
+                    }
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+/// Representative depth (10 levels per side) and trade frames of each connector
+fn sample_messages(exchange: Exchange) -> (String, String) {
+    let levels = |fmt: &dyn Fn(f64, f64) -> String, base: f64, step: f64| -> String {
+        (0..10)
+            .map(|i| fmt(base + step * i as f64, 0.5 + i as f64 * 0.25))
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    let pair = |p: f64, q: f64| format!(r#"["{:.1}","{:.3}"]"#, p, q);
+
+    match exchange {
+        Exchange::Binance => (
+            format!(
+                r#"{{"stream":"btcusdt@depth@100ms","data":{{"e":"depthUpdate","E":1700000000000,"T":1700000000000,"s":"BTCUSDT","U":100,"u":110,"pu":99,"b":[{}],"a":[{}]}}}}"#,
+                levels(&pair, 97000.0, -0.1),
+                levels(&pair, 97000.1, 0.1)
+            ),
+            r#"{"stream":"btcusdt@aggTrade","data":{"e":"aggTrade","E":1700000000000,"a":5933014,"s":"BTCUSDT","p":"97000.1","q":"0.012","f":100,"l":105,"T":1700000000000,"m":true}}"#.to_string(),
+        ),
+        Exchange::Bybit => (
+            format!(
+                r#"{{"topic":"orderbook.50.BTCUSDT","type":"delta","ts":1700000000000,"data":{{"s":"BTCUSDT","b":[{}],"a":[{}],"u":400,"seq":7961638724}},"cts":1700000000000}}"#,
+                levels(&pair, 97000.0, -0.1),
+                levels(&pair, 97000.1, 0.1)
+            ),
+            r#"{"topic":"publicTrade.BTCUSDT","type":"snapshot","ts":1700000000000,"data":[{"T":1700000000000,"s":"BTCUSDT","S":"Buy","v":"0.012","p":"97000.1","L":"PlusTick","i":"20f43950-d8dd-5b31-9112-a178eb6023af","BT":false}]}"#.to_string(),
+        ),
+        Exchange::Coinbase => {
+            let update = |side: &'static str| {
+                move |p: f64, q: f64| {
+                    format!(
+                        r#"{{"side":"{}","event_time":"2024-01-01T00:00:00.000000Z","price_level":"{:.1}","new_quantity":"{:.3}"}}"#,
+                        side, p, q
+                    )
+                }
+            };
+            (
+                format!(
+                    r#"{{"channel":"l2_data","client_id":"","timestamp":"2024-01-01T00:00:00.000000Z","sequence_num":1,"events":[{{"type":"update","product_id":"BTC-USD","updates":[{},{}]}}]}}"#,
+                    levels(&update("bid"), 97000.0, -0.1),
+                    levels(&update("offer"), 97000.1, 0.1)
+                ),
+                r#"{"channel":"market_trades","client_id":"","timestamp":"2024-01-01T00:00:00.000000Z","sequence_num":2,"events":[{"type":"update","trades":[{"trade_id":"123","product_id":"BTC-USD","price":"97000.1","size":"0.012","side":"BUY","time":"2024-01-01T00:00:00.123456Z"}]}]}"#.to_string(),
+            )
+        }
+        Exchange::Kraken => {
+            let level = |p: f64, q: f64| format!(r#"{{"price":{:.1},"qty":{:.3}}}"#, p, q);
+            (
+                format!(
+                    r#"{{"channel":"book","type":"update","data":[{{"symbol":"BTC/USD","bids":[{}],"asks":[{}],"checksum":2845854188,"timestamp":"2024-01-01T00:00:00.000000Z"}}]}}"#,
+                    levels(&level, 97000.0, -0.1),
+                    levels(&level, 97000.1, 0.1)
+                ),
+                r#"{"channel":"trade","type":"update","data":[{"symbol":"BTC/USD","side":"buy","price":97000.1,"qty":0.012,"ord_type":"market","trade_id":4665906,"timestamp":"2024-01-01T00:00:00.123456Z"}]}"#.to_string(),
+            )
+        }
+        Exchange::Okx => {
+            let level = |p: f64, q: f64| format!(r#"["{:.1}","{:.3}","0","3"]"#, p, q);
+            (
+                format!(
+                    r#"{{"arg":{{"channel":"books","instId":"BTC-USDT"}},"action":"update","data":[{{"asks":[{}],"bids":[{}],"ts":"1700000000000","checksum":-855196043,"prevSeqId":123456,"seqId":123457}}]}}"#,
+                    levels(&level, 97000.1, 0.1),
+                    levels(&level, 97000.0, -0.1)
+                ),
+                r#"{"arg":{"channel":"trades","instId":"BTC-USDT"},"data":[{"instId":"BTC-USDT","tradeId":"130639474","px":"97000.1","sz":"0.012","side":"buy","ts":"1700000000000","count":"1"}]}"#.to_string(),
+            )
+        }
+        Exchange::KuCoin => {
+            let level = |p: f64, q: f64| format!(r#"["{:.1}","{:.3}","1000"]"#, p, q);
+            (
+                format!(
+                    r#"{{"type":"message","topic":"/market/level2:BTC-USDT","subject":"trade.l2update","data":{{"changes":{{"asks":[{}],"bids":[{}]}},"sequenceEnd":123457,"sequenceStart":123456,"symbol":"BTC-USDT","time":1700000000000}}}}"#,
+                    levels(&level, 97000.1, 0.1),
+                    levels(&level, 97000.0, -0.1)
+                ),
+                r#"{"type":"message","topic":"/market/match:BTC-USDT","subject":"trade.l3match","data":{"makerOrderId":"a","price":"97000.1","sequence":"123458","side":"buy","size":"0.012","symbol":"BTC-USDT","takerOrderId":"b","time":"1700000000000000000","tradeId":"c","type":"match"}}"#.to_string(),
+            )
+        }
+        Exchange::FlowRs | Exchange::Replay => unreachable!("not an exchange feed"),
+    }
+}
+
+/// `parse_message` of every connector on the same depth and trade content
+fn bench_parse_message(c: &mut Criterion) {
+    let symbols = vec!["BTCUSDT".to_string()];
+    let connectors = [
+        ExchangeConnector::Binance(BinanceConn::new(symbols.clone())),
+        ExchangeConnector::Bybit(BybitConn::new(symbols.clone())),
+        ExchangeConnector::Coinbase(CoinbaseConn::new(symbols.clone())),
+        ExchangeConnector::Kraken(KrakenConn::new(symbols.clone())),
+        ExchangeConnector::Okx(OkxConn::new(symbols.clone())),
+        ExchangeConnector::KuCoin(KuCoinConn::new(symbols)),
+    ];
+    let mut group = c.benchmark_group("parse_message");
+
+    for connector in &connectors {
+        let exchange = connector.exchange();
+        let (depth, trade) = sample_messages(exchange);
+        for (kind, raw) in [("depth", depth), ("trade", trade)] {
+            // A sample the connector rejects would only benchmark the error path
+            assert!(
+                matches!(connector.parse_message(&raw), Ok(Some(_))),
+                "{} {} sample does not parse",
+                exchange.name(),
+                kind
+            );
+            group.bench_with_input(BenchmarkId::new(exchange.name(), kind), &raw, |b, raw| {
+                b.iter(|| black_box(connector.parse_message(black_box(raw))))
+            });
+        }
+    }
+    group.finish();
+}
+
+/// serde_json vs simd-json decoding the depth and trade samples of each connector
+///
+/// Decodes into `IgnoredAny` to time the JSON layer alone; simd-json's time
+/// includes copying the frame into the mutable buffer it parses in place, as
+/// `exchanges::json` does.
+fn bench_json_decoders(c: &mut Criterion) {
+    let mut group = c.benchmark_group("json_decode");
+    for exchange in [
+        Exchange::Binance,
+        Exchange::Bybit,
+        Exchange::Coinbase,
+        Exchange::Kraken,
+        Exchange::Okx,
+        Exchange::KuCoin,
+    ] {
+        let (depth, trade) = sample_messages(exchange);
+        for (kind, raw) in [("depth", depth), ("trade", trade)] {
+            let id = format!("{}/{}", exchange.name(), kind);
+            group.bench_with_input(BenchmarkId::new("serde_json", &id), &raw, |b, raw| {
+                b.iter(|| black_box(serde_json::from_str::<IgnoredAny>(black_box(raw)).unwrap()))
+            });
+            let mut buf = Vec::with_capacity(raw.len());
+            group.bench_with_input(BenchmarkId::new("simd_json", &id), &raw, |b, raw| {
+                b.iter(|| {
+                    buf.clear();
+                    buf.extend_from_slice(black_box(raw).as_bytes());
+                    black_box(simd_json::serde::from_slice::<IgnoredAny>(&mut buf).unwrap())
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+/// Symbol, bids, asks and update id of a depth frame
+type ParsedDepth = (String, Vec<(u64, u64)>, Vec<(u64, u64)>, u64);
+/// Symbol, price, quantity, buyer-initiated and time of a trade
+type ParsedTrade = (String, u64, u64, bool, i64);
+
+/// Bybit depth frame walked through an untyped `serde_json::Value`, the way the
+/// connector used to parse it: the baseline for the typed structs
+fn bybit_depth_via_value(raw: &str) -> Option<ParsedDepth> {
+    let msg: serde_json::Value = serde_json::from_str(raw).ok()?;
+    let symbol = msg["topic"].as_str()?.split('.').nth(2)?.to_string();
+    let levels = |side: &serde_json::Value| -> Vec<(u64, u64)> {
+        side.as_array()
+            .map(|levels| {
+                levels
+                    .iter()
+                    .filter_map(|level| {
+                        let price = fast_parse_u64_inner(level[0].as_str()?)?;
+                        let qty = fast_parse_u64_inner(level[1].as_str()?)?;
+                        Some((price, qty))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let data = &msg["data"];
+    Some((
+        symbol,
+        levels(&data["b"]),
+        levels(&data["a"]),
+        data["u"].as_u64().unwrap_or(0),
+    ))
+}
+
+/// Bybit trade frame through `serde_json::Value` (see `bybit_depth_via_value`)
+fn bybit_trades_via_value(raw: &str) -> Option<Vec<ParsedTrade>> {
+    let msg: serde_json::Value = serde_json::from_str(raw).ok()?;
+    let symbol = msg["topic"].as_str()?.split('.').nth(1)?;
+    let trades = msg["data"]
+        .as_array()?
+        .iter()
+        .filter_map(|trade| {
+            let price = fast_parse_u64_inner(trade["p"].as_str()?)?;
+            let qty = fast_parse_u64_inner(trade["v"].as_str()?)?;
+            let is_buy = match trade["S"].as_str()? {
+                "Buy" => true,
+                "Sell" => false,
+                _ => return None,
+            };
+            let time = trade["T"].as_i64().unwrap_or(0);
+            Some((symbol.to_string(), price, qty, is_buy, time))
+        })
+        .collect();
+    Some(trades)
+}
+
+/// Untyped `Value` walk vs the borrowed typed structs on the same Bybit frames
+fn bench_bybit_value_vs_typed(c: &mut Criterion) {
+    let connector = ExchangeConnector::Bybit(BybitConn::new(vec!["BTCUSDT".to_string()]));
+    let (depth, trade) = sample_messages(Exchange::Bybit);
+    assert!(bybit_depth_via_value(&depth)
+        .is_some_and(|(_, bids, asks, _)| bids.len() == 10 && asks.len() == 10));
+    assert!(bybit_trades_via_value(&trade).is_some_and(|trades| trades.len() == 1));
+
+    let mut group = c.benchmark_group("bybit_parse");
+    group.bench_function("depth/value", |b| {
+        b.iter(|| black_box(bybit_depth_via_value(black_box(&depth))))
+    });
+    group.bench_function("depth/typed", |b| {
+        b.iter(|| black_box(connector.parse_message(black_box(&depth))))
+    });
+    group.bench_function("trade/value", |b| {
+        b.iter(|| black_box(bybit_trades_via_value(black_box(&trade))))
+    });
+    group.bench_function("trade/typed", |b| {
+        b.iter(|| black_box(connector.parse_message(black_box(&trade))))
+    });
+    group.finish();
+}
+
+/// Metrics recorded per depth message by the exchange read loop
+fn bench_metrics_recording(c: &mut Criterion) {
+    let metrics = MetricsCollector::new();
+    // Taken once: the read loop needs the receive time regardless of metrics
+    let start = Instant::now();
+    c.bench_function("metrics/depth_message", |b| {
+        b.iter(|| {
+            metrics.record_bytes(Exchange::Binance, black_box(512));
+            metrics.record_depth();
+            metrics.record_top_of_book_change(black_box("BTCUSDT"), TopOfBookChange::SizeOnly);
+            metrics.record_latency(start);
+            metrics.record_messages(1);
+        })
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_timestamps,
+    bench_apply_update_depth,
+    bench_parse_message,
+    bench_bybit_value_vs_typed,
+    bench_json_decoders,
+    bench_metrics_recording
+);
+criterion_main!(benches);
@@ -0,0 +1,180 @@
+//! Cross-exchange arbitrage scanner
+//!
+//! Per symbol, pairs the highest bid with the lowest ask across fresh books and
+//! flags the pair when buying on one venue and selling on the other would earn
+//! more than the threshold (before fees).
+
+use crate::orderbook::OrderBookManager;
+use crate::types::{ClientMessage, BOOK_STALE_AFTER_SECS};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Default bid-over-ask spread, relative to the ask, that counts as an opportunity
+pub const DEFAULT_ARBITRAGE_BPS: f64 = 10.0;
+
+/// (buy exchange, sell exchange) of an open opportunity
+type VenuePair = (String, String);
+
+/// Flags symbols whose best bid on one exchange exceeds the best ask on another
+#[derive(Debug)]
+pub struct ArbitrageScanner {
+    threshold_bps: f64,
+    /// symbol -> pair last reported, while it stays above the threshold
+    open: HashMap<String, VenuePair>,
+}
+
+impl ArbitrageScanner {
+    pub fn new(threshold_bps: f64) -> Self {
+        Self {
+            threshold_bps,
+            open: HashMap::new(),
+        }
+    }
+
+    /// Compare the tops of all fresh books and return newly opened opportunities
+    ///
+    /// A pair is reported once while it stays above the threshold; it is
+    /// reported again if it closes and reopens, or if another pair takes over.
+    pub fn scan(&mut self, orderbook_manager: &OrderBookManager) -> Vec<ClientMessage> {
+        let max_age = Duration::from_secs(BOOK_STALE_AFTER_SECS);
+
+        // symbol -> (best bid, its exchange), (best ask, its exchange)
+        type Best = Option<(Decimal, String)>;
+        let mut tops: HashMap<String, (Best, Best)> = HashMap::new();
+        for entry in orderbook_manager.iter() {
+            let book = entry.value();
+            if !book.is_fresh(max_age) {
+                continue;
+            }
+            let (best_bid, best_ask) = tops.entry(book.symbol().to_string()).or_default();
+            if let Some(bid) = book.best_bid() {
+                if best_bid.as_ref().is_none_or(|(best, _)| bid > *best) {
+                    *best_bid = Some((bid, book.exchange().to_string()));
+                }
+            }
+            if let Some(ask) = book.best_ask() {
+                if best_ask.as_ref().is_none_or(|(best, _)| ask < *best) {
+                    *best_ask = Some((ask, book.exchange().to_string()));
+                }
+            }
+        }
+
+        let mut opportunities = Vec::new();
+        let mut still_open = HashMap::new();
+
+        for (symbol, tops) in tops {
+            let (Some((bid, sell_exchange)), Some((ask, buy_exchange))) = tops else {
+                continue;
+            };
+            if buy_exchange == sell_exchange || ask.is_zero() {
+                continue;
+            }
+            let spread = bid - ask;
+            let spread_bps = (spread / ask * Decimal::from(10_000))
+                .to_f64()
+                .unwrap_or_default();
+            if spread_bps <= self.threshold_bps {
+                continue;
+            }
+
+            let pair = (buy_exchange, sell_exchange);
+            if self.open.get(&symbol) != Some(&pair) {
+                tracing::info!(
+                    "{} arbitrage: buy on {} at {}, sell on {} at {} ({:.1} bps)",
+                    symbol,
+                    pair.0,
+                    ask,
+                    pair.1,
+                    bid,
+                    spread_bps
+                );
+                opportunities.push(ClientMessage::Arbitrage {
+                    symbol: symbol.clone(),
+                    buy_exchange: pair.0.clone(),
+                    sell_exchange: pair.1.clone(),
+                    spread,
+                    spread_percent: (spread / ask * Decimal::ONE_HUNDRED).round_dp(4),
+                });
+            }
+            still_open.insert(symbol, pair);
+        }
+
+        // Anything not crossed this round (closed or stale) starts over
+        self.open = still_open;
+        opportunities
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook::PRICE_FACTOR;
+    use rust_decimal_macros::dec;
+
+    const ONE: u64 = PRICE_FACTOR;
+
+    fn set_top(manager: &OrderBookManager, exchange: &str, bid: u64, ask: u64) {
+        manager
+            .get_or_create(exchange, "BTCUSDT")
+            .initialize_from_snapshot(vec![(bid * ONE, ONE)], vec![(ask * ONE, ONE)], 1);
+    }
+
+    #[test]
+    fn test_crossed_books_reported_once_per_opportunity() {
+        let manager = OrderBookManager::with_streams(&[], &[]);
+        // Binance bids above Kraken's ask: buy on Kraken at 100, sell on Binance at 101
+        set_top(&manager, "Binance", 101, 102);
+        set_top(&manager, "Kraken", 99, 100);
+        // Not initialized: its top would cross both, but it is skipped
+        manager.get_or_create("Bybit", "BTCUSDT").apply_update(
+            vec![(200 * ONE, ONE)],
+            vec![(50 * ONE, ONE)],
+            0,
+            1,
+        );
+
+        let mut scanner = ArbitrageScanner::new(DEFAULT_ARBITRAGE_BPS);
+        let opportunities = scanner.scan(&manager);
+        assert_eq!(opportunities.len(), 1);
+        match &opportunities[0] {
+            ClientMessage::Arbitrage {
+                symbol,
+                buy_exchange,
+                sell_exchange,
+                spread,
+                spread_percent,
+            } => {
+                assert_eq!(symbol, "BTCUSDT");
+                assert_eq!(
+                    (buy_exchange.as_str(), sell_exchange.as_str()),
+                    ("Kraken", "Binance")
+                );
+                assert_eq!(*spread, dec!(1));
+                assert_eq!(*spread_percent, dec!(1));
+            }
+            other => panic!("expected Arbitrage, got {:?}", other),
+        }
+
+        // Still open: not reported again
+        assert!(scanner.scan(&manager).is_empty());
+
+        // Closed, then reopened
+        set_top(&manager, "Kraken", 99, 101);
+        assert!(scanner.scan(&manager).is_empty());
+        set_top(&manager, "Kraken", 99, 100);
+        assert_eq!(scanner.scan(&manager).len(), 1);
+    }
+
+    #[test]
+    fn test_spread_below_threshold_ignored() {
+        let manager = OrderBookManager::with_streams(&[], &[]);
+        // 1% crossed, under a 2% threshold
+        set_top(&manager, "Binance", 101, 102);
+        set_top(&manager, "Kraken", 99, 100);
+
+        let mut scanner = ArbitrageScanner::new(200.0);
+        assert!(scanner.scan(&manager).is_empty());
+    }
+}
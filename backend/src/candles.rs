@@ -0,0 +1,388 @@
+//! Rolling OHLCV candle aggregation from the trade tape
+//!
+//! Each (exchange, symbol, interval) keeps a bounded history of closed candles
+//! for client backfill, and so does the consolidated series merging the
+//! symbol's trades from every exchange (`CONSOLIDATED_EXCHANGE`). A candle
+//! closes when the first trade of a later interval arrives; intervals without
+//! any trade get no candle, or a flat one at the previous close when gap
+//! filling is on.
+
+use crate::types::{Candle, CandleInterval, Trade, CONSOLIDATED_EXCHANGE};
+use dashmap::DashMap;
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+/// Closed candles retained per (exchange, symbol, interval)
+pub const CANDLE_HISTORY_LEN: usize = 500;
+
+/// Open candle plus the closed history of one (exchange, symbol, interval)
+#[derive(Debug, Default)]
+struct CandleSeries {
+    current: Option<Candle>,
+    closed: VecDeque<Candle>,
+}
+
+impl CandleSeries {
+    fn push_closed(&mut self, candle: Candle, history_len: usize) {
+        if self.closed.len() == history_len {
+            self.closed.pop_front();
+        }
+        self.closed.push_back(candle);
+    }
+}
+
+impl Candle {
+    /// Fold a trade of this interval into the high, low and volume
+    fn add_trade(&mut self, trade: &Trade) {
+        self.high = self.high.max(trade.price);
+        self.low = self.low.min(trade.price);
+        self.volume += trade.quantity;
+        self.trade_count += 1;
+    }
+}
+
+/// Series key: exchange (or `CONSOLIDATED_EXCHANGE`), symbol, interval
+type SeriesKey = (String, String, CandleInterval);
+
+/// Candle aggregator for every exchange, tracked symbol and interval
+#[derive(Debug)]
+pub struct CandleStore {
+    series: DashMap<SeriesKey, CandleSeries>,
+    history_len: usize,
+    fill_gaps: bool,
+}
+
+impl CandleStore {
+    pub fn new(history_len: usize) -> Self {
+        Self {
+            series: DashMap::new(),
+            history_len,
+            fill_gaps: false,
+        }
+    }
+
+    /// Close intervals without trades as flat candles (zero volume) instead of skipping them
+    pub fn with_fill_gaps(mut self, fill_gaps: bool) -> Self {
+        self.fill_gaps = fill_gaps;
+        self
+    }
+
+    /// Fold a trade into every interval of its exchange's series and of the
+    /// consolidated one, returning the candles it closed
+    ///
+    /// Late trades (older than the open candle) go to the closed candle of
+    /// their interval, in the history clients backfill from, without moving
+    /// its close; one with no such candle left is dropped. With gap filling, the flat
+    /// candles of skipped intervals follow the one that closed (at most
+    /// `history_len` of them).
+    pub fn record_trade(&self, trade: &Trade) -> Vec<Candle> {
+        let mut closed = Vec::new();
+
+        for exchange in [trade.exchange.as_str(), CONSOLIDATED_EXCHANGE] {
+            for interval in CandleInterval::ALL {
+                self.record_in_series(exchange, interval, trade, &mut closed);
+            }
+        }
+
+        closed
+    }
+
+    /// Fold a trade into one series, pushing the candles it closed to `closed`
+    fn record_in_series(
+        &self,
+        exchange: &str,
+        interval: CandleInterval,
+        trade: &Trade,
+        closed: &mut Vec<Candle>,
+    ) {
+        let open_time = trade.timestamp - trade.timestamp.rem_euclid(interval.millis());
+        let mut series = self
+            .series
+            .entry((exchange.to_string(), trade.symbol.clone(), interval))
+            .or_default();
+
+        match series.current.as_mut() {
+            Some(candle) if open_time == candle.open_time => {
+                candle.add_trade(trade);
+                candle.close = trade.price;
+            }
+            Some(candle) if open_time < candle.open_time => {
+                if let Some(candle) = series
+                    .closed
+                    .iter_mut()
+                    .rev()
+                    .find(|c| c.open_time == open_time)
+                {
+                    candle.add_trade(trade);
+                }
+            }
+            _ => {
+                let candle = Candle {
+                    exchange: exchange.to_string(),
+                    symbol: trade.symbol.clone(),
+                    interval,
+                    open_time,
+                    open: trade.price,
+                    high: trade.price,
+                    low: trade.price,
+                    close: trade.price,
+                    volume: trade.quantity,
+                    trade_count: 1,
+                };
+                if let Some(finished) = series.current.replace(candle) {
+                    let gap = if self.fill_gaps {
+                        self.flat_candles(&finished, open_time)
+                    } else {
+                        Vec::new()
+                    };
+                    for candle in std::iter::once(finished).chain(gap) {
+                        series.push_closed(candle.clone(), self.history_len);
+                        closed.push(candle);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Flat candles at `finished`'s close for the intervals between it and `next_open`
+    fn flat_candles(&self, finished: &Candle, next_open: i64) -> Vec<Candle> {
+        let step = finished.interval.millis();
+        // Older ones would be evicted from the history right away
+        let first = (finished.open_time + step).max(next_open - self.history_len as i64 * step);
+        (first..next_open)
+            .step_by(step as usize)
+            .map(|open_time| Candle {
+                open_time,
+                open: finished.close,
+                high: finished.close,
+                low: finished.close,
+                volume: 0,
+                trade_count: 0,
+                ..finished.clone()
+            })
+            .collect()
+    }
+
+    /// Last `count` closed candles of a series, oldest first
+    pub fn history(
+        &self,
+        exchange: &str,
+        symbol: &str,
+        interval: CandleInterval,
+        count: usize,
+    ) -> Vec<Candle> {
+        self.series
+            .get(&(exchange.to_string(), symbol.to_string(), interval))
+            .map(|series| {
+                let skip = series.closed.len().saturating_sub(count);
+                series.closed.iter().skip(skip).cloned().collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl Default for CandleStore {
+    fn default() -> Self {
+        Self::new(CANDLE_HISTORY_LEN)
+    }
+}
+
+/// Shared candle aggregator
+pub type SharedCandleStore = Arc<CandleStore>;
+
+pub fn create_shared_candle_store(fill_gaps: bool) -> SharedCandleStore {
+    Arc::new(CandleStore::default().with_fill_gaps(fill_gaps))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::TradeSide;
+
+    const MINUTE: i64 = 60_000;
+
+    fn trade(timestamp: i64, price: u64, quantity: u64) -> Trade {
+        trade_on("Binance", timestamp, price, quantity)
+    }
+
+    fn trade_on(exchange: &str, timestamp: i64, price: u64, quantity: u64) -> Trade {
+        Trade {
+            exchange: exchange.to_string(),
+            symbol: "BTCUSDT".to_string(),
+            price,
+            quantity,
+            notional: Trade::notional_of(price, quantity),
+            side: TradeSide::Buy,
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn test_trades_roll_into_ohlcv_candles() {
+        let store = CandleStore::new(10);
+        store.record_trade(&trade(10 * MINUTE, 100, 1));
+        store.record_trade(&trade(10 * MINUTE + 1_000, 105, 2));
+        store.record_trade(&trade(10 * MINUTE + 2_000, 95, 3));
+        store.record_trade(&trade(10 * MINUTE + 3_000, 101, 4));
+
+        // Next minute closes the 1s and 1m candles but not the 5m one, in the
+        // exchange's series then the consolidated one
+        let closed = store.record_trade(&trade(11 * MINUTE, 200, 1));
+        let intervals: Vec<(&str, CandleInterval)> = closed
+            .iter()
+            .map(|c| (c.exchange.as_str(), c.interval))
+            .collect();
+        assert_eq!(
+            intervals,
+            vec![
+                ("Binance", CandleInterval::S1),
+                ("Binance", CandleInterval::M1),
+                (CONSOLIDATED_EXCHANGE, CandleInterval::S1),
+                (CONSOLIDATED_EXCHANGE, CandleInterval::M1)
+            ]
+        );
+        let candle = &closed[1];
+        assert_eq!(candle.open_time, 10 * MINUTE);
+        assert_eq!(
+            (candle.open, candle.high, candle.low, candle.close),
+            (100, 105, 95, 101)
+        );
+        assert_eq!((candle.volume, candle.trade_count), (10, 4));
+
+        assert_eq!(
+            store
+                .history("Binance", "BTCUSDT", CandleInterval::M1, 100)
+                .len(),
+            1
+        );
+        assert!(store
+            .history("Binance", "BTCUSDT", CandleInterval::M5, 100)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_quiet_seconds_skipped_or_filled_flat() {
+        let series = |store: &CandleStore| {
+            store.record_trade(&trade(1_000, 100, 1));
+            store.record_trade(&trade(1_500, 102, 1));
+            store.record_trade(&trade(4_200, 99, 1))
+        };
+
+        // No trade during seconds 2 and 3: no candle for them
+        let closed = series(&CandleStore::new(10));
+        let closed: Vec<&Candle> = closed
+            .iter()
+            .filter(|c| c.exchange == "Binance" && c.interval == CandleInterval::S1)
+            .collect();
+        assert_eq!(closed.len(), 1);
+        assert_eq!((closed[0].open_time, closed[0].close), (1_000, 102));
+
+        let store = CandleStore::new(10).with_fill_gaps(true);
+        let closed = series(&store);
+        let flat: Vec<(i64, u64, u64, u64)> = closed
+            .iter()
+            .filter(|c| c.exchange == "Binance" && c.interval == CandleInterval::S1)
+            .map(|c| (c.open_time, c.open, c.low, c.volume))
+            .collect();
+        assert_eq!(
+            flat,
+            vec![
+                (1_000, 100, 100, 2),
+                (2_000, 102, 102, 0),
+                (3_000, 102, 102, 0)
+            ]
+        );
+        assert_eq!(
+            store
+                .history("Binance", "BTCUSDT", CandleInterval::S1, 10)
+                .len(),
+            3
+        );
+
+        // A long silence fills at most the retained history
+        let closed = store.record_trade(&trade(60_000, 99, 1));
+        let filled = closed
+            .iter()
+            .filter(|c| c.exchange == "Binance" && c.interval == CandleInterval::S1)
+            .count();
+        assert_eq!(filled, 11);
+        let history = store.history("Binance", "BTCUSDT", CandleInterval::S1, 100);
+        assert_eq!(history.len(), 10);
+        assert_eq!(history.last().unwrap().open_time, 59_000);
+    }
+
+    #[test]
+    fn test_late_trade_after_rollover_goes_to_its_closed_candle() {
+        let store = CandleStore::new(10);
+        store.record_trade(&trade(10 * MINUTE, 100, 1));
+        store.record_trade(&trade(11 * MINUTE, 200, 1));
+
+        // Delayed by the feed: belongs to the minute that already closed
+        assert!(store
+            .record_trade(&trade(10 * MINUTE + 30_000, 50, 2))
+            .is_empty());
+        let closed = store.history("Binance", "BTCUSDT", CandleInterval::M1, 10);
+        assert_eq!(
+            (closed[0].open, closed[0].low, closed[0].close),
+            (100, 50, 100)
+        );
+        assert_eq!((closed[0].volume, closed[0].trade_count), (3, 2));
+
+        // The open candle is untouched
+        let closed = store.record_trade(&trade(12 * MINUTE, 300, 1));
+        let current = closed
+            .iter()
+            .find(|c| c.interval == CandleInterval::M1)
+            .unwrap();
+        assert_eq!(current.open_time, 11 * MINUTE);
+        assert_eq!((current.low, current.volume), (200, 1));
+
+        // Older than any candle kept: dropped
+        store.record_trade(&trade(MINUTE, 1, 1));
+        let history = store.history("Binance", "BTCUSDT", CandleInterval::M1, 10);
+        assert_eq!(history.len(), 2);
+        assert!(history.iter().all(|c| c.low >= 50));
+    }
+
+    #[test]
+    fn test_history_is_bounded_and_returns_latest() {
+        let store = CandleStore::new(3);
+        for minute in 0..6 {
+            store.record_trade(&trade(minute * MINUTE, 100 + minute as u64, 1));
+        }
+
+        // 5 closed candles, only the last 3 retained
+        let history = store.history("Binance", "BTCUSDT", CandleInterval::M1, 100);
+        let open_times: Vec<i64> = history.iter().map(|c| c.open_time).collect();
+        assert_eq!(open_times, vec![2 * MINUTE, 3 * MINUTE, 4 * MINUTE]);
+
+        let latest = store.history("Binance", "BTCUSDT", CandleInterval::M1, 1);
+        assert_eq!(latest[0].open_time, 4 * MINUTE);
+        assert!(store
+            .history("Binance", "ETHUSDT", CandleInterval::M1, 10)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_exchanges_get_their_own_series_and_a_consolidated_one() {
+        let store = CandleStore::new(10);
+        store.record_trade(&trade_on("Binance", 0, 100, 1));
+        store.record_trade(&trade_on("Kraken", 1_000, 90, 2));
+        store.record_trade(&trade_on("Binance", MINUTE, 100, 1));
+        store.record_trade(&trade_on("Kraken", MINUTE, 90, 1));
+
+        let ohlcv = |exchange: &str| {
+            let history = store.history(exchange, "BTCUSDT", CandleInterval::M1, 10);
+            assert_eq!(history.len(), 1, "{}", exchange);
+            let c = &history[0];
+            assert_eq!(c.exchange, exchange);
+            (c.open, c.high, c.low, c.close, c.volume)
+        };
+        assert_eq!(ohlcv("Binance"), (100, 100, 100, 100, 1));
+        assert_eq!(ohlcv("Kraken"), (90, 90, 90, 90, 2));
+        assert_eq!(ohlcv(CONSOLIDATED_EXCHANGE), (100, 100, 90, 90, 3));
+        assert!(store
+            .history("OKX", "BTCUSDT", CandleInterval::M1, 10)
+            .is_empty());
+    }
+}
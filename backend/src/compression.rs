@@ -0,0 +1,92 @@
+//! Per-message compression policy for client connections
+//!
+//! permessage-deflate is negotiated per connection, but compressing tiny frames
+//! (a single trade) costs CPU and can even grow them. The policy decides per
+//! message so only payloads above a size threshold are compressed.
+//!
+//! tungstenite 0.28 has no permessage-deflate support yet, so the server does
+//! not consult this policy until a compressing transport is available.
+//! Hand-building RSV1 frames is not a way around it: once the extension is
+//! negotiated, browsers compress what they send too, and tungstenite's reader
+//! rejects any incoming frame with a reserved bit set.
+
+/// Messages smaller than this are sent uncompressed by default
+pub const DEFAULT_COMPRESS_MIN_BYTES: usize = 512;
+
+/// Decides which outgoing messages are worth compressing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionPolicy {
+    /// False when the client did not negotiate compression
+    pub enabled: bool,
+    /// Payloads below this size go out raw even when compression is enabled
+    pub min_bytes: usize,
+}
+
+impl CompressionPolicy {
+    pub fn new(enabled: bool, min_bytes: usize) -> Self {
+        Self { enabled, min_bytes }
+    }
+
+    /// Policy for connections without compression
+    pub fn disabled() -> Self {
+        Self::new(false, DEFAULT_COMPRESS_MIN_BYTES)
+    }
+
+    /// True if a payload of this size should be compressed
+    #[inline]
+    pub fn should_compress(&self, payload_len: usize) -> bool {
+        self.enabled && payload_len >= self.min_bytes
+    }
+}
+
+impl Default for CompressionPolicy {
+    fn default() -> Self {
+        Self::new(true, DEFAULT_COMPRESS_MIN_BYTES)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook::{OrderBook, PRICE_FACTOR};
+    use crate::types::{ClientMessage, Trade, TradeSide, ORDERBOOK_DISPLAY_DEPTH};
+
+    const ONE: u64 = PRICE_FACTOR;
+
+    fn json_len(msg: &ClientMessage) -> usize {
+        serde_json::to_string(msg).unwrap().len()
+    }
+
+    #[test]
+    fn test_book_batch_compressed_trade_raw() {
+        let policy = CompressionPolicy::default();
+
+        let trade = ClientMessage::Trade(Trade {
+            exchange: "Binance".to_string(),
+            symbol: "BTCUSDT".to_string(),
+            price: 100_000 * ONE,
+            quantity: ONE / 10,
+            notional: Trade::notional_of(100_000 * ONE, ONE / 10),
+            side: TradeSide::Buy,
+            timestamp: 1_700_000_000_000,
+        });
+
+        let updates = ["BTCUSDT", "ETHUSDT", "SOLUSDT"]
+            .iter()
+            .map(|symbol| {
+                let mut book = OrderBook::new(symbol, "Binance");
+                book.initialize_from_snapshot(
+                    (1..=5).map(|i| ((100 - i) * ONE, ONE)).collect(),
+                    (1..=5).map(|i| ((100 + i) * ONE, ONE)).collect(),
+                    1,
+                );
+                book.to_book_update(ORDERBOOK_DISPLAY_DEPTH)
+            })
+            .collect();
+        let batch = ClientMessage::BookBatch(updates);
+
+        assert!(!policy.should_compress(json_len(&trade)));
+        assert!(policy.should_compress(json_len(&batch)));
+        assert!(!CompressionPolicy::disabled().should_compress(json_len(&batch)));
+    }
+}
@@ -1,44 +1,164 @@
+use super::json;
+use super::symbols::{Channel, ChannelFilter};
 use super::utils::{fast_parse_u64, fast_parse_u64_inner};
-/// Binance Futures exchange connector
-use super::{DepthSnapshot, Exchange, MarketMessage};
+/// Binance Spot / USDT-M Futures exchange connector
+use super::{DepthSnapshot, Exchange, MarketMessage, NetworkTimeouts};
 use crate::types::{Trade, TradeSide};
 use serde::Deserialize;
 use std::error::Error;
 
+/// Depth limits accepted by the futures REST depth endpoint
+const DEPTH_LIMITS: [usize; 7] = [5, 10, 20, 50, 100, 500, 1000];
+/// Largest limit the spot REST depth endpoint accepts (any value up to it works)
+const SPOT_MAX_DEPTH_LIMIT: usize = 5000;
+
+/// Binance market the connector streams
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BinanceMarket {
+    Spot,
+    /// USDT-margined perpetual futures
+    #[default]
+    UsdFutures,
+}
+
+impl BinanceMarket {
+    /// "spot" or "futures" (also "usd_futures", "usdm"), case-insensitive
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "spot" => Some(BinanceMarket::Spot),
+            "futures" | "usd_futures" | "usdm" => Some(BinanceMarket::UsdFutures),
+            _ => None,
+        }
+    }
+
+    fn ws_url(self) -> &'static str {
+        match self {
+            BinanceMarket::Spot => "wss://stream.binance.com:9443",
+            BinanceMarket::UsdFutures => "wss://fstream.binance.com",
+        }
+    }
+
+    fn rest_url(self) -> &'static str {
+        match self {
+            BinanceMarket::Spot => "https://api.binance.com",
+            BinanceMarket::UsdFutures => "https://fapi.binance.com",
+        }
+    }
+
+    fn depth_path(self) -> &'static str {
+        match self {
+            BinanceMarket::Spot => "/api/v3/depth",
+            BinanceMarket::UsdFutures => "/fapi/v1/depth",
+        }
+    }
+
+    /// Closest limit the depth endpoint accepts, rounding up
+    fn depth_limit(self, limit: usize) -> usize {
+        match self {
+            BinanceMarket::Spot => limit.clamp(1, SPOT_MAX_DEPTH_LIMIT),
+            BinanceMarket::UsdFutures => DEPTH_LIMITS
+                .iter()
+                .copied()
+                .find(|&l| l >= limit)
+                .unwrap_or(DEPTH_LIMITS[DEPTH_LIMITS.len() - 1]),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct BinanceConnector {
     symbols: Vec<String>,
+    channels: ChannelFilter,
+    market: BinanceMarket,
+    /// REST client for depth snapshots (cheap to clone, shares its pool)
+    http: reqwest::Client,
+    rest_url: String,
 }
 
 impl BinanceConnector {
+    /// USDT-M Futures connector; `with_market` switches to spot
     pub fn new(symbols: Vec<String>) -> Self {
-        Self { symbols }
+        let market = BinanceMarket::default();
+        Self {
+            symbols,
+            channels: ChannelFilter::default(),
+            market,
+            http: Self::http_client(NetworkTimeouts::default()),
+            rest_url: market.rest_url().to_string(),
+        }
+    }
+
+    /// Stream `market` instead (WebSocket host, REST host and depth endpoint)
+    pub fn with_market(mut self, market: BinanceMarket) -> Self {
+        self.market = market;
+        self.rest_url = market.rest_url().to_string();
+        self
+    }
+
+    /// Fetch snapshots from another REST host (tests serve them locally)
+    #[cfg(test)]
+    pub fn with_rest_url(mut self, rest_url: &str) -> Self {
+        self.rest_url = rest_url.to_string();
+        self
+    }
+
+    fn http_client(timeouts: NetworkTimeouts) -> reqwest::Client {
+        reqwest::Client::builder()
+            .connect_timeout(timeouts.connect)
+            .read_timeout(timeouts.read)
+            .build()
+            .unwrap_or_else(|e| {
+                tracing::warn!("[Binance] HTTP client without timeouts: {}", e);
+                reqwest::Client::new()
+            })
+    }
+
+    pub fn set_timeouts(&mut self, timeouts: NetworkTimeouts) {
+        self.http = Self::http_client(timeouts);
+    }
+
+    /// Subscribe only the channels `filter` enables per symbol
+    pub fn set_channel_filter(&mut self, filter: ChannelFilter) {
+        self.channels = filter;
+    }
+
+    /// Native symbol for a channel (stream names are lowercase on both channels)
+    pub fn exchange_symbol(&self, symbol: &str, _channel: Channel) -> String {
+        symbol.to_lowercase()
+    }
+
+    /// Canonical symbol from a native one ("btcusdt" or "BTCUSDT" -> "BTCUSDT")
+    pub fn canonical_symbol(&self, native: &str) -> String {
+        native.to_uppercase()
     }
 
     pub fn build_subscription_url(&self, symbols: &[&str]) -> String {
         let streams = symbols
             .iter()
             .flat_map(|s| {
-                vec![
-                    format!("{}@depth@100ms", s.to_lowercase()),
-                    format!("{}@aggTrade", s.to_lowercase()),
+                [
+                    (Channel::Book, "depth@100ms"),
+                    (Channel::Trades, "aggTrade"),
                 ]
+                .into_iter()
+                .filter(|&(channel, _)| self.channels.wants(s, channel))
+                .map(|(channel, stream)| format!("{}@{}", self.exchange_symbol(s, channel), stream))
             })
             .collect::<Vec<_>>()
             .join("/");
 
-        format!("wss://fstream.binance.com/stream?streams={}", streams)
+        format!("{}/stream?streams={}", self.market.ws_url(), streams)
     }
 
     pub fn parse_message(&self, raw: &str) -> Result<Option<MarketMessage>, Box<dyn Error + Send>> {
         let is_depth = raw.as_bytes().windows(6).any(|w| w == b"@depth");
 
         if is_depth {
-            let msg: BinanceDepthStream =
-                serde_json::from_str(raw).map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
-            let symbol = msg.data.symbol.clone();
+            let mut scratch = json::Scratch::default();
+            let msg: BinanceDepthStream = json::from_str(raw, &mut scratch)?;
+            let symbol = self.canonical_symbol(msg.data.symbol);
 
-            // Use fast_parse_u64_inner to avoid Box allocation on hot path
+            // Zero-copy: prices and quantities borrow from `raw`, no Decimal in between
             let bids: Vec<(u64, u64)> = msg
                 .data
                 .bids
@@ -67,27 +187,32 @@ impl BinanceConnector {
                 bids,
                 asks,
                 update_id: msg.data.final_update_id,
+                first_update_id: Some(msg.data.first_update_id),
+                prev_update_id: msg.data.prev_final_update_id,
                 is_snapshot: false, // Binance always sends deltas
+                checksum: None,
+                change_sequences: None,
             }))
         } else {
-            let msg: BinanceTradeStream =
-                serde_json::from_str(raw).map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+            let mut scratch = json::Scratch::default();
+            let msg: BinanceTradeStream = json::from_str(raw, &mut scratch)?;
 
             // Use fast_parse_u64_inner for zero-allocation parsing
-            let price = match fast_parse_u64_inner(&msg.data.price) {
+            let price = match fast_parse_u64_inner(msg.data.price) {
                 Some(p) => p,
                 None => return Ok(None), // Skip malformed trade
             };
-            let quantity = match fast_parse_u64_inner(&msg.data.qty) {
+            let quantity = match fast_parse_u64_inner(msg.data.qty) {
                 Some(q) => q,
                 None => return Ok(None),
             };
 
             let trade = Trade {
                 exchange: "Binance".to_string(),
-                symbol: msg.data.symbol.clone(),
+                symbol: self.canonical_symbol(msg.data.symbol),
                 price,
                 quantity,
+                notional: Trade::notional_of(price, quantity),
                 side: if msg.data.is_buyer_maker {
                     TradeSide::Sell
                 } else {
@@ -105,18 +230,47 @@ impl BinanceConnector {
         symbol: &str,
         limit: usize,
     ) -> Result<Option<DepthSnapshot>, Box<dyn Error + Send>> {
+        // Trades-only symbols have no book to seed
+        if !self.channels.wants(symbol, Channel::Book) {
+            return Ok(None);
+        }
         let url = format!(
-            "https://fapi.binance.com/fapi/v1/depth?symbol={}&limit={}",
-            symbol, limit
+            "{}{}?symbol={}&limit={}",
+            self.rest_url,
+            self.market.depth_path(),
+            symbol,
+            self.market.depth_limit(limit)
         );
 
-        let response: BinanceDepthResponse = reqwest::get(&url)
+        let reply: BinanceDepthReply = self
+            .http
+            .get(&url)
+            .send()
             .await
             .map_err(|e| Box::new(e) as Box<dyn Error + Send>)?
             .json()
             .await
             .map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
 
+        // Spot answers without the futures event/transaction times; both carry
+        // lastUpdateId, and an id of 0 would let any delta bridge the book
+        let response = match reply {
+            BinanceDepthReply::Book(response) if response.last_update_id > 0 => response,
+            BinanceDepthReply::Book(_) => {
+                return Err(boxed_error(&format!(
+                    "Binance {} for {} has no lastUpdateId",
+                    self.market.depth_path(),
+                    symbol
+                )))
+            }
+            BinanceDepthReply::Error { code, msg } => {
+                return Err(boxed_error(&format!(
+                    "Binance REST error {} for {}: {}",
+                    code, symbol, msg
+                )))
+            }
+        };
+
         Ok(Some(DepthSnapshot {
             bids: response.bids,
             asks: response.asks,
@@ -124,54 +278,72 @@ impl BinanceConnector {
         }))
     }
 
+    /// Binance streams deltas after the REST snapshot
+    pub fn always_snapshot(&self) -> bool {
+        false
+    }
+
     pub fn supported_symbols(&self) -> Vec<String> {
         self.symbols.clone()
     }
+
+    pub fn set_symbols(&mut self, symbols: Vec<String>) {
+        self.symbols = symbols;
+    }
 }
 
 // Binance-specific types
 #[derive(Debug, Deserialize)]
-struct BinanceDepthStream {
-    #[allow(dead_code)]
-    stream: String,
-    data: BinanceDepthUpdate,
+struct BinanceDepthStream<'a> {
+    #[serde(borrow)]
+    data: BinanceDepthUpdate<'a>,
 }
 
 #[derive(Debug, Deserialize)]
-struct BinanceDepthUpdate {
+struct BinanceDepthUpdate<'a> {
     #[serde(rename = "s")]
-    symbol: String,
+    symbol: &'a str,
     #[serde(rename = "U")]
-    _first_update_id: u64,
+    first_update_id: u64,
     #[serde(rename = "u")]
     final_update_id: u64,
-    #[serde(rename = "b")]
-    bids: Vec<(String, String)>,
-    #[serde(rename = "a")]
-    asks: Vec<(String, String)>,
+    /// Final update id of the previous event (futures streams only)
+    #[serde(rename = "pu", default)]
+    prev_final_update_id: Option<u64>,
+    #[serde(rename = "b", borrow)]
+    bids: Vec<(&'a str, &'a str)>,
+    #[serde(rename = "a", borrow)]
+    asks: Vec<(&'a str, &'a str)>,
 }
 
 #[derive(Debug, Deserialize)]
-struct BinanceTradeStream {
-    #[allow(dead_code)]
-    stream: String,
-    data: BinanceAggTrade,
+struct BinanceTradeStream<'a> {
+    #[serde(borrow)]
+    data: BinanceAggTrade<'a>,
 }
 
 #[derive(Debug, Deserialize)]
-struct BinanceAggTrade {
+struct BinanceAggTrade<'a> {
     #[serde(rename = "s")]
-    symbol: String,
+    symbol: &'a str,
     #[serde(rename = "E")]
     event_time: i64,
     #[serde(rename = "p")]
-    price: String,
+    price: &'a str,
     #[serde(rename = "q")]
-    qty: String,
+    qty: &'a str,
     #[serde(rename = "m")]
     is_buyer_maker: bool,
 }
 
+/// Depth snapshot, or the `{code, msg}` body Binance answers errors with
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum BinanceDepthReply {
+    Book(BinanceDepthResponse),
+    Error { code: i64, msg: String },
+}
+
 #[derive(Debug, Deserialize)]
 struct BinanceDepthResponse {
     #[serde(rename = "lastUpdateId")]
@@ -196,3 +368,7 @@ where
         })
         .collect()
 }
+
+fn boxed_error(message: &str) -> Box<dyn Error + Send> {
+    Box::<dyn Error + Send + Sync>::from(message.to_string())
+}
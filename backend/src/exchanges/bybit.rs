@@ -1,17 +1,37 @@
+use super::json;
+use super::symbols::{Channel, ChannelFilter};
 use super::utils::fast_parse_u64_inner;
 /// Bybit exchange connector
-use super::{DepthSnapshot, Exchange, MarketMessage};
-use crate::types::{Trade, TradeSide};
+use super::{stream_depth, DepthSnapshot, Exchange, MarketMessage, SubscribePacing};
+use crate::types::{Trade, TradeSide, ORDERBOOK_DEPTH};
+use serde::Deserialize;
 use std::error::Error;
+use std::time::Duration;
+
+/// Bybit rejects subscribe frames with more args than this
+const MAX_ARGS_PER_SUBSCRIBE: usize = 10;
+/// Depths the linear orderbook topic comes in (besides level 1)
+const BOOK_DEPTHS: &[usize] = &[50, 200, 500];
+/// Bybit closes connections that stay silent for 30s; it recommends a 20s ping
+const PING_INTERVAL: Duration = Duration::from_secs(20);
 
 #[derive(Clone)]
 pub struct BybitConnector {
     symbols: Vec<String>,
+    channels: ChannelFilter,
+    pacing: SubscribePacing,
+    /// Depth of the orderbook topic subscribed to
+    book_depth: usize,
 }
 
 impl BybitConnector {
     pub fn new(symbols: Vec<String>) -> Self {
-        Self { symbols }
+        Self {
+            symbols,
+            channels: ChannelFilter::default(),
+            pacing: SubscribePacing::new(MAX_ARGS_PER_SUBSCRIBE, Duration::ZERO),
+            book_depth: stream_depth(BOOK_DEPTHS, ORDERBOOK_DEPTH),
+        }
     }
 
     pub fn build_subscription_url(&self, _symbols: &[&str]) -> String {
@@ -19,121 +39,180 @@ impl BybitConnector {
         "wss://stream.bybit.com/v5/public/linear".to_string()
     }
 
+    /// Native symbol for a channel (Bybit uses "BTCUSDT" on both channels)
+    pub fn exchange_symbol(&self, symbol: &str, _channel: Channel) -> String {
+        symbol.to_string()
+    }
+
+    /// Canonical symbol from the symbol segment of a topic
+    pub fn canonical_symbol(&self, native: &str) -> String {
+        native.to_string()
+    }
+
     /// Build subscription messages for Bybit WebSocket
     pub fn get_subscription_messages(&self, symbols: &[&str]) -> Vec<String> {
+        let book_topic = format!("orderbook.{}", self.book_depth);
         let args: Vec<String> = symbols
             .iter()
-            .flat_map(|s| vec![format!("orderbook.50.{}", s), format!("publicTrade.{}", s)])
+            .flat_map(|s| {
+                [
+                    (Channel::Book, book_topic.as_str()),
+                    (Channel::Trades, "publicTrade"),
+                ]
+                .into_iter()
+                .filter(|&(channel, _)| self.channels.wants(s, channel))
+                .map(|(channel, topic)| format!("{}.{}", topic, self.exchange_symbol(s, channel)))
+            })
             .collect();
 
-        let subscription = serde_json::json!({
-            "op": "subscribe",
-            "args": args
-        });
+        self.pacing
+            .chunks(&args)
+            .map(|chunk| {
+                serde_json::json!({
+                    "op": "subscribe",
+                    "args": chunk
+                })
+                .to_string()
+            })
+            .collect()
+    }
+
+    pub fn subscribe_pacing(&self) -> SubscribePacing {
+        self.pacing
+    }
 
-        vec![subscription.to_string()]
+    pub fn set_subscribe_pacing(&mut self, pacing: SubscribePacing) {
+        self.pacing = pacing;
+    }
+
+    /// Subscribe the orderbook topic deep enough for `depth` stored levels
+    pub fn set_book_depth(&mut self, depth: usize) {
+        self.book_depth = stream_depth(BOOK_DEPTHS, depth);
+    }
+
+    pub fn book_depth(&self) -> usize {
+        self.book_depth
+    }
+
+    /// Subscribe only the channels `filter` enables per symbol
+    pub fn set_channel_filter(&mut self, filter: ChannelFilter) {
+        self.channels = filter;
+    }
+
+    /// Client ping Bybit requires: WebSocket ping frames do not count
+    pub fn keepalive(&self) -> (Duration, String) {
+        (PING_INTERVAL, r#"{"op":"ping"}"#.to_string())
     }
 
     pub fn parse_message(&self, raw: &str) -> Result<Option<MarketMessage>, Box<dyn Error + Send>> {
-        let msg: serde_json::Value =
-            serde_json::from_str(raw).map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
-
-        // Bybit format: {"topic": "orderbook.25.BTCUSDT", "type": "snapshot|delta", "data": {...}}
-        if let Some(topic) = msg["topic"].as_str() {
-            // Handle orderbook updates
-            if topic.starts_with("orderbook") {
-                let parts: Vec<&str> = topic.split('.').collect();
-                if parts.len() >= 3 {
-                    let symbol = parts[2].to_string();
-                    let msg_type = msg["type"].as_str().unwrap_or("delta");
-
-                    if msg_type == "snapshot" {
-                        tracing::debug!("[Bybit] Received snapshot for {}", symbol);
-                    }
-
-                    let bids: Vec<(u64, u64)> = msg["data"]["b"]
-                        .as_array()
-                        .unwrap_or(&vec![])
-                        .iter()
-                        .filter_map(|item| {
-                            let price = fast_parse_u64_inner(item[0].as_str()?)?;
-                            let qty = fast_parse_u64_inner(item[1].as_str()?)?;
-                            Some((price, qty))
-                        })
-                        .collect();
-
-                    let asks: Vec<(u64, u64)> = msg["data"]["a"]
-                        .as_array()
-                        .unwrap_or(&vec![])
-                        .iter()
-                        .filter_map(|item| {
-                            let price = fast_parse_u64_inner(item[0].as_str()?)?;
-                            let qty = fast_parse_u64_inner(item[1].as_str()?)?;
-                            Some((price, qty))
-                        })
-                        .collect();
-
-                    let update_id = msg["data"]["u"].as_u64().unwrap_or(0);
-                    let is_snapshot = msg_type == "snapshot";
-
-                    return Ok(Some(MarketMessage::DepthUpdate {
-                        exchange: Exchange::Bybit,
-                        symbol,
-                        bids,
-                        asks,
-                        update_id,
-                        is_snapshot,
-                    }));
-                }
-            }
-            // Handle trade updates
-            else if topic.starts_with("publicTrade") {
-                let parts: Vec<&str> = topic.split('.').collect();
-                if parts.len() >= 2 {
-                    let symbol = parts[1].to_string();
-
-                    if let Some(trades_array) = msg["data"].as_array() {
-                        for trade_data in trades_array {
-                            if let (Some(price_str), Some(qty_str), Some(side_str)) = (
-                                trade_data["p"].as_str(),
-                                trade_data["v"].as_str(),
-                                trade_data["S"].as_str(),
-                            ) {
-                                let price = match fast_parse_u64_inner(price_str) {
-                                    Some(p) => p,
-                                    None => continue,
-                                };
-                                let quantity = match fast_parse_u64_inner(qty_str) {
-                                    Some(q) => q,
-                                    None => continue,
-                                };
-
-                                let side = match side_str {
-                                    "Buy" => TradeSide::Buy,
-                                    "Sell" => TradeSide::Sell,
-                                    _ => continue,
-                                };
-
-                                let timestamp = trade_data["T"].as_i64().unwrap_or(0);
-
-                                let trade = Trade {
-                                    exchange: "Bybit".to_string(),
-                                    symbol: symbol.clone(),
-                                    price,
-                                    quantity,
-                                    side,
-                                    timestamp,
-                                };
-
-                                return Ok(Some(MarketMessage::Trade(trade)));
-                            }
-                        }
-                    }
-                }
+        // Bybit format: {"topic": "orderbook.50.BTCUSDT", "type": "snapshot|delta", "data": {...}}
+        // Only the topic is read first (data is skipped), then the matching typed parser runs
+        #[derive(Deserialize)]
+        struct TopicHeader<'a> {
+            topic: Option<&'a str>,
+            // Responses to our requests: {"success":false,"ret_msg":"...","op":"subscribe"}
+            op: Option<&'a str>,
+            success: Option<bool>,
+            ret_msg: Option<&'a str>,
+        }
+
+        let mut scratch = json::Scratch::default();
+        let header: TopicHeader = json::from_str(raw, &mut scratch)?;
+
+        // Subscription acks and pongs carry no topic
+        let Some(topic) = header.topic else {
+            // A refused subscription (unknown symbol...) would otherwise just never send data
+            if header.op == Some("subscribe") && header.success == Some(false) {
+                tracing::warn!(
+                    "[Bybit] Subscription failed: {}",
+                    header.ret_msg.unwrap_or(raw)
+                );
+                return Ok(Some(MarketMessage::Raw(raw.to_string())));
             }
+            return Ok(None);
+        };
+
+        let mut parts = topic.split('.');
+        match parts.next() {
+            Some("orderbook") => match parts.nth(1) {
+                Some(native) => self.parse_depth_message(raw, native),
+                None => Ok(None),
+            },
+            Some("publicTrade") => match parts.next() {
+                Some(native) => self.parse_trade_message(raw, native),
+                None => Ok(None),
+            },
+            _ => Ok(None),
         }
+    }
 
-        Ok(None)
+    fn parse_depth_message(
+        &self,
+        raw: &str,
+        native: &str,
+    ) -> Result<Option<MarketMessage>, Box<dyn Error + Send>> {
+        // Zero-copy: prices and quantities borrow from `raw`
+        let mut scratch = json::Scratch::default();
+        let msg: BybitDepthMessage = json::from_str(raw, &mut scratch)?;
+
+        let symbol = self.canonical_symbol(native);
+        let is_snapshot = msg.type_ == Some("snapshot");
+        if is_snapshot {
+            tracing::debug!("[Bybit] Received snapshot for {}", symbol);
+        }
+
+        Ok(Some(MarketMessage::DepthUpdate {
+            exchange: Exchange::Bybit,
+            symbol,
+            bids: Self::to_levels(&msg.data.bids),
+            asks: Self::to_levels(&msg.data.asks),
+            update_id: msg.data.update_id,
+            first_update_id: None,
+            prev_update_id: None,
+            is_snapshot,
+            checksum: None,
+            change_sequences: None,
+        }))
+    }
+
+    fn to_levels(levels: &[[&str; 2]]) -> Vec<(u64, u64)> {
+        levels
+            .iter()
+            .filter_map(|[price, qty]| {
+                Some((fast_parse_u64_inner(price)?, fast_parse_u64_inner(qty)?))
+            })
+            .collect()
+    }
+
+    fn parse_trade_message(
+        &self,
+        raw: &str,
+        native: &str,
+    ) -> Result<Option<MarketMessage>, Box<dyn Error + Send>> {
+        let mut scratch = json::Scratch::default();
+        let msg: BybitTradeMessage = json::from_str(raw, &mut scratch)?;
+
+        // Every well-formed trade of the frame
+        let trades = msg.data.iter().filter_map(|trade| {
+            let side = match trade.side {
+                "Buy" => TradeSide::Buy,
+                "Sell" => TradeSide::Sell,
+                _ => return None,
+            };
+            let price = fast_parse_u64_inner(trade.price)?;
+            let quantity = fast_parse_u64_inner(trade.qty)?;
+            Some(Trade {
+                exchange: "Bybit".to_string(),
+                symbol: self.canonical_symbol(native),
+                price,
+                quantity,
+                notional: Trade::notional_of(price, quantity),
+                side,
+                timestamp: trade.time,
+            })
+        });
+
+        Ok(MarketMessage::from_trades(trades.collect()))
     }
 
     /// Bybit sends initial snapshot via WebSocket, so REST fetch not needed
@@ -146,7 +225,52 @@ impl BybitConnector {
         Ok(None)
     }
 
+    /// Bybit sends one snapshot then deltas
+    pub fn always_snapshot(&self) -> bool {
+        false
+    }
+
     pub fn supported_symbols(&self) -> Vec<String> {
         self.symbols.clone()
     }
+
+    pub fn set_symbols(&mut self, symbols: Vec<String>) {
+        self.symbols = symbols;
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct BybitDepthMessage<'a> {
+    #[serde(rename = "type", default)]
+    type_: Option<&'a str>,
+    #[serde(borrow)]
+    data: BybitDepthData<'a>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BybitDepthData<'a> {
+    #[serde(rename = "b", borrow, default)]
+    bids: Vec<[&'a str; 2]>,
+    #[serde(rename = "a", borrow, default)]
+    asks: Vec<[&'a str; 2]>,
+    #[serde(rename = "u", default)]
+    update_id: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct BybitTradeMessage<'a> {
+    #[serde(borrow)]
+    data: Vec<BybitTradeData<'a>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BybitTradeData<'a> {
+    #[serde(rename = "T", default)]
+    time: i64,
+    #[serde(rename = "S")]
+    side: &'a str,
+    #[serde(rename = "v")]
+    qty: &'a str,
+    #[serde(rename = "p")]
+    price: &'a str,
 }
@@ -1,82 +1,169 @@
+use super::json;
+use super::symbols::{Channel, ChannelFilter, SymbolMap};
+use super::timestamp::parse_rfc3339_millis;
 use super::utils::fast_parse_u64_inner;
-use super::{DepthSnapshot, Exchange, MarketMessage};
+use super::{DepthSnapshot, Exchange, MarketMessage, ParseResult, SubscribePacing};
 use crate::types::{Trade, TradeSide};
 use serde::Deserialize;
 use std::error::Error;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Coinbase lists the majors against USD: USDT symbols stream the USD books
+const QUOTE_ALIASES: &[(&str, &str)] = &[("USDT", "USD")];
 
 #[derive(Clone)]
 pub struct CoinbaseConnector {
     symbols: Vec<String>,
+    symbol_map: SymbolMap,
+    /// Last `sequence_num` seen on the connection (numbered across all channels)
+    last_sequence: Arc<AtomicU64>,
+    channels: ChannelFilter,
+    pacing: SubscribePacing,
 }
 
 impl CoinbaseConnector {
     pub fn new(symbols: Vec<String>) -> Self {
-        Self { symbols }
+        Self {
+            symbol_map: SymbolMap::new('-', QUOTE_ALIASES, &symbols),
+            symbols,
+            last_sequence: Arc::new(AtomicU64::new(0)),
+            channels: ChannelFilter::default(),
+            pacing: SubscribePacing::default(),
+        }
     }
 
     pub fn build_subscription_url(&self, _symbols: &[&str]) -> String {
         "wss://advanced-trade-ws.coinbase.com".to_string()
     }
 
-    pub fn get_subscription_messages(&self) -> Vec<String> {
-        // Optimisation: vec! macro vs push manuel n'a pas bcp d'impact ici car c'est fait 1 fois
-        // Mais on prépare proprement les product_ids
-        let product_ids: Vec<String> = self
-            .symbols
+    /// Native product id for a channel ("BTCUSDT" -> "BTC-USD" on both channels)
+    pub fn exchange_symbol(&self, symbol: &str, _channel: Channel) -> String {
+        self.symbol_map.to_native(symbol)
+    }
+
+    /// Canonical symbol from a product id ("BTC-USD" -> "BTCUSDT")
+    pub fn canonical_symbol(&self, native: &str) -> String {
+        self.symbol_map.to_canonical(native)
+    }
+
+    fn product_ids(&self, channel: Channel) -> Vec<String> {
+        self.symbols
             .iter()
-            .map(|s| {
-                let base = s.trim_end_matches("USDT");
-                format!("{}-USD", base)
-            })
-            .collect();
+            .filter(|s| self.channels.wants(s, channel))
+            .map(|s| self.exchange_symbol(s, channel))
+            .collect()
+    }
 
-        // On clone product_ids car utilisé 2 fois, c'est inévitable mais négligeable (init)
-        let sub_l2 = CoinbaseSubscribe {
-            type_: "subscribe",
-            product_ids: product_ids.clone(),
-            channel: "level2",
-        };
+    pub fn get_subscription_messages(&self) -> Vec<String> {
+        let book_ids = self.product_ids(Channel::Book);
+        let trade_ids = self.product_ids(Channel::Trades);
+
+        let sub_l2 = self
+            .pacing
+            .chunks(&book_ids)
+            .map(|chunk| CoinbaseSubscribe {
+                type_: "subscribe",
+                product_ids: chunk.to_vec(),
+                channel: "level2",
+            });
+
+        let sub_trades = self
+            .pacing
+            .chunks(&trade_ids)
+            .map(|chunk| CoinbaseSubscribe {
+                type_: "subscribe",
+                product_ids: chunk.to_vec(),
+                channel: "market_trades",
+            });
+
+        sub_l2
+            .chain(sub_trades)
+            .map(|sub| serde_json::to_string(&sub).unwrap_or_default())
+            .collect()
+    }
 
-        let sub_trades = CoinbaseSubscribe {
-            type_: "subscribe",
-            product_ids,
-            channel: "market_trades",
-        };
+    pub fn subscribe_pacing(&self) -> SubscribePacing {
+        self.pacing
+    }
 
-        vec![
-            serde_json::to_string(&sub_l2).unwrap_or_default(),
-            serde_json::to_string(&sub_trades).unwrap_or_default(),
-        ]
+    pub fn set_subscribe_pacing(&mut self, pacing: SubscribePacing) {
+        self.pacing = pacing;
     }
 
-    /// Cœur du réacteur : Parsing Zero-Copy
+    /// Subscribe only the channels `filter` enables per symbol
+    pub fn set_channel_filter(&mut self, filter: ChannelFilter) {
+        self.channels = filter;
+    }
+
+    /// Parse a frame and check its place in the connection sequence
+    ///
+    /// Frames must come in arrival order; see `parse_frame` otherwise.
     pub fn parse_message(&self, raw: &str) -> Result<Option<MarketMessage>, Box<dyn Error + Send>> {
-        // 1. Filtrage ultra-rapide (SIMD friendly) des messages de contrôle
-        // On check les chaînes brutes pour éviter tout parsing JSON si inutile
-        if raw.contains(r#""channel":"subscriptions""#)
-            || raw.contains(r#""channel":"heartbeats""#)
-            || raw.contains(r#""subscriptions":{"#)
-        {
-            return Ok(None);
+        let (sequence, parsed) = self.parse_frame(raw);
+        match sequence.and_then(|sequence| self.sequence_resync(sequence)) {
+            Some(resync) => Ok(Some(resync)),
+            None => parsed,
         }
+    }
 
-        // 2. Parsing partiel "Zero-Copy" pour router le message
+    /// Cœur du réacteur : Parsing Zero-Copy
+    ///
+    /// Parses a frame without touching the connection sequence, so frames can be
+    /// parsed in any order. Returns the frame's `sequence_num` alongside, for
+    /// `sequence_resync` in arrival order.
+    pub fn parse_frame(&self, raw: &str) -> (Option<u64>, ParseResult) {
+        // 1. Parsing partiel "Zero-Copy" pour router le message
         // On ne décode que le strict nécessaire pour savoir quel parser lancer
         #[derive(Deserialize)]
         struct ChannelHeader<'a> {
             channel: &'a str,
+            sequence_num: Option<u64>,
         }
 
-        let header: ChannelHeader = match serde_json::from_str(raw) {
+        let mut scratch = json::Scratch::default();
+        let header: ChannelHeader = match json::from_str(raw, &mut scratch) {
             Ok(h) => h,
-            Err(_) => return Ok(None), // Ignorer les erreurs de parsing (bruit)
+            Err(_) => return (None, Ok(None)), // Ignorer les erreurs de parsing (bruit)
         };
 
-        match header.channel {
+        let parsed = match header.channel {
             "l2_data" => self.parse_level2_message(raw),
             "market_trades" => self.parse_trade_message(raw),
             _ => Ok(None),
+        };
+        (header.sequence_num, parsed)
+    }
+
+    /// Advance the connection sequence with a frame's `sequence_num`, in arrival order
+    ///
+    /// Every message (heartbeats included) counts. A `Resync` on a gap.
+    pub fn sequence_resync(&self, sequence: u64) -> Option<MarketMessage> {
+        let expected = self.check_sequence(sequence)?;
+        Some(MarketMessage::Resync {
+            exchange: Exchange::Coinbase,
+            reason: format!("sequence gap: expected {}, got {}", expected, sequence),
+        })
+    }
+
+    /// Record a sequence number, returning the expected one on a gap
+    ///
+    /// Numbers must be checked in arrival order: frames parsed concurrently go
+    /// through `parse_frame` and are checked once back in order. Anything above
+    /// the next expected number is a gap. Numbers at or below the last one seen
+    /// are duplicates and ignored. A new connection restarts at 0.
+    fn check_sequence(&self, sequence: u64) -> Option<u64> {
+        if sequence == 0 {
+            self.last_sequence.store(0, Ordering::Relaxed);
+            return None;
+        }
+        let last = self.last_sequence.load(Ordering::Relaxed);
+        if sequence <= last {
+            return None;
         }
+        self.last_sequence.store(sequence, Ordering::Relaxed);
+        let expected = last + 1;
+        (sequence > expected).then_some(expected)
     }
 
     fn parse_level2_message(
@@ -84,14 +171,15 @@ impl CoinbaseConnector {
         raw: &str,
     ) -> Result<Option<MarketMessage>, Box<dyn Error + Send>> {
         // Zero-Copy deserialization: les champs &'a str pointent dans 'raw'
-        let msg: CoinbaseLevel2Message =
-            serde_json::from_str(raw).map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+        let mut scratch = json::Scratch::default();
+        let msg: CoinbaseLevel2Message = json::from_str(raw, &mut scratch)?;
 
-        // Coinbase envoie souvent 1 seul event, on prend le premier
-        if let Some(event) = msg.events.first() {
+        // Usually one event, but a frame can carry one per product: keep them all
+        let mut updates = Vec::with_capacity(msg.events.len());
+        for event in &msg.events {
             // Transformation du symbole : allocation obligatoire ici pour le String final
             // Optimisation possible : utiliser un cache de symboles si la liste est fixe
-            let symbol = event.product_id.replace("-USD", "USDT");
+            let symbol = self.canonical_symbol(event.product_id);
 
             // Collect avec filter_map : allocation exacte, pas de boucle + push
             let bids: Vec<(u64, u64)> = event
@@ -118,67 +206,60 @@ impl CoinbaseConnector {
 
             let is_snapshot = event.type_ == "snapshot";
 
-            return Ok(Some(MarketMessage::DepthUpdate {
+            updates.push(MarketMessage::DepthUpdate {
                 exchange: Exchange::Coinbase,
                 symbol,
                 bids,
                 asks,
                 update_id: msg.sequence_num,
+                first_update_id: None,
+                prev_update_id: None,
                 is_snapshot,
-            }));
+                checksum: None,
+                change_sequences: None,
+            });
         }
 
-        Ok(None)
+        Ok(MarketMessage::batch(updates))
     }
 
     fn parse_trade_message(
         &self,
         raw: &str,
     ) -> Result<Option<MarketMessage>, Box<dyn Error + Send>> {
-        let msg: CoinbaseTradeMessage =
-            serde_json::from_str(raw).map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
-
-        if let Some(event) = msg.events.first() {
-            // Pour l'instant on prend le premier trade du batch
-            // TODO: Adapter MarketMessage pour accepter Vec<Trade> pour plus d'efficacité
-            if let Some(trade_data) = event.trades.first() {
-                let symbol = trade_data.product_id.replace("-USD", "USDT");
-
-                let price = match fast_parse_u64_inner(trade_data.price) {
-                    Some(p) => p,
-                    None => return Ok(None),
-                };
-                let quantity = match fast_parse_u64_inner(trade_data.size) {
-                    Some(q) => q,
-                    None => return Ok(None),
-                };
+        let mut scratch = json::Scratch::default();
+        let msg: CoinbaseTradeMessage = json::from_str(raw, &mut scratch)?;
 
+        // Every trade of every event: a busy second packs many in one frame
+        let trades = msg
+            .events
+            .iter()
+            .flat_map(|event| &event.trades)
+            .filter_map(|trade_data| {
+                let price = fast_parse_u64_inner(trade_data.price)?;
+                let quantity = fast_parse_u64_inner(trade_data.size)?;
                 let side = match trade_data.side {
                     "BUY" => TradeSide::Buy,
                     "SELL" => TradeSide::Sell,
-                    _ => return Ok(None),
+                    _ => return None,
                 };
 
-                // Parsing de date : c'est souvent le goulot d'étranglement restant
-                // chrono est correct, mais pour de l'ultra-perf, on parserait manuellement le timestamp
-                let timestamp = chrono::DateTime::parse_from_rfc3339(trade_data.time)
-                    .map(|dt| dt.timestamp_millis())
-                    .unwrap_or(0);
+                // Parsing de date : chemin rapide sans chrono pour le format fixe de Coinbase
+                let timestamp = parse_rfc3339_millis(trade_data.time).unwrap_or(0);
 
-                let trade = Trade {
+                Some(Trade {
                     exchange: "Coinbase".to_string(),
-                    symbol,
+                    symbol: self.canonical_symbol(trade_data.product_id),
                     price,
                     quantity,
+                    notional: Trade::notional_of(price, quantity),
                     side,
                     timestamp,
-                };
-
-                return Ok(Some(MarketMessage::Trade(trade)));
-            }
-        }
+                })
+            })
+            .collect();
 
-        Ok(None)
+        Ok(MarketMessage::from_trades(trades))
     }
 
     pub async fn fetch_snapshot(
@@ -189,9 +270,19 @@ impl CoinbaseConnector {
         Ok(None)
     }
 
+    /// Coinbase sends one snapshot then deltas
+    pub fn always_snapshot(&self) -> bool {
+        false
+    }
+
     pub fn supported_symbols(&self) -> Vec<String> {
         self.symbols.clone()
     }
+
+    pub fn set_symbols(&mut self, symbols: Vec<String>) {
+        self.symbol_map.set_symbols(&symbols);
+        self.symbols = symbols;
+    }
 }
 
 // --- OPTIMIZED DTOs (Data Transfer Objects) ---
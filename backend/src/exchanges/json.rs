@@ -0,0 +1,67 @@
+//! JSON decoding of exchange frames
+//!
+//! `serde_json` by default. With the `simd-json` feature, frames go through
+//! simd-json instead, which parses in place: `from_str` copies the frame into
+//! the caller's `Scratch` and borrowed fields point into that copy, so the
+//! scratch must outlive the decoded value. Without the feature `Scratch` is
+//! empty and nothing is copied.
+
+use serde::Deserialize;
+use std::error::Error;
+
+/// Mutable copy of a frame for the in-place parser (one per decoded value)
+#[derive(Debug, Default)]
+pub struct Scratch {
+    #[cfg(feature = "simd-json")]
+    buf: Vec<u8>,
+}
+
+/// Decode `raw` into `T`, which may borrow from `raw` or `scratch`
+#[cfg(not(feature = "simd-json"))]
+#[inline]
+pub fn from_str<'a, T: Deserialize<'a>>(
+    raw: &'a str,
+    _scratch: &'a mut Scratch,
+) -> Result<T, Box<dyn Error + Send>> {
+    serde_json::from_str(raw).map_err(|e| Box::new(e) as Box<dyn Error + Send>)
+}
+
+/// Decode `raw` into `T`, which may borrow from `raw` or `scratch`
+#[cfg(feature = "simd-json")]
+#[inline]
+pub fn from_str<'a, T: Deserialize<'a>>(
+    raw: &'a str,
+    scratch: &'a mut Scratch,
+) -> Result<T, Box<dyn Error + Send>> {
+    scratch.buf.clear();
+    scratch.buf.extend_from_slice(raw.as_bytes());
+    simd_json::serde::from_slice(&mut scratch.buf).map_err(|e| Box::new(e) as Box<dyn Error + Send>)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Level<'a> {
+        #[serde(borrow)]
+        price: &'a str,
+        size: u64,
+    }
+
+    #[test]
+    fn test_borrowed_fields_and_errors() {
+        let mut scratch = Scratch::default();
+        let level: Level = from_str(r#"{"price":"97000.1","size":3}"#, &mut scratch).unwrap();
+        assert_eq!(
+            level,
+            Level {
+                price: "97000.1",
+                size: 3
+            }
+        );
+
+        let mut scratch = Scratch::default();
+        assert!(from_str::<Level>(r#"{"price":"97000.1""#, &mut scratch).is_err());
+    }
+}
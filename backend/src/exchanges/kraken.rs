@@ -1,18 +1,62 @@
+use super::json;
+use super::symbols::{Channel, ChannelFilter, SymbolMap};
+use super::timestamp::parse_rfc3339_millis;
 use super::utils::fast_parse_f64_inner;
 /// Kraken exchange connector (WebSocket v2)
-use super::{DepthSnapshot, Exchange, MarketMessage};
-use crate::types::{Trade, TradeSide};
+use super::{stream_depth, BookChecksum, DepthSnapshot, Exchange, MarketMessage, SubscribePacing};
+use crate::types::{Trade, TradeSide, ORDERBOOK_DEPTH};
+use dashmap::DashMap;
 use serde::Deserialize;
 use std::error::Error;
+use std::sync::Arc;
+
+/// Levels per side covered by Kraken's book checksum
+const CHECKSUM_LEVELS: usize = 10;
+/// Quantity precision assumed for the checksum until the instrument channel
+/// gives the pair's own (most spot pairs use 8)
+const QTY_DECIMALS: u32 = 8;
+/// Depths the book channel can be subscribed at
+const BOOK_DEPTHS: &[usize] = &[10, 25, 100, 500, 1000];
+/// Kraken lists the majors against USD: USDT symbols stream the USD books
+const QUOTE_ALIASES: &[(&str, &str)] = &[("USDT", "USD")];
 
 #[derive(Clone)]
 pub struct KrakenConnector {
     symbols: Vec<String>,
+    symbol_map: SymbolMap,
+    channels: ChannelFilter,
+    pacing: SubscribePacing,
+    /// Depth the book channel is subscribed at
+    book_depth: usize,
+    /// Price and quantity precision per native pair, from the instrument channel
+    precisions: Arc<DashMap<String, PairPrecision>>,
+    /// Price precision per pair, inferred from the finest price seen in its
+    /// book, for pairs the instrument channel hasn't described yet
+    ///
+    /// Levels arrive as JSON numbers, so trailing zeros (and the pair's
+    /// precision with them) are lost; over a whole snapshot some level
+    /// almost always uses the last decimal.
+    price_decimals: Arc<DashMap<String, u32>>,
+}
+
+/// Decimals Kraken prints a pair's prices and quantities with in its checksum
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PairPrecision {
+    price: u32,
+    qty: u32,
 }
 
 impl KrakenConnector {
     pub fn new(symbols: Vec<String>) -> Self {
-        Self { symbols }
+        Self {
+            symbol_map: SymbolMap::new('/', QUOTE_ALIASES, &symbols),
+            symbols,
+            channels: ChannelFilter::default(),
+            pacing: SubscribePacing::default(),
+            book_depth: stream_depth(BOOK_DEPTHS, ORDERBOOK_DEPTH),
+            precisions: Arc::new(DashMap::new()),
+            price_decimals: Arc::new(DashMap::new()),
+        }
     }
 
     /// Build WebSocket URL (Kraken uses base URL only)
@@ -20,47 +64,98 @@ impl KrakenConnector {
         "wss://ws.kraken.com/v2".to_string()
     }
 
+    /// Native pair for a channel ("BTCUSDT" -> "BTC/USD" on both channels)
+    pub fn exchange_symbol(&self, symbol: &str, _channel: Channel) -> String {
+        self.symbol_map.to_native(symbol)
+    }
+
+    /// Canonical symbol from a native pair ("BTC/USD" -> "BTCUSDT")
+    pub fn canonical_symbol(&self, native: &str) -> String {
+        self.symbol_map.to_canonical(native)
+    }
+
+    fn pairs(&self, channel: Channel) -> Vec<String> {
+        self.symbols
+            .iter()
+            .filter(|s| self.channels.wants(s, channel))
+            .map(|s| self.exchange_symbol(s, channel))
+            .collect()
+    }
+
     /// Get subscription messages (Kraken requires post-connection subscription)
     pub fn get_subscription_messages(&self) -> Vec<String> {
-        let symbols: Vec<String> = self
-            .symbols
-            .iter()
-            .map(|s| {
-                // Convert BTCUSDT -> BTC/USD format
-                let base = s.trim_end_matches("USDT");
-                format!("{}/USD", base)
-            })
-            .collect();
-
-        // Subscribe to both book and trade channels
-        let subscriptions = vec![
-            KrakenSubscribe {
+        // Subscribe to both book and trade channels, pairs split per the pacing
+        let book_pairs = self.pairs(Channel::Book);
+        let trade_pairs = self.pairs(Channel::Trades);
+        // Pair precisions first, so they are known before the book snapshots
+        let instrument = (!book_pairs.is_empty()).then(|| KrakenSubscribe {
+            method: "subscribe".to_string(),
+            params: KrakenSubscribeParams {
+                channel: "instrument".to_string(),
+                symbol: Vec::new(),
+                depth: None,
+                snapshot: Some(true),
+            },
+        });
+        let book = self
+            .pacing
+            .chunks(&book_pairs)
+            .map(|chunk| KrakenSubscribe {
                 method: "subscribe".to_string(),
                 params: KrakenSubscribeParams {
                     channel: "book".to_string(),
-                    symbol: symbols.clone(),
-                    depth: Some(25),
+                    symbol: chunk.to_vec(),
+                    depth: Some(self.book_depth),
                     snapshot: Some(true),
                 },
-            },
-            KrakenSubscribe {
+            });
+        let trades = self
+            .pacing
+            .chunks(&trade_pairs)
+            .map(|chunk| KrakenSubscribe {
                 method: "subscribe".to_string(),
                 params: KrakenSubscribeParams {
                     channel: "trade".to_string(),
-                    symbol: symbols,
+                    symbol: chunk.to_vec(),
                     depth: None,
                     snapshot: None,
                 },
-            },
-        ];
-
-        // Return both subscriptions as separate messages
-        subscriptions
-            .iter()
-            .filter_map(|sub| serde_json::to_string(sub).ok())
+            });
+
+        // Each subscription is a separate message
+        instrument
+            .into_iter()
+            .chain(book)
+            .chain(trades)
+            .filter_map(|sub| serde_json::to_string(&sub).ok())
             .collect()
     }
 
+    pub fn subscribe_pacing(&self) -> SubscribePacing {
+        self.pacing
+    }
+
+    pub fn set_subscribe_pacing(&mut self, pacing: SubscribePacing) {
+        self.pacing = pacing;
+    }
+
+    /// Subscribe the book channel deep enough for `depth` stored levels
+    ///
+    /// Levels past the subscribed depth get no updates, and the checksum
+    /// expects the book cut at that depth.
+    pub fn set_book_depth(&mut self, depth: usize) {
+        self.book_depth = stream_depth(BOOK_DEPTHS, depth);
+    }
+
+    pub fn book_depth(&self) -> usize {
+        self.book_depth
+    }
+
+    /// Subscribe only the channels `filter` enables per symbol
+    pub fn set_channel_filter(&mut self, filter: ChannelFilter) {
+        self.channels = filter;
+    }
+
     pub fn parse_message(&self, raw: &str) -> Result<Option<MarketMessage>, Box<dyn Error + Send>> {
         // Check if it's a subscription confirmation, status, or heartbeat message
         if raw.contains("\"method\":\"subscribe\"")
@@ -73,7 +168,8 @@ impl KrakenConnector {
         }
 
         // Parse channel type first
-        let channel_check: serde_json::Value = match serde_json::from_str(raw) {
+        let mut scratch = json::Scratch::default();
+        let channel_check: serde_json::Value = match json::from_str(raw, &mut scratch) {
             Ok(v) => v,
             Err(e) => {
                 let preview = if raw.len() > 200 { &raw[..200] } else { raw };
@@ -91,6 +187,7 @@ impl KrakenConnector {
         match channel {
             "book" => self.parse_book_message(raw),
             "trade" => self.parse_trade_message(raw),
+            "instrument" => self.parse_instrument_message(raw),
             _ => {
                 tracing::debug!("[Kraken] Ignoring channel: {}", channel);
                 Ok(None)
@@ -102,12 +199,13 @@ impl KrakenConnector {
         &self,
         raw: &str,
     ) -> Result<Option<MarketMessage>, Box<dyn Error + Send>> {
-        let msg: KrakenBookMessage =
-            serde_json::from_str(raw).map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+        let mut scratch = json::Scratch::default();
+        let msg: KrakenBookMessage = json::from_str(raw, &mut scratch)?;
 
+        // One entry per pair: a frame can carry several
+        let mut updates = Vec::with_capacity(msg.data.len());
         for data in msg.data {
-            // Convert BTC/USD -> BTCUSDT
-            let symbol = data.symbol.replace("/USD", "USDT");
+            let symbol = self.canonical_symbol(&data.symbol);
             let is_snapshot = msg.type_ == "snapshot";
 
             let bids: Vec<(u64, u64)> = data
@@ -135,28 +233,90 @@ impl KrakenConnector {
                 continue;
             }
 
-            return Ok(Some(MarketMessage::DepthUpdate {
+            let precision = match self.precisions.get(&data.symbol) {
+                Some(precision) => *precision,
+                None => PairPrecision {
+                    price: self.record_price_decimals(&symbol, &bids, &asks, is_snapshot),
+                    qty: QTY_DECIMALS,
+                },
+            };
+            let checksum = data.checksum.map(|expected| BookChecksum {
+                expected: expected as u32,
+                levels: CHECKSUM_LEVELS,
+                price_decimals: precision.price,
+                qty_decimals: precision.qty,
+            });
+
+            updates.push(MarketMessage::DepthUpdate {
                 exchange: Exchange::Kraken,
                 symbol,
                 bids,
                 asks,
                 update_id: data.checksum.unwrap_or(0) as u64,
+                first_update_id: None,
+                prev_update_id: None,
                 is_snapshot,
-            }));
+                checksum,
+                change_sequences: None,
+            });
         }
 
+        Ok(MarketMessage::batch(updates))
+    }
+
+    /// Record the precision of every pair listed (snapshot or update)
+    fn parse_instrument_message(
+        &self,
+        raw: &str,
+    ) -> Result<Option<MarketMessage>, Box<dyn Error + Send>> {
+        let mut scratch = json::Scratch::default();
+        let msg: KrakenInstrumentMessage = json::from_str(raw, &mut scratch)?;
+        for pair in msg.data.pairs {
+            self.precisions.insert(
+                pair.symbol,
+                PairPrecision {
+                    price: pair.price_precision,
+                    qty: pair.qty_precision,
+                },
+            );
+        }
         Ok(None)
     }
 
+    /// Fold the decimals used by these levels into the pair's price precision
+    ///
+    /// A snapshot restarts the inference; deltas can only refine it.
+    fn record_price_decimals(
+        &self,
+        symbol: &str,
+        bids: &[(u64, u64)],
+        asks: &[(u64, u64)],
+        is_snapshot: bool,
+    ) -> u32 {
+        let seen = bids
+            .iter()
+            .chain(asks)
+            .map(|&(price, _)| decimals(price))
+            .max()
+            .unwrap_or(0);
+        let mut entry = self.price_decimals.entry(symbol.to_string()).or_insert(0);
+        if is_snapshot {
+            *entry = seen;
+        } else {
+            *entry = (*entry).max(seen);
+        }
+        *entry
+    }
+
     fn parse_trade_message(
         &self,
         raw: &str,
     ) -> Result<Option<MarketMessage>, Box<dyn Error + Send>> {
-        let msg: KrakenTradeMessage =
-            serde_json::from_str(raw).map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+        let mut scratch = json::Scratch::default();
+        let msg: KrakenTradeMessage = json::from_str(raw, &mut scratch)?;
+        let mut trades = Vec::with_capacity(msg.data.len());
         for data in msg.data {
-            // Convert BTC/USD -> BTCUSDT
-            let symbol = data.symbol.replace("/USD", "USDT");
+            let symbol = self.canonical_symbol(&data.symbol);
 
             // Kraken envoie des f64, on les convertit en u64 scaled
             let price = match fast_parse_f64_inner(data.price) {
@@ -175,23 +335,22 @@ impl KrakenConnector {
             };
 
             // Kraken timestamp est en format ISO 8601, parser vers milliseconds
-            let timestamp = chrono::DateTime::parse_from_rfc3339(&data.timestamp)
-                .map(|dt| dt.timestamp_millis())
-                .unwrap_or(0);
+            let timestamp = parse_rfc3339_millis(&data.timestamp).unwrap_or(0);
 
             let trade = Trade {
                 exchange: "Kraken".to_string(),
                 symbol,
                 price,
                 quantity,
+                notional: Trade::notional_of(price, quantity),
                 side,
                 timestamp,
             };
 
-            return Ok(Some(MarketMessage::Trade(trade)));
+            trades.push(trade);
         }
 
-        Ok(None)
+        Ok(MarketMessage::from_trades(trades))
     }
 
     /// Kraken sends initial snapshot via WebSocket, so REST fetch not needed
@@ -204,9 +363,29 @@ impl KrakenConnector {
         Ok(None)
     }
 
+    /// Kraken sends one snapshot then deltas
+    pub fn always_snapshot(&self) -> bool {
+        false
+    }
+
     pub fn supported_symbols(&self) -> Vec<String> {
         self.symbols.clone()
     }
+
+    pub fn set_symbols(&mut self, symbols: Vec<String>) {
+        self.symbol_map.set_symbols(&symbols);
+        self.symbols = symbols;
+    }
+}
+
+/// Decimal places needed to print a scaled price exactly (0 to 8)
+fn decimals(mut scaled: u64) -> u32 {
+    let mut decimals = 8;
+    while decimals > 0 && scaled.is_multiple_of(10) {
+        scaled /= 10;
+        decimals -= 1;
+    }
+    decimals
 }
 
 // Kraken-specific types
@@ -245,13 +424,33 @@ struct KrakenSubscribe {
 #[derive(Debug, serde::Serialize)]
 struct KrakenSubscribeParams {
     channel: String,
+    /// Empty for the instrument channel, which lists every pair
+    #[serde(skip_serializing_if = "Vec::is_empty")]
     symbol: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    depth: Option<u32>,
+    depth: Option<usize>,
     #[serde(skip_serializing_if = "Option::is_none")]
     snapshot: Option<bool>,
 }
 
+#[derive(Debug, Deserialize)]
+struct KrakenInstrumentMessage {
+    data: KrakenInstrumentData,
+}
+
+#[derive(Debug, Deserialize)]
+struct KrakenInstrumentData {
+    #[serde(default)]
+    pairs: Vec<KrakenPair>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KrakenPair {
+    symbol: String,
+    price_precision: u32,
+    qty_precision: u32,
+}
+
 #[derive(Debug, Deserialize)]
 struct KrakenTradeMessage {
     #[allow(dead_code)]
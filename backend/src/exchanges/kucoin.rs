@@ -0,0 +1,460 @@
+use super::json;
+use super::symbols::{quote_asset, Channel, ChannelFilter};
+use super::utils::{fast_parse_u64, fast_parse_u64_inner};
+/// KuCoin exchange connector (spot public WebSocket)
+///
+/// KuCoin has no fixed WebSocket URL: each connection first asks the REST
+/// `bullet-public` endpoint for a token and a server, and must then send its
+/// own JSON ping within the `pingInterval` that endpoint returns (18s today),
+/// or the server drops it after `pingTimeout`.
+use super::{DepthSnapshot, Exchange, MarketMessage, NetworkTimeouts, SubscribePacing};
+use crate::types::{Trade, TradeSide};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Spot REST API base URL
+const REST_URL: &str = "https://api.kucoin.com";
+/// KuCoin accepts at most this many symbols per topic
+const MAX_SYMBOLS_PER_TOPIC: usize = 100;
+/// Ping interval until the bootstrap returns the server's own
+const DEFAULT_PING_INTERVAL_MS: u64 = 18_000;
+
+#[derive(Clone)]
+pub struct KuCoinConnector {
+    symbols: Vec<String>,
+    channels: ChannelFilter,
+    pacing: SubscribePacing,
+    /// REST client for the token bootstrap and depth snapshots
+    http: reqwest::Client,
+    rest_url: String,
+    /// Ping interval the last bootstrap asked for, in milliseconds
+    ping_interval_ms: Arc<AtomicU64>,
+}
+
+impl KuCoinConnector {
+    pub fn new(symbols: Vec<String>) -> Self {
+        Self {
+            symbols,
+            channels: ChannelFilter::default(),
+            pacing: SubscribePacing::new(MAX_SYMBOLS_PER_TOPIC, Duration::ZERO),
+            http: Self::http_client(NetworkTimeouts::default()),
+            rest_url: REST_URL.to_string(),
+            ping_interval_ms: Arc::new(AtomicU64::new(DEFAULT_PING_INTERVAL_MS)),
+        }
+    }
+
+    /// Bootstrap and fetch snapshots from another REST host (tests serve them locally)
+    #[cfg(test)]
+    pub fn with_rest_url(mut self, rest_url: &str) -> Self {
+        self.rest_url = rest_url.to_string();
+        self
+    }
+
+    fn http_client(timeouts: NetworkTimeouts) -> reqwest::Client {
+        reqwest::Client::builder()
+            .connect_timeout(timeouts.connect)
+            .read_timeout(timeouts.read)
+            .build()
+            .unwrap_or_else(|e| {
+                tracing::warn!("[KuCoin] HTTP client without timeouts: {}", e);
+                reqwest::Client::new()
+            })
+    }
+
+    pub fn set_timeouts(&mut self, timeouts: NetworkTimeouts) {
+        self.http = Self::http_client(timeouts);
+    }
+
+    /// Ask for a connection token and build the WebSocket URL from it
+    ///
+    /// Tokens are single-use in practice (24h validity), so this runs on every
+    /// (re)connect.
+    pub async fn build_subscription_url(
+        &self,
+        _symbols: &[&str],
+    ) -> Result<String, Box<dyn Error + Send>> {
+        let url = format!("{}/api/v1/bullet-public", self.rest_url);
+        let response: KuCoinResponse<KuCoinBullet> = self
+            .http
+            .post(&url)
+            .send()
+            .await
+            .map_err(|e| Box::new(e) as Box<dyn Error + Send>)?
+            .json()
+            .await
+            .map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+        let bullet = response.into_data()?;
+
+        let server = bullet
+            .instance_servers
+            .into_iter()
+            .next()
+            .ok_or_else(|| boxed_error("bullet-public returned no instance server"))?;
+        self.ping_interval_ms
+            .store(server.ping_interval, Ordering::Relaxed);
+
+        let connect_id = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        Ok(format!(
+            "{}?token={}&connectId={}",
+            server.endpoint, bullet.token, connect_id
+        ))
+    }
+
+    /// Client ping KuCoin requires, with the interval the bootstrap asked for
+    pub fn keepalive(&self) -> (Duration, String) {
+        let interval = Duration::from_millis(self.ping_interval_ms.load(Ordering::Relaxed));
+        (interval, r#"{"id":"ping","type":"ping"}"#.to_string())
+    }
+
+    /// Native symbol for a channel ("BTCUSDT" -> "BTC-USDT" on both channels)
+    pub fn exchange_symbol(&self, symbol: &str, _channel: Channel) -> String {
+        match quote_asset(symbol) {
+            Some(quote) => format!("{}-{}", &symbol[..symbol.len() - quote.len()], quote),
+            None => symbol.to_string(),
+        }
+    }
+
+    /// Canonical symbol from a native one ("BTC-USDT" -> "BTCUSDT")
+    pub fn canonical_symbol(&self, native: &str) -> String {
+        native.replace('-', "")
+    }
+
+    /// Build subscription messages: one topic per channel, symbols comma-separated
+    pub fn get_subscription_messages(&self, symbols: &[&str]) -> Vec<String> {
+        [
+            (Channel::Book, "/market/level2"),
+            (Channel::Trades, "/market/match"),
+        ]
+        .into_iter()
+        .flat_map(|(channel, topic)| {
+            let natives: Vec<String> = symbols
+                .iter()
+                .filter(|s| self.channels.wants(s, channel))
+                .map(|s| self.exchange_symbol(s, channel))
+                .collect();
+            self.pacing
+                .chunks(&natives)
+                .filter(|chunk| !chunk.is_empty())
+                .map(|chunk| format!("{}:{}", topic, chunk.join(",")))
+                .collect::<Vec<_>>()
+        })
+        .enumerate()
+        .map(|(i, topic)| {
+            serde_json::to_string(&KuCoinSubscribe {
+                id: (i + 1).to_string(),
+                type_: "subscribe",
+                topic,
+                private_channel: false,
+                response: true,
+            })
+            .unwrap_or_default()
+        })
+        .collect()
+    }
+
+    pub fn subscribe_pacing(&self) -> SubscribePacing {
+        self.pacing
+    }
+
+    pub fn set_subscribe_pacing(&mut self, pacing: SubscribePacing) {
+        self.pacing = pacing;
+    }
+
+    /// Subscribe only the channels `filter` enables per symbol
+    pub fn set_channel_filter(&mut self, filter: ChannelFilter) {
+        self.channels = filter;
+    }
+
+    pub fn parse_message(&self, raw: &str) -> Result<Option<MarketMessage>, Box<dyn Error + Send>> {
+        // KuCoin format: {"type": "message", "topic": "/market/level2:BTC-USDT", "data": {...}}
+        // Only the header is read first (data is skipped), then the matching typed parser runs
+        #[derive(Deserialize)]
+        struct TopicHeader<'a> {
+            #[serde(rename = "type")]
+            type_: &'a str,
+            topic: Option<&'a str>,
+        }
+
+        let mut scratch = json::Scratch::default();
+        let header: TopicHeader = json::from_str(raw, &mut scratch)?;
+
+        // Welcome, subscription acks and pongs carry no market data
+        match header.type_ {
+            "message" => {}
+            "error" => {
+                tracing::warn!("[KuCoin] Error message: {}", raw);
+                return Ok(None);
+            }
+            _ => return Ok(None),
+        }
+        let Some((channel, native)) = header.topic.and_then(|t| t.split_once(':')) else {
+            return Ok(None);
+        };
+
+        match channel {
+            "/market/level2" => self.parse_depth_message(raw, native),
+            "/market/match" => self.parse_trade_message(raw, native),
+            _ => Ok(None),
+        }
+    }
+
+    fn parse_depth_message(
+        &self,
+        raw: &str,
+        native: &str,
+    ) -> Result<Option<MarketMessage>, Box<dyn Error + Send>> {
+        // Zero-copy: prices and quantities borrow from `raw`
+        let mut scratch = json::Scratch::default();
+        let msg: KuCoinDepthMessage = json::from_str(raw, &mut scratch)?;
+        let data = msg.data;
+        let (bids, bid_sequences) = Self::to_levels(&data.changes.bids);
+        let (asks, ask_sequences) = Self::to_levels(&data.changes.asks);
+
+        Ok(Some(MarketMessage::DepthUpdate {
+            exchange: Exchange::KuCoin,
+            symbol: self.canonical_symbol(native),
+            bids,
+            asks,
+            update_id: data.sequence_end,
+            // Sequences are contiguous across messages, like Binance's ids
+            first_update_id: Some(data.sequence_start),
+            prev_update_id: None,
+            is_snapshot: false, // Deltas only, the book is seeded over REST
+            checksum: None,
+            // The first delta after a snapshot straddles it: some changes are in it
+            change_sequences: Some((bid_sequences, ask_sequences)),
+        }))
+    }
+
+    /// [price, size, sequence] -> (price, qty) and sequence, skipping
+    /// sequence-only "0" prices
+    fn to_levels(levels: &[[&str; 3]]) -> (Vec<(u64, u64)>, Vec<u64>) {
+        levels
+            .iter()
+            .filter_map(|[price, qty, sequence]| {
+                Some((
+                    (fast_parse_u64_inner(price)?, fast_parse_u64_inner(qty)?),
+                    sequence.parse::<u64>().ok()?,
+                ))
+            })
+            .filter(|&((price, _), _)| price > 0)
+            .unzip()
+    }
+
+    fn parse_trade_message(
+        &self,
+        raw: &str,
+        native: &str,
+    ) -> Result<Option<MarketMessage>, Box<dyn Error + Send>> {
+        let mut scratch = json::Scratch::default();
+        let msg: KuCoinTradeMessage = json::from_str(raw, &mut scratch)?;
+        let data = msg.data;
+
+        let side = match data.side {
+            "buy" => TradeSide::Buy,
+            "sell" => TradeSide::Sell,
+            _ => return Ok(None),
+        };
+        let (Some(price), Some(quantity)) = (
+            fast_parse_u64_inner(data.price),
+            fast_parse_u64_inner(data.size),
+        ) else {
+            return Ok(None); // Skip malformed trade
+        };
+        // Match times are in nanoseconds
+        let Some(timestamp) = data.time.parse::<i64>().ok().map(|ns| ns / 1_000_000) else {
+            return Ok(None);
+        };
+
+        Ok(Some(MarketMessage::Trade(Trade {
+            exchange: "KuCoin".to_string(),
+            symbol: self.canonical_symbol(native),
+            price,
+            quantity,
+            notional: Trade::notional_of(price, quantity),
+            side,
+            timestamp,
+        })))
+    }
+
+    /// Seed the book from the public top-N REST endpoint
+    pub async fn fetch_snapshot(
+        &self,
+        symbol: &str,
+        limit: usize,
+    ) -> Result<Option<DepthSnapshot>, Box<dyn Error + Send>> {
+        // Trades-only symbols have no book to seed
+        if !self.channels.wants(symbol, Channel::Book) {
+            return Ok(None);
+        }
+        // Only 20 and 100 levels are public (the full book needs an API key)
+        let depth = if limit <= 20 { 20 } else { 100 };
+        let url = format!(
+            "{}/api/v1/market/orderbook/level2_{}?symbol={}",
+            self.rest_url,
+            depth,
+            self.exchange_symbol(symbol, Channel::Book)
+        );
+
+        let response: KuCoinResponse<KuCoinDepthSnapshot> = self
+            .http
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| Box::new(e) as Box<dyn Error + Send>)?
+            .json()
+            .await
+            .map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+        let snapshot = response.into_data()?;
+
+        Ok(Some(DepthSnapshot {
+            bids: snapshot.bids,
+            asks: snapshot.asks,
+            last_update_id: parse_sequence(&snapshot.sequence)?,
+        }))
+    }
+
+    /// KuCoin streams deltas after the REST snapshot
+    pub fn always_snapshot(&self) -> bool {
+        false
+    }
+
+    pub fn supported_symbols(&self) -> Vec<String> {
+        self.symbols.clone()
+    }
+
+    pub fn set_symbols(&mut self, symbols: Vec<String>) {
+        self.symbols = symbols;
+    }
+}
+
+fn boxed_error(message: &str) -> Box<dyn Error + Send> {
+    Box::<dyn Error + Send + Sync>::from(message.to_string())
+}
+
+fn parse_sequence(sequence: &str) -> Result<u64, Box<dyn Error + Send>> {
+    sequence
+        .parse()
+        .map_err(|_| boxed_error(&format!("invalid snapshot sequence {:?}", sequence)))
+}
+
+// KuCoin-specific types
+
+#[derive(Debug, Serialize)]
+struct KuCoinSubscribe {
+    id: String,
+    #[serde(rename = "type")]
+    type_: &'static str,
+    topic: String,
+    #[serde(rename = "privateChannel")]
+    private_channel: bool,
+    response: bool,
+}
+
+/// REST envelope: `code` is "200000" on success
+#[derive(Debug, Deserialize)]
+struct KuCoinResponse<T> {
+    code: String,
+    #[serde(default)]
+    msg: Option<String>,
+    data: Option<T>,
+}
+
+impl<T> KuCoinResponse<T> {
+    fn into_data(self) -> Result<T, Box<dyn Error + Send>> {
+        match self.data {
+            Some(data) if self.code == "200000" => Ok(data),
+            _ => Err(boxed_error(&format!(
+                "KuCoin REST error {}: {}",
+                self.code,
+                self.msg.unwrap_or_default()
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct KuCoinBullet {
+    token: String,
+    #[serde(rename = "instanceServers")]
+    instance_servers: Vec<KuCoinInstanceServer>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KuCoinInstanceServer {
+    endpoint: String,
+    #[serde(rename = "pingInterval", default = "default_ping_interval")]
+    ping_interval: u64,
+}
+
+fn default_ping_interval() -> u64 {
+    DEFAULT_PING_INTERVAL_MS
+}
+
+#[derive(Debug, Deserialize)]
+struct KuCoinDepthSnapshot {
+    sequence: String,
+    #[serde(deserialize_with = "deserialize_price_levels")]
+    bids: Vec<(u64, u64)>,
+    #[serde(deserialize_with = "deserialize_price_levels")]
+    asks: Vec<(u64, u64)>,
+}
+
+fn deserialize_price_levels<'de, D>(deserializer: D) -> Result<Vec<(u64, u64)>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::de::Error;
+    let raw: Vec<(String, String)> = Vec::deserialize(deserializer)?;
+    raw.into_iter()
+        .map(|(p, q)| {
+            let price = fast_parse_u64(&p).map_err(D::Error::custom)?;
+            let qty = fast_parse_u64(&q).map_err(D::Error::custom)?;
+            Ok((price, qty))
+        })
+        .collect()
+}
+
+#[derive(Debug, Deserialize)]
+struct KuCoinDepthMessage<'a> {
+    #[serde(borrow)]
+    data: KuCoinDepthData<'a>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KuCoinDepthData<'a> {
+    #[serde(borrow)]
+    changes: KuCoinChanges<'a>,
+    #[serde(rename = "sequenceStart")]
+    sequence_start: u64,
+    #[serde(rename = "sequenceEnd")]
+    sequence_end: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct KuCoinChanges<'a> {
+    #[serde(borrow, default)]
+    bids: Vec<[&'a str; 3]>,
+    #[serde(borrow, default)]
+    asks: Vec<[&'a str; 3]>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KuCoinTradeMessage<'a> {
+    #[serde(borrow)]
+    data: KuCoinTradeData<'a>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KuCoinTradeData<'a> {
+    price: &'a str,
+    size: &'a str,
+    side: &'a str,
+    time: &'a str,
+}
@@ -1,38 +1,173 @@
 /// Manages WebSocket connections to multiple exchanges with auto-reconnect
-use super::{ExchangeConnector, MarketMessage};
+use super::{
+    Exchange, ExchangeConnector, MarketMessage, NetworkTimeouts, ParseResult, SymbolRegistry,
+};
+use crate::health::{create_shared_connection_health, ConnectionHealth, SharedConnectionHealth};
 use crate::metrics::SharedMetrics;
-use crate::orderbook::SharedOrderBookManager;
-use crate::types::ClientMessage;
-use futures_util::{SinkExt, StreamExt};
+use crate::orderbook::{SharedOrderBookManager, UpdateSequence};
+use crate::publisher::MarketPublisher;
+use crate::telemetry;
+use crate::types::{ClientMessage, MAX_TRADE_AGE_MS, MAX_TRADE_FUTURE_SKEW_MS};
+use futures_util::stream::FuturesOrdered;
+use futures_util::{Sink, SinkExt, Stream, StreamExt};
 use std::error::Error;
+use std::io;
 use std::sync::Arc;
-use tokio::sync::broadcast;
-use tokio_tungstenite::{connect_async, tungstenite::Message as WsMessage};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, watch};
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{Error as WsError, Message as WsMessage},
+};
 
+/// Whether a trade time (epoch ms) can be bucketed into candles
+///
+/// Rejects zero (failed parse), far-past and future timestamps relative to `now_ms`.
+fn plausible_trade_timestamp(timestamp: i64, now_ms: i64) -> bool {
+    timestamp > 0
+        && timestamp <= now_ms + MAX_TRADE_FUTURE_SKEW_MS
+        && timestamp >= now_ms - MAX_TRADE_AGE_MS
+}
+
+/// First reconnect delay, doubled on each consecutive failure
+const RECONNECT_BASE_DELAY: Duration = Duration::from_secs(1);
+/// Upper bound of the reconnect delay during a long outage
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(60);
+/// A connection up at least this long resets the delay to the base
+const RECONNECT_STABLE_AFTER: Duration = Duration::from_secs(60);
+
+/// Delay before reconnect `attempt` (0-based): 1s, 2s, 4s, ... capped at 60s
+fn reconnect_delay(attempt: u32) -> Duration {
+    RECONNECT_BASE_DELAY
+        .saturating_mul(1u32.checked_shl(attempt).unwrap_or(u32::MAX))
+        .min(RECONNECT_MAX_DELAY)
+}
+
+/// Spread `delay` by up to ±10% so exchanges aren't hit by every task at once
+///
+/// `seed` is any varying value; the sub-second clock is good enough here.
+fn jittered(delay: Duration, seed: u32) -> Duration {
+    let spread = delay / 10;
+    let offset = spread.mul_f64((seed % 2001) as f64 / 1000.0);
+    (delay + offset).saturating_sub(spread)
+}
+
+/// Wrap a WebSocket read half so that `idle` without any frame ends it with a timeout error
+///
+/// Pings count as traffic, so this only fires on a connection that is open but
+/// dead; the error then goes through the usual reconnect path.
+fn with_idle_timeout(
+    stream: impl Stream<Item = Result<WsMessage, WsError>> + Unpin,
+    idle: Duration,
+) -> impl Stream<Item = Result<WsMessage, WsError>> + Unpin {
+    Box::pin(futures_util::stream::unfold(
+        Some(stream),
+        move |stream| async move {
+            let mut stream = stream?;
+            match tokio::time::timeout(idle, stream.next()).await {
+                Ok(Some(item)) => Some((item, Some(stream))),
+                Ok(None) => None,
+                Err(_) => {
+                    let error = io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        format!("no message for {:?}", idle),
+                    );
+                    Some((Err(WsError::Io(error)), None))
+                }
+            }
+        },
+    ))
+}
 
 /// Multi-Exchange Manager
 ///
 /// Manages connections to multiple exchanges and unifies their market data streams
 pub struct ExchangeManager {
     connectors: Vec<ExchangeConnector>,
+    /// Tracked symbols; connections resubscribe when it changes
+    symbol_registry: SymbolRegistry,
     orderbook_manager: SharedOrderBookManager,
     metrics: SharedMetrics,
+    /// Max messages parsed concurrently per exchange (0 = parse inline on the read task)
+    parse_workers: usize,
+    /// Period of the background REST resnapshot (zero = disabled)
+    snapshot_refresh: Duration,
+    /// WebSocket connect and idle limits before reconnecting
+    timeouts: NetworkTimeouts,
+    /// Fan-out of every book and trade message to Redis (disabled by default)
+    publisher: MarketPublisher,
+    /// Connection state per exchange, for readiness probes
+    health: SharedConnectionHealth,
 }
 
 impl ExchangeManager {
     /// Create a new manager with multiple exchange connectors
     pub fn new(
         connectors: Vec<ExchangeConnector>,
+        symbol_registry: SymbolRegistry,
         orderbook_manager: SharedOrderBookManager,
         metrics: SharedMetrics,
     ) -> Self {
+        let exchanges: Vec<&'static str> = connectors
+            .iter()
+            .map(|connector| connector.exchange().name())
+            .collect();
         Self {
+            health: create_shared_connection_health(&exchanges),
             connectors,
+            symbol_registry,
             orderbook_manager,
-            metrics,
+            metrics: Arc::clone(&metrics),
+            parse_workers: 0,
+            snapshot_refresh: Duration::ZERO,
+            timeouts: NetworkTimeouts::default(),
+            publisher: MarketPublisher::new(Arc::clone(&metrics)),
         }
     }
 
+    /// Parse up to `parse_workers` messages per exchange in parallel on the Tokio pool
+    ///
+    /// Results are still applied to the books in arrival order, so per-symbol
+    /// ordering is preserved. 0 keeps the default inline parsing.
+    pub fn with_parse_workers(mut self, parse_workers: usize) -> Self {
+        self.parse_workers = parse_workers;
+        self
+    }
+
+    /// Periodically replace books with a fresh REST snapshot to undo slow drift
+    ///
+    /// Runs alongside the WebSocket, which stays connected. Exchanges that only
+    /// send snapshots over the WebSocket are skipped. Zero disables it.
+    pub fn with_snapshot_refresh(mut self, interval: Duration) -> Self {
+        self.snapshot_refresh = interval;
+        self
+    }
+
+    /// Bound WebSocket connects and REST snapshot requests
+    ///
+    /// A timeout fails the attempt like any other connection error, so the
+    /// usual reconnect backoff applies.
+    pub fn with_timeouts(mut self, timeouts: NetworkTimeouts) -> Self {
+        self.timeouts = timeouts;
+        self.connectors = self
+            .connectors
+            .into_iter()
+            .map(|connector| connector.with_timeouts(timeouts))
+            .collect();
+        self
+    }
+
+    /// Also publish every depth update and trade received (see `publisher`)
+    pub fn with_publisher(mut self, publisher: MarketPublisher) -> Self {
+        self.publisher = publisher;
+        self
+    }
+
+    /// Connection state of every exchange, kept up to date by the connection tasks
+    pub fn connection_health(&self) -> SharedConnectionHealth {
+        Arc::clone(&self.health)
+    }
+
     /// Start all exchange connections (spawns one task per exchange)
     pub async fn start_all(
         &self,
@@ -43,14 +178,40 @@ impl ExchangeManager {
         tracing::info!("Starting {} exchange connection(s)", self.connectors.len());
 
         for connector in &self.connectors {
+            // Safety-net resnapshot, next to (not instead of) the live stream
+            if !self.snapshot_refresh.is_zero() {
+                let handle = tokio::spawn(Self::run_snapshot_refresh(
+                    connector.clone(),
+                    self.symbol_registry.clone(),
+                    self.orderbook_manager.clone(),
+                    self.snapshot_refresh,
+                ));
+                handles.push(handle);
+            }
+
             let connector = connector.clone();
+            let symbols_rx = self.symbol_registry.subscribe();
             let broadcast_tx = client_broadcast_tx.clone();
             let orderbook_manager = self.orderbook_manager.clone();
             let metrics = self.metrics.clone();
+            let parse_workers = self.parse_workers;
+            let timeouts = self.timeouts;
+            let publisher = self.publisher.clone();
+            let health = Arc::clone(&self.health);
 
             let handle = tokio::spawn(async move {
-                Self::run_exchange_connection(connector, broadcast_tx, orderbook_manager, metrics)
-                    .await;
+                Self::run_exchange_connection(
+                    connector,
+                    symbols_rx,
+                    broadcast_tx,
+                    orderbook_manager,
+                    metrics,
+                    publisher,
+                    parse_workers,
+                    timeouts,
+                    health,
+                )
+                .await;
             });
 
             handles.push(handle);
@@ -59,35 +220,147 @@ impl ExchangeManager {
         handles
     }
 
+    /// Background resnapshot of one exchange's books every `period`
+    ///
+    /// Stops as soon as the exchange turns out to have no REST snapshots.
+    async fn run_snapshot_refresh(
+        connector: ExchangeConnector,
+        symbol_registry: SymbolRegistry,
+        orderbook_manager: SharedOrderBookManager,
+        period: Duration,
+    ) {
+        let exchange_name = connector.exchange().name();
+        let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+
+            let mut refreshed = 0;
+            for symbol in symbol_registry.current() {
+                // Books not initialized yet are handled by the connection itself
+                let last_update_id = match orderbook_manager.get(exchange_name, &symbol) {
+                    Some(book) if book.is_initialized() => book.last_update_id(),
+                    _ => continue,
+                };
+
+                match connector
+                    .fetch_snapshot(&symbol, orderbook_manager.max_depth())
+                    .await
+                {
+                    Ok(Some(snapshot)) => {
+                        let mut book = orderbook_manager.get_or_create(exchange_name, &symbol);
+                        // A delta may have landed while the request was in flight
+                        if snapshot.last_update_id < last_update_id.max(book.last_update_id()) {
+                            tracing::debug!(
+                                "[{}] Stale refresh snapshot for {}, keeping the live book",
+                                exchange_name,
+                                symbol
+                            );
+                            continue;
+                        }
+                        if book.initialize_from_snapshot(
+                            snapshot.bids,
+                            snapshot.asks,
+                            snapshot.last_update_id,
+                        ) {
+                            refreshed += 1;
+                        }
+                    }
+                    Ok(None) => {
+                        tracing::debug!(
+                            "[{}] WebSocket snapshots only, no periodic refresh",
+                            exchange_name
+                        );
+                        return;
+                    }
+                    Err(e) => {
+                        tracing::warn!(
+                            "[{}] Refresh snapshot failed for {}: {}",
+                            exchange_name,
+                            symbol,
+                            e
+                        );
+                    }
+                }
+            }
+
+            if refreshed > 0 {
+                tracing::info!(
+                    "[{}] Refreshed {} order book(s) from REST",
+                    exchange_name,
+                    refreshed
+                );
+            }
+        }
+    }
+
     /// Run a single exchange connection with auto-reconnect
+    ///
+    /// A change of the symbol set drops the current connection and immediately
+    /// reconnects with the new subscriptions.
+    #[allow(clippy::too_many_arguments)]
     async fn run_exchange_connection(
-        connector: ExchangeConnector,
+        mut connector: ExchangeConnector,
+        mut symbols_rx: watch::Receiver<Vec<String>>,
         client_broadcast_tx: broadcast::Sender<ClientMessage>,
         orderbook_manager: SharedOrderBookManager,
         metrics: SharedMetrics,
+        publisher: MarketPublisher,
+        parse_workers: usize,
+        timeouts: NetworkTimeouts,
+        health: SharedConnectionHealth,
     ) {
         let exchange = connector.exchange();
         let exchange_name = exchange.name();
+        let mut attempt = 0;
 
         loop {
+            connector.set_symbols(symbols_rx.borrow_and_update().clone());
             tracing::info!("[{}] Starting connection...", exchange_name);
+            let started = Instant::now();
 
-            match Self::connect_and_process(
-                connector.clone(),
-                client_broadcast_tx.clone(),
-                Arc::clone(&orderbook_manager),
-                Arc::clone(&metrics),
-            )
-            .await
-            {
+            let result = tokio::select! {
+                result = Self::connect_and_process(
+                    connector.clone(),
+                    client_broadcast_tx.clone(),
+                    Arc::clone(&orderbook_manager),
+                    Arc::clone(&metrics),
+                    publisher.clone(),
+                    parse_workers,
+                    timeouts,
+                    &health,
+                ) => result,
+                Ok(()) = symbols_rx.changed() => {
+                    tracing::info!("[{}] Symbol set changed, resubscribing", exchange_name);
+                    continue;
+                }
+            };
+
+            health.set_disconnected(exchange_name);
+            if started.elapsed() >= RECONNECT_STABLE_AFTER {
+                attempt = 0;
+            }
+            let seed = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |d| d.subsec_nanos());
+            let delay = jittered(reconnect_delay(attempt), seed);
+            attempt = attempt.saturating_add(1);
+
+            match result {
                 Ok(_) => {
-                    tracing::info!("[{}] Connection closed gracefully", exchange_name);
+                    tracing::info!(
+                        "[{}] Connection closed gracefully, reconnecting in {:.1}s...",
+                        exchange_name,
+                        delay.as_secs_f64()
+                    );
                 }
                 Err(e) => {
                     tracing::error!(
-                        "[{}] Connection error: {}, reconnecting in 5s...",
+                        "[{}] Connection error: {}, reconnecting in {:.1}s...",
                         exchange_name,
-                        e
+                        e,
+                        delay.as_secs_f64()
                     );
                     metrics.record_reconnect();
                 }
@@ -100,16 +373,21 @@ impl ExchangeManager {
                 }
             }
 
-            tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+            tokio::time::sleep(delay).await;
         }
     }
 
     /// Connect to exchange and process messages
+    #[allow(clippy::too_many_arguments)]
     async fn connect_and_process(
         connector: ExchangeConnector,
         client_broadcast_tx: broadcast::Sender<ClientMessage>,
         orderbook_manager: SharedOrderBookManager,
         metrics: SharedMetrics,
+        publisher: MarketPublisher,
+        parse_workers: usize,
+        timeouts: NetworkTimeouts,
+        health: &ConnectionHealth,
     ) -> Result<(), Box<dyn Error + Send + Sync>> {
         let exchange_name = connector.exchange().name();
         let symbols_owned = connector.supported_symbols();
@@ -124,32 +402,83 @@ impl ExchangeManager {
         )
         .await;
 
+        // A replay reads its capture instead of a socket, with nothing to subscribe to
+        if let ExchangeConnector::Replay(replay) = &connector {
+            let mut frames = replay.frames().await?;
+            tracing::info!(
+                "[{}] Replaying {} through the {} parser",
+                exchange_name,
+                replay.path().display(),
+                replay.source().exchange().name()
+            );
+            health.set_connected(exchange_name);
+            return Self::process_messages(
+                &mut frames,
+                &connector,
+                client_broadcast_tx,
+                orderbook_manager,
+                metrics,
+                publisher,
+                exchange_name,
+                parse_workers,
+            )
+            .await;
+        }
+
         // 2. Connect to exchange WebSocket
-        let (mut exchange_ws_write, mut exchange_ws_read) =
-            Self::connect_websocket(&connector, &symbols).await?;
+        let (mut exchange_ws_write, exchange_ws_read) =
+            Self::connect_websocket(&connector, &symbols, timeouts.connect).await?;
+        // A connection that stays open but goes silent must not freeze the books
+        let mut exchange_ws_read = with_idle_timeout(exchange_ws_read, timeouts.idle);
 
         // 3. Subscribe to streams (if needed)
         Self::subscribe_to_streams(&connector, &symbols, &mut exchange_ws_write, exchange_name)
             .await?;
+        health.set_connected(exchange_name);
 
-        // 4. Process messages from exchange
-        Self::process_websocket_messages(
+        // 4. Process messages from exchange, pinging alongside if it asks for it
+        let processing = Self::process_messages(
             &mut exchange_ws_read,
             &connector,
             client_broadcast_tx,
             orderbook_manager,
             metrics,
+            publisher,
             exchange_name,
-        )
-        .await?;
+            parse_workers,
+        );
+        tokio::select! {
+            result = processing => result?,
+            result = Self::send_keepalives(&connector, &mut exchange_ws_write) => result?,
+        }
 
         Ok(())
     }
 
+    /// Send the exchange's application-level ping on its interval, forever
+    ///
+    /// Never completes for exchanges without one; fails once the socket can't
+    /// be written to.
+    async fn send_keepalives(
+        connector: &ExchangeConnector,
+        exchange_ws_write: &mut (impl Sink<WsMessage, Error = WsError> + Unpin),
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let Some((interval, ping)) = connector.keepalive() else {
+            return std::future::pending().await;
+        };
+        let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+        loop {
+            ticker.tick().await;
+            exchange_ws_write
+                .send(WsMessage::Text(ping.clone().into()))
+                .await?;
+        }
+    }
+
     /// Initialize orderbooks from REST API snapshots (if needed)
     ///
-    /// Exchanges that use WebSocket snapshots (Kraken, Coinbase, Bybit) return Ok(None).
-    /// Only Binance currently fetches REST snapshots.
+    /// Exchanges that use WebSocket snapshots (Kraken, Coinbase, Bybit, OKX) return Ok(None).
+    /// Binance and KuCoin fetch REST snapshots.
     async fn initialize_orderbooks_from_rest(
         connector: &ExchangeConnector,
         symbols: &[&str],
@@ -159,16 +488,20 @@ impl ExchangeManager {
         let mut initialized_count = 0;
 
         for symbol in symbols {
-            match connector.fetch_snapshot(symbol, 10).await {
+            match connector
+                .fetch_snapshot(symbol, orderbook_manager.max_depth())
+                .await
+            {
                 Ok(Some(snapshot)) => {
                     tracing::debug!("[{}] REST snapshot for {}", exchange_name, symbol);
                     let mut book = orderbook_manager.get_or_create(exchange_name, symbol);
-                    book.initialize_from_snapshot(
+                    if book.initialize_from_snapshot(
                         snapshot.bids,
                         snapshot.asks,
                         snapshot.last_update_id,
-                    );
-                    initialized_count += 1;
+                    ) {
+                        initialized_count += 1;
+                    }
                 }
                 Ok(None) => {
                     // Exchange uses WebSocket snapshots - skip REST fetch
@@ -195,10 +528,48 @@ impl ExchangeManager {
         }
     }
 
+    /// Rebuild a book from a fresh REST snapshot after a sequence gap
+    ///
+    /// Fails when no snapshot can be had, leaving the book desynced (not
+    /// served) until the reconnect rebuilds it.
+    async fn refetch_snapshot(
+        connector: &ExchangeConnector,
+        symbol: &str,
+        orderbook_manager: &SharedOrderBookManager,
+        exchange_name: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let snapshot = connector
+            .fetch_snapshot(symbol, orderbook_manager.max_depth())
+            .await;
+        let mut book = orderbook_manager.get_or_create(exchange_name, symbol);
+        let reason = match snapshot {
+            Ok(Some(snapshot)) => {
+                if book.initialize_from_snapshot(
+                    snapshot.bids,
+                    snapshot.asks,
+                    snapshot.last_update_id,
+                ) {
+                    return Ok(());
+                }
+                "rejected snapshot".to_string()
+            }
+            // Reconnecting gets a fresh snapshot over the WebSocket instead
+            Ok(None) => "no REST snapshot".to_string(),
+            Err(e) => e.to_string(),
+        };
+        book.mark_desynced();
+        Err(format!(
+            "[{}] {} snapshot re-fetch after a gap failed: {}",
+            exchange_name, symbol, reason
+        )
+        .into())
+    }
+
     /// Connect to exchange WebSocket
     async fn connect_websocket(
         connector: &ExchangeConnector,
         symbols: &[&str],
+        connect_timeout: Duration,
     ) -> Result<
         (
             futures_util::stream::SplitSink<
@@ -216,10 +587,15 @@ impl ExchangeManager {
         Box<dyn Error + Send + Sync>,
     > {
         let exchange_name = connector.exchange().name();
-        let url = connector.build_subscription_url(symbols);
+        let url = connector
+            .build_subscription_url(symbols)
+            .await
+            .map_err(|e| format!("WebSocket URL bootstrap failed: {}", e))?;
 
         tracing::info!("[{}] Connecting to WebSocket: {}...", exchange_name, url);
-        let (ws_stream, _) = connect_async(&url).await?;
+        let (ws_stream, _) = tokio::time::timeout(connect_timeout, connect_async(&url))
+            .await
+            .map_err(|_| format!("connect timed out after {:?}", connect_timeout))??;
         tracing::info!("[{}] WebSocket connected", exchange_name);
 
         Ok(ws_stream.split())
@@ -238,6 +614,7 @@ impl ExchangeManager {
         exchange_name: &str,
     ) -> Result<(), Box<dyn Error + Send + Sync>> {
         let sub_messages = connector.get_subscription_messages(symbols);
+        let pacing = connector.subscribe_pacing();
         if !sub_messages.is_empty() {
             tracing::info!(
                 "[{}] Sending {} subscription message(s)...",
@@ -246,6 +623,9 @@ impl ExchangeManager {
             );
 
             for (i, sub_msg) in sub_messages.iter().enumerate() {
+                if i > 0 && !pacing.delay.is_zero() {
+                    tokio::time::sleep(pacing.delay).await;
+                }
                 tracing::debug!(
                     "[{}] Sending subscription #{}: {}",
                     exchange_name,
@@ -262,17 +642,52 @@ impl ExchangeManager {
         Ok(())
     }
 
+    /// Process messages until the stream ends, in parallel when `parse_workers` > 0
+    #[allow(clippy::too_many_arguments)]
+    async fn process_messages(
+        exchange_ws_read: &mut (impl Stream<Item = Result<WsMessage, WsError>> + Unpin),
+        connector: &ExchangeConnector,
+        client_broadcast_tx: broadcast::Sender<ClientMessage>,
+        orderbook_manager: SharedOrderBookManager,
+        metrics: SharedMetrics,
+        publisher: MarketPublisher,
+        exchange_name: &str,
+        parse_workers: usize,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if parse_workers > 0 {
+            Self::process_websocket_messages_parallel(
+                exchange_ws_read,
+                connector,
+                client_broadcast_tx,
+                orderbook_manager,
+                metrics,
+                publisher,
+                exchange_name,
+                parse_workers,
+            )
+            .await
+        } else {
+            Self::process_websocket_messages(
+                exchange_ws_read,
+                connector,
+                client_broadcast_tx,
+                orderbook_manager,
+                metrics,
+                publisher,
+                exchange_name,
+            )
+            .await
+        }
+    }
+
     /// Process WebSocket messages in a loop
     async fn process_websocket_messages(
-        exchange_ws_read: &mut futures_util::stream::SplitStream<
-            tokio_tungstenite::WebSocketStream<
-                tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
-            >,
-        >,
+        exchange_ws_read: &mut (impl Stream<Item = Result<WsMessage, WsError>> + Unpin),
         connector: &ExchangeConnector,
         client_broadcast_tx: broadcast::Sender<ClientMessage>,
         orderbook_manager: SharedOrderBookManager,
         metrics: SharedMetrics,
+        publisher: MarketPublisher,
         exchange_name: &str,
     ) -> Result<(), Box<dyn Error + Send + Sync>> {
         while let Some(exchange_ws_msg) = exchange_ws_read.next().await {
@@ -286,9 +701,9 @@ impl ExchangeManager {
                         &client_broadcast_tx,
                         &orderbook_manager,
                         &metrics,
-                        exchange_name,
+                        &publisher,
                     )
-                    .await;
+                    .await?;
                 }
                 WsMessage::Binary(_) => {
                     // Some exchanges use binary messages
@@ -307,49 +722,222 @@ impl ExchangeManager {
         Ok(())
     }
 
+    /// Process WebSocket messages, parsing up to `parse_workers` of them in parallel
+    ///
+    /// Each text frame is parsed in its own Tokio task (work-stealing across cores),
+    /// while `FuturesOrdered` hands results back in arrival order so books are
+    /// updated exactly as with serial processing. Parse tasks finish in any
+    /// order, so connection sequence numbers are only checked once results are
+    /// back in arrival order (see `sequenced`).
+    #[allow(clippy::too_many_arguments)]
+    async fn process_websocket_messages_parallel(
+        exchange_ws_read: &mut (impl Stream<Item = Result<WsMessage, WsError>> + Unpin),
+        connector: &ExchangeConnector,
+        client_broadcast_tx: broadcast::Sender<ClientMessage>,
+        orderbook_manager: SharedOrderBookManager,
+        metrics: SharedMetrics,
+        publisher: MarketPublisher,
+        exchange_name: &str,
+        parse_workers: usize,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let shared_connector = Arc::new(connector.clone());
+        let mut in_flight = FuturesOrdered::new();
+        let mut stream_open = true;
+
+        while stream_open || !in_flight.is_empty() {
+            tokio::select! {
+                // Apply parsed messages in arrival order
+                Some(parsed) = in_flight.next(), if !in_flight.is_empty() => {
+                    match parsed {
+                        Ok((start, bytes, sequence, result)) => {
+                            Self::handle_parse_result(
+                                Self::sequenced(connector, sequence, result),
+                                start,
+                                bytes,
+                                connector,
+                                &client_broadcast_tx,
+                                &orderbook_manager,
+                                &metrics,
+                                &publisher,
+                            )
+                            .await?;
+                        }
+                        Err(e) => {
+                            tracing::error!("[{}] Parse task failed: {}", exchange_name, e);
+                        }
+                    }
+                }
+
+                // Read more only while the pool has room (bounded)
+                exchange_ws_msg = exchange_ws_read.next(), if stream_open && in_flight.len() < parse_workers => {
+                    match exchange_ws_msg {
+                        Some(Ok(WsMessage::Text(text))) => {
+                            let start = Instant::now();
+                            let bytes = text.len() as u64;
+                            metrics.record_bytes(connector.exchange(), bytes);
+
+                            let connector = Arc::clone(&shared_connector);
+                            in_flight.push_back(tokio::spawn(async move {
+                                let (sequence, result) = connector.parse_frame(&text);
+                                (start, bytes, sequence, result)
+                            }));
+                        }
+                        Some(Ok(WsMessage::Close(_))) => {
+                            tracing::info!("[{}] WebSocket closed by server", exchange_name);
+                            stream_open = false;
+                        }
+                        Some(Ok(_)) => {
+                            // Binary/Ping/Pong - ignore
+                        }
+                        Some(Err(e)) => return Err(e.into()),
+                        None => stream_open = false,
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// A frame's parse result once its connection sequence number (if any) is
+    /// checked; frames must come through here in arrival order
+    fn sequenced(
+        connector: &ExchangeConnector,
+        sequence: Option<u64>,
+        result: ParseResult,
+    ) -> ParseResult {
+        match sequence.and_then(|sequence| connector.sequence_resync(sequence)) {
+            Some(resync) => Ok(Some(resync)),
+            None => result,
+        }
+    }
+
     /// Handle a single text message from the WebSocket
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(
+            skip_all,
+            fields(exchange = connector.exchange().name(), bytes = text.len())
+        )
+    )]
     async fn handle_text_message(
         text: &str,
         connector: &ExchangeConnector,
         client_broadcast_tx: &broadcast::Sender<ClientMessage>,
         orderbook_manager: &SharedOrderBookManager,
         metrics: &SharedMetrics,
-        exchange_name: &str,
-    ) {
-        let start = std::time::Instant::now();
+        publisher: &MarketPublisher,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let start = Instant::now();
+        let bytes = text.len() as u64;
 
         // Record raw metrics
-        metrics.record_bytes(text.len() as u64);
+        metrics.record_bytes(connector.exchange(), bytes);
 
         // Parse message via connector
-        match connector.parse_message(text) {
+        let result = connector.parse_message(text);
+        Self::handle_parse_result(
+            result,
+            start,
+            bytes,
+            connector,
+            client_broadcast_tx,
+            orderbook_manager,
+            metrics,
+            publisher,
+        )
+        .await
+    }
+
+    /// Apply a parsed message to the books and record its latency from `start`
+    ///
+    /// `bytes` is the frame's size, attributed to the stream(s) it carried.
+    /// Fails when the connector asks for a resync, so the connection is
+    /// re-established and the books rebuilt from fresh snapshots.
+    #[allow(clippy::too_many_arguments)]
+    async fn handle_parse_result(
+        result: ParseResult,
+        start: Instant,
+        bytes: u64,
+        connector: &ExchangeConnector,
+        client_broadcast_tx: &broadcast::Sender<ClientMessage>,
+        orderbook_manager: &SharedOrderBookManager,
+        metrics: &SharedMetrics,
+        publisher: &MarketPublisher,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match result {
+            Ok(Some(MarketMessage::Resync { exchange, reason })) => {
+                // Any book on this connection may have missed the dropped message
+                let desynced = orderbook_manager
+                    .mark_desynced(exchange.name(), &connector.supported_symbols());
+                return Err(format!(
+                    "[{}] resync requested: {} ({} books desynced)",
+                    exchange.name(),
+                    reason,
+                    desynced
+                )
+                .into());
+            }
             Ok(Some(market_msg)) => {
-                // Check if it's a relevant message (not Raw) before processing
-                let is_relevant = !matches!(&market_msg, MarketMessage::Raw(_));
+                // Count before processing consumes it: a batch counts each message
+                let message_count = market_msg.message_count();
 
-                Self::process_market_message(market_msg, client_broadcast_tx, orderbook_manager)
-                    .await;
+                Self::process_market_message(
+                    market_msg,
+                    start,
+                    bytes,
+                    connector,
+                    client_broadcast_tx,
+                    orderbook_manager,
+                    metrics,
+                    publisher,
+                )
+                .await?;
 
                 metrics.record_latency(start);
-                if is_relevant {
-                    metrics.record_message();
+                if message_count > 0 {
+                    metrics.record_messages(message_count);
                 }
             }
             Ok(None) => {
                 // Message parsed but not relevant (e.g., heartbeat)
+                metrics.record_ignored();
             }
             Err(e) => {
-                tracing::debug!("[{}] Failed to parse message: {}", exchange_name, e);
+                tracing::debug!(
+                    "[{}] Failed to parse message: {}",
+                    connector.exchange().name(),
+                    e
+                );
             }
         }
+        Ok(())
     }
 
     /// Process a normalized market message and broadcast to clients
+    ///
+    /// When the connector is `always_snapshot`, every depth message fully
+    /// replaces the book. Fails when a book no longer matches the exchange's
+    /// checksum, or can't be rebuilt after a sequence gap, so the connection
+    /// is re-established like on a resync.
+    #[allow(clippy::too_many_arguments)]
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(
+            skip_all,
+            fields(exchange = connector.exchange().name(), symbol = tracing::field::Empty)
+        )
+    )]
     async fn process_market_message(
         msg: MarketMessage,
+        start: Instant,
+        bytes: u64,
+        connector: &ExchangeConnector,
         client_broadcast_tx: &broadcast::Sender<ClientMessage>,
         orderbook_manager: &SharedOrderBookManager,
-    ) {
+        metrics: &SharedMetrics,
+        publisher: &MarketPublisher,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
         match msg {
             MarketMessage::DepthUpdate {
                 exchange,
@@ -357,27 +945,766 @@ impl ExchangeManager {
                 bids,
                 asks,
                 update_id,
+                first_update_id,
+                prev_update_id,
                 is_snapshot,
+                checksum,
+                change_sequences,
             } => {
+                telemetry::record_symbol(&symbol);
+                if !orderbook_manager.accepts_symbol(exchange.name(), &symbol) {
+                    return Ok(());
+                }
+                metrics.record_depth();
+                metrics.record_stream(exchange, &symbol, bytes, start);
                 let exchange_name = exchange.name();
+                let is_snapshot = is_snapshot || connector.always_snapshot();
+
+                // Sequenced deltas must chain on, or the book is rebuilt from REST
+                if let Some(first_update_id) = first_update_id.filter(|_| !is_snapshot) {
+                    let sequence = |manager: &SharedOrderBookManager| {
+                        let book = manager.get_or_create(exchange_name, &symbol);
+                        book.is_initialized().then(|| {
+                            book.check_sequence(first_update_id, prev_update_id, update_id)
+                        })
+                    };
+                    let mut position = sequence(orderbook_manager);
+                    if position == Some(UpdateSequence::Gap) {
+                        tracing::warn!(
+                            "[{}] {} update id gap at {}..={}, re-fetching snapshot",
+                            exchange_name,
+                            symbol,
+                            first_update_id,
+                            update_id
+                        );
+                        Self::refetch_snapshot(
+                            connector,
+                            &symbol,
+                            orderbook_manager,
+                            exchange_name,
+                        )
+                        .await?;
+                        // The fresh snapshot most likely covers this delta already
+                        position = sequence(orderbook_manager);
+                    }
+                    if matches!(position, Some(UpdateSequence::Stale | UpdateSequence::Gap)) {
+                        return Ok(());
+                    }
+                }
+
+                // Changes the book's snapshot already covers would roll it back
+                let (bids, asks) = match change_sequences {
+                    Some((bid_sequences, ask_sequences)) => {
+                        let last = orderbook_manager
+                            .get(exchange_name, &symbol)
+                            .filter(|book| book.is_initialized())
+                            .map_or(0, |book| book.last_update_id());
+                        let newer = |levels: Vec<(u64, u64)>, sequences: Vec<u64>| {
+                            levels
+                                .into_iter()
+                                .zip(sequences)
+                                .filter(|&(_, sequence)| sequence > last)
+                                .map(|(level, _)| level)
+                                .collect::<Vec<_>>()
+                        };
+                        (newer(bids, bid_sequences), newer(asks, ask_sequences))
+                    }
+                    None => (bids, asks),
+                };
 
+                publisher.publish_depth(exchange, &symbol, &bids, &asks, update_id, is_snapshot);
                 let mut book = orderbook_manager.get_or_create(exchange_name, &symbol);
 
                 if is_snapshot {
-                    book.initialize_from_snapshot(bids, asks, update_id);
-                    tracing::debug!("[{}] Snapshot received for {}", exchange_name, symbol);
+                    if book.initialize_from_snapshot(bids, asks, update_id) {
+                        tracing::debug!("[{}] Snapshot received for {}", exchange_name, symbol);
+                    }
                     // No broadcast - server will poll orderbook state
                 } else {
-                    book.apply_update(bids, asks, 0, update_id);
+                    let change = book.apply_update(bids, asks, 0, update_id);
+                    metrics.record_top_of_book_change(&symbol, change.top_of_book);
                     // No broadcast - server will poll orderbook state
                 }
+
+                // A dropped delta would otherwise leave the book wrong for good
+                if let Some(checksum) = checksum.filter(|_| book.is_initialized()) {
+                    let computed = book.checksum(
+                        checksum.levels,
+                        checksum.price_decimals,
+                        checksum.qty_decimals,
+                    );
+                    if computed.is_some_and(|computed| computed != checksum.expected) {
+                        book.mark_desynced();
+                        return Err(format!(
+                            "[{}] {} checksum mismatch: expected {}, computed {:?}",
+                            exchange_name, symbol, checksum.expected, computed
+                        )
+                        .into());
+                    }
+                }
             }
             MarketMessage::Trade(trade) => {
+                telemetry::record_symbol(&trade.symbol);
+                if !orderbook_manager.accepts_symbol(&trade.exchange, &trade.symbol) {
+                    return Ok(());
+                }
+                metrics.record_trade();
+                // Relayed trades keep their origin venue
+                let exchange = Exchange::from_name(&trade.exchange).unwrap_or(connector.exchange());
+                metrics.record_stream(exchange, &trade.symbol, bytes, start);
+                // A single bad timestamp would open a garbage candle far from now
+                if !plausible_trade_timestamp(
+                    trade.timestamp,
+                    chrono::Utc::now().timestamp_millis(),
+                ) {
+                    metrics.record_rejected_trade();
+                    tracing::debug!(
+                        "[{}] Dropping {} trade with implausible timestamp {}",
+                        trade.exchange,
+                        trade.symbol,
+                        trade.timestamp
+                    );
+                    return Ok(());
+                }
+                // Overlaid on the book so clients get it without joining the streams
+                orderbook_manager
+                    .get_or_create(&trade.exchange, &trade.symbol)
+                    .record_trade_price(trade.price);
+                publisher.publish_trade(&trade);
                 let _ = client_broadcast_tx.send(ClientMessage::Trade(trade));
             }
+            MarketMessage::Batch(messages) => {
+                // The frame's bytes are shared evenly between its messages
+                let bytes = bytes / messages.len().max(1) as u64;
+                for msg in messages {
+                    Box::pin(Self::process_market_message(
+                        msg,
+                        start,
+                        bytes,
+                        connector,
+                        client_broadcast_tx,
+                        orderbook_manager,
+                        metrics,
+                        publisher,
+                    ))
+                    .await?;
+                }
+            }
             MarketMessage::Raw(_) => {
-                // Debug messages - ignore
+                // Status messages, logged by the connector - no market data
+                metrics.record_ignored();
+            }
+            MarketMessage::Resync { .. } => {
+                // Handled before processing (see handle_parse_result)
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchanges::{
+        BinanceConn, CoinbaseConn, FlowRsUpstreamConn, KrakenConn, KuCoinConn, ReplayConn,
+    };
+    use crate::metrics::create_shared_metrics;
+    use crate::orderbook::create_shared_orderbook_manager;
+    use crate::types::ORDERBOOK_DEPTH;
+    use rust_decimal::Decimal;
+
+    /// Binance depth delta setting the bid at 100.0 to `qty` on `symbol`
+    fn depth_update(symbol: &str, update_id: u64, qty: u64) -> WsMessage {
+        WsMessage::Text(
+            format!(
+                r#"{{"stream":"{}@depth@100ms","data":{{"s":"{}","U":{},"u":{},"b":[["100.0","{}"]],"a":[]}}}}"#,
+                symbol.to_lowercase(),
+                symbol,
+                update_id,
+                update_id,
+                qty
+            )
+            .into(),
+        )
+    }
+
+    /// Coinbase level2 frame `kind` ("snapshot" or "update") setting a BTC-USD
+    /// bid at `price` and the ask at 97001
+    fn coinbase_l2(sequence: u64, kind: &str, price: &str) -> String {
+        format!(
+            r#"{{"channel":"l2_data","sequence_num":{},"events":[{{"type":"{}","product_id":"BTC-USD","updates":[{{"side":"bid","price_level":"{}","new_quantity":"1"}},{{"side":"offer","price_level":"97001","new_quantity":"1"}}]}}]}}"#,
+            sequence, kind, price
+        )
+    }
+
+    #[test]
+    fn test_reconnect_delay_backs_off_exponentially_with_cap() {
+        let delays: Vec<u64> = (0..8).map(|a| reconnect_delay(a).as_secs()).collect();
+        assert_eq!(delays, vec![1, 2, 4, 8, 16, 32, 60, 60]);
+        assert_eq!(reconnect_delay(u32::MAX), RECONNECT_MAX_DELAY);
+
+        let delay = Duration::from_secs(10);
+        assert_eq!(jittered(delay, 0), Duration::from_secs(9));
+        assert_eq!(jittered(delay, 1000), delay);
+        assert_eq!(jittered(delay, 2000), Duration::from_secs(11));
+        for seed in [7, 999_999_999, u32::MAX] {
+            let d = jittered(delay, seed);
+            assert!(d >= Duration::from_secs(9) && d <= Duration::from_secs(11));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_silent_stream_times_out() {
+        let frames = futures_util::stream::iter(vec![Ok(WsMessage::Ping(Default::default()))])
+            .chain(futures_util::stream::pending());
+        let mut stream = with_idle_timeout(frames, Duration::from_millis(20));
+
+        assert!(matches!(stream.next().await, Some(Ok(WsMessage::Ping(_)))));
+        match stream.next().await {
+            Some(Err(WsError::Io(e))) => assert_eq!(e.kind(), io::ErrorKind::TimedOut),
+            other => panic!("expected a timeout, got {:?}", other),
+        }
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_parallel_parsing_preserves_per_symbol_order() {
+        let symbols = ["BTCUSDT", "ETHUSDT"];
+        let connector = ExchangeConnector::Binance(BinanceConn::new(
+            symbols.iter().map(|s| s.to_string()).collect(),
+        ));
+        let orderbook_manager =
+            create_shared_orderbook_manager(&["Binance"], &symbols, ORDERBOOK_DEPTH);
+        for symbol in symbols {
+            orderbook_manager
+                .get_or_create("Binance", symbol)
+                .initialize_from_snapshot(
+                    vec![(100 * 100_000_000, 1)],
+                    vec![(101 * 100_000_000, 1)],
+                    0,
+                );
+        }
+        let (tx, _rx) = broadcast::channel(16);
+
+        // Interleave both symbols; each bid quantity counts up with the update id
+        let updates = 500;
+        let messages: Vec<Result<WsMessage, WsError>> = (1..=updates)
+            .flat_map(|i| symbols.map(|symbol| Ok(depth_update(symbol, i, i))))
+            .collect();
+        let mut stream = futures_util::stream::iter(messages);
+
+        ExchangeManager::process_websocket_messages_parallel(
+            &mut stream,
+            &connector,
+            tx,
+            orderbook_manager.clone(),
+            create_shared_metrics(),
+            MarketPublisher::new(create_shared_metrics()),
+            "Binance",
+            8,
+        )
+        .await
+        .unwrap();
+
+        // Out-of-order application would leave an older quantity or update id behind
+        for symbol in symbols {
+            let book = orderbook_manager.get("Binance", symbol).unwrap();
+            assert_eq!(book.last_update_id(), updates);
+            let (bids, _) = book.get_top_levels(1);
+            assert_eq!(bids[0].quantity, Decimal::from(updates));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_parallel_parsing_checks_sequence_in_arrival_order() {
+        let connector = ExchangeConnector::Coinbase(CoinbaseConn::new(vec!["BTCUSDT".to_string()]));
+
+        // Frame 2 parsed before frame 1, checked in arrival order: no gap
+        let (second_sequence, second) = connector.parse_frame(&coinbase_l2(2, "update", "96999"));
+        let (first_sequence, first) = connector.parse_frame(&coinbase_l2(1, "snapshot", "97000"));
+        for (sequence, result) in [(first_sequence, first), (second_sequence, second)] {
+            assert!(matches!(
+                ExchangeManager::sequenced(&connector, sequence, result),
+                Ok(Some(MarketMessage::DepthUpdate { .. }))
+            ));
+        }
+
+        // And through the worker pool, where parse tasks finish in any order
+        let connector = ExchangeConnector::Coinbase(CoinbaseConn::new(vec!["BTCUSDT".to_string()]));
+        let orderbook_manager =
+            create_shared_orderbook_manager(&["Coinbase"], &["BTCUSDT"], ORDERBOOK_DEPTH);
+        let messages: Vec<Result<WsMessage, WsError>> =
+            std::iter::once(coinbase_l2(1, "snapshot", "97000"))
+                .chain((2..=500).map(|i| coinbase_l2(i, "update", "96999")))
+                .map(|raw| Ok(WsMessage::Text(raw.into())))
+                .collect();
+        let mut stream = futures_util::stream::iter(messages);
+        let (tx, _rx) = broadcast::channel(16);
+        ExchangeManager::process_websocket_messages_parallel(
+            &mut stream,
+            &connector,
+            tx,
+            orderbook_manager.clone(),
+            create_shared_metrics(),
+            MarketPublisher::new(create_shared_metrics()),
+            "Coinbase",
+            8,
+        )
+        .await
+        .unwrap();
+        assert!(orderbook_manager
+            .get("Coinbase", "BTCUSDT")
+            .unwrap()
+            .is_initialized());
+    }
+
+    #[tokio::test]
+    async fn test_always_snapshot_feed_replaces_books() {
+        use crate::orderbook::OrderBook;
+        use crate::types::ClientMessage;
+
+        let connector = ExchangeConnector::FlowRs(FlowRsUpstreamConn::new(
+            "ws://upstream:8080/ws".to_string(),
+            vec!["BTCUSDT".to_string()],
+        ));
+        let orderbook_manager =
+            create_shared_orderbook_manager(&["Kraken"], &["BTCUSDT"], ORDERBOOK_DEPTH);
+        let metrics = create_shared_metrics();
+        let publisher = MarketPublisher::new(create_shared_metrics());
+        let (tx, _rx) = broadcast::channel(16);
+        let one = crate::orderbook::PRICE_FACTOR;
+        let relay = |bids: Vec<(u64, u64)>, id: u64| {
+            let mut book = OrderBook::new("BTCUSDT", "Kraken");
+            book.initialize_from_snapshot(bids, vec![(97_001 * one, one)], id);
+            let raw =
+                serde_json::to_string(&ClientMessage::BookUpdate(book.to_book_update(10))).unwrap();
+            ExchangeManager::handle_parse_result(
+                connector.parse_message(&raw),
+                Instant::now(),
+                raw.len() as u64,
+                &connector,
+                &tx,
+                &orderbook_manager,
+                &metrics,
+                &publisher,
+            )
+        };
+
+        relay(vec![(97_000 * one, one), (96_999 * one, one)], 1)
+            .await
+            .unwrap();
+        // 96,999 left the upstream's top levels: merging would keep it forever
+        relay(vec![(97_000 * one, 2 * one)], 2).await.unwrap();
+
+        let book = orderbook_manager.get("Kraken", "BTCUSDT").unwrap();
+        assert_eq!(book.level_counts(), (1, 1));
+        assert_eq!(book.last_update_id(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_connect_gives_up_after_timeout() {
+        // Accepts the TCP connection but never answers the WebSocket handshake,
+        // so only the timeout ends it
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            // Held open, unanswered, until the test ends
+            std::future::pending::<()>().await;
+            drop(stream);
+        });
+        let connector =
+            ExchangeConnector::FlowRs(FlowRsUpstreamConn::new(format!("ws://{}/", addr), vec![]));
+        let timeout = Duration::from_millis(200);
+
+        let started = Instant::now();
+        let Err(error) = ExchangeManager::connect_websocket(&connector, &[], timeout).await else {
+            panic!("the handshake never completes");
+        };
+        assert!(error.to_string().contains("timed out"), "{}", error);
+        assert!(started.elapsed() >= timeout);
+        assert!(started.elapsed() < timeout + Duration::from_millis(500));
+        server.abort();
+    }
+
+    /// Answer the next HTTP request on a local port with `body` as JSON
+    async fn serve_json_once(body: &'static str) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let _ = stream.read(&mut request).await;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+        });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_binance_spot_endpoints_and_snapshot() {
+        use crate::exchanges::BinanceMarket;
+
+        let futures = BinanceConn::new(vec!["BTCUSDT".to_string()]);
+        assert!(futures
+            .build_subscription_url(&["BTCUSDT"])
+            .starts_with("wss://fstream.binance.com/stream?streams=btcusdt@depth@100ms/"));
+        let spot = futures.with_market(BinanceMarket::parse("Spot").unwrap());
+        assert_eq!(
+            spot.build_subscription_url(&["BTCUSDT"]),
+            "wss://stream.binance.com:9443/stream?streams=btcusdt@depth@100ms/btcusdt@aggTrade"
+        );
+
+        // Spot snapshots carry no event/transaction time
+        let rest_url = serve_json_once(
+            r#"{"lastUpdateId":1027024,"bids":[["4.00000000","431.00000000"]],"asks":[["4.00000200","12.00000000"]]}"#,
+        )
+        .await;
+        let snapshot = spot
+            .clone()
+            .with_rest_url(&rest_url)
+            .fetch_snapshot("BTCUSDT", 20)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(snapshot.last_update_id, 1027024);
+        assert_eq!(snapshot.bids, vec![(4 * 100_000_000, 431 * 100_000_000)]);
+        assert_eq!(snapshot.asks, vec![(400_000_200, 12 * 100_000_000)]);
+
+        // Error bodies surface as errors instead of an empty book
+        let rest_url = serve_json_once(r#"{"code":-1121,"msg":"Invalid symbol."}"#).await;
+        let err = spot
+            .with_rest_url(&rest_url)
+            .fetch_snapshot("BTCUSDT", 20)
+            .await
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("Invalid symbol."));
+    }
+
+    #[tokio::test]
+    async fn test_binance_gap_rebuilds_book_from_snapshot() {
+        let rest_url = serve_json_once(
+            r#"{"lastUpdateId":111,"bids":[["100.0","7"]],"asks":[["101.0","1"]]}"#,
+        )
+        .await;
+        let connector = ExchangeConnector::Binance(
+            BinanceConn::new(vec!["BTCUSDT".to_string()]).with_rest_url(&rest_url),
+        );
+        let orderbook_manager =
+            create_shared_orderbook_manager(&["Binance"], &["BTCUSDT"], ORDERBOOK_DEPTH);
+        orderbook_manager
+            .get_or_create("Binance", "BTCUSDT")
+            .initialize_from_snapshot(
+                vec![(100 * 100_000_000, 1)],
+                vec![(101 * 100_000_000, 1)],
+                100,
+            );
+        let metrics = create_shared_metrics();
+        let publisher = MarketPublisher::new(create_shared_metrics());
+        let (tx, _rx) = broadcast::channel(16);
+        let apply = |raw: &str| {
+            ExchangeManager::handle_parse_result(
+                connector.parse_message(raw),
+                Instant::now(),
+                raw.len() as u64,
+                &connector,
+                &tx,
+                &orderbook_manager,
+                &metrics,
+                &publisher,
+            )
+        };
+
+        // Straddles the snapshot, then a stale replay is dropped
+        let bridge = r#"{"stream":"btcusdt@depth@100ms","data":{"s":"BTCUSDT","U":98,"u":105,"pu":97,"b":[["100.0","5"]],"a":[]}}"#;
+        apply(bridge).await.unwrap();
+        apply(bridge).await.unwrap();
+        {
+            let book = orderbook_manager.get("Binance", "BTCUSDT").unwrap();
+            assert_eq!(book.last_update_id(), 105);
+            assert_eq!(book.get_top_levels(1).0[0].quantity, Decimal::from(5));
+        }
+
+        // 106..=109 never arrived: rebuilt from REST (id 111), then this delta applies on top
+        let after_gap = r#"{"stream":"btcusdt@depth@100ms","data":{"s":"BTCUSDT","U":110,"u":112,"pu":109,"b":[["99.0","3"]],"a":[]}}"#;
+        apply(after_gap).await.unwrap();
+        let book = orderbook_manager.get("Binance", "BTCUSDT").unwrap();
+        assert!(book.is_initialized());
+        assert_eq!(book.last_update_id(), 112);
+        let (bids, _) = book.get_top_levels(2);
+        assert_eq!(bids[0].quantity, Decimal::from(7));
+        assert_eq!(bids[1].quantity, Decimal::from(3));
+    }
+
+    #[tokio::test]
+    async fn test_kraken_checksum_mismatch_forces_resync() {
+        let connector = ExchangeConnector::Kraken(KrakenConn::new(vec!["BTCUSDT".to_string()]));
+        let orderbook_manager =
+            create_shared_orderbook_manager(&["Kraken"], &["BTCUSDT"], ORDERBOOK_DEPTH);
+        let metrics = create_shared_metrics();
+        let publisher = MarketPublisher::new(create_shared_metrics());
+        let (tx, _rx) = broadcast::channel(16);
+        let apply = |raw: &str| {
+            ExchangeManager::handle_parse_result(
+                connector.parse_message(raw),
+                Instant::now(),
+                raw.len() as u64,
+                &connector,
+                &tx,
+                &orderbook_manager,
+                &metrics,
+                &publisher,
+            )
+        };
+
+        // Checksums computed with Kraken's reference algorithm (zlib CRC32)
+        let snapshot = r#"{"channel":"book","type":"snapshot","data":[{"symbol":"BTC/USD","asks":[{"price":97000.1,"qty":0.5},{"price":97000.6,"qty":1.25},{"price":97001.1,"qty":0.001},{"price":97001.6,"qty":2.0},{"price":97002.1,"qty":0.75},{"price":97002.6,"qty":0.5},{"price":97003.1,"qty":1.25},{"price":97003.6,"qty":0.001},{"price":97004.1,"qty":2.0},{"price":97004.6,"qty":0.75}],"bids":[{"price":97000.0,"qty":1.0},{"price":96999.5,"qty":0.3},{"price":96999.0,"qty":0.01},{"price":96998.5,"qty":4.5},{"price":96998.0,"qty":0.2},{"price":96997.5,"qty":1.0},{"price":96997.0,"qty":0.3},{"price":96996.5,"qty":0.01},{"price":96996.0,"qty":4.5},{"price":96995.5,"qty":0.2}],"checksum":2774674489}]}"#;
+        apply(snapshot).await.unwrap();
+        let delta = r#"{"channel":"book","type":"update","data":[{"symbol":"BTC/USD","bids":[{"price":97000.0,"qty":2.0}],"asks":[],"checksum":3835778231}]}"#;
+        apply(delta).await.unwrap();
+        assert!(orderbook_manager
+            .get("Kraken", "BTCUSDT")
+            .unwrap()
+            .is_initialized());
+
+        // A delta we missed: the next checksum no longer matches our book
+        let stale = r#"{"channel":"book","type":"update","data":[{"symbol":"BTC/USD","bids":[{"price":96999.5,"qty":0.4}],"asks":[],"checksum":3835778231}]}"#;
+        assert!(apply(stale).await.is_err());
+        assert!(!orderbook_manager
+            .get("Kraken", "BTCUSDT")
+            .unwrap()
+            .is_initialized());
+    }
+
+    #[tokio::test]
+    async fn test_kraken_dust_threshold_keeps_checksummed_levels() {
+        use crate::orderbook::{OrderBookManager, QTY_FACTOR};
+
+        let connector = ExchangeConnector::Kraken(KrakenConn::new(vec!["BTCUSDT".to_string()]));
+        let orderbook_manager = Arc::new(
+            OrderBookManager::with_streams(&["Kraken"], &["BTCUSDT"])
+                .with_max_depth(ORDERBOOK_DEPTH)
+                .with_min_qty("Kraken", QTY_FACTOR / 100),
+        );
+        let metrics = create_shared_metrics();
+        let publisher = MarketPublisher::new(create_shared_metrics());
+        let (tx, _rx) = broadcast::channel(16);
+        let apply = |raw: &str| {
+            ExchangeManager::handle_parse_result(
+                connector.parse_message(raw),
+                Instant::now(),
+                raw.len() as u64,
+                &connector,
+                &tx,
+                &orderbook_manager,
+                &metrics,
+                &publisher,
+            )
+        };
+
+        // Two 0.001 asks under the threshold, both in the checksummed top 10
+        let snapshot = r#"{"channel":"book","type":"snapshot","data":[{"symbol":"BTC/USD","asks":[{"price":97000.1,"qty":0.5},{"price":97000.6,"qty":1.25},{"price":97001.1,"qty":0.001},{"price":97001.6,"qty":2.0},{"price":97002.1,"qty":0.75},{"price":97002.6,"qty":0.5},{"price":97003.1,"qty":1.25},{"price":97003.6,"qty":0.001},{"price":97004.1,"qty":2.0},{"price":97004.6,"qty":0.75}],"bids":[{"price":97000.0,"qty":1.0},{"price":96999.5,"qty":0.3},{"price":96999.0,"qty":0.01},{"price":96998.5,"qty":4.5},{"price":96998.0,"qty":0.2},{"price":96997.5,"qty":1.0},{"price":96997.0,"qty":0.3},{"price":96996.5,"qty":0.01},{"price":96996.0,"qty":4.5},{"price":96995.5,"qty":0.2}],"checksum":2774674489}]}"#;
+        apply(snapshot).await.unwrap();
+        let delta = r#"{"channel":"book","type":"update","data":[{"symbol":"BTC/USD","bids":[{"price":97000.0,"qty":2.0}],"asks":[],"checksum":3835778231}]}"#;
+        apply(delta).await.unwrap();
+
+        let book = orderbook_manager.get("Kraken", "BTCUSDT").unwrap();
+        assert!(book.is_initialized());
+        assert_eq!(book.level_counts(), (10, 10));
+    }
+
+    #[tokio::test]
+    async fn test_coinbase_sequence_gap_desyncs_books() {
+        let connector = ExchangeConnector::Coinbase(CoinbaseConn::new(vec!["BTCUSDT".to_string()]));
+        let orderbook_manager =
+            create_shared_orderbook_manager(&["Coinbase"], &["BTCUSDT"], ORDERBOOK_DEPTH);
+        let metrics = create_shared_metrics();
+        let publisher = MarketPublisher::new(create_shared_metrics());
+        let (tx, _rx) = broadcast::channel(16);
+        let apply = |raw: String| {
+            let result = connector.parse_message(&raw);
+            ExchangeManager::handle_parse_result(
+                result,
+                Instant::now(),
+                raw.len() as u64,
+                &connector,
+                &tx,
+                &orderbook_manager,
+                &metrics,
+                &publisher,
+            )
+        };
+        let l2 = coinbase_l2;
+        let initialized = || {
+            orderbook_manager
+                .get("Coinbase", "BTCUSDT")
+                .unwrap()
+                .is_initialized()
+        };
+
+        apply(l2(1, "snapshot", "97000")).await.unwrap();
+        apply(l2(2, "update", "96999")).await.unwrap();
+        assert!(initialized());
+        // Carried by another connection
+        let one = crate::orderbook::PRICE_FACTOR;
+        orderbook_manager
+            .get_or_create("Coinbase", "ETHUSDT")
+            .initialize_from_snapshot(vec![(3_000 * one, one)], vec![(3_001 * one, one)], 1);
+
+        // 3 and 4 were dropped
+        let err = apply(l2(5, "update", "96998")).await.unwrap_err();
+        assert!(err.to_string().contains("expected 3"), "{}", err);
+        assert!(!initialized());
+        assert!(orderbook_manager
+            .get("Coinbase", "ETHUSDT")
+            .unwrap()
+            .is_initialized());
+
+        // The resubscribe restarts the sequence with a fresh snapshot
+        apply(r#"{"channel":"subscriptions","sequence_num":0,"events":[]}"#.to_string())
+            .await
+            .unwrap();
+        apply(l2(1, "snapshot", "97000")).await.unwrap();
+        assert!(initialized());
+    }
+
+    #[tokio::test]
+    async fn test_replay_builds_books_from_capture() {
+        let capture = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/kraken_btcusd.capture"
+        );
+        let source = ExchangeConnector::Kraken(KrakenConn::new(vec!["BTCUSDT".to_string()]));
+        let orderbook_manager =
+            create_shared_orderbook_manager(&["Kraken"], &["BTCUSDT"], ORDERBOOK_DEPTH);
+        let manager = ExchangeManager::new(
+            vec![ExchangeConnector::Replay(ReplayConn::new(capture, source))],
+            SymbolRegistry::new(vec!["BTCUSDT".to_string()]),
+            Arc::clone(&orderbook_manager),
+            create_shared_metrics(),
+        );
+        let (tx, _rx) = broadcast::channel(16);
+        let started = Instant::now();
+        let handles = manager.start_all(tx).await;
+
+        // Snapshot (bid 1.0 @ 97000) then, 70ms later, a delta taking it to 2.0
+        let best_bid = || {
+            let book = orderbook_manager.get("Kraken", "BTCUSDT")?;
+            let (bids, _) = book.levels();
+            bids.first().map(|level| (level.price, level.qty))
+        };
+        let one = crate::orderbook::PRICE_FACTOR;
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while best_bid() != Some((97_000 * one, 2 * one)) {
+                tokio::time::sleep(Duration::from_millis(5)).await;
             }
+        })
+        .await
+        .expect("capture never applied");
+        assert!(started.elapsed() >= Duration::from_millis(70));
+
+        let book = orderbook_manager.get("Kraken", "BTCUSDT").unwrap();
+        assert!(book.is_initialized());
+        assert_eq!(book.best_ask(), Some(Decimal::new(970001, 1)));
+        assert_eq!(book.level_counts(), (10, 10));
+        drop(book);
+        for handle in handles {
+            handle.abort();
         }
     }
+
+    #[test]
+    fn test_implausible_trade_timestamps_rejected() {
+        let now = 1_700_000_000_000;
+        assert!(plausible_trade_timestamp(now, now));
+        assert!(plausible_trade_timestamp(now - 60_000, now));
+        assert!(plausible_trade_timestamp(now + 1_000, now));
+
+        assert!(!plausible_trade_timestamp(0, now));
+        assert!(!plausible_trade_timestamp(-1, now));
+        assert!(!plausible_trade_timestamp(
+            now + MAX_TRADE_FUTURE_SKEW_MS + 1,
+            now
+        ));
+        assert!(!plausible_trade_timestamp(now - MAX_TRADE_AGE_MS - 1, now));
+    }
+
+    #[tokio::test]
+    async fn test_kucoin_url_comes_from_token_bootstrap() {
+        let rest_url = serve_json_once(
+            r#"{"code":"200000","data":{"token":"abc123","instanceServers":[{"endpoint":"wss://ws-api-spot.kucoin.com/","encrypt":true,"protocol":"websocket","pingInterval":15000,"pingTimeout":10000}]}}"#,
+        )
+        .await;
+        let connector = ExchangeConnector::KuCoin(
+            KuCoinConn::new(vec!["BTCUSDT".to_string()]).with_rest_url(&rest_url),
+        );
+
+        let url = connector
+            .build_subscription_url(&["BTCUSDT"])
+            .await
+            .unwrap();
+        assert!(url.starts_with("wss://ws-api-spot.kucoin.com/?token=abc123&connectId="));
+        let (interval, ping) = connector.keepalive().unwrap();
+        assert_eq!(interval, Duration::from_millis(15_000));
+        assert_eq!(ping, r#"{"id":"ping","type":"ping"}"#);
+    }
+
+    #[tokio::test]
+    async fn test_kucoin_skips_changes_the_snapshot_covers() {
+        use crate::types::PriceLevel;
+
+        let connector = ExchangeConnector::KuCoin(KuCoinConn::new(vec!["BTCUSDT".to_string()]));
+        let orderbook_manager =
+            create_shared_orderbook_manager(&["KuCoin"], &["BTCUSDT"], ORDERBOOK_DEPTH);
+        let metrics = create_shared_metrics();
+        let publisher = MarketPublisher::new(create_shared_metrics());
+        let (tx, _rx) = broadcast::channel(16);
+        let one = 100_000_000;
+        orderbook_manager
+            .get_or_create("KuCoin", "BTCUSDT")
+            .initialize_from_snapshot(vec![(100 * one, 2 * one)], vec![(101 * one, one)], 11);
+
+        // Sequences 10..=13 straddle the snapshot at 11: 10 and 11 are in it already
+        let straddling = r#"{"type":"message","topic":"/market/level2:BTC-USDT","subject":"trade.l2update","data":{"changes":{"asks":[["101","0","10"],["102","4","13"]],"bids":[["100","1","11"],["99","3","12"]]},"sequenceEnd":13,"sequenceStart":10,"symbol":"BTC-USDT","time":1700000000000}}"#;
+        ExchangeManager::handle_parse_result(
+            connector.parse_message(straddling),
+            Instant::now(),
+            straddling.len() as u64,
+            &connector,
+            &tx,
+            &orderbook_manager,
+            &metrics,
+            &publisher,
+        )
+        .await
+        .unwrap();
+
+        let book = orderbook_manager.get("KuCoin", "BTCUSDT").unwrap();
+        assert_eq!(book.last_update_id(), 13);
+        let (bids, asks) = book.get_top_levels(5);
+        let levels = |levels: &[PriceLevel]| -> Vec<(Decimal, Decimal)> {
+            levels.iter().map(|l| (l.price, l.quantity)).collect()
+        };
+        assert_eq!(
+            levels(&bids),
+            vec![
+                (Decimal::from(100), Decimal::from(2)),
+                (Decimal::from(99), Decimal::from(3))
+            ]
+        );
+        assert_eq!(
+            levels(&asks),
+            vec![
+                (Decimal::from(101), Decimal::from(1)),
+                (Decimal::from(102), Decimal::from(4))
+            ]
+        );
+    }
 }
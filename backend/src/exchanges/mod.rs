@@ -2,20 +2,34 @@
 pub mod binance;
 pub mod bybit;
 pub mod coinbase;
+pub mod json;
 pub mod kraken;
+pub mod kucoin;
 pub mod manager;
+pub mod okx;
+pub mod replay;
+pub mod symbols;
+pub mod timestamp;
+pub mod upstream;
 pub mod utils;
 
 use std::error::Error;
+use std::time::Duration;
 
 use crate::types::Trade;
 
 // Re-export main types
 pub use binance::BinanceConnector as BinanceConn;
+pub use binance::BinanceMarket;
 pub use bybit::BybitConnector as BybitConn;
 pub use coinbase::CoinbaseConnector as CoinbaseConn;
 pub use kraken::KrakenConnector as KrakenConn;
+pub use kucoin::KuCoinConnector as KuCoinConn;
 pub use manager::ExchangeManager;
+pub use okx::OkxConnector as OkxConn;
+pub use replay::ReplayConnector as ReplayConn;
+pub use symbols::{ChannelFilter, SymbolRegistry};
+pub use upstream::FlowRsUpstreamConnector as FlowRsUpstreamConn;
 
 /// Exchange identifier
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -24,19 +38,65 @@ pub enum Exchange {
     Bybit,
     Coinbase,
     Kraken,
+    Okx,
+    KuCoin,
+    /// Another FlowRS instance relaying its books and trades
+    FlowRs,
+    /// Recorded frames of one exchange played back from a file
+    Replay,
 }
 
 impl Exchange {
+    /// Every feed source, in discriminant order (`exchange as usize` indexes it)
+    #[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+    pub const ALL: [Exchange; 8] = [
+        Exchange::Binance,
+        Exchange::Bybit,
+        Exchange::Coinbase,
+        Exchange::Kraken,
+        Exchange::Okx,
+        Exchange::KuCoin,
+        Exchange::FlowRs,
+        Exchange::Replay,
+    ];
+
+    /// Trading venues books can originate from (excludes the FlowRS relay and replays)
+    pub const VENUES: [Exchange; 6] = [
+        Exchange::Binance,
+        Exchange::Bybit,
+        Exchange::Coinbase,
+        Exchange::Kraken,
+        Exchange::Okx,
+        Exchange::KuCoin,
+    ];
+
     pub fn name(&self) -> &'static str {
         match self {
             Exchange::Binance => "Binance",
             Exchange::Bybit => "Bybit",
             Exchange::Coinbase => "Coinbase",
             Exchange::Kraken => "Kraken",
+            Exchange::Okx => "OKX",
+            Exchange::KuCoin => "KuCoin",
+            Exchange::FlowRs => "FlowRS",
+            Exchange::Replay => "Replay",
         }
     }
+
+    /// Sends a checksum of its top levels, dust included, that the book must match
+    pub fn checksums_books(&self) -> bool {
+        matches!(self, Exchange::Kraken)
+    }
+
+    /// Venue from its display name ("Binance" -> Exchange::Binance)
+    pub fn from_name(name: &str) -> Option<Exchange> {
+        Self::VENUES.into_iter().find(|venue| venue.name() == name)
+    }
 }
 
+/// Result of parsing one raw exchange message
+pub type ParseResult = Result<Option<MarketMessage>, Box<dyn Error + Send>>;
+
 /// Normalized market data message from any exchange
 #[derive(Debug, Clone)]
 pub enum MarketMessage {
@@ -47,15 +107,71 @@ pub enum MarketMessage {
         bids: Vec<(u64, u64)>, // (price, qty) scaled by 1e8
         asks: Vec<(u64, u64)>, // (price, qty) scaled by 1e8
         update_id: u64,
+        /// First update id covered by a delta, for exchanges with contiguous ids (Binance, KuCoin)
+        ///
+        /// Set, the manager checks deltas chain on (see `OrderBook::check_sequence`)
+        /// and rebuilds the book from a REST snapshot on a gap.
+        first_update_id: Option<u64>,
+        /// Final update id of the exchange's previous delta, when it sends one
+        prev_update_id: Option<u64>,
         is_snapshot: bool, // true for full snapshot, false for delta update
+        /// Checksum of the book once this update is applied, for exchanges that send one
+        checksum: Option<BookChecksum>,
+        /// Sequence of each change in `bids` and `asks`, for exchanges numbering
+        /// changes one by one (KuCoin): those the book already has are skipped
+        change_sequences: Option<(Vec<u64>, Vec<u64>)>,
     },
     /// Individual trade
     Trade(Trade),
-    /// Exchange-specific message (for debugging)
+    /// Several messages decoded from one frame, applied in order
+    Batch(Vec<MarketMessage>),
+    /// Feed lost continuity (e.g. sequence gap): the exchange's books stop being
+    /// served and the connection is re-established to get fresh snapshots
+    Resync { exchange: Exchange, reason: String },
+    /// Exchange-specific message worth surfacing but carrying no market data
+    /// (e.g. a refused subscription, already logged by the connector)
     #[allow(dead_code)]
     Raw(String),
 }
 
+impl MarketMessage {
+    /// Trades of one frame: None if empty, a `Batch` when there are several
+    pub fn from_trades(trades: Vec<Trade>) -> Option<MarketMessage> {
+        Self::batch(trades.into_iter().map(MarketMessage::Trade).collect())
+    }
+
+    /// Messages of one frame: None if empty, a `Batch` when there are several
+    pub fn batch(mut messages: Vec<MarketMessage>) -> Option<MarketMessage> {
+        match messages.len() {
+            0 => None,
+            1 => messages.pop(),
+            _ => Some(MarketMessage::Batch(messages)),
+        }
+    }
+
+    /// Exchange messages this stands for: every message of a `Batch`, none for `Raw`
+    pub fn message_count(&self) -> u64 {
+        match self {
+            MarketMessage::Batch(messages) => messages.iter().map(Self::message_count).sum(),
+            MarketMessage::Raw(_) => 0,
+            _ => 1,
+        }
+    }
+}
+
+/// Book checksum sent by the exchange, checked against our book after each update
+///
+/// Uses Kraken's scheme (see `OrderBook::checksum`): prices and quantities are
+/// printed as integers at the pair's precision, so both precisions are needed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BookChecksum {
+    pub expected: u32,
+    /// Levels per side covered by the checksum
+    pub levels: usize,
+    pub price_decimals: u32,
+    pub qty_decimals: u32,
+}
+
 /// Exchange connector enum with static dispatch
 #[derive(Clone)]
 pub enum ExchangeConnector {
@@ -63,6 +179,10 @@ pub enum ExchangeConnector {
     Bybit(BybitConn),
     Coinbase(CoinbaseConn),
     Kraken(KrakenConn),
+    Okx(OkxConn),
+    KuCoin(KuCoinConn),
+    FlowRs(FlowRsUpstreamConn),
+    Replay(ReplayConn),
 }
 
 impl ExchangeConnector {
@@ -73,16 +193,41 @@ impl ExchangeConnector {
             ExchangeConnector::Bybit(_) => Exchange::Bybit,
             ExchangeConnector::Coinbase(_) => Exchange::Coinbase,
             ExchangeConnector::Kraken(_) => Exchange::Kraken,
+            ExchangeConnector::Okx(_) => Exchange::Okx,
+            ExchangeConnector::KuCoin(_) => Exchange::KuCoin,
+            ExchangeConnector::FlowRs(_) => Exchange::FlowRs,
+            ExchangeConnector::Replay(_) => Exchange::Replay,
         }
     }
 
     /// Build WebSocket subscription URL for the given symbols
-    pub fn build_subscription_url(&self, symbols: &[&str]) -> String {
+    ///
+    /// Async because some exchanges (KuCoin) hand out the URL over REST first.
+    pub async fn build_subscription_url(
+        &self,
+        symbols: &[&str],
+    ) -> Result<String, Box<dyn Error + Send>> {
         match self {
-            ExchangeConnector::Binance(b) => b.build_subscription_url(symbols),
-            ExchangeConnector::Bybit(b) => b.build_subscription_url(symbols),
-            ExchangeConnector::Coinbase(c) => c.build_subscription_url(symbols),
-            ExchangeConnector::Kraken(k) => k.build_subscription_url(symbols),
+            ExchangeConnector::Binance(b) => Ok(b.build_subscription_url(symbols)),
+            ExchangeConnector::Bybit(b) => Ok(b.build_subscription_url(symbols)),
+            ExchangeConnector::Coinbase(c) => Ok(c.build_subscription_url(symbols)),
+            ExchangeConnector::Kraken(k) => Ok(k.build_subscription_url(symbols)),
+            ExchangeConnector::Okx(o) => Ok(o.build_subscription_url(symbols)),
+            ExchangeConnector::KuCoin(k) => k.build_subscription_url(symbols).await,
+            ExchangeConnector::FlowRs(f) => Ok(f.build_subscription_url(symbols)),
+            ExchangeConnector::Replay(r) => Ok(format!("file://{}", r.path().display())),
+        }
+    }
+
+    /// Application-level ping the exchange requires, and how often to send it
+    ///
+    /// None for exchanges happy with WebSocket ping/pong frames.
+    pub fn keepalive(&self) -> Option<(Duration, String)> {
+        match self {
+            ExchangeConnector::Bybit(b) => Some(b.keepalive()),
+            ExchangeConnector::Okx(o) => Some(o.keepalive()),
+            ExchangeConnector::KuCoin(k) => Some(k.keepalive()),
+            _ => None,
         }
     }
 
@@ -93,6 +238,35 @@ impl ExchangeConnector {
             ExchangeConnector::Bybit(b) => b.parse_message(raw),
             ExchangeConnector::Coinbase(c) => c.parse_message(raw),
             ExchangeConnector::Kraken(k) => k.parse_message(raw),
+            ExchangeConnector::Okx(o) => o.parse_message(raw),
+            ExchangeConnector::KuCoin(k) => k.parse_message(raw),
+            ExchangeConnector::FlowRs(f) => f.parse_message(raw),
+            ExchangeConnector::Replay(r) => r.parse_message(raw),
+        }
+    }
+
+    /// Parse a frame without connection-level checks, so frames can be parsed
+    /// concurrently and out of order
+    ///
+    /// Also returns the frame's connection sequence number for feeds numbering
+    /// their whole connection (Coinbase): hand it to `sequence_resync` in arrival
+    /// order. `parse_message` is both steps at once.
+    pub fn parse_frame(&self, raw: &str) -> (Option<u64>, ParseResult) {
+        match self {
+            ExchangeConnector::Coinbase(c) => c.parse_frame(raw),
+            ExchangeConnector::Replay(r) => r.source().parse_frame(raw),
+            _ => (None, self.parse_message(raw)),
+        }
+    }
+
+    /// `Resync` if a frame's connection sequence number reveals a gap
+    ///
+    /// Must be called in arrival order, with the numbers from `parse_frame`.
+    pub fn sequence_resync(&self, sequence: u64) -> Option<MarketMessage> {
+        match self {
+            ExchangeConnector::Coinbase(c) => c.sequence_resync(sequence),
+            ExchangeConnector::Replay(r) => r.source().sequence_resync(sequence),
+            _ => None,
         }
     }
 
@@ -108,6 +282,27 @@ impl ExchangeConnector {
             ExchangeConnector::Bybit(b) => b.fetch_snapshot(symbol, limit).await,
             ExchangeConnector::Coinbase(c) => c.fetch_snapshot(symbol, limit).await,
             ExchangeConnector::Kraken(k) => k.fetch_snapshot(symbol, limit).await,
+            ExchangeConnector::Okx(o) => o.fetch_snapshot(symbol, limit).await,
+            ExchangeConnector::KuCoin(k) => k.fetch_snapshot(symbol, limit).await,
+            ExchangeConnector::FlowRs(f) => f.fetch_snapshot(symbol, limit).await,
+            ExchangeConnector::Replay(r) => r.fetch_snapshot(symbol, limit).await,
+        }
+    }
+
+    /// True for feeds that only ever send full top-N snapshots (no deltas)
+    ///
+    /// Every depth message from such an exchange replaces the book instead of
+    /// being merged, so levels missing from a snapshot don't linger as stale.
+    pub fn always_snapshot(&self) -> bool {
+        match self {
+            ExchangeConnector::Binance(b) => b.always_snapshot(),
+            ExchangeConnector::Bybit(b) => b.always_snapshot(),
+            ExchangeConnector::Coinbase(c) => c.always_snapshot(),
+            ExchangeConnector::Kraken(k) => k.always_snapshot(),
+            ExchangeConnector::Okx(o) => o.always_snapshot(),
+            ExchangeConnector::KuCoin(k) => k.always_snapshot(),
+            ExchangeConnector::FlowRs(f) => f.always_snapshot(),
+            ExchangeConnector::Replay(r) => r.always_snapshot(),
         }
     }
 
@@ -118,6 +313,98 @@ impl ExchangeConnector {
             ExchangeConnector::Bybit(b) => b.supported_symbols(),
             ExchangeConnector::Coinbase(c) => c.supported_symbols(),
             ExchangeConnector::Kraken(k) => k.supported_symbols(),
+            ExchangeConnector::Okx(o) => o.supported_symbols(),
+            ExchangeConnector::KuCoin(k) => k.supported_symbols(),
+            ExchangeConnector::FlowRs(f) => f.supported_symbols(),
+            ExchangeConnector::Replay(r) => r.supported_symbols(),
+        }
+    }
+
+    /// Replace the tracked symbols (takes effect on the next connection)
+    pub fn set_symbols(&mut self, symbols: Vec<String>) {
+        match self {
+            ExchangeConnector::Binance(b) => b.set_symbols(symbols),
+            ExchangeConnector::Bybit(b) => b.set_symbols(symbols),
+            ExchangeConnector::Coinbase(c) => c.set_symbols(symbols),
+            ExchangeConnector::Kraken(k) => k.set_symbols(symbols),
+            ExchangeConnector::Okx(o) => o.set_symbols(symbols),
+            ExchangeConnector::KuCoin(k) => k.set_symbols(symbols),
+            ExchangeConnector::FlowRs(f) => f.set_symbols(symbols),
+            ExchangeConnector::Replay(r) => r.set_symbols(symbols),
+        }
+    }
+
+    /// Restrict subscriptions to the channels enabled per symbol (a relay takes all)
+    pub fn with_channel_filter(mut self, filter: ChannelFilter) -> Self {
+        match &mut self {
+            ExchangeConnector::Binance(b) => b.set_channel_filter(filter),
+            ExchangeConnector::Bybit(b) => b.set_channel_filter(filter),
+            ExchangeConnector::Coinbase(c) => c.set_channel_filter(filter),
+            ExchangeConnector::Kraken(k) => k.set_channel_filter(filter),
+            ExchangeConnector::Okx(o) => o.set_channel_filter(filter),
+            ExchangeConnector::KuCoin(k) => k.set_channel_filter(filter),
+            // The capture holds whatever was subscribed when it was recorded
+            ExchangeConnector::FlowRs(_) | ExchangeConnector::Replay(_) => {}
+        }
+        self
+    }
+
+    /// Apply REST timeouts (only connectors with REST snapshots or bootstraps use them)
+    pub fn with_timeouts(mut self, timeouts: NetworkTimeouts) -> Self {
+        match &mut self {
+            ExchangeConnector::Binance(b) => b.set_timeouts(timeouts),
+            ExchangeConnector::KuCoin(k) => k.set_timeouts(timeouts),
+            _ => {}
+        }
+        self
+    }
+
+    /// Override how subscriptions are chunked and paced (URL-subscribed exchanges ignore it)
+    pub fn with_subscribe_pacing(mut self, pacing: SubscribePacing) -> Self {
+        match &mut self {
+            ExchangeConnector::Bybit(b) => b.set_subscribe_pacing(pacing),
+            ExchangeConnector::Coinbase(c) => c.set_subscribe_pacing(pacing),
+            ExchangeConnector::Kraken(k) => k.set_subscribe_pacing(pacing),
+            ExchangeConnector::Okx(o) => o.set_subscribe_pacing(pacing),
+            ExchangeConnector::KuCoin(k) => k.set_subscribe_pacing(pacing),
+            ExchangeConnector::Binance(_)
+            | ExchangeConnector::FlowRs(_)
+            | ExchangeConnector::Replay(_) => {}
+        }
+        self
+    }
+
+    /// Subscribe book channels deep enough for `depth` stored levels, where
+    /// the exchange lets the client pick a depth
+    pub fn with_book_depth(mut self, depth: usize) -> Self {
+        match &mut self {
+            ExchangeConnector::Bybit(b) => b.set_book_depth(depth),
+            ExchangeConnector::Kraken(k) => k.set_book_depth(depth),
+            _ => {}
+        }
+        self
+    }
+
+    /// Levels per side the subscribed book channel streams, for exchanges
+    /// whose book subscription sets it (None: the feed or REST snapshot does)
+    pub fn book_depth(&self) -> Option<usize> {
+        match self {
+            ExchangeConnector::Bybit(b) => Some(b.book_depth()),
+            ExchangeConnector::Kraken(k) => Some(k.book_depth()),
+            _ => None,
+        }
+    }
+
+    pub fn subscribe_pacing(&self) -> SubscribePacing {
+        match self {
+            ExchangeConnector::Bybit(b) => b.subscribe_pacing(),
+            ExchangeConnector::Coinbase(c) => c.subscribe_pacing(),
+            ExchangeConnector::Kraken(k) => k.subscribe_pacing(),
+            ExchangeConnector::Okx(o) => o.subscribe_pacing(),
+            ExchangeConnector::KuCoin(k) => k.subscribe_pacing(),
+            ExchangeConnector::Binance(_)
+            | ExchangeConnector::FlowRs(_)
+            | ExchangeConnector::Replay(_) => SubscribePacing::default(),
         }
     }
 
@@ -129,6 +416,69 @@ impl ExchangeConnector {
             ExchangeConnector::Bybit(b) => b.get_subscription_messages(symbols),
             ExchangeConnector::Coinbase(c) => c.get_subscription_messages(),
             ExchangeConnector::Kraken(k) => k.get_subscription_messages(),
+            ExchangeConnector::Okx(o) => o.get_subscription_messages(symbols),
+            ExchangeConnector::KuCoin(k) => k.get_subscription_messages(symbols),
+            ExchangeConnector::FlowRs(_) => vec![], // Upstream pushes everything on connect
+            ExchangeConnector::Replay(_) => vec![], // Nothing to subscribe to in a file
+        }
+    }
+}
+
+/// How post-connect subscriptions are split into frames and paced
+///
+/// Some exchanges cap the number of args per subscribe frame or throttle
+/// bursts of subscribe frames.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SubscribePacing {
+    /// Max subscription args per frame (0 = everything in one frame)
+    pub chunk_size: usize,
+    /// Pause between consecutive subscribe frames
+    pub delay: Duration,
+}
+
+impl SubscribePacing {
+    pub fn new(chunk_size: usize, delay: Duration) -> Self {
+        Self { chunk_size, delay }
+    }
+
+    /// Split subscription args into groups of at most `chunk_size`
+    pub fn chunks<'a, T>(&self, args: &'a [T]) -> std::slice::Chunks<'a, T> {
+        let size = match self.chunk_size {
+            0 => args.len().max(1),
+            n => n,
+        };
+        args.chunks(size)
+    }
+}
+
+/// Smallest of the depths an exchange offers (ascending) covering `wanted`
+/// levels, or its deepest when none does
+fn stream_depth(offered: &[usize], wanted: usize) -> usize {
+    offered
+        .iter()
+        .copied()
+        .find(|&depth| depth >= wanted)
+        .or(offered.last().copied())
+        .unwrap_or(wanted)
+}
+
+/// Network timeouts so a hung DNS lookup or handshake ends in a reconnect
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NetworkTimeouts {
+    /// WebSocket and REST connect, TLS handshake included
+    pub connect: Duration,
+    /// Max wait for each read of a REST response
+    pub read: Duration,
+    /// Max silence on an open WebSocket (pings included) before reconnecting
+    pub idle: Duration,
+}
+
+impl Default for NetworkTimeouts {
+    fn default() -> Self {
+        Self {
+            connect: Duration::from_secs(10),
+            read: Duration::from_secs(10),
+            idle: Duration::from_secs(30),
         }
     }
 }
@@ -151,5 +501,688 @@ mod tests {
         assert_eq!(Exchange::Bybit.name(), "Bybit");
         assert_eq!(Exchange::Coinbase.name(), "Coinbase");
         assert_eq!(Exchange::Kraken.name(), "Kraken");
+        assert_eq!(Exchange::Okx.name(), "OKX");
+        assert_eq!(Exchange::KuCoin.name(), "KuCoin");
+        assert_eq!(Exchange::from_name("Kraken"), Some(Exchange::Kraken));
+        assert_eq!(Exchange::from_name("FlowRS"), None);
+        assert_eq!(Exchange::from_name("Replay"), None);
+        for (i, exchange) in Exchange::ALL.iter().enumerate() {
+            assert_eq!(*exchange as usize, i);
+        }
+    }
+
+    #[test]
+    fn test_upstream_relays_book_batches_and_trades() {
+        let connector = ExchangeConnector::FlowRs(FlowRsUpstreamConn::new(
+            "ws://upstream:8080/ws".to_string(),
+            vec!["BTCUSDT".to_string()],
+        ));
+        let one = crate::orderbook::PRICE_FACTOR;
+
+        let mut book = crate::orderbook::OrderBook::new("BTCUSDT", "Kraken");
+        book.initialize_from_snapshot(vec![(97_000 * one, one / 2)], vec![(97_001 * one, one)], 7);
+        let untracked = crate::orderbook::OrderBook::new("ETHUSDT", "Kraken");
+        let frame = serde_json::to_string(&crate::types::ClientMessage::BookBatch(vec![
+            book.to_book_update(3),
+            untracked.to_book_update(3),
+        ]))
+        .unwrap();
+
+        match connector.parse_message(&frame) {
+            Ok(Some(MarketMessage::Batch(messages))) => {
+                assert_eq!(messages.len(), 1);
+                match &messages[0] {
+                    MarketMessage::DepthUpdate {
+                        exchange,
+                        bids,
+                        asks,
+                        update_id,
+                        is_snapshot,
+                        ..
+                    } => {
+                        assert_eq!(*exchange, Exchange::Kraken);
+                        assert_eq!(bids, &vec![(97_000 * one, one / 2)]);
+                        assert_eq!(asks, &vec![(97_001 * one, one)]);
+                        // The origin exchange's id, not a relay-local counter
+                        assert_eq!(*update_id, 7);
+                        // Full books all the same: the connector replaces instead of merging
+                        assert!(!is_snapshot);
+                        assert!(connector.always_snapshot());
+                    }
+                    other => panic!("expected DepthUpdate, got {:?}", other),
+                }
+            }
+            other => panic!("expected Batch, got {:?}", other.ok()),
+        }
+
+        let trade = Trade {
+            exchange: "Binance".to_string(),
+            symbol: "BTCUSDT".to_string(),
+            price: 97_000 * one + one / 10,
+            quantity: one / 100,
+            // Older upstreams send no notional; the relay fills it in
+            notional: 0,
+            side: crate::types::TradeSide::Sell,
+            timestamp: 1,
+        };
+        let frame = serde_json::to_string(&crate::types::ClientMessage::Trade(trade)).unwrap();
+        match connector.parse_message(&frame) {
+            Ok(Some(MarketMessage::Trade(relayed))) => {
+                assert_eq!(relayed.price, 97_000 * one + one / 10);
+                assert_eq!(relayed.quantity, one / 100);
+                assert_eq!(relayed.notional, 970 * one + one / 1000);
+            }
+            other => panic!("expected Trade, got {:?}", other.ok()),
+        }
+    }
+
+    /// Parse a message and return the canonical symbol it resolved to
+    fn parsed_symbol(connector: &ExchangeConnector, raw: &str) -> String {
+        match connector.parse_message(raw) {
+            Ok(Some(MarketMessage::DepthUpdate { symbol, .. })) => symbol,
+            Ok(Some(MarketMessage::Trade(trade))) => trade.symbol,
+            other => panic!("unexpected parse result: {:?}", other.ok()),
+        }
+    }
+
+    fn assert_channels_resolve(connector: ExchangeConnector, book: &str, trade: &str) {
+        assert_eq!(parsed_symbol(&connector, book), "BTCUSDT");
+        assert_eq!(parsed_symbol(&connector, trade), "BTCUSDT");
+    }
+
+    #[test]
+    fn test_binance_book_and_trade_resolve_to_canonical_symbol() {
+        assert_channels_resolve(
+            ExchangeConnector::Binance(BinanceConn::new(vec!["BTCUSDT".to_string()])),
+            r#"{"stream":"btcusdt@depth@100ms","data":{"e":"depthUpdate","E":1,"s":"BTCUSDT","U":1,"u":2,"b":[["97000.10","1.5"]],"a":[["97000.20","2"]]}}"#,
+            r#"{"stream":"btcusdt@aggTrade","data":{"e":"aggTrade","E":1,"s":"BTCUSDT","p":"97000.1","q":"0.01","m":true}}"#,
+        );
+    }
+
+    #[test]
+    fn test_bybit_book_and_trade_resolve_to_canonical_symbol() {
+        assert_channels_resolve(
+            ExchangeConnector::Bybit(BybitConn::new(vec!["BTCUSDT".to_string()])),
+            r#"{"topic":"orderbook.50.BTCUSDT","type":"snapshot","ts":1,"data":{"s":"BTCUSDT","b":[["97000.1","1.2"]],"a":[["97000.2","0.5"]],"u":1,"seq":1}}"#,
+            r#"{"topic":"publicTrade.BTCUSDT","type":"snapshot","ts":1,"data":[{"T":1,"s":"BTCUSDT","S":"Buy","v":"0.01","p":"97000.1"}]}"#,
+        );
+    }
+
+    #[test]
+    fn test_binance_typed_parsing() {
+        let connector = ExchangeConnector::Binance(BinanceConn::new(vec!["BTCUSDT".to_string()]));
+        let one = crate::orderbook::PRICE_FACTOR;
+
+        let delta = r#"{"stream":"btcusdt@depth@100ms","data":{"e":"depthUpdate","E":1700000000000,"T":1700000000000,"s":"BTCUSDT","U":100,"u":105,"pu":99,"b":[["97000.10","1.500"],["96999.90","0.000"]],"a":[["97000.20","0.00012345"]]}}"#;
+        match connector.parse_message(delta) {
+            Ok(Some(MarketMessage::DepthUpdate {
+                bids,
+                asks,
+                update_id,
+                first_update_id,
+                prev_update_id,
+                ..
+            })) => {
+                assert_eq!(
+                    bids,
+                    vec![
+                        (97_000 * one + one / 10, one + one / 2),
+                        (96_999 * one + 9 * one / 10, 0)
+                    ]
+                );
+                assert_eq!(asks, vec![(97_000 * one + one / 5, 12_345)]);
+                assert_eq!(update_id, 105);
+                assert_eq!(first_update_id, Some(100));
+                assert_eq!(prev_update_id, Some(99));
+            }
+            other => panic!("expected DepthUpdate, got {:?}", other.ok()),
+        }
+
+        let trade = r#"{"stream":"btcusdt@aggTrade","data":{"e":"aggTrade","E":1700000000123,"a":1,"s":"BTCUSDT","p":"97000.1","q":"0.010","f":1,"l":2,"T":1700000000120,"m":false}}"#;
+        match connector.parse_message(trade) {
+            Ok(Some(MarketMessage::Trade(trade))) => {
+                assert_eq!(trade.price, 97_000 * one + one / 10);
+                assert_eq!(trade.quantity, one / 100);
+                assert_eq!(trade.side, crate::types::TradeSide::Buy);
+                assert_eq!(trade.timestamp, 1700000000123);
+            }
+            other => panic!("expected Trade, got {:?}", other.ok()),
+        }
+    }
+
+    #[test]
+    fn test_bybit_typed_parsing() {
+        let connector = ExchangeConnector::Bybit(BybitConn::new(vec!["BTCUSDT".to_string()]));
+        let one = crate::orderbook::PRICE_FACTOR;
+
+        let delta = r#"{"topic":"orderbook.50.BTCUSDT","type":"delta","ts":1,"data":{"s":"BTCUSDT","b":[["97000.1","1.2"],["96999.9","0"]],"a":[],"u":42,"seq":7},"cts":1}"#;
+        match connector.parse_message(delta) {
+            Ok(Some(MarketMessage::DepthUpdate {
+                bids,
+                asks,
+                update_id,
+                is_snapshot,
+                ..
+            })) => {
+                assert_eq!(
+                    bids,
+                    vec![
+                        (97_000 * one + one / 10, one + one / 5),
+                        (96_999 * one + 9 * one / 10, 0)
+                    ]
+                );
+                assert!(asks.is_empty());
+                assert_eq!(update_id, 42);
+                assert!(!is_snapshot);
+            }
+            other => panic!("expected DepthUpdate, got {:?}", other.ok()),
+        }
+
+        // Trades with an unknown side are dropped, the rest kept
+        let trades = r#"{"topic":"publicTrade.BTCUSDT","type":"snapshot","ts":1,"data":[{"T":5,"s":"BTCUSDT","S":"?","v":"1","p":"1"},{"T":7,"s":"BTCUSDT","S":"Sell","v":"0.01","p":"97000.1"}]}"#;
+        match connector.parse_message(trades) {
+            Ok(Some(MarketMessage::Trade(trade))) => {
+                assert_eq!(trade.side, crate::types::TradeSide::Sell);
+                assert_eq!(trade.quantity, one / 100);
+                assert_eq!(trade.timestamp, 7);
+                assert_eq!(trade.notional, 970 * one + one / 1000);
+                let json = serde_json::to_value(&trade).unwrap();
+                assert_eq!(json["notional"], "970.001");
+            }
+            other => panic!("expected Trade, got {:?}", other.ok()),
+        }
+
+        let ack = r#"{"success":true,"ret_msg":"","conn_id":"abc","op":"subscribe"}"#;
+        assert!(matches!(connector.parse_message(ack), Ok(None)));
+        let refused = r#"{"success":false,"ret_msg":"error:handler not found,topic:orderbook.50.FOOUSDT","conn_id":"abc","op":"subscribe"}"#;
+        assert!(matches!(
+            connector.parse_message(refused),
+            Ok(Some(MarketMessage::Raw(_)))
+        ));
+        let pong = r#"{"success":true,"ret_msg":"pong","conn_id":"abc","op":"ping"}"#;
+        assert!(matches!(connector.parse_message(pong), Ok(None)));
+    }
+
+    #[test]
+    fn test_coinbase_book_and_trade_resolve_to_canonical_symbol() {
+        assert_channels_resolve(
+            ExchangeConnector::Coinbase(CoinbaseConn::new(vec!["BTCUSDT".to_string()])),
+            r#"{"channel":"l2_data","sequence_num":1,"events":[{"type":"update","product_id":"BTC-USD","updates":[{"side":"bid","price_level":"97000.1","new_quantity":"0.5"}]}]}"#,
+            r#"{"channel":"market_trades","sequence_num":2,"events":[{"type":"update","trades":[{"product_id":"BTC-USD","price":"97000.1","size":"0.01","side":"BUY","time":"2024-01-01T00:00:00.000Z"}]}]}"#,
+        );
+    }
+
+    /// Prices of the trades in a parsed frame, in order
+    fn batched_trade_prices(connector: &ExchangeConnector, raw: &str) -> Vec<u64> {
+        let messages = match connector.parse_message(raw) {
+            Ok(Some(MarketMessage::Batch(messages))) => messages,
+            other => panic!("expected a batch of trades, got {:?}", other.ok()),
+        };
+        messages
+            .into_iter()
+            .map(|msg| match msg {
+                MarketMessage::Trade(trade) => trade.price,
+                other => panic!("expected Trade, got {:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_connectors_keep_every_trade_of_a_frame() {
+        let one = crate::orderbook::PRICE_FACTOR;
+
+        let coinbase = ExchangeConnector::Coinbase(CoinbaseConn::new(vec!["BTCUSDT".to_string()]));
+        let raw = r#"{"channel":"market_trades","sequence_num":0,"events":[{"type":"update","trades":[{"product_id":"BTC-USD","price":"97000","size":"0.01","side":"BUY","time":"2024-01-01T00:00:00.000Z"},{"product_id":"BTC-USD","price":"97001","size":"0.02","side":"SELL","time":"2024-01-01T00:00:00.001Z"}]},{"type":"update","trades":[{"product_id":"BTC-USD","price":"97002","size":"0.03","side":"BUY","time":"2024-01-01T00:00:00.002Z"}]}]}"#;
+        assert_eq!(
+            batched_trade_prices(&coinbase, raw),
+            vec![97_000 * one, 97_001 * one, 97_002 * one]
+        );
+
+        let bybit = ExchangeConnector::Bybit(BybitConn::new(vec!["BTCUSDT".to_string()]));
+        let raw = r#"{"topic":"publicTrade.BTCUSDT","type":"snapshot","ts":1,"data":[{"T":5,"s":"BTCUSDT","S":"Buy","v":"1","p":"97000"},{"T":6,"s":"BTCUSDT","S":"?","v":"1","p":"1"},{"T":7,"s":"BTCUSDT","S":"Sell","v":"0.01","p":"97001"}]}"#;
+        let message = bybit.parse_message(raw).unwrap().unwrap();
+        assert_eq!(message.message_count(), 2);
+        assert_eq!(
+            batched_trade_prices(&bybit, raw),
+            vec![97_000 * one, 97_001 * one]
+        );
+
+        let okx = ExchangeConnector::Okx(OkxConn::new(vec!["BTCUSDT".to_string()]));
+        let raw = r#"{"arg":{"channel":"trades","instId":"BTC-USDT"},"data":[{"instId":"BTC-USDT","tradeId":"1","px":"97000","sz":"0.01","side":"buy","ts":"1"},{"instId":"BTC-USDT","tradeId":"2","px":"97001","sz":"0.02","side":"sell","ts":"2"}]}"#;
+        assert_eq!(
+            batched_trade_prices(&okx, raw),
+            vec![97_000 * one, 97_001 * one]
+        );
+
+        let kraken = ExchangeConnector::Kraken(KrakenConn::new(vec!["BTCUSDT".to_string()]));
+        let raw = r#"{"channel":"trade","type":"update","data":[{"symbol":"BTC/USD","side":"buy","price":97000,"qty":0.01,"timestamp":"2024-01-01T00:00:00.000000Z"},{"symbol":"BTC/USD","side":"sell","price":97001,"qty":0.02,"timestamp":"2024-01-01T00:00:00.001000Z"}]}"#;
+        assert_eq!(
+            batched_trade_prices(&kraken, raw),
+            vec![97_000 * one, 97_001 * one]
+        );
+    }
+
+    #[test]
+    fn test_coinbase_keeps_every_book_of_a_frame() {
+        let connector = ExchangeConnector::Coinbase(CoinbaseConn::new(vec![
+            "BTCUSDT".to_string(),
+            "ETHUSDT".to_string(),
+        ]));
+        let raw = r#"{"channel":"l2_data","sequence_num":0,"events":[{"type":"snapshot","product_id":"BTC-USD","updates":[{"side":"bid","event_time":"2024-01-01T00:00:00Z","price_level":"97000","new_quantity":"1"}]},{"type":"update","product_id":"ETH-USD","updates":[{"side":"offer","event_time":"2024-01-01T00:00:00Z","price_level":"3000","new_quantity":"2"}]}]}"#;
+        let messages = match connector.parse_message(raw) {
+            Ok(Some(MarketMessage::Batch(messages))) => messages,
+            other => panic!("expected a batch of books, got {:?}", other.ok()),
+        };
+        let books: Vec<(String, bool)> = messages
+            .into_iter()
+            .map(|msg| match msg {
+                MarketMessage::DepthUpdate {
+                    symbol,
+                    is_snapshot,
+                    ..
+                } => (symbol, is_snapshot),
+                other => panic!("expected DepthUpdate, got {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            books,
+            vec![
+                ("BTCUSDT".to_string(), true),
+                ("ETHUSDT".to_string(), false)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_kraken_keeps_every_book_of_a_frame() {
+        let connector = ExchangeConnector::Kraken(KrakenConn::new(vec![
+            "BTCUSDT".to_string(),
+            "ETHUSDT".to_string(),
+        ]));
+        let raw = r#"{"channel":"book","type":"update","data":[{"symbol":"BTC/USD","bids":[{"price":97000.1,"qty":1.5}],"asks":[],"checksum":1},{"symbol":"ETH/USD","bids":[],"asks":[{"price":3000.5,"qty":2.0}],"checksum":2}]}"#;
+        let messages = match connector.parse_message(raw) {
+            Ok(Some(MarketMessage::Batch(messages))) => messages,
+            other => panic!("expected a batch of books, got {:?}", other.ok()),
+        };
+        let books: Vec<(String, u64)> = messages
+            .into_iter()
+            .map(|msg| match msg {
+                MarketMessage::DepthUpdate {
+                    symbol, update_id, ..
+                } => (symbol, update_id),
+                other => panic!("expected DepthUpdate, got {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            books,
+            vec![("BTCUSDT".to_string(), 1), ("ETHUSDT".to_string(), 2)]
+        );
+    }
+
+    /// Coinbase l2_data delta carrying the given connection sequence number
+    fn coinbase_l2(sequence: u64) -> String {
+        format!(
+            r#"{{"channel":"l2_data","sequence_num":{},"events":[{{"type":"update","product_id":"BTC-USD","updates":[{{"side":"bid","price_level":"97000.1","new_quantity":"0.5"}}]}}]}}"#,
+            sequence
+        )
+    }
+
+    #[test]
+    fn test_bybit_subscription_split_into_chunks() {
+        let symbols: Vec<String> = (0..100).map(|i| format!("COIN{}USDT", i)).collect();
+        let refs: Vec<&str> = symbols.iter().map(String::as_str).collect();
+        let connector = ExchangeConnector::Bybit(BybitConn::new(symbols.clone()))
+            .with_subscribe_pacing(SubscribePacing::new(10, Duration::from_millis(50)));
+
+        // 100 symbols x (orderbook + publicTrade) = 200 args
+        let frames = connector.get_subscription_messages(&refs);
+        assert_eq!(frames.len(), 20);
+        for frame in &frames {
+            let json: serde_json::Value = serde_json::from_str(frame).unwrap();
+            assert_eq!(json["op"], "subscribe");
+            assert_eq!(json["args"].as_array().unwrap().len(), 10);
+        }
+        assert_eq!(
+            connector.subscribe_pacing().delay,
+            Duration::from_millis(50)
+        );
+
+        // Chunk size 0 keeps everything in a single frame
+        let single = ExchangeConnector::Bybit(BybitConn::new(symbols.clone()))
+            .with_subscribe_pacing(SubscribePacing::default())
+            .get_subscription_messages(&refs);
+        assert_eq!(single.len(), 1);
+    }
+
+    #[test]
+    fn test_bybit_keepalive_pings_and_ignores_pong() {
+        let connector = ExchangeConnector::Bybit(BybitConn::new(vec!["BTCUSDT".to_string()]));
+        let (interval, ping) = connector.keepalive().unwrap();
+        assert!(interval < Duration::from_secs(30));
+        assert_eq!(ping, r#"{"op":"ping"}"#);
+
+        let pong = connector
+            .parse_message(r#"{"success":true,"ret_msg":"pong","conn_id":"abc","op":"ping"}"#)
+            .unwrap();
+        assert!(pong.is_none());
+
+        let binance = ExchangeConnector::Binance(BinanceConn::new(vec!["BTCUSDT".to_string()]));
+        assert!(binance.keepalive().is_none());
+    }
+
+    #[test]
+    fn test_okx_keepalive_pings_and_ignores_pong() {
+        let connector = ExchangeConnector::Okx(OkxConn::new(vec!["BTCUSDT".to_string()]));
+        let (interval, ping) = connector.keepalive().unwrap();
+        assert!(interval < Duration::from_secs(30));
+        assert_eq!(ping, "ping");
+        assert!(connector.parse_message("pong").unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_channel_filter_limits_subscriptions() {
+        let symbols = vec!["BTCUSDT".to_string(), "ETHUSDT".to_string()];
+        let refs = ["BTCUSDT", "ETHUSDT"];
+        let filter = ChannelFilter::parse("BTCUSDT:book,ETHUSDT:trades");
+
+        let binance = ExchangeConnector::Binance(BinanceConn::new(symbols.clone()))
+            .with_channel_filter(filter.clone());
+        assert_eq!(
+            binance.build_subscription_url(&refs).await.unwrap(),
+            "wss://fstream.binance.com/stream?streams=btcusdt@depth@100ms/ethusdt@aggTrade"
+        );
+
+        let bybit = ExchangeConnector::Bybit(BybitConn::new(symbols.clone()))
+            .with_channel_filter(filter.clone());
+        let frames = bybit.get_subscription_messages(&refs);
+        let json: serde_json::Value = serde_json::from_str(&frames[0]).unwrap();
+        assert_eq!(
+            json["args"],
+            serde_json::json!(["orderbook.50.BTCUSDT", "publicTrade.ETHUSDT"])
+        );
+
+        // One frame per channel, each with only the symbols that want it
+        let coinbase = ExchangeConnector::Coinbase(CoinbaseConn::new(symbols.clone()))
+            .with_channel_filter(filter.clone());
+        let frames = coinbase.get_subscription_messages(&refs);
+        assert_eq!(frames.len(), 2);
+        assert!(frames[0].contains(r#""product_ids":["BTC-USD"],"channel":"level2""#));
+        assert!(frames[1].contains(r#""product_ids":["ETH-USD"],"channel":"market_trades""#));
+
+        // A channel no symbol wants is not subscribed at all
+        let kraken = ExchangeConnector::Kraken(KrakenConn::new(vec!["BTCUSDT".to_string()]))
+            .with_channel_filter(filter);
+        let frames = kraken.get_subscription_messages(&["BTCUSDT"]);
+        assert_eq!(frames.len(), 2);
+        assert!(frames[0].contains(r#""channel":"instrument""#));
+        assert!(frames[1].contains(r#""channel":"book""#));
+    }
+
+    #[test]
+    fn test_book_subscription_depth_covers_stored_depth() {
+        let symbols = vec!["BTCUSDT".to_string()];
+        let book_depth = |connector: &ExchangeConnector| {
+            connector
+                .get_subscription_messages(&["BTCUSDT"])
+                .iter()
+                .map(|frame| serde_json::from_str::<serde_json::Value>(frame).unwrap())
+                .find_map(|json| match json["params"]["channel"].as_str() {
+                    Some("book") => json["params"]["depth"].as_u64(),
+                    _ => json["args"][0]
+                        .as_str()
+                        .and_then(|topic| topic.split('.').nth(1))
+                        .and_then(|depth| depth.parse().ok()),
+                })
+                .unwrap()
+        };
+
+        for (stored, kraken_depth, bybit_depth) in [
+            (10, 10, 50),
+            (25, 25, 50),
+            (60, 100, 200),
+            (100, 100, 200),
+            (300, 500, 500),
+            (5000, 1000, 500),
+        ] {
+            let kraken =
+                ExchangeConnector::Kraken(KrakenConn::new(symbols.clone())).with_book_depth(stored);
+            assert_eq!(book_depth(&kraken), kraken_depth, "Kraken at {}", stored);
+            assert_eq!(kraken.book_depth(), Some(kraken_depth as usize));
+
+            let bybit =
+                ExchangeConnector::Bybit(BybitConn::new(symbols.clone())).with_book_depth(stored);
+            assert_eq!(book_depth(&bybit), bybit_depth, "Bybit at {}", stored);
+            assert_eq!(bybit.book_depth(), Some(bybit_depth as usize));
+        }
+
+        let okx = ExchangeConnector::Okx(OkxConn::new(symbols)).with_book_depth(100);
+        assert_eq!(okx.book_depth(), None);
+    }
+
+    #[test]
+    fn test_coinbase_frames_parsed_out_of_order_are_not_a_gap() {
+        let connector = ExchangeConnector::Coinbase(CoinbaseConn::new(vec!["BTCUSDT".to_string()]));
+
+        // Parsed last to first, checked in arrival order
+        let sequences: Vec<Option<u64>> = [3, 2, 1]
+            .map(|sequence| connector.parse_frame(&coinbase_l2(sequence)).0)
+            .into_iter()
+            .rev()
+            .collect();
+        assert_eq!(sequences, vec![Some(1), Some(2), Some(3)]);
+        for sequence in sequences.into_iter().flatten() {
+            assert!(connector.sequence_resync(sequence).is_none());
+        }
+
+        // Frames arriving out of order are a gap, and the late one a duplicate
+        assert!(matches!(
+            connector.sequence_resync(5),
+            Some(MarketMessage::Resync { .. })
+        ));
+        assert!(connector.sequence_resync(4).is_none());
+        assert!(connector.sequence_resync(6).is_none());
+    }
+
+    #[test]
+    fn test_coinbase_sequence_gap_requests_resync() {
+        let connector = ExchangeConnector::Coinbase(CoinbaseConn::new(vec!["BTCUSDT".to_string()]));
+        let heartbeat = r#"{"channel":"heartbeats","sequence_num":2,"events":[]}"#;
+
+        // Subscription ack starts the connection at 0; heartbeats count too
+        for raw in [
+            r#"{"channel":"subscriptions","sequence_num":0,"events":[]}"#.to_string(),
+            coinbase_l2(1),
+            heartbeat.to_string(),
+            coinbase_l2(3),
+        ] {
+            let parsed = connector.parse_message(&raw).unwrap();
+            assert!(!matches!(parsed, Some(MarketMessage::Resync { .. })));
+        }
+
+        // 4 and 5 were dropped
+        match connector.parse_message(&coinbase_l2(6)) {
+            Ok(Some(MarketMessage::Resync { exchange, reason })) => {
+                assert_eq!(exchange, Exchange::Coinbase);
+                assert!(reason.contains("expected 4"));
+            }
+            other => panic!("expected Resync, got {:?}", other.ok()),
+        }
+
+        // A reconnect restarts the sequence without flagging a gap
+        let parsed = connector
+            .parse_message(r#"{"channel":"subscriptions","sequence_num":0,"events":[]}"#)
+            .unwrap();
+        assert!(parsed.is_none());
+        assert!(matches!(
+            connector.parse_message(&coinbase_l2(1)),
+            Ok(Some(MarketMessage::DepthUpdate { .. }))
+        ));
+    }
+
+    #[test]
+    fn test_kraken_book_and_trade_resolve_to_canonical_symbol() {
+        assert_channels_resolve(
+            ExchangeConnector::Kraken(KrakenConn::new(vec!["BTCUSDT".to_string()])),
+            r#"{"channel":"book","type":"update","data":[{"symbol":"BTC/USD","bids":[{"price":97000.1,"qty":1.5}],"asks":[],"checksum":1}]}"#,
+            r#"{"channel":"trade","type":"update","data":[{"symbol":"BTC/USD","side":"buy","price":97000.1,"qty":0.01,"timestamp":"2024-01-01T00:00:00.000000Z"}]}"#,
+        );
+    }
+
+    #[test]
+    fn test_kraken_checksum_precision_from_instrument_channel() {
+        let connector = ExchangeConnector::Kraken(KrakenConn::new(vec!["BTCUSDT".to_string()]));
+        let snapshot = r#"{"channel":"book","type":"snapshot","data":[{"symbol":"BTC/USD","bids":[{"price":97000.0,"qty":1.0}],"asks":[{"price":97001.0,"qty":2.0}],"checksum":1}]}"#;
+        let precision = |raw: &str| match connector.parse_message(raw) {
+            Ok(Some(MarketMessage::DepthUpdate {
+                checksum: Some(checksum),
+                ..
+            })) => (checksum.price_decimals, checksum.qty_decimals),
+            other => panic!("expected a checksummed depth update, got {:?}", other.ok()),
+        };
+
+        // Whole-dollar levels alone would suggest no decimals at all
+        assert_eq!(precision(snapshot), (0, 8));
+
+        let instrument = r#"{"channel":"instrument","type":"snapshot","data":{"assets":[],"pairs":[{"symbol":"BTC/USD","base":"BTC","quote":"USD","status":"online","qty_precision":8,"qty_increment":0.00000001,"price_precision":1,"price_increment":0.1,"cost_precision":5,"qty_min":0.0001},{"symbol":"DOGE/USD","base":"DOGE","quote":"USD","status":"online","qty_precision":6,"price_precision":7}]}}"#;
+        assert!(connector.parse_message(instrument).unwrap().is_none());
+        assert_eq!(precision(snapshot), (1, 8));
+    }
+
+    #[test]
+    fn test_okx_book_and_trade_resolve_to_canonical_symbol() {
+        assert_channels_resolve(
+            ExchangeConnector::Okx(OkxConn::new(vec!["BTCUSDT".to_string()])),
+            r#"{"arg":{"channel":"books","instId":"BTC-USDT"},"action":"snapshot","data":[{"asks":[["97000.2","0.5","0","1"]],"bids":[["97000.1","1.2","0","2"]],"ts":"1","checksum":0,"prevSeqId":-1,"seqId":10}]}"#,
+            r#"{"arg":{"channel":"trades","instId":"BTC-USDT"},"data":[{"instId":"BTC-USDT","tradeId":"1","px":"97000.1","sz":"0.01","side":"buy","ts":"1700000000000","count":"1"}]}"#,
+        );
+    }
+
+    #[test]
+    fn test_kucoin_book_and_trade_resolve_to_canonical_symbol() {
+        assert_channels_resolve(
+            ExchangeConnector::KuCoin(KuCoinConn::new(vec!["BTCUSDT".to_string()])),
+            r#"{"type":"message","topic":"/market/level2:BTC-USDT","subject":"trade.l2update","data":{"changes":{"asks":[["97000.2","0.5","12"]],"bids":[["97000.1","1.2","11"]]},"sequenceEnd":12,"sequenceStart":11,"symbol":"BTC-USDT","time":1700000000000}}"#,
+            r#"{"type":"message","topic":"/market/match:BTC-USDT","subject":"trade.l3match","data":{"price":"97000.1","sequence":"13","side":"sell","size":"0.01","symbol":"BTC-USDT","time":"1700000000123456789","type":"match"}}"#,
+        );
+    }
+
+    #[test]
+    fn test_kucoin_subscriptions_and_sequences() {
+        let connector = KuCoinConn::new(vec!["BTCUSDT".to_string(), "ETHUSDT".to_string()]);
+        assert_eq!(
+            connector.get_subscription_messages(&["BTCUSDT", "ETHUSDT"]),
+            vec![
+                r#"{"id":"1","type":"subscribe","topic":"/market/level2:BTC-USDT,ETH-USDT","privateChannel":false,"response":true}"#,
+                r#"{"id":"2","type":"subscribe","topic":"/market/match:BTC-USDT,ETH-USDT","privateChannel":false,"response":true}"#,
+            ]
+        );
+
+        let connector = ExchangeConnector::KuCoin(connector);
+        let update = r#"{"type":"message","topic":"/market/level2:ETH-USDT","subject":"trade.l2update","data":{"changes":{"asks":[["0","0","20"]],"bids":[["3000.5","0","21"]]},"sequenceEnd":21,"sequenceStart":20,"symbol":"ETH-USDT","time":1700000000000}}"#;
+        match connector.parse_message(update) {
+            Ok(Some(MarketMessage::DepthUpdate {
+                symbol,
+                bids,
+                asks,
+                update_id,
+                first_update_id,
+                is_snapshot,
+                ..
+            })) => {
+                assert_eq!(symbol, "ETHUSDT");
+                assert_eq!(
+                    bids,
+                    vec![(
+                        3_000 * crate::orderbook::PRICE_FACTOR + crate::orderbook::PRICE_FACTOR / 2,
+                        0
+                    )]
+                );
+                // Sequence-only changes carry price "0"
+                assert!(asks.is_empty());
+                assert_eq!(update_id, 21);
+                assert_eq!(first_update_id, Some(20));
+                assert!(!is_snapshot);
+            }
+            other => panic!("expected DepthUpdate, got {:?}", other.ok()),
+        }
+
+        let trade = r#"{"type":"message","topic":"/market/match:BTC-USDT","subject":"trade.l3match","data":{"price":"97000.1","sequence":"13","side":"sell","size":"0.01","symbol":"BTC-USDT","time":"1700000000123456789","type":"match"}}"#;
+        match connector.parse_message(trade) {
+            Ok(Some(MarketMessage::Trade(trade))) => {
+                assert_eq!(trade.timestamp, 1_700_000_000_123);
+                assert_eq!(trade.side, crate::types::TradeSide::Sell);
+            }
+            other => panic!("expected Trade, got {:?}", other.ok()),
+        }
+
+        for control in [
+            r#"{"id":"hQvf8jkno","type":"welcome"}"#,
+            r#"{"id":"1","type":"ack"}"#,
+            r#"{"id":"ping","type":"pong"}"#,
+        ] {
+            assert!(matches!(connector.parse_message(control), Ok(None)));
+        }
+    }
+
+    #[test]
+    fn test_okx_snapshot_and_update_actions() {
+        let connector = OkxConn::new(vec!["BTCUSDT".to_string()]);
+        assert_eq!(
+            connector.exchange_symbol("BTCUSDT", symbols::Channel::Book),
+            "BTC-USDT"
+        );
+        let subscriptions = connector.get_subscription_messages(&["BTCUSDT"]);
+        assert_eq!(
+            subscriptions,
+            vec![
+                r#"{"args":[{"channel":"books","instId":"BTC-USDT"},{"channel":"trades","instId":"BTC-USDT"}],"op":"subscribe"}"#
+            ]
+        );
+
+        let connector = ExchangeConnector::Okx(connector);
+        let snapshot = r#"{"arg":{"channel":"books","instId":"BTC-USDT"},"action":"snapshot","data":[{"asks":[["97000.2","0.5","0","1"]],"bids":[["97000.1","1.2","0","2"]],"ts":"1","checksum":0,"prevSeqId":-1,"seqId":10}]}"#;
+        match connector.parse_message(snapshot) {
+            Ok(Some(MarketMessage::DepthUpdate {
+                is_snapshot,
+                update_id,
+                prev_update_id,
+                ..
+            })) => {
+                assert!(is_snapshot);
+                assert_eq!(update_id, 10);
+                assert_eq!(prev_update_id, None);
+            }
+            other => panic!("expected DepthUpdate, got {:?}", other.ok()),
+        }
+
+        let update = r#"{"arg":{"channel":"books","instId":"BTC-USDT"},"action":"update","data":[{"asks":[],"bids":[["97000.1","0","0","0"]],"ts":"2","checksum":0,"prevSeqId":10,"seqId":12}]}"#;
+        match connector.parse_message(update) {
+            Ok(Some(MarketMessage::DepthUpdate {
+                is_snapshot,
+                bids,
+                update_id,
+                first_update_id,
+                prev_update_id,
+                ..
+            })) => {
+                assert!(!is_snapshot);
+                assert_eq!(
+                    bids,
+                    vec![(
+                        97_000 * crate::orderbook::PRICE_FACTOR
+                            + crate::orderbook::PRICE_FACTOR / 10,
+                        0
+                    )]
+                );
+                assert_eq!(update_id, 12);
+                assert_eq!(first_update_id, Some(11));
+                assert_eq!(prev_update_id, Some(10));
+            }
+            other => panic!("expected DepthUpdate, got {:?}", other.ok()),
+        }
+
+        let ack = r#"{"event":"subscribe","arg":{"channel":"books","instId":"BTC-USDT"},"connId":"a4d3ae55"}"#;
+        assert!(matches!(connector.parse_message(ack), Ok(None)));
+        assert!(matches!(connector.parse_message("pong"), Ok(None)));
     }
 }
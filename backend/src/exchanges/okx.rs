@@ -0,0 +1,279 @@
+use super::json;
+use super::symbols::{quote_asset, Channel, ChannelFilter};
+use super::utils::fast_parse_u64_inner;
+/// OKX exchange connector (v5 public WebSocket, spot)
+use super::{DepthSnapshot, Exchange, MarketMessage, SubscribePacing};
+use crate::types::{Trade, TradeSide};
+use serde::Deserialize;
+use std::error::Error;
+use std::time::Duration;
+
+/// OKX closes connections idle for 30s
+const PING_INTERVAL: Duration = Duration::from_secs(20);
+
+#[derive(Clone)]
+pub struct OkxConnector {
+    symbols: Vec<String>,
+    channels: ChannelFilter,
+    pacing: SubscribePacing,
+}
+
+impl OkxConnector {
+    pub fn new(symbols: Vec<String>) -> Self {
+        Self {
+            symbols,
+            channels: ChannelFilter::default(),
+            pacing: SubscribePacing::default(),
+        }
+    }
+
+    pub fn build_subscription_url(&self, _symbols: &[&str]) -> String {
+        // OKX subscribes after connection, like Bybit
+        "wss://ws.okx.com:8443/ws/v5/public".to_string()
+    }
+
+    /// Native instrument id for a channel ("BTCUSDT" -> "BTC-USDT" on both channels)
+    pub fn exchange_symbol(&self, symbol: &str, _channel: Channel) -> String {
+        match quote_asset(symbol) {
+            Some(quote) => format!("{}-{}", &symbol[..symbol.len() - quote.len()], quote),
+            None => symbol.to_string(),
+        }
+    }
+
+    /// Canonical symbol from an instrument id ("BTC-USDT" -> "BTCUSDT")
+    pub fn canonical_symbol(&self, native: &str) -> String {
+        native.replace('-', "")
+    }
+
+    /// Client ping OKX requires: a bare "ping" text, answered by "pong"
+    pub fn keepalive(&self) -> (Duration, String) {
+        (PING_INTERVAL, "ping".to_string())
+    }
+
+    /// Build subscription messages for the OKX WebSocket
+    pub fn get_subscription_messages(&self, symbols: &[&str]) -> Vec<String> {
+        let args: Vec<OkxArg> = symbols
+            .iter()
+            .flat_map(|s| {
+                [(Channel::Book, "books"), (Channel::Trades, "trades")]
+                    .into_iter()
+                    .filter(|&(channel, _)| self.channels.wants(s, channel))
+                    .map(|(channel, name)| OkxArg {
+                        channel: name,
+                        inst_id: self.exchange_symbol(s, channel),
+                    })
+            })
+            .collect();
+
+        self.pacing
+            .chunks(&args)
+            .map(|chunk| {
+                serde_json::json!({
+                    "op": "subscribe",
+                    "args": chunk
+                })
+                .to_string()
+            })
+            .collect()
+    }
+
+    pub fn subscribe_pacing(&self) -> SubscribePacing {
+        self.pacing
+    }
+
+    pub fn set_subscribe_pacing(&mut self, pacing: SubscribePacing) {
+        self.pacing = pacing;
+    }
+
+    /// Subscribe only the channels `filter` enables per symbol
+    pub fn set_channel_filter(&mut self, filter: ChannelFilter) {
+        self.channels = filter;
+    }
+
+    pub fn parse_message(&self, raw: &str) -> Result<Option<MarketMessage>, Box<dyn Error + Send>> {
+        // OKX format: {"arg": {"channel": "books", "instId": "BTC-USDT"}, "action": "snapshot|update", "data": [...]}
+        // Only the arg is read first (data is skipped), then the matching typed parser runs
+        #[derive(Deserialize)]
+        struct ArgHeader<'a> {
+            #[serde(borrow)]
+            arg: Option<OkxArgRef<'a>>,
+            event: Option<&'a str>,
+        }
+
+        // Bare "pong" answers to keepalive pings
+        let mut scratch = json::Scratch::default();
+        let header: ArgHeader = match json::from_str(raw, &mut scratch) {
+            Ok(h) => h,
+            Err(_) => return Ok(None),
+        };
+
+        // Subscription acks and errors carry an event instead of data
+        if let Some(event) = header.event {
+            if event == "error" {
+                tracing::warn!("[OKX] Subscription error: {}", raw);
+            }
+            return Ok(None);
+        }
+        let Some(arg) = header.arg else {
+            return Ok(None);
+        };
+
+        match arg.channel {
+            "books" => self.parse_depth_message(raw),
+            "trades" => self.parse_trade_message(raw),
+            _ => Ok(None),
+        }
+    }
+
+    fn parse_depth_message(
+        &self,
+        raw: &str,
+    ) -> Result<Option<MarketMessage>, Box<dyn Error + Send>> {
+        // Zero-copy: prices and quantities borrow from `raw`
+        let mut scratch = json::Scratch::default();
+        let msg: OkxDepthMessage = json::from_str(raw, &mut scratch)?;
+
+        let Some(data) = msg.data.first() else {
+            return Ok(None);
+        };
+        let is_snapshot = msg.action == Some("snapshot");
+        if is_snapshot {
+            tracing::debug!("[OKX] Received snapshot for {}", msg.arg.inst_id);
+        }
+
+        // Each update names the seqId of the previous one (-1 on a snapshot)
+        let prev_seq_id = (!is_snapshot)
+            .then(|| u64::try_from(data.prev_seq_id).ok())
+            .flatten();
+
+        Ok(Some(MarketMessage::DepthUpdate {
+            exchange: Exchange::Okx,
+            symbol: self.canonical_symbol(msg.arg.inst_id),
+            bids: Self::to_levels(&data.bids),
+            asks: Self::to_levels(&data.asks),
+            update_id: data.seq_id,
+            first_update_id: prev_seq_id.map(|prev| prev + 1),
+            prev_update_id: prev_seq_id,
+            is_snapshot,
+            checksum: None,
+            change_sequences: None,
+        }))
+    }
+
+    /// [price, size, deprecated, order count] -> (price, qty)
+    fn to_levels(levels: &[[&str; 4]]) -> Vec<(u64, u64)> {
+        levels
+            .iter()
+            .filter_map(|[price, qty, ..]| {
+                Some((fast_parse_u64_inner(price)?, fast_parse_u64_inner(qty)?))
+            })
+            .collect()
+    }
+
+    fn parse_trade_message(
+        &self,
+        raw: &str,
+    ) -> Result<Option<MarketMessage>, Box<dyn Error + Send>> {
+        let mut scratch = json::Scratch::default();
+        let msg: OkxTradeMessage = json::from_str(raw, &mut scratch)?;
+
+        // Every well-formed trade of the frame, in order
+        let trades = msg.data.iter().filter_map(|trade| {
+            let side = match trade.side {
+                "buy" => TradeSide::Buy,
+                "sell" => TradeSide::Sell,
+                _ => return None,
+            };
+            let price = fast_parse_u64_inner(trade.price)?;
+            let quantity = fast_parse_u64_inner(trade.size)?;
+            Some(Trade {
+                exchange: "OKX".to_string(),
+                symbol: self.canonical_symbol(trade.inst_id),
+                price,
+                quantity,
+                notional: Trade::notional_of(price, quantity),
+                side,
+                timestamp: trade.ts.parse().ok()?,
+            })
+        });
+
+        Ok(MarketMessage::from_trades(trades.collect()))
+    }
+
+    /// OKX sends the initial snapshot via WebSocket, so REST fetch not needed
+    pub async fn fetch_snapshot(
+        &self,
+        _symbol: &str,
+        _limit: usize,
+    ) -> Result<Option<DepthSnapshot>, Box<dyn Error + Send>> {
+        Ok(None)
+    }
+
+    /// OKX sends one snapshot then deltas
+    pub fn always_snapshot(&self) -> bool {
+        false
+    }
+
+    pub fn supported_symbols(&self) -> Vec<String> {
+        self.symbols.clone()
+    }
+
+    pub fn set_symbols(&mut self, symbols: Vec<String>) {
+        self.symbols = symbols;
+    }
+}
+
+// OKX-specific types
+
+#[derive(Debug, serde::Serialize)]
+struct OkxArg {
+    channel: &'static str,
+    #[serde(rename = "instId")]
+    inst_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct OkxArgRef<'a> {
+    channel: &'a str,
+    #[serde(rename = "instId")]
+    inst_id: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+struct OkxDepthMessage<'a> {
+    #[serde(borrow)]
+    arg: OkxArgRef<'a>,
+    action: Option<&'a str>,
+    #[serde(borrow)]
+    data: Vec<OkxDepthData<'a>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OkxDepthData<'a> {
+    #[serde(borrow)]
+    bids: Vec<[&'a str; 4]>,
+    #[serde(borrow)]
+    asks: Vec<[&'a str; 4]>,
+    #[serde(rename = "seqId")]
+    seq_id: u64,
+    #[serde(rename = "prevSeqId")]
+    prev_seq_id: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct OkxTradeMessage<'a> {
+    #[serde(borrow)]
+    data: Vec<OkxTradeData<'a>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OkxTradeData<'a> {
+    #[serde(rename = "instId")]
+    inst_id: &'a str,
+    #[serde(rename = "px")]
+    price: &'a str,
+    #[serde(rename = "sz")]
+    size: &'a str,
+    side: &'a str,
+    ts: &'a str,
+}
@@ -0,0 +1,133 @@
+/// Recorded exchange frames played back as a live feed (offline and CI runs)
+///
+/// A capture holds one raw WebSocket frame per line, prefixed with the epoch ms
+/// it was received at and a space:
+///
+/// ```text
+/// 1700000000000 {"channel":"book","type":"snapshot","data":[...]}
+/// ```
+///
+/// Frames are parsed by the connector of the exchange they were recorded from
+/// and handed to the manager at the recorded cadence, as if read off that
+/// exchange's WebSocket. Nothing goes over the network: REST snapshots are not
+/// available, so only exchanges sending snapshots over the WebSocket (Bybit,
+/// Coinbase, Kraken, OKX) end up with initialized books. Once the capture runs
+/// out the manager treats it as a closed connection, and the reconnect plays
+/// it again from the top.
+use super::{DepthSnapshot, ExchangeConnector, MarketMessage};
+use futures_util::Stream;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use tokio::time::{Duration, Instant};
+use tokio_tungstenite::tungstenite::{Error as WsError, Message as WsMessage};
+
+#[derive(Clone)]
+pub struct ReplayConnector {
+    path: PathBuf,
+    /// Connector of the exchange the capture was recorded from
+    source: Box<ExchangeConnector>,
+}
+
+impl ReplayConnector {
+    pub fn new(path: impl Into<PathBuf>, source: ExchangeConnector) -> Self {
+        Self {
+            path: path.into(),
+            source: Box::new(source),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Connector the recorded frames are parsed with
+    pub fn source(&self) -> &ExchangeConnector {
+        &self.source
+    }
+
+    /// Read the capture and stream its frames, each one at its recorded offset
+    /// from the first
+    pub async fn frames(
+        &self,
+    ) -> Result<impl Stream<Item = Result<WsMessage, WsError>> + Unpin, Box<dyn Error + Send + Sync>>
+    {
+        let capture = tokio::fs::read_to_string(&self.path)
+            .await
+            .map_err(|e| format!("cannot read capture {}: {}", self.path.display(), e))?;
+        let frames = parse_capture(&capture)
+            .map_err(|e| format!("capture {}: {}", self.path.display(), e))?;
+
+        let start = Instant::now();
+        let first_ms = frames.first().map_or(0, |(received_ms, _)| *received_ms);
+        Ok(Box::pin(futures_util::stream::unfold(
+            frames.into_iter(),
+            move |mut frames| async move {
+                let (received_ms, frame) = frames.next()?;
+                let offset = received_ms.saturating_sub(first_ms).max(0) as u64;
+                tokio::time::sleep_until(start + Duration::from_millis(offset)).await;
+                Some((Ok(WsMessage::Text(frame.into())), frames))
+            },
+        )))
+    }
+
+    pub fn parse_message(&self, raw: &str) -> Result<Option<MarketMessage>, Box<dyn Error + Send>> {
+        self.source.parse_message(raw)
+    }
+
+    /// The capture is all there is: no REST snapshots
+    pub async fn fetch_snapshot(
+        &self,
+        _symbol: &str,
+        _limit: usize,
+    ) -> Result<Option<DepthSnapshot>, Box<dyn Error + Send>> {
+        Ok(None)
+    }
+
+    pub fn always_snapshot(&self) -> bool {
+        self.source.always_snapshot()
+    }
+
+    pub fn supported_symbols(&self) -> Vec<String> {
+        self.source.supported_symbols()
+    }
+
+    pub fn set_symbols(&mut self, symbols: Vec<String>) {
+        self.source.set_symbols(symbols);
+    }
+}
+
+/// `(received epoch ms, frame)` of every non-blank line
+fn parse_capture(capture: &str) -> Result<Vec<(i64, String)>, String> {
+    capture
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            line.split_once(' ')
+                .and_then(|(received_ms, frame)| {
+                    Some((received_ms.parse().ok()?, frame.trim().to_string()))
+                })
+                .ok_or_else(|| format!("line {}: expected `<epoch ms> <frame>`", i + 1))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_capture() {
+        let frames = parse_capture("1000 {\"a\":1}\n\n1250 {\"b\": 2}\n").unwrap();
+        assert_eq!(
+            frames,
+            vec![
+                (1000, "{\"a\":1}".to_string()),
+                (1250, "{\"b\": 2}".to_string())
+            ]
+        );
+
+        let err = parse_capture("1000 {}\n{\"no\":\"timestamp\"}").unwrap_err();
+        assert!(err.starts_with("line 2"), "{}", err);
+    }
+}
@@ -0,0 +1,402 @@
+//! Symbol formatting shared by the exchange connectors
+//!
+//! Canonical symbols use the concatenated Binance style (e.g. "BTCUSDT").
+//! Each connector maps them to its native format per channel and back.
+//! The set of tracked symbols lives in a [`SymbolRegistry`] and can change at runtime.
+
+use crate::types::TRADING_PAIRS;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// Market data channel a subscription targets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Channel {
+    /// Order book depth
+    Book,
+    /// Trade tape
+    Trades,
+}
+
+/// Channels subscribed per symbol; symbols without an override get both
+#[derive(Debug, Clone, Default)]
+pub struct ChannelFilter {
+    overrides: Arc<HashMap<String, Channel>>,
+}
+
+impl ChannelFilter {
+    /// Parse `SYMBOL:channel` entries, comma-separated (e.g. `FLOWRS_SYMBOL_CHANNELS`)
+    ///
+    /// Channels are `book` (alias `depth`), `trades` (alias `trade`) or `both`.
+    /// Invalid entries are skipped with a warning.
+    pub fn parse(raw: &str) -> Self {
+        let mut overrides = HashMap::new();
+        for entry in raw.split(',').filter(|entry| !entry.trim().is_empty()) {
+            let parsed = entry.split_once(':').and_then(|(symbol, channel)| {
+                let channel = match channel.trim().to_ascii_lowercase().as_str() {
+                    "book" | "depth" => Some(Channel::Book),
+                    "trades" | "trade" => Some(Channel::Trades),
+                    "both" => None,
+                    _ => return None,
+                };
+                Some((normalize_symbol(symbol)?, channel))
+            });
+            match parsed {
+                Some((symbol, Some(channel))) => {
+                    overrides.insert(symbol, channel);
+                }
+                Some((symbol, None)) => {
+                    overrides.remove(&symbol);
+                }
+                None => tracing::warn!("Ignoring invalid symbol channel {:?}", entry),
+            }
+        }
+        Self {
+            overrides: Arc::new(overrides),
+        }
+    }
+
+    /// Whether `channel` should be subscribed for a canonical symbol
+    pub fn wants(&self, symbol: &str, channel: Channel) -> bool {
+        self.overrides
+            .get(symbol)
+            .is_none_or(|&only| only == channel)
+    }
+}
+
+/// Quote assets recognized at the end of a canonical symbol (longest match wins)
+const QUOTE_ASSETS: &[&str] = &["FDUSD", "USDT", "USDC", "BUSD", "USD", "EUR", "BTC", "ETH"];
+
+/// Quote asset of a canonical symbol: "BTCUSDT" -> Some("USDT")
+pub fn quote_asset(symbol: &str) -> Option<&'static str> {
+    QUOTE_ASSETS
+        .iter()
+        .copied()
+        .filter(|quote| symbol.len() > quote.len() && symbol.ends_with(quote))
+        .max_by_key(|quote| quote.len())
+}
+
+/// Price increment of the tracked symbols, scaled by `PRICE_FACTOR`
+///
+/// These are Binance spot ticks. Venues quoting a coarser grid (Kraken BTC in
+/// 0.1) show whole multiples of them.
+const TICK_SIZES: &[(&str, u64)] = &[
+    ("BTCUSDT", 1_000_000),  // 0.01
+    ("ETHUSDT", 1_000_000),  // 0.01
+    ("SOLUSDT", 1_000_000),  // 0.01
+    ("XRPUSDT", 10_000),     // 0.0001
+    ("DOGEUSDT", 1_000),     // 0.00001
+    ("ADAUSDT", 10_000),     // 0.0001
+    ("AVAXUSDT", 1_000_000), // 0.01
+    ("DOTUSDT", 100_000),    // 0.001
+    ("LINKUSDT", 1_000_000), // 0.01
+];
+
+/// Price increment of a canonical symbol, scaled by `PRICE_FACTOR` (None if not listed)
+pub fn tick_size(symbol: &str) -> Option<u64> {
+    TICK_SIZES
+        .iter()
+        .find(|(listed, _)| *listed == symbol)
+        .map(|&(_, tick)| tick)
+}
+
+/// Canonical <-> native pair mapping of an exchange writing pairs as "BASE<sep>QUOTE"
+///
+/// Base and quote are split on the known quote assets, so "ETHBTC" becomes
+/// "ETH-BTC" rather than a USD pair. `quote_aliases` lists canonical quotes
+/// the exchange lists under another name (Coinbase and Kraken price the
+/// majors in USD, not USDT); an alias is skipped for a symbol whose aliased
+/// form is tracked too, so "BTCUSD" and "BTCUSDT" never share a native pair.
+/// Native pairs map back through the table of tracked symbols.
+#[derive(Debug, Clone)]
+pub struct SymbolMap {
+    separator: char,
+    /// (canonical quote, native quote)
+    quote_aliases: &'static [(&'static str, &'static str)],
+    to_canonical: HashMap<String, String>,
+}
+
+impl SymbolMap {
+    pub fn new(
+        separator: char,
+        quote_aliases: &'static [(&'static str, &'static str)],
+        symbols: &[String],
+    ) -> Self {
+        let mut map = Self {
+            separator,
+            quote_aliases,
+            to_canonical: HashMap::new(),
+        };
+        map.set_symbols(symbols);
+        map
+    }
+
+    /// Rebuild the reverse table for a new tracked symbol list
+    pub fn set_symbols(&mut self, symbols: &[String]) {
+        let tracked = |symbol: &str| symbols.iter().any(|s| s == symbol);
+        self.to_canonical = symbols
+            .iter()
+            .map(|symbol| (self.native(symbol, tracked), symbol.clone()))
+            .collect();
+    }
+
+    /// Native pair of a canonical symbol: "BTCUSDT" -> "BTC-USD", "ETHBTC" -> "ETH-BTC"
+    ///
+    /// Symbols without a known quote asset are passed through unchanged.
+    pub fn to_native(&self, symbol: &str) -> String {
+        self.native(symbol, |s| self.to_canonical.values().any(|c| c == s))
+    }
+
+    fn native(&self, symbol: &str, tracked: impl Fn(&str) -> bool) -> String {
+        let Some(quote) = quote_asset(symbol) else {
+            return symbol.to_string();
+        };
+        let base = &symbol[..symbol.len() - quote.len()];
+        let native_quote = self
+            .quote_aliases
+            .iter()
+            .find(|&&(canonical, alias)| {
+                canonical == quote && !tracked(&format!("{}{}", base, alias))
+            })
+            .map_or(quote, |&(_, alias)| alias);
+        format!("{}{}{}", base, self.separator, native_quote)
+    }
+
+    /// Canonical symbol of a native pair: "BTC-USD" -> "BTCUSDT"
+    ///
+    /// Pairs that aren't tracked (yet) go through the aliases in reverse.
+    pub fn to_canonical(&self, native: &str) -> String {
+        if let Some(symbol) = self.to_canonical.get(native) {
+            return symbol.clone();
+        }
+        let (base, quote) = native.split_once(self.separator).unwrap_or((native, ""));
+        let quote = self
+            .quote_aliases
+            .iter()
+            .find(|&&(_, alias)| alias == quote)
+            .map_or(quote, |&(canonical, _)| canonical);
+        format!("{}{}", base, quote)
+    }
+}
+
+/// Separators clients put between base and quote ("BTC-USDT", "btc_usdt", "BTC/USDT")
+pub const SYMBOL_SEPARATORS: &[char] = &['-', '_', '/', ':'];
+
+/// Normalize a user-supplied symbol to the canonical form: " btc-usdt " -> "BTCUSDT"
+///
+/// Returns None for empty or otherwise non-alphanumeric input.
+pub fn normalize_symbol(raw: &str) -> Option<String> {
+    let symbol: String = raw
+        .trim()
+        .chars()
+        .filter(|c| !SYMBOL_SEPARATORS.contains(c))
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    if symbol.is_empty() || !symbol.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+    Some(symbol)
+}
+
+/// Parse a comma-separated symbol list (e.g. `FLOWRS_SYMBOLS`) into canonical symbols
+///
+/// Duplicates (after normalization) are dropped keeping the first occurrence,
+/// so each symbol is subscribed once. Invalid entries are skipped. An empty
+/// result falls back to `TRADING_PAIRS`.
+pub fn parse_symbol_list(raw: &str) -> Vec<String> {
+    let mut symbols: Vec<String> = Vec::new();
+    let mut duplicates: Vec<String> = Vec::new();
+
+    for entry in raw.split(',').filter(|entry| !entry.trim().is_empty()) {
+        match normalize_symbol(entry) {
+            Some(symbol) if symbols.contains(&symbol) => duplicates.push(symbol),
+            Some(symbol) => symbols.push(symbol),
+            None => tracing::warn!("Ignoring invalid symbol {:?}", entry),
+        }
+    }
+
+    if !duplicates.is_empty() {
+        tracing::warn!("Removed duplicate symbols: {:?}", duplicates);
+    }
+    if symbols.is_empty() {
+        tracing::warn!("Symbol list {:?} is empty, using the default pairs", raw);
+        return TRADING_PAIRS.iter().map(|s| s.to_string()).collect();
+    }
+    symbols
+}
+
+/// Runtime-editable set of tracked canonical symbols
+///
+/// Exchange tasks watch it and resubscribe whenever it changes.
+#[derive(Debug, Clone)]
+pub struct SymbolRegistry {
+    tx: Arc<watch::Sender<Vec<String>>>,
+}
+
+impl SymbolRegistry {
+    pub fn new(symbols: Vec<String>) -> Self {
+        let (tx, _) = watch::channel(symbols);
+        Self { tx: Arc::new(tx) }
+    }
+
+    /// Snapshot of the currently tracked symbols
+    pub fn current(&self) -> Vec<String> {
+        self.tx.borrow().clone()
+    }
+
+    /// Receiver notified on every change of the symbol set
+    pub fn subscribe(&self) -> watch::Receiver<Vec<String>> {
+        self.tx.subscribe()
+    }
+
+    /// Normalize a client-supplied symbol and check that it is tracked
+    pub fn resolve(&self, raw: &str) -> Result<String, String> {
+        let symbol = normalize_symbol(raw).ok_or_else(|| format!("invalid symbol {:?}", raw))?;
+        if self.tx.borrow().contains(&symbol) {
+            Ok(symbol)
+        } else {
+            Err(format!("unknown symbol {}", symbol))
+        }
+    }
+
+    /// Start tracking a symbol. Returns false if it was already tracked
+    pub fn add(&self, symbol: &str) -> bool {
+        self.tx.send_if_modified(|symbols| {
+            if symbols.iter().any(|s| s == symbol) {
+                return false;
+            }
+            symbols.push(symbol.to_string());
+            true
+        })
+    }
+
+    /// Stop tracking a symbol. Returns false if it wasn't tracked
+    pub fn remove(&self, symbol: &str) -> bool {
+        self.tx.send_if_modified(|symbols| {
+            let before = symbols.len();
+            symbols.retain(|s| s != symbol);
+            symbols.len() != before
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USD_FOR_USDT: &[(&str, &str)] = &[("USDT", "USD")];
+
+    fn symbol_map(separator: char, symbols: &[&str]) -> SymbolMap {
+        let symbols: Vec<String> = symbols.iter().map(|s| s.to_string()).collect();
+        SymbolMap::new(separator, USD_FOR_USDT, &symbols)
+    }
+
+    #[test]
+    fn test_symbol_map_round_trip() {
+        let map = symbol_map('-', &["BTCUSDT", "ETHUSDT", "ETHBTC", "USDCUSDT", "BTCEUR"]);
+        for (canonical, native) in [
+            ("BTCUSDT", "BTC-USD"),
+            ("ETHUSDT", "ETH-USD"),
+            // Trimming "USDT" and appending "-USD" got these wrong
+            ("ETHBTC", "ETH-BTC"),
+            ("USDCUSDT", "USDC-USD"),
+            ("BTCEUR", "BTC-EUR"),
+        ] {
+            assert_eq!(map.to_native(canonical), native);
+            assert_eq!(map.to_canonical(native), canonical);
+        }
+
+        let kraken = symbol_map('/', &["BTCUSDT"]);
+        assert_eq!(kraken.to_native("BTCUSDT"), "BTC/USD");
+        assert_eq!(kraken.to_canonical("BTC/USD"), "BTCUSDT");
+        // Not tracked: aliases applied in reverse
+        assert_eq!(kraken.to_canonical("SOL/USD"), "SOLUSDT");
+        assert_eq!(kraken.to_canonical("SOL/EUR"), "SOLEUR");
+    }
+
+    #[test]
+    fn test_symbol_map_keeps_usd_and_usdt_apart() {
+        let map = symbol_map('-', &["BTCUSD", "BTCUSDT"]);
+        assert_eq!(map.to_native("BTCUSD"), "BTC-USD");
+        assert_eq!(map.to_native("BTCUSDT"), "BTC-USDT");
+        assert_eq!(map.to_canonical("BTC-USD"), "BTCUSD");
+        assert_eq!(map.to_canonical("BTC-USDT"), "BTCUSDT");
+    }
+
+    #[test]
+    fn test_quote_asset() {
+        assert_eq!(quote_asset("BTCUSDT"), Some("USDT"));
+        assert_eq!(quote_asset("ETHBTC"), Some("BTC"));
+        assert_eq!(quote_asset("BTCFDUSD"), Some("FDUSD"));
+        assert_eq!(quote_asset("USDT"), None);
+        assert_eq!(quote_asset("BTCXYZ"), None);
+    }
+
+    #[test]
+    fn test_channel_filter_defaults_to_both() {
+        let filter =
+            ChannelFilter::parse("btc-usdt:book, ETHUSDT:Trades,SOLUSDT:both,XRPUSDT:tape");
+        assert!(filter.wants("BTCUSDT", Channel::Book));
+        assert!(!filter.wants("BTCUSDT", Channel::Trades));
+        assert!(!filter.wants("ETHUSDT", Channel::Book));
+        assert!(filter.wants("ETHUSDT", Channel::Trades));
+        for symbol in ["SOLUSDT", "XRPUSDT", "DOGEUSDT"] {
+            assert!(filter.wants(symbol, Channel::Book));
+            assert!(filter.wants(symbol, Channel::Trades));
+        }
+    }
+
+    #[test]
+    fn test_normalize_symbol() {
+        for raw in [" btcusdt ", "BTC-USDT", "btc_usdt", "Btc/Usdt"] {
+            assert_eq!(normalize_symbol(raw), Some("BTCUSDT".to_string()));
+        }
+        assert_eq!(normalize_symbol(""), None);
+        assert_eq!(normalize_symbol("--"), None);
+        assert_eq!(normalize_symbol("BTC.USDT"), None);
+    }
+
+    #[test]
+    fn test_parse_symbol_list_dedups_in_order() {
+        assert_eq!(
+            parse_symbol_list("ETHUSDT, btc-usdt,ETHUSDT,BTCUSDT ,sol_usdt"),
+            vec!["ETHUSDT", "BTCUSDT", "SOLUSDT"]
+        );
+        assert_eq!(parse_symbol_list("BTCUSDT,,bad.symbol"), vec!["BTCUSDT"]);
+    }
+
+    #[test]
+    fn test_parse_symbol_list_empty_falls_back_to_defaults() {
+        for raw in ["", " , ", "bad.symbol"] {
+            assert_eq!(parse_symbol_list(raw), TRADING_PAIRS);
+        }
+    }
+
+    #[test]
+    fn test_registry_resolve_rejects_unknown_symbols() {
+        let registry = SymbolRegistry::new(vec!["BTCUSDT".to_string()]);
+        assert_eq!(registry.resolve("btc-usdt"), Ok("BTCUSDT".to_string()));
+        assert_eq!(
+            registry.resolve("eth_usdt"),
+            Err("unknown symbol ETHUSDT".to_string())
+        );
+        assert!(registry.resolve("BTC USDT!").is_err());
+    }
+
+    #[test]
+    fn test_registry_add_remove_notifies_watchers() {
+        let registry = SymbolRegistry::new(vec!["BTCUSDT".to_string()]);
+        let mut rx = registry.subscribe();
+
+        assert!(!registry.add("BTCUSDT"));
+        assert!(!rx.has_changed().unwrap());
+
+        assert!(registry.add("PEPEUSDT"));
+        assert!(rx.has_changed().unwrap());
+        assert_eq!(*rx.borrow_and_update(), vec!["BTCUSDT", "PEPEUSDT"]);
+
+        assert!(registry.remove("BTCUSDT"));
+        assert!(!registry.remove("BTCUSDT"));
+        assert_eq!(registry.current(), vec!["PEPEUSDT"]);
+    }
+}
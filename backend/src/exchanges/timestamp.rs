@@ -0,0 +1,167 @@
+//! Trade timestamp parsing for the RFC 3339 strings sent by Coinbase and Kraken
+//!
+//! Both exchanges emit UTC timestamps in one fixed layout
+//! (`YYYY-MM-DDThh:mm:ss.ffffffZ`), so a byte-level parser handles the hot path
+//! and chrono is only used for anything else (offsets, odd precision...).
+
+/// Parse an RFC 3339 timestamp to epoch milliseconds
+///
+/// Tries the fixed-layout fast path first and falls back to chrono.
+#[inline]
+pub fn parse_rfc3339_millis(s: &str) -> Option<i64> {
+    fast_parse_rfc3339_millis(s).or_else(|| {
+        chrono::DateTime::parse_from_rfc3339(s)
+            .ok()
+            .map(|dt| dt.timestamp_millis())
+    })
+}
+
+/// Fast path for `YYYY-MM-DDThh:mm:ss[.f{1,9}]Z` (UTC only)
+///
+/// Returns None for any other layout. A leap second (`:60`) counts as the
+/// first second of the next minute, like chrono's `timestamp_millis`.
+pub fn fast_parse_rfc3339_millis(s: &str) -> Option<i64> {
+    let b = s.as_bytes();
+    if b.len() < 20 || b[b.len() - 1] != b'Z' {
+        return None;
+    }
+    if b[4] != b'-' || b[7] != b'-' || b[10] != b'T' || b[13] != b':' || b[16] != b':' {
+        return None;
+    }
+
+    let year = digits(&b[0..4])? as i64;
+    let month = digits(&b[5..7])?;
+    let day = digits(&b[8..10])?;
+    let hour = digits(&b[11..13])? as i64;
+    let minute = digits(&b[14..16])? as i64;
+    let second = digits(&b[17..19])? as i64;
+    if !(1..=12).contains(&month)
+        || day == 0
+        || day > days_in_month(year, month)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return None;
+    }
+
+    // Optional fraction: only the first 3 digits matter for millis
+    let millis = match &b[19..b.len() - 1] {
+        [] => 0,
+        [b'.', frac @ ..] if (1..=9).contains(&frac.len()) => {
+            let mut millis = 0;
+            for i in 0..3 {
+                let digit = match frac.get(i) {
+                    Some(d) if d.is_ascii_digit() => (d - b'0') as i64,
+                    Some(_) => return None,
+                    None => 0,
+                };
+                millis = millis * 10 + digit;
+            }
+            if !frac.iter().all(u8::is_ascii_digit) {
+                return None;
+            }
+            millis
+        }
+        _ => return None,
+    };
+
+    let days = days_from_civil(year, month, day);
+    let seconds = days * 86_400 + hour * 3_600 + minute * 60 + second;
+    Some(seconds * 1_000 + millis)
+}
+
+/// Parse a fixed-width run of ASCII digits
+#[inline(always)]
+fn digits(bytes: &[u8]) -> Option<u32> {
+    bytes.iter().try_fold(0u32, |acc, &b| {
+        b.is_ascii_digit().then(|| acc * 10 + (b - b'0') as u32)
+    })
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date (Howard Hinnant's algorithm)
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chrono_millis(s: &str) -> Option<i64> {
+        chrono::DateTime::parse_from_rfc3339(s)
+            .ok()
+            .map(|dt| dt.timestamp_millis())
+    }
+
+    #[test]
+    fn test_fast_path_matches_chrono() {
+        for s in [
+            "1970-01-01T00:00:00Z",
+            "2024-02-29T23:59:59.999Z",
+            "2023-09-25T07:49:37.708706Z",
+            "2019-08-14T20:42:27.265Z",
+            "2025-01-15T12:00:00.123456789Z",
+            "2025-01-15T12:00:00.1Z",
+            // Missing fractional seconds
+            "2025-12-31T23:59:59Z",
+            // Leap second
+            "2016-12-31T23:59:60.500Z",
+            "1969-07-20T20:17:40Z",
+        ] {
+            let fast = fast_parse_rfc3339_millis(s);
+            assert!(fast.is_some(), "fast path rejected {}", s);
+            assert_eq!(fast, chrono_millis(s), "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_other_layouts_fall_back_to_chrono() {
+        for s in [
+            "2025-01-15T12:00:00.123+00:00",
+            "2025-01-15T14:00:00+02:00",
+            "2025-01-15t12:00:00z",
+        ] {
+            assert_eq!(fast_parse_rfc3339_millis(s), None, "{}", s);
+            assert_eq!(parse_rfc3339_millis(s), chrono_millis(s), "{}", s);
+            assert!(parse_rfc3339_millis(s).is_some(), "{}", s);
+        }
+        assert_eq!(
+            parse_rfc3339_millis("2025-01-15T12:00:00.123+00:00"),
+            parse_rfc3339_millis("2025-01-15T12:00:00.123Z")
+        );
+    }
+
+    #[test]
+    fn test_rejects_invalid_timestamps() {
+        for s in [
+            "",
+            "2025-01-15",
+            "2025-02-29T00:00:00Z",
+            "2025-13-01T00:00:00Z",
+            "2025-01-15T24:00:00Z",
+            "2025-01-15T12:00:61Z",
+            "2025-01-15T12:00:00.Z",
+            "2025-01-15T12:00:00.12a4Z",
+            "2025-01-15T12:00:00.1234567890Z",
+        ] {
+            assert_eq!(fast_parse_rfc3339_millis(s), None, "{}", s);
+            assert_eq!(parse_rfc3339_millis(s), chrono_millis(s), "{}", s);
+        }
+    }
+}
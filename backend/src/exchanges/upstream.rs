@@ -0,0 +1,125 @@
+/// Upstream FlowRS instance consumed as an exchange (multi-region relay)
+///
+/// Reads another instance's client WebSocket and turns its `ClientMessage`
+/// frames back into `MarketMessage`s, so a downstream instance can fan out
+/// without connecting to every exchange itself.
+use super::json;
+use super::{DepthSnapshot, Exchange, MarketMessage};
+use crate::orderbook::{PRICE_FACTOR, QTY_FACTOR};
+use crate::types::{BookUpdate, ClientMessage, PriceLevel, Trade};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::error::Error;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+#[derive(Clone)]
+pub struct FlowRsUpstreamConnector {
+    url: String,
+    symbols: Vec<String>,
+    /// Local update ids for upstreams that don't relay the exchange's own id
+    sequence: Arc<AtomicU64>,
+}
+
+impl FlowRsUpstreamConnector {
+    pub fn new(url: String, symbols: Vec<String>) -> Self {
+        Self {
+            url,
+            symbols,
+            sequence: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn build_subscription_url(&self, _symbols: &[&str]) -> String {
+        self.url.clone()
+    }
+
+    pub fn parse_message(&self, raw: &str) -> Result<Option<MarketMessage>, Box<dyn Error + Send>> {
+        let mut scratch = json::Scratch::default();
+        let msg: ClientMessage = json::from_str(raw, &mut scratch)?;
+
+        match msg {
+            ClientMessage::BookUpdate(update) => Ok(self.depth_update(update)),
+            ClientMessage::BookBatch(updates) => {
+                let depth_updates: Vec<MarketMessage> = updates
+                    .into_iter()
+                    .filter_map(|update| self.depth_update(update))
+                    .collect();
+                Ok(Some(MarketMessage::Batch(depth_updates)))
+            }
+            ClientMessage::Trade(mut trade) if self.tracks(&trade.symbol) => {
+                // Upstreams predating the field send none
+                if trade.notional == 0 {
+                    trade.notional = Trade::notional_of(trade.price, trade.quantity);
+                }
+                Ok(Some(MarketMessage::Trade(trade)))
+            }
+            // Metrics, symbol lists, candles... are computed locally by each instance
+            _ => Ok(None),
+        }
+    }
+
+    /// Upstream books only carry the top levels: each one replaces the book
+    /// whole, as `always_snapshot` tells the manager
+    fn depth_update(&self, update: BookUpdate) -> Option<MarketMessage> {
+        if !self.tracks(&update.symbol) {
+            return None;
+        }
+        let exchange = Exchange::from_name(&update.exchange)?;
+
+        Some(MarketMessage::DepthUpdate {
+            exchange,
+            symbol: update.symbol,
+            bids: Self::to_internal_levels(&update.bids),
+            asks: Self::to_internal_levels(&update.asks),
+            // Keep the origin exchange's id so downstream clients can cross-check it
+            update_id: match update.update_id {
+                0 => self.sequence.fetch_add(1, Ordering::Relaxed) + 1,
+                id => id,
+            },
+            first_update_id: None,
+            prev_update_id: None,
+            is_snapshot: false,
+            // Relayed books are truncated to the display depth
+            checksum: None,
+            change_sequences: None,
+        })
+    }
+
+    fn tracks(&self, symbol: &str) -> bool {
+        self.symbols.iter().any(|s| s == symbol)
+    }
+
+    fn to_internal_levels(levels: &[PriceLevel]) -> Vec<(u64, u64)> {
+        levels
+            .iter()
+            .filter_map(|level| {
+                let price = (level.price * Decimal::from(PRICE_FACTOR)).to_u64()?;
+                let qty = (level.quantity * Decimal::from(QTY_FACTOR)).to_u64()?;
+                Some((price, qty))
+            })
+            .collect()
+    }
+
+    pub async fn fetch_snapshot(
+        &self,
+        _symbol: &str,
+        _limit: usize,
+    ) -> Result<Option<DepthSnapshot>, Box<dyn Error + Send>> {
+        // The upstream sends its current books right after connect
+        Ok(None)
+    }
+
+    /// Every relayed book is the upstream's full top-N view, never a delta
+    pub fn always_snapshot(&self) -> bool {
+        true
+    }
+
+    pub fn supported_symbols(&self) -> Vec<String> {
+        self.symbols.clone()
+    }
+
+    pub fn set_symbols(&mut self, symbols: Vec<String>) {
+        self.symbols = symbols;
+    }
+}
@@ -0,0 +1,123 @@
+//! Fast fixed-point parsing helpers shared by the exchange connectors
+
+use crate::orderbook::PRICE_FACTOR;
+
+/// Number of decimal places kept by the 1e8 scale factor
+const SCALE_DECIMALS: u32 = 8;
+
+/// Parse a decimal string into a u64 scaled by 1e8 (pure integer math)
+///
+/// Returns `None` on empty input, non-digit characters, multiple dots or overflow.
+/// Digits beyond the 8th decimal place are truncated.
+#[inline]
+pub fn fast_parse_u64_inner(s: &str) -> Option<u64> {
+    let bytes = s.as_bytes();
+    if bytes.is_empty() {
+        return None;
+    }
+
+    let mut int_part: u64 = 0;
+    let mut frac_part: u64 = 0;
+    let mut frac_digits: u32 = 0;
+    let mut seen_dot = false;
+    let mut seen_digit = false;
+
+    for &b in bytes {
+        match b {
+            b'0'..=b'9' => {
+                seen_digit = true;
+                let digit = (b - b'0') as u64;
+                if seen_dot {
+                    if frac_digits < SCALE_DECIMALS {
+                        frac_part = frac_part * 10 + digit;
+                        frac_digits += 1;
+                    }
+                } else {
+                    int_part = int_part.checked_mul(10)?.checked_add(digit)?;
+                }
+            }
+            b'.' if !seen_dot => seen_dot = true,
+            _ => return None,
+        }
+    }
+
+    if !seen_digit {
+        return None;
+    }
+
+    let frac_scaled = frac_part * 10u64.pow(SCALE_DECIMALS - frac_digits);
+    int_part.checked_mul(PRICE_FACTOR)?.checked_add(frac_scaled)
+}
+
+/// Same as `fast_parse_u64_inner` but with an error for serde deserializers
+pub fn fast_parse_u64(s: &str) -> Result<u64, String> {
+    fast_parse_u64_inner(s).ok_or_else(|| format!("invalid decimal: {:?}", s))
+}
+
+/// Convert an f64 (Kraken sends JSON numbers) into a u64 scaled by 1e8
+#[inline]
+pub fn fast_parse_f64_inner(value: f64) -> Option<u64> {
+    if !value.is_finite() || value < 0.0 {
+        return None;
+    }
+    let scaled = (value * PRICE_FACTOR as f64).round();
+    if scaled >= u64::MAX as f64 {
+        return None;
+    }
+    Some(scaled as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decimal_strings_scale_without_rounding() {
+        assert_eq!(fast_parse_u64_inner("0.00001234"), Some(1_234));
+        assert_eq!(
+            fast_parse_u64_inner("12345678.90123456"),
+            Some(1_234_567_890_123_456)
+        );
+        assert_eq!(fast_parse_u64_inner("97000"), Some(97_000 * PRICE_FACTOR));
+        // Trailing zeros and a bare trailing dot change nothing
+        assert_eq!(
+            fast_parse_u64_inner("97000.10000000"),
+            fast_parse_u64_inner("97000.1")
+        );
+        assert_eq!(fast_parse_u64_inner("5."), Some(5 * PRICE_FACTOR));
+        // Past the 8th decimal digits are truncated
+        assert_eq!(fast_parse_u64_inner("0.123456789"), Some(12_345_678));
+    }
+
+    #[test]
+    fn test_malformed_input_is_rejected() {
+        for bad in [
+            "",
+            ".",
+            "1.2.3",
+            "abc",
+            "-1",
+            "1e5",
+            " 1",
+            "1,5",
+            "99999999999999999999",
+        ] {
+            assert_eq!(fast_parse_u64_inner(bad), None, "{:?}", bad);
+        }
+        assert!(fast_parse_u64("nope").unwrap_err().contains("nope"));
+    }
+
+    #[test]
+    fn test_f64_input_scales_to_nearest_unit() {
+        assert_eq!(fast_parse_f64_inner(0.00001234), Some(1_234));
+        assert_eq!(
+            fast_parse_f64_inner(97000.1),
+            Some(97_000 * PRICE_FACTOR + PRICE_FACTOR / 10)
+        );
+        assert_eq!(fast_parse_f64_inner(0.0), Some(0));
+        assert_eq!(fast_parse_f64_inner(-1.0), None);
+        assert_eq!(fast_parse_f64_inner(f64::NAN), None);
+        assert_eq!(fast_parse_f64_inner(f64::INFINITY), None);
+        assert_eq!(fast_parse_f64_inner(1e12), None);
+    }
+}
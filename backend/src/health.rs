@@ -0,0 +1,139 @@
+//! Per-exchange connection state for health and readiness probes
+//!
+//! Each exchange task reports when its WebSocket comes up and when it drops;
+//! the HTTP server reads the states back for `/readyz`.
+
+use dashmap::DashMap;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Consecutive disconnects without a successful connect before an exchange is down
+pub const DOWN_AFTER_FAILURES: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionState {
+    /// Subscribed and receiving
+    Connected,
+    /// Connecting, first time or after a disconnect
+    Reconnecting,
+    /// Failed `DOWN_AFTER_FAILURES` times in a row, still retrying
+    Down,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExchangeStatus {
+    pub state: ConnectionState,
+    /// Disconnects since the last successful connect
+    pub consecutive_failures: u32,
+    /// Epoch ms of the last connection drop, None if it never dropped
+    pub last_reconnect_ms: Option<i64>,
+}
+
+impl ExchangeStatus {
+    /// Before the first connect
+    fn initial() -> Self {
+        Self {
+            state: ConnectionState::Reconnecting,
+            consecutive_failures: 0,
+            last_reconnect_ms: None,
+        }
+    }
+}
+
+/// Connection state of every exchange, updated by its connection task
+#[derive(Debug, Default)]
+pub struct ConnectionHealth {
+    exchanges: DashMap<&'static str, ExchangeStatus>,
+}
+
+impl ConnectionHealth {
+    /// Track `exchanges`, all reconnecting until their first connect
+    pub fn new(exchanges: &[&'static str]) -> Self {
+        let health = Self::default();
+        for &exchange in exchanges {
+            health.exchanges.insert(exchange, ExchangeStatus::initial());
+        }
+        health
+    }
+
+    /// The WebSocket is up and subscribed
+    pub fn set_connected(&self, exchange: &'static str) {
+        let mut status = self.status_mut(exchange);
+        status.state = ConnectionState::Connected;
+        status.consecutive_failures = 0;
+    }
+
+    /// The connection dropped (or failed to come up) and will be retried
+    pub fn set_disconnected(&self, exchange: &'static str) {
+        let mut status = self.status_mut(exchange);
+        status.consecutive_failures = status.consecutive_failures.saturating_add(1);
+        status.last_reconnect_ms = Some(chrono::Utc::now().timestamp_millis());
+        status.state = if status.consecutive_failures >= DOWN_AFTER_FAILURES {
+            ConnectionState::Down
+        } else {
+            ConnectionState::Reconnecting
+        };
+    }
+
+    /// Whether at least one exchange is connected
+    pub fn any_connected(&self) -> bool {
+        self.exchanges
+            .iter()
+            .any(|entry| entry.state == ConnectionState::Connected)
+    }
+
+    /// Every tracked exchange's status, sorted by name
+    pub fn snapshot(&self) -> BTreeMap<&'static str, ExchangeStatus> {
+        self.exchanges
+            .iter()
+            .map(|entry| (*entry.key(), entry.value().clone()))
+            .collect()
+    }
+
+    fn status_mut(
+        &self,
+        exchange: &'static str,
+    ) -> dashmap::mapref::one::RefMut<'_, &'static str, ExchangeStatus> {
+        self.exchanges
+            .entry(exchange)
+            .or_insert_with(ExchangeStatus::initial)
+    }
+}
+
+pub type SharedConnectionHealth = Arc<ConnectionHealth>;
+
+pub fn create_shared_connection_health(exchanges: &[&'static str]) -> SharedConnectionHealth {
+    Arc::new(ConnectionHealth::new(exchanges))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exchange_down_after_repeated_failures() {
+        let health = ConnectionHealth::new(&["Binance", "Kraken"]);
+        assert!(!health.any_connected());
+
+        health.set_connected("Binance");
+        assert!(health.any_connected());
+
+        for _ in 0..DOWN_AFTER_FAILURES - 1 {
+            health.set_disconnected("Kraken");
+        }
+        assert_eq!(
+            health.snapshot()["Kraken"].state,
+            ConnectionState::Reconnecting
+        );
+        health.set_disconnected("Kraken");
+        let kraken = &health.snapshot()["Kraken"];
+        assert_eq!(kraken.state, ConnectionState::Down);
+        assert!(kraken.last_reconnect_ms.is_some());
+
+        // One good connect clears the failure count
+        health.set_connected("Kraken");
+        assert_eq!(health.snapshot()["Kraken"].consecutive_failures, 0);
+    }
+}
@@ -0,0 +1,885 @@
+//! HTTP endpoints served next to the WebSocket feed (debugging and monitoring)
+//!
+//! - `GET /books`: every initialized book as one JSON object keyed by "exchange:symbol"
+//! - `GET /book/{exchange}/{symbol}?depth=10`: one book as a `book_update` message
+//!
+//! Both book endpoints take the WebSocket auth token when one is set (see
+//! `server::presents_token`), as they serve the same data as the feed.
+//!
+//! - `GET /metrics`: `MetricsCollector` readings in the Prometheus text format
+//! - `GET /healthz`: 200 while the process serves requests (liveness)
+//! - `GET /readyz`: 200 once an exchange is connected, a book initialized and
+//!   the book coverage reaches its minimum, 503 before; the body lists the
+//!   coverage and each exchange's connection state (readiness)
+//! - `GET /debug/jemalloc`: allocator stats (allocated/active/resident bytes)
+//! - `POST /debug/heap-profile`: dump a jemalloc heap profile (admin token only,
+//!   see `server::presents_admin_token`)
+
+use crate::exchanges::symbols::normalize_symbol;
+use crate::exchanges::Exchange;
+use crate::health::{ExchangeStatus, SharedConnectionHealth};
+use crate::jemalloc;
+use crate::metrics::SharedMetrics;
+use crate::orderbook::{OrderBookManager, SharedOrderBookManager};
+use crate::server::{presents_admin_token, presents_token};
+use crate::types::{Metrics, PriceLevel, StreamMetrics, BOOK_STALE_AFTER_SECS};
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::header::{HeaderValue, CONTENT_TYPE, WWW_AUTHENTICATE};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+/// Shared state handed to every HTTP request
+#[derive(Clone)]
+pub struct HttpState {
+    pub orderbook_manager: SharedOrderBookManager,
+    pub metrics: SharedMetrics,
+    pub connection_health: SharedConnectionHealth,
+    /// Levels per side included in book dumps
+    pub display_depth: usize,
+    /// Secret required by endpoints with side effects (heap profile dumps
+    /// write to disk); None disables them
+    pub admin_token: Option<String>,
+    /// Where heap profiles are written
+    pub heap_profile_dir: PathBuf,
+    /// Secret book requests must present, like WebSocket clients (None: no auth)
+    pub auth_token: Option<String>,
+    /// Fraction of expected books that must be initialized and fresh for
+    /// `/readyz` to pass (0.0: any single book will do), see `coverage`
+    pub min_ready_coverage: f64,
+}
+
+/// Point-in-time view of one book for `GET /books`
+#[derive(Debug, Serialize)]
+struct BookSummary {
+    bids: Vec<PriceLevel>,
+    asks: Vec<PriceLevel>,
+    /// None for one-sided or crossed books
+    spread: Option<Decimal>,
+    last_update_id: u64,
+    /// Milliseconds since the last snapshot or delta
+    age_ms: Option<u64>,
+    stale: bool,
+    bid_levels: usize,
+    ask_levels: usize,
+}
+
+/// Body of `GET /readyz`
+#[derive(Debug, Serialize)]
+struct Readiness {
+    ready: bool,
+    initialized_books: usize,
+    /// See `OrderBookManager::coverage`
+    coverage: f64,
+    exchanges: BTreeMap<&'static str, ExchangeStatus>,
+}
+
+/// Start the HTTP server for debug/monitoring endpoints
+pub async fn start_http_server(
+    addr: &str,
+    state: HttpState,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let listener = TcpListener::bind(addr).await?;
+    tracing::info!("HTTP server listening on {}", addr);
+
+    loop {
+        let (stream, client_addr) = listener.accept().await?;
+        let state = state.clone();
+
+        tokio::spawn(async move {
+            let service = service_fn(|request| {
+                let state = state.clone();
+                async move { Ok::<_, Infallible>(route(&request, &state)) }
+            });
+            if let Err(e) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                tracing::debug!("HTTP client {} error: {}", client_addr, e);
+            }
+        });
+    }
+}
+
+fn route<B>(request: &Request<B>, state: &HttpState) -> Response<Full<Bytes>> {
+    match (request.method(), request.uri().path()) {
+        (&Method::GET, path)
+            if (path == "/books" || path.starts_with("/book/"))
+                && !state
+                    .auth_token
+                    .as_deref()
+                    .is_none_or(|token| presents_token(request, token)) =>
+        {
+            let mut response = error_response(StatusCode::UNAUTHORIZED, "missing or invalid token");
+            response
+                .headers_mut()
+                .insert(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+            response
+        }
+        (&Method::GET, "/books") => json_response(&books_summary(
+            &state.orderbook_manager,
+            state.display_depth,
+        )),
+        (&Method::GET, path) if path.starts_with("/book/") => book_response(
+            &state.orderbook_manager,
+            &path["/book/".len()..],
+            request.uri().query(),
+            state.display_depth,
+        ),
+        (&Method::GET, "/metrics") => text_response(
+            PROMETHEUS_CONTENT_TYPE,
+            prometheus_text(&state.metrics.peek_metrics()),
+        ),
+        (&Method::GET, "/healthz") => json_response(&serde_json::json!({ "status": "ok" })),
+        (&Method::GET, "/readyz") => readiness_response(state),
+        (&Method::GET, "/debug/jemalloc") => match jemalloc::stats() {
+            Ok(stats) => json_response(&stats),
+            Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, &e),
+        },
+        (&Method::POST, "/debug/heap-profile") if state.admin_token.is_none() => {
+            error_response(StatusCode::FORBIDDEN, "admin commands are disabled")
+        }
+        (&Method::POST, "/debug/heap-profile")
+            if !state
+                .admin_token
+                .as_deref()
+                .is_some_and(|token| presents_admin_token(request, token)) =>
+        {
+            let mut response =
+                error_response(StatusCode::UNAUTHORIZED, "missing or invalid admin token");
+            response
+                .headers_mut()
+                .insert(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+            response
+        }
+        (&Method::POST, "/debug/heap-profile") => {
+            match jemalloc::dump_heap_profile(&state.heap_profile_dir) {
+                Ok(path) => {
+                    tracing::info!("Heap profile written to {}", path.display());
+                    json_response(&serde_json::json!({
+                        "path": path,
+                        "stats": jemalloc::stats().ok(),
+                    }))
+                }
+                Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, &e),
+            }
+        }
+        (
+            _,
+            "/books"
+            | "/metrics"
+            | "/healthz"
+            | "/readyz"
+            | "/debug/jemalloc"
+            | "/debug/heap-profile",
+        ) => status_response(StatusCode::METHOD_NOT_ALLOWED),
+        (_, path) if path.starts_with("/book/") => status_response(StatusCode::METHOD_NOT_ALLOWED),
+        _ => status_response(StatusCode::NOT_FOUND),
+    }
+}
+
+/// 200 when at least one exchange is connected, one book initialized and the
+/// coverage at its minimum, else 503
+fn readiness_response(state: &HttpState) -> Response<Full<Bytes>> {
+    let initialized_books = state
+        .orderbook_manager
+        .iter()
+        .filter(|entry| entry.value().is_initialized())
+        .count();
+    let coverage = state.orderbook_manager.coverage();
+    let readiness = Readiness {
+        ready: initialized_books > 0
+            && coverage >= state.min_ready_coverage
+            && state.connection_health.any_connected(),
+        initialized_books,
+        coverage,
+        exchanges: state.connection_health.snapshot(),
+    };
+    let mut response = json_response(&readiness);
+    if !readiness.ready {
+        *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+    }
+    response
+}
+
+/// One book as the `book_update` message WebSocket clients get
+///
+/// `path` is "{exchange}/{symbol}", both case-insensitive ("binance/btc-usdt"
+/// works). `depth` defaults to the display depth and is clamped to the stored
+/// one. 404 for an unknown exchange or symbol, or a book not initialized yet.
+fn book_response(
+    orderbook_manager: &OrderBookManager,
+    path: &str,
+    query: Option<&str>,
+    display_depth: usize,
+) -> Response<Full<Bytes>> {
+    let Some((exchange, symbol)) = path.split_once('/') else {
+        return status_response(StatusCode::NOT_FOUND);
+    };
+    let exchange = Exchange::ALL
+        .into_iter()
+        .find(|e| e.name().eq_ignore_ascii_case(exchange));
+    let (Some(exchange), Some(symbol)) = (exchange, normalize_symbol(symbol)) else {
+        return error_response(StatusCode::NOT_FOUND, "unknown exchange or symbol");
+    };
+
+    let depth = match query_param(query, "depth").map(str::parse::<usize>) {
+        None => display_depth,
+        Some(Ok(depth)) => depth,
+        Some(Err(_)) => return error_response(StatusCode::BAD_REQUEST, "invalid depth"),
+    }
+    .clamp(1, orderbook_manager.max_depth());
+
+    match orderbook_manager.get(exchange.name(), &symbol) {
+        Some(book) if book.is_initialized() => json_response(&book.to_client_message(depth)),
+        _ => error_response(StatusCode::NOT_FOUND, "no initialized book"),
+    }
+}
+
+/// Value of `name` in a query string
+fn query_param<'a>(query: Option<&'a str>, name: &str) -> Option<&'a str> {
+    query?.split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        (key == name).then_some(value)
+    })
+}
+
+/// Every initialized book, keyed by "exchange:symbol"
+fn books_summary(
+    orderbook_manager: &OrderBookManager,
+    display_depth: usize,
+) -> BTreeMap<String, BookSummary> {
+    let max_age = Duration::from_secs(BOOK_STALE_AFTER_SECS);
+
+    orderbook_manager
+        .iter()
+        .filter(|entry| entry.value().is_initialized())
+        .map(|entry| {
+            let book = entry.value();
+            let (bids, asks) = book.get_top_levels(display_depth);
+            let (bid_levels, ask_levels) = book.level_counts();
+            let summary = BookSummary {
+                bids,
+                asks,
+                spread: book.spread().map(|(spread, _)| spread),
+                last_update_id: book.last_update_id(),
+                age_ms: book.last_update_age().map(|age| age.as_millis() as u64),
+                stale: !book.is_fresh(max_age),
+                bid_levels,
+                ask_levels,
+            };
+            (entry.key().clone(), summary)
+        })
+        .collect()
+}
+
+/// Prometheus text exposition format, version 0.0.4
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Render a metrics report in the Prometheus text format
+///
+/// Rates are gauges over the WebSocket report interval; totals are counters,
+/// so `rate()` over them works at any scrape interval.
+fn prometheus_text(metrics: &Metrics) -> String {
+    let gauges = [
+        (
+            "flowrs_messages_per_second",
+            "Exchange messages per second",
+            metrics.messages_per_second as f64,
+        ),
+        (
+            "flowrs_depth_updates_per_second",
+            "Book snapshots and deltas per second",
+            metrics.depth_per_second as f64,
+        ),
+        (
+            "flowrs_trades_per_second",
+            "Trades per second",
+            metrics.trades_per_second as f64,
+        ),
+        (
+            "flowrs_ignored_messages_per_second",
+            "Control messages per second",
+            metrics.ignored_per_second as f64,
+        ),
+        (
+            "flowrs_bytes_per_second",
+            "Bytes received per second",
+            metrics.bytes_per_second as f64,
+        ),
+        (
+            "flowrs_latency_avg_microseconds",
+            "Average processing latency",
+            metrics.latency_avg_us,
+        ),
+        (
+            "flowrs_latency_p50_microseconds",
+            "Median processing latency",
+            metrics.latency_p50_us as f64,
+        ),
+        (
+            "flowrs_latency_p95_microseconds",
+            "95th percentile latency",
+            metrics.latency_p95_us as f64,
+        ),
+        (
+            "flowrs_latency_p99_microseconds",
+            "99th percentile latency",
+            metrics.latency_p99_us as f64,
+        ),
+        (
+            "flowrs_latency_min_microseconds",
+            "Lowest latency in the sample window",
+            metrics.latency_min_us as f64,
+        ),
+        (
+            "flowrs_latency_max_microseconds",
+            "Highest latency in the sample window",
+            metrics.latency_max_us as f64,
+        ),
+        (
+            "flowrs_latency_stddev_microseconds",
+            "Standard deviation of latency in the sample window",
+            metrics.latency_stddev_us,
+        ),
+        (
+            "flowrs_active_connections",
+            "Connected WebSocket clients",
+            metrics.active_connections as f64,
+        ),
+        (
+            "flowrs_degraded_clients",
+            "Clients in snapshot-only mode",
+            metrics.degraded_clients as f64,
+        ),
+        (
+            "flowrs_book_coverage_ratio",
+            "Expected books initialized and fresh",
+            metrics.book_coverage,
+        ),
+        (
+            "flowrs_memory_used_megabytes",
+            "Resident memory of the process",
+            metrics.memory_used_mb,
+        ),
+        (
+            "flowrs_cpu_usage_percent",
+            "Process CPU usage, percent of one core",
+            metrics.cpu_usage_percent,
+        ),
+        (
+            "flowrs_cpu_cores",
+            "Logical cores available to the process",
+            metrics.cpu_cores as f64,
+        ),
+        (
+            "flowrs_uptime_seconds",
+            "Seconds since startup",
+            metrics.uptime_seconds as f64,
+        ),
+    ];
+    let counters = [
+        (
+            "flowrs_messages_total",
+            "Exchange messages processed",
+            metrics.total_messages,
+        ),
+        (
+            "flowrs_bytes_received_total",
+            "Bytes received from exchanges",
+            metrics.bytes_received,
+        ),
+        (
+            "flowrs_websocket_reconnects_total",
+            "Exchange reconnects",
+            metrics.websocket_reconnects,
+        ),
+        (
+            "flowrs_slow_consumer_drops_total",
+            "Clients disconnected for not reading",
+            metrics.slow_consumer_drops,
+        ),
+        (
+            "flowrs_publish_drops_total",
+            "Market data messages dropped by Redis/Kafka sinks",
+            metrics.publish_drops,
+        ),
+        (
+            "flowrs_auth_rejections_total",
+            "WebSocket handshakes refused for a bad token",
+            metrics.auth_rejections,
+        ),
+        (
+            "flowrs_broadcast_lagged_total",
+            "Broadcast messages skipped by lagging clients",
+            metrics.broadcast_lagged,
+        ),
+        (
+            "flowrs_price_anomalies_total",
+            "Mids diverging from the median",
+            metrics.price_anomalies,
+        ),
+        (
+            "flowrs_rejected_trades_total",
+            "Trades with implausible timestamps",
+            metrics.rejected_trades,
+        ),
+    ];
+
+    let mut out = String::new();
+    for (name, help, value) in gauges {
+        write_family(&mut out, name, "gauge", help, [(String::new(), value)]);
+    }
+    for (name, help, value) in counters {
+        write_family(
+            &mut out,
+            name,
+            "counter",
+            help,
+            [(String::new(), value as f64)],
+        );
+    }
+    if let Some(lifetime) = &metrics.latency_lifetime {
+        write_family(
+            &mut out,
+            "flowrs_latency_lifetime_microseconds",
+            "gauge",
+            "Processing latency percentiles since startup",
+            [
+                ("0.5", lifetime.p50_us),
+                ("0.99", lifetime.p99_us),
+                ("0.999", lifetime.p999_us),
+                ("1", lifetime.max_us),
+            ]
+            .map(|(quantile, value)| (format!("{{quantile=\"{}\"}}", quantile), value as f64)),
+        );
+        write_family(
+            &mut out,
+            "flowrs_latency_lifetime_samples_total",
+            "counter",
+            "Latency samples in the lifetime percentiles",
+            [(String::new(), lifetime.count as f64)],
+        );
+    }
+    write_family(
+        &mut out,
+        "flowrs_exchange_bytes_per_second",
+        "gauge",
+        "Bytes received per second by exchange",
+        metrics
+            .exchange_bytes_per_second
+            .iter()
+            .map(|(exchange, rate)| (format!("{{exchange=\"{}\"}}", exchange), *rate as f64)),
+    );
+
+    let stream_labels =
+        |s: &StreamMetrics| format!("{{exchange=\"{}\",symbol=\"{}\"}}", s.exchange, s.symbol);
+    let streams = &metrics.streams;
+    write_family(
+        &mut out,
+        "flowrs_stream_messages_per_second",
+        "gauge",
+        "Messages per second by exchange and symbol",
+        streams
+            .iter()
+            .map(|s| (stream_labels(s), s.messages_per_second as f64)),
+    );
+    write_family(
+        &mut out,
+        "flowrs_stream_bytes_per_second",
+        "gauge",
+        "Bytes per second by exchange and symbol",
+        streams
+            .iter()
+            .map(|s| (stream_labels(s), s.bytes_per_second as f64)),
+    );
+    write_family(
+        &mut out,
+        "flowrs_stream_latency_p99_microseconds",
+        "gauge",
+        "99th percentile latency by exchange and symbol",
+        streams
+            .iter()
+            .map(|s| (stream_labels(s), s.latency_p99_us as f64)),
+    );
+    out
+}
+
+/// Append one metric family: its HELP and TYPE lines, then `(labels, value)` samples
+fn write_family(
+    out: &mut String,
+    name: &str,
+    kind: &str,
+    help: &str,
+    samples: impl IntoIterator<Item = (String, f64)>,
+) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    for (labels, value) in samples {
+        let _ = writeln!(out, "{}{} {}", name, labels, value);
+    }
+}
+
+fn text_response(content_type: &str, body: String) -> Response<Full<Bytes>> {
+    Response::builder()
+        .header(CONTENT_TYPE, content_type)
+        .body(Full::new(Bytes::from(body)))
+        .unwrap()
+}
+
+fn json_response(body: &impl Serialize) -> Response<Full<Bytes>> {
+    match serde_json::to_vec(body) {
+        Ok(json) => Response::builder()
+            .header(CONTENT_TYPE, "application/json")
+            .body(Full::new(Bytes::from(json)))
+            .unwrap(),
+        Err(e) => {
+            tracing::error!("Failed to serialize HTTP response: {}", e);
+            status_response(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response<Full<Bytes>> {
+    let mut response = json_response(&serde_json::json!({ "error": message }));
+    *response.status_mut() = status;
+    response
+}
+
+fn status_response(status: StatusCode) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(Bytes::from(
+        status.canonical_reason().unwrap_or_default(),
+    )));
+    *response.status_mut() = status;
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::health::create_shared_connection_health;
+    use crate::metrics::create_shared_metrics;
+    use crate::orderbook::{create_shared_orderbook_manager, PRICE_FACTOR};
+    use crate::types::ORDERBOOK_DEPTH;
+    use http_body_util::BodyExt;
+
+    const ONE: u64 = PRICE_FACTOR;
+
+    #[tokio::test]
+    async fn test_books_dump_lists_initialized_books() {
+        let orderbook_manager =
+            create_shared_orderbook_manager(&["Binance"], &["BTCUSDT"], ORDERBOOK_DEPTH);
+        orderbook_manager
+            .get_or_create("Binance", "BTCUSDT")
+            .initialize_from_snapshot(
+                (1..=5).map(|i| ((100 - i) * ONE, ONE)).collect(),
+                (1..=5).map(|i| ((100 + i) * ONE, ONE)).collect(),
+                42,
+            );
+        // Created but never initialized: left out
+        orderbook_manager.get_or_create("Kraken", "BTCUSDT");
+
+        let state = HttpState {
+            orderbook_manager,
+            metrics: create_shared_metrics(),
+            connection_health: create_shared_connection_health(&[]),
+            display_depth: 3,
+            admin_token: None,
+            heap_profile_dir: std::env::temp_dir(),
+            min_ready_coverage: 0.0,
+            auth_token: None,
+        };
+        let request = Request::get("/books").body(()).unwrap();
+        let response = route(&request, &state);
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let books: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let books = books.as_object().unwrap();
+        assert_eq!(books.len(), 1);
+
+        let book = &books["Binance:BTCUSDT"];
+        assert_eq!(book["last_update_id"], 42);
+        assert_eq!(book["bids"].as_array().unwrap().len(), 3);
+        assert_eq!(
+            (book["bid_levels"].as_u64(), book["ask_levels"].as_u64()),
+            (Some(5), Some(5))
+        );
+        assert_eq!(book["spread"], "2");
+        assert_eq!(book["stale"], false);
+
+        let request = Request::get("/nope").body(()).unwrap();
+        assert_eq!(route(&request, &state).status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_ready_once_connected_with_a_book() {
+        let orderbook_manager = create_shared_orderbook_manager(&["Binance"], &["BTCUSDT"], 20);
+        let connection_health = create_shared_connection_health(&["Binance", "Kraken"]);
+        let state = HttpState {
+            orderbook_manager: orderbook_manager.clone(),
+            metrics: create_shared_metrics(),
+            connection_health: connection_health.clone(),
+            display_depth: 3,
+            admin_token: None,
+            heap_profile_dir: std::env::temp_dir(),
+            min_ready_coverage: 0.0,
+            auth_token: None,
+        };
+        let get = |uri: &str| route(&Request::get(uri).body(()).unwrap(), &state);
+
+        assert_eq!(get("/healthz").status(), StatusCode::OK);
+        assert_eq!(get("/readyz").status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        // Connected but no book yet
+        connection_health.set_connected("Binance");
+        assert_eq!(get("/readyz").status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        orderbook_manager
+            .get_or_create("Binance", "BTCUSDT")
+            .initialize_from_snapshot(vec![(99 * ONE, ONE)], vec![(101 * ONE, ONE)], 1);
+        connection_health.set_disconnected("Kraken");
+        let response = get("/readyz");
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let readiness: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(readiness["initialized_books"], 1);
+        assert_eq!(readiness["exchanges"]["Binance"]["state"], "connected");
+        assert_eq!(readiness["exchanges"]["Kraken"]["state"], "reconnecting");
+        assert!(readiness["exchanges"]["Kraken"]["last_reconnect_ms"].is_i64());
+    }
+
+    #[tokio::test]
+    async fn test_book_endpoints_need_the_auth_token() {
+        let orderbook_manager = create_shared_orderbook_manager(&["Binance"], &["BTCUSDT"], 20);
+        orderbook_manager
+            .get_or_create("Binance", "BTCUSDT")
+            .initialize_from_snapshot(vec![(99 * ONE, ONE)], vec![(101 * ONE, ONE)], 1);
+        let state = HttpState {
+            orderbook_manager,
+            metrics: create_shared_metrics(),
+            connection_health: create_shared_connection_health(&[]),
+            display_depth: 3,
+            admin_token: None,
+            heap_profile_dir: std::env::temp_dir(),
+            min_ready_coverage: 0.0,
+            auth_token: Some("s3cret".to_string()),
+        };
+        let get = |uri: &str, bearer: Option<&str>| {
+            let mut request = Request::get(uri);
+            if let Some(token) = bearer {
+                request = request.header("authorization", format!("Bearer {}", token));
+            }
+            route(&request.body(()).unwrap(), &state).status()
+        };
+
+        for uri in ["/books", "/book/binance/btcusdt"] {
+            assert_eq!(get(uri, None), StatusCode::UNAUTHORIZED);
+            assert_eq!(get(uri, Some("wrong")), StatusCode::UNAUTHORIZED);
+            assert_eq!(get(uri, Some("s3cret")), StatusCode::OK);
+        }
+        assert_eq!(get("/books?token=s3cret", None), StatusCode::OK);
+
+        // Probes and metrics stay open
+        assert_eq!(get("/healthz", None), StatusCode::OK);
+        assert_eq!(get("/metrics", None), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_readiness_waits_for_book_coverage() {
+        let orderbook_manager =
+            create_shared_orderbook_manager(&["Binance"], &["BTCUSDT", "ETHUSDT"], 20);
+        let connection_health = create_shared_connection_health(&["Binance"]);
+        connection_health.set_connected("Binance");
+        let state = HttpState {
+            orderbook_manager: orderbook_manager.clone(),
+            metrics: create_shared_metrics(),
+            connection_health,
+            display_depth: 3,
+            admin_token: None,
+            heap_profile_dir: std::env::temp_dir(),
+            min_ready_coverage: 0.8,
+            auth_token: None,
+        };
+        let readyz = || async {
+            let response = route(&Request::get("/readyz").body(()).unwrap(), &state);
+            let status = response.status();
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let readiness: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            (status, readiness["coverage"].as_f64().unwrap())
+        };
+
+        // One book of two is not enough
+        for (symbol, expected) in [("BTCUSDT", 0.5), ("ETHUSDT", 1.0)] {
+            let (status, _) = readyz().await;
+            assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+            orderbook_manager
+                .get_or_create("Binance", symbol)
+                .initialize_from_snapshot(vec![(99 * ONE, ONE)], vec![(101 * ONE, ONE)], 1);
+            assert_eq!(readyz().await.1, expected);
+        }
+        assert_eq!(readyz().await, (StatusCode::OK, 1.0));
+    }
+
+    #[tokio::test]
+    async fn test_single_book_by_exchange_and_symbol() {
+        let orderbook_manager = create_shared_orderbook_manager(&["Binance"], &["BTCUSDT"], 20);
+        orderbook_manager
+            .get_or_create("Binance", "BTCUSDT")
+            .initialize_from_snapshot(
+                (1..=30).map(|i| ((100 - i) * ONE, ONE)).collect(),
+                (1..=30).map(|i| ((100 + i) * ONE, ONE)).collect(),
+                42,
+            );
+        orderbook_manager.get_or_create("Kraken", "BTCUSDT");
+        let state = HttpState {
+            orderbook_manager,
+            metrics: create_shared_metrics(),
+            connection_health: create_shared_connection_health(&[]),
+            display_depth: 3,
+            admin_token: None,
+            heap_profile_dir: std::env::temp_dir(),
+            min_ready_coverage: 0.0,
+            auth_token: None,
+        };
+        let get = |uri: &str| route(&Request::get(uri).body(()).unwrap(), &state);
+
+        let body = |response: Response<Full<Bytes>>| async move {
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+        };
+        let book = body(get("/book/binance/btc-usdt?depth=5")).await;
+        assert_eq!(book["type"], "book_update");
+        assert_eq!(book["data"]["update_id"], 42);
+        assert_eq!(book["data"]["bids"].as_array().unwrap().len(), 5);
+
+        // Default display depth, and clamped to the 20 levels stored
+        let book = body(get("/book/Binance/BTCUSDT")).await;
+        assert_eq!(book["data"]["asks"].as_array().unwrap().len(), 3);
+        let book = body(get("/book/Binance/BTCUSDT?depth=500")).await;
+        assert_eq!(book["data"]["asks"].as_array().unwrap().len(), 20);
+
+        assert_eq!(
+            get("/book/Binance/BTCUSDT?depth=x").status(),
+            StatusCode::BAD_REQUEST
+        );
+        // Not initialized, not tracked, unknown exchange
+        for uri in [
+            "/book/Kraken/BTCUSDT",
+            "/book/Binance/ETHUSDT",
+            "/book/Nope/BTCUSDT",
+        ] {
+            assert_eq!(get(uri).status(), StatusCode::NOT_FOUND, "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_jemalloc_stats_and_gated_heap_dump() {
+        let state = HttpState {
+            orderbook_manager: create_shared_orderbook_manager(&[], &[], ORDERBOOK_DEPTH),
+            metrics: create_shared_metrics(),
+            connection_health: create_shared_connection_health(&[]),
+            display_depth: 3,
+            admin_token: None,
+            heap_profile_dir: std::env::temp_dir(),
+            min_ready_coverage: 0.0,
+            auth_token: None,
+        };
+
+        let request = Request::get("/debug/jemalloc").body(()).unwrap();
+        let response = route(&request, &state);
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let stats: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        for key in ["allocated", "active", "resident"] {
+            assert!(stats[key].is_u64(), "missing {}", key);
+        }
+
+        // Writes to disk: admin only, and off without an admin token
+        let request = Request::post("/debug/heap-profile").body(()).unwrap();
+        assert_eq!(route(&request, &state).status(), StatusCode::FORBIDDEN);
+
+        let state = HttpState {
+            admin_token: Some("adm1n".to_string()),
+            ..state
+        };
+        for request in [
+            Request::post("/debug/heap-profile"),
+            Request::post("/debug/heap-profile?admin_token=wrong"),
+            Request::post("/debug/heap-profile").header("authorization", "Bearer wrong"),
+        ] {
+            let request = request.body(()).unwrap();
+            assert_eq!(route(&request, &state).status(), StatusCode::UNAUTHORIZED);
+        }
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "metrics")]
+    async fn test_prometheus_scrape_leaves_report_accounting_alone() {
+        let state = HttpState {
+            orderbook_manager: create_shared_orderbook_manager(&[], &[], ORDERBOOK_DEPTH),
+            metrics: create_shared_metrics(),
+            connection_health: create_shared_connection_health(&[]),
+            display_depth: 3,
+            admin_token: None,
+            heap_profile_dir: std::env::temp_dir(),
+            min_ready_coverage: 0.0,
+            auth_token: None,
+        };
+        for _ in 0..3 {
+            state.metrics.record_messages(1);
+            state.metrics.record_depth();
+        }
+        state.metrics.record_stream(
+            crate::exchanges::Exchange::Kraken,
+            "BTCUSDT",
+            120,
+            std::time::Instant::now(),
+        );
+
+        for _ in 0..2 {
+            let request = Request::get("/metrics").body(()).unwrap();
+            let response = route(&request, &state);
+            assert_eq!(response.status(), StatusCode::OK);
+            assert!(response.headers()[CONTENT_TYPE]
+                .to_str()
+                .unwrap()
+                .starts_with("text/plain; version=0.0.4"));
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let text = String::from_utf8(body.to_vec()).unwrap();
+            assert!(
+                text.contains("# TYPE flowrs_messages_total counter\nflowrs_messages_total 3\n")
+            );
+            assert!(text.contains("flowrs_active_connections 0\n"));
+        }
+
+        // Scrapes reset nothing: the WebSocket report still sees the whole interval
+        let report = state.metrics.compute_metrics();
+        assert!(report.depth_per_second > 0);
+
+        let request = Request::get("/metrics").body(()).unwrap();
+        let body = route(&request, &state)
+            .into_body()
+            .collect()
+            .await
+            .unwrap()
+            .to_bytes();
+        let text = String::from_utf8(body.to_vec()).unwrap();
+        assert!(text.contains(&format!(
+            "flowrs_depth_updates_per_second {}\n",
+            report.depth_per_second
+        )));
+        assert!(text
+            .contains("flowrs_stream_messages_per_second{exchange=\"Kraken\",symbol=\"BTCUSDT\"}"));
+    }
+}
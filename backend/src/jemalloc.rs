@@ -0,0 +1,55 @@
+//! jemalloc introspection: allocator stats and on-demand heap profile dumps
+
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tikv_jemalloc_ctl::{epoch, stats};
+
+/// Allocator-wide byte counts (see jemalloc's `stats.*` mallctls)
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct JemallocStats {
+    /// Bytes allocated by the application
+    pub allocated: usize,
+    /// Bytes in active pages (allocated + page fragmentation)
+    pub active: usize,
+    /// Bytes in physically resident data pages mapped by the allocator
+    pub resident: usize,
+}
+
+/// Read fresh allocator stats (jemalloc caches them until the epoch advances)
+pub fn stats() -> Result<JemallocStats, String> {
+    epoch::advance().map_err(|e| format!("jemalloc epoch: {}", e))?;
+    Ok(JemallocStats {
+        allocated: stats::allocated::read().map_err(|e| format!("stats.allocated: {}", e))?,
+        active: stats::active::read().map_err(|e| format!("stats.active: {}", e))?,
+        resident: stats::resident::read().map_err(|e| format!("stats.resident: {}", e))?,
+    })
+}
+
+/// Dump a heap profile into `dir`, returning the file written
+///
+/// Needs the `jemalloc-profiling` feature and profiling enabled at runtime
+/// (`_RJEM_MALLOC_CONF=prof:true`); otherwise jemalloc refuses the dump.
+#[cfg(feature = "jemalloc-profiling")]
+pub fn dump_heap_profile(dir: &Path) -> Result<PathBuf, String> {
+    use std::ffi::{c_char, CString};
+
+    let millis = chrono::Utc::now().timestamp_millis();
+    let path = dir.join(format!("flowrs-heap-{}.prof", millis));
+    let c_path = CString::new(path.to_string_lossy().into_owned())
+        .map_err(|_| format!("invalid profile path {}", path.display()))?;
+
+    // SAFETY: prof.dump takes a NUL-terminated path, which outlives the call
+    unsafe { tikv_jemalloc_ctl::raw::write(b"prof.dump\0", c_path.as_ptr() as *const c_char) }
+        .map_err(|e| {
+            format!(
+                "prof.dump failed (is _RJEM_MALLOC_CONF=prof:true set?): {}",
+                e
+            )
+        })?;
+    Ok(path)
+}
+
+#[cfg(not(feature = "jemalloc-profiling"))]
+pub fn dump_heap_profile(_dir: &Path) -> Result<PathBuf, String> {
+    Err("built without the jemalloc-profiling feature".to_string())
+}
@@ -1,7 +1,26 @@
 // Expose modules for benchmarks and tests
 
+pub mod arbitrage;
+pub mod candles;
+pub mod compression;
 pub mod exchanges; // Multi-exchange support
+pub mod health;
+pub mod http;
+pub mod jemalloc;
+#[cfg(feature = "metrics")]
 pub mod metrics;
+// Same API with no-op bodies: recording calls compile away
+#[cfg(not(feature = "metrics"))]
+#[path = "metrics_noop.rs"]
+pub mod metrics;
+pub mod monitor;
 pub mod orderbook;
+pub mod publisher;
+pub mod rate_limit;
+pub mod recorder;
 pub mod server;
+pub mod snapshot;
+pub mod tape;
+pub mod telemetry;
+pub mod trade_history;
 pub mod types;
@@ -1,20 +1,50 @@
 //! Real-time order book aggregator for cryptocurrency exchanges
 
+mod arbitrage;
+mod candles;
 mod exchanges;
+mod health;
+mod http;
+mod jemalloc;
+#[cfg(feature = "metrics")]
 mod metrics;
+// Same API with no-op bodies: recording calls compile away
+#[cfg(not(feature = "metrics"))]
+#[path = "metrics_noop.rs"]
+mod metrics;
+mod monitor;
 mod orderbook;
+mod publisher;
+mod rate_limit;
+mod recorder;
 mod server;
+mod snapshot;
+mod tape;
+mod telemetry;
+mod trade_history;
 mod types;
 
+use crate::arbitrage::{ArbitrageScanner, DEFAULT_ARBITRAGE_BPS};
+use crate::candles::create_shared_candle_store;
+use crate::exchanges::symbols::{parse_symbol_list, quote_asset, Channel};
 use crate::exchanges::{
-    BinanceConn, BybitConn, CoinbaseConn, ExchangeConnector, ExchangeManager, KrakenConn,
+    BinanceConn, BinanceMarket, BybitConn, ChannelFilter, CoinbaseConn, Exchange,
+    ExchangeConnector, ExchangeManager, FlowRsUpstreamConn, KrakenConn, KuCoinConn,
+    NetworkTimeouts, OkxConn, ReplayConn, SymbolRegistry,
 };
 use crate::metrics::create_shared_metrics;
-use crate::orderbook::create_shared_orderbook_manager;
-use crate::types::{ClientMessage, TRADING_PAIRS};
+use crate::monitor::{ConsistencyMonitor, DEFAULT_ANOMALY_BPS, DEFAULT_ANOMALY_GRACE_SECS};
+use crate::orderbook::{OrderBookManager, QTY_FACTOR};
+use crate::publisher::{KafkaConfig, MarketPublisher};
+use crate::recorder::{TradeRecorder, RECORDER_FLUSH_INTERVAL};
+use crate::trade_history::create_shared_trade_history;
+use crate::types::{ClientMessage, ORDERBOOK_DEPTH, ORDERBOOK_DISPLAY_DEPTH, TRADING_PAIRS};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::broadcast;
-use tracing_subscriber::EnvFilter;
+use tokio::sync::{broadcast, watch};
 
 use tikv_jemallocator::Jemalloc;
 
@@ -22,50 +52,398 @@ use tikv_jemallocator::Jemalloc;
 static GLOBAL: Jemalloc = Jemalloc;
 
 const SERVER_ADDR: &str = "0.0.0.0:8080";
-const BROADCAST_CAPACITY: usize = 16384; // Increased for multiple symbols
+const DEFAULT_HTTP_ADDR: &str = "0.0.0.0:8081";
+const DEFAULT_BROADCAST_CAPACITY: usize = 16384; // Increased for multiple symbols
+/// Largest broadcast capacity accepted (every slot holds a message)
+const MAX_BROADCAST_CAPACITY: usize = 1 << 20;
+const DEFAULT_SNAPSHOT_REFRESH_SECS: u64 = 600;
+/// Longest wait for the trade recorder to flush on shutdown
+const RECORDER_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    // Initialize logging
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
-        )
-        .init();
+    // Initialize logging (and trace export with the otel feature)
+    let _telemetry = telemetry::init();
 
     tracing::info!("Starting Order Book Visualizer Backend");
-    tracing::info!(
-        "Tracking {} trading pairs: {:?}",
-        TRADING_PAIRS.len(),
-        TRADING_PAIRS
-    );
+    // Comma-separated override of the tracked pairs (duplicates removed)
+    let symbols: Vec<String> = match std::env::var("FLOWRS_SYMBOLS") {
+        Ok(raw) => parse_symbol_list(&raw),
+        Err(_) => TRADING_PAIRS.iter().map(|s| s.to_string()).collect(),
+    };
+    tracing::info!("Tracking {} trading pairs: {:?}", symbols.len(), symbols);
 
-    let orderbook_manager = create_shared_orderbook_manager();
-    let metrics = create_shared_metrics();
-    let (client_broadcast_tx, _) = broadcast::channel::<ClientMessage>(BROADCAST_CAPACITY);
+    // FLOWRS_BINANCE_MARKET=spot streams Binance spot instead of USDT-M Futures
+    let binance_market = match std::env::var("FLOWRS_BINANCE_MARKET") {
+        Ok(raw) => BinanceMarket::parse(&raw).unwrap_or_else(|| {
+            tracing::warn!("Unknown FLOWRS_BINANCE_MARKET {:?}, using futures", raw);
+            BinanceMarket::default()
+        }),
+        Err(_) => BinanceMarket::default(),
+    };
+
+    // Relay mode: mirror another FlowRS instance instead of hitting the exchanges
+    let upstream_url = std::env::var("FLOWRS_UPSTREAM_URL").ok();
+    let exchange_connectors = match &upstream_url {
+        Some(url) => vec![ExchangeConnector::FlowRs(FlowRsUpstreamConn::new(
+            url.clone(),
+            symbols.clone(),
+        ))],
+        None => vec![
+            ExchangeConnector::Binance(
+                BinanceConn::new(symbols.clone()).with_market(binance_market),
+            ),
+            ExchangeConnector::Bybit(BybitConn::new(symbols.clone())),
+            ExchangeConnector::Coinbase(CoinbaseConn::new(symbols.clone())),
+            ExchangeConnector::Kraken(KrakenConn::new(symbols.clone())),
+            ExchangeConnector::Okx(OkxConn::new(symbols.clone())),
+            ExchangeConnector::KuCoin(KuCoinConn::new(symbols.clone())),
+        ],
+    };
+
+    // Offline mode: FLOWRS_REPLAY_FILE=capture.txt plays a capture recorded from
+    // FLOWRS_REPLAY_EXCHANGE (e.g. Kraken) instead of connecting anywhere
+    let replay_file = std::env::var("FLOWRS_REPLAY_FILE").ok();
+    let exchange_connectors = match &replay_file {
+        Some(path) => {
+            let source_name = std::env::var("FLOWRS_REPLAY_EXCHANGE").unwrap_or_default();
+            let Some(source) = exchange_connectors
+                .into_iter()
+                .find(|c| c.exchange().name().eq_ignore_ascii_case(&source_name))
+            else {
+                return Err(format!(
+                    "FLOWRS_REPLAY_FILE needs FLOWRS_REPLAY_EXCHANGE set to one of {:?}, got {:?}",
+                    Exchange::VENUES.map(|e| e.name()),
+                    source_name
+                )
+                .into());
+            };
+            vec![ExchangeConnector::Replay(ReplayConn::new(path, source))]
+        }
+        None => exchange_connectors,
+    };
+
+    // Per-symbol channels, e.g. FLOWRS_SYMBOL_CHANNELS=BTCUSDT:book,ETHUSDT:trades
+    // (book, trades or both; unlisted symbols get both)
+    let channel_filter = std::env::var("FLOWRS_SYMBOL_CHANNELS")
+        .map(|v| ChannelFilter::parse(&v))
+        .unwrap_or_default();
 
-    let symbols: Vec<String> = TRADING_PAIRS.iter().map(|s| s.to_string()).collect();
-    let exchange_connectors = vec![
-        ExchangeConnector::Binance(BinanceConn::new(symbols.clone())),
-        ExchangeConnector::Bybit(BybitConn::new(symbols.clone())),
-        ExchangeConnector::Coinbase(CoinbaseConn::new(symbols.clone())),
-        ExchangeConnector::Kraken(KrakenConn::new(symbols.clone())),
-    ];
+    // Levels kept in memory per side, e.g. FLOWRS_BOOK_DEPTH=100; exchanges
+    // with a choice of book depths are subscribed deep enough to cover it
+    let book_depth: usize = std::env::var("FLOWRS_BOOK_DEPTH")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&depth| depth > 0)
+        .unwrap_or(ORDERBOOK_DEPTH);
+
+    // Per-exchange subscribe chunking/pacing, e.g. FLOWRS_BYBIT_SUBSCRIBE_CHUNK=10
+    // and FLOWRS_BYBIT_SUBSCRIBE_DELAY_MS=200 (unset keeps the connector default),
+    // plus the per-symbol channel filter and book depth
+    let exchange_connectors: Vec<ExchangeConnector> = exchange_connectors
+        .into_iter()
+        .map(|connector| {
+            let prefix = format!(
+                "FLOWRS_{}_SUBSCRIBE",
+                connector.exchange().name().to_uppercase()
+            );
+            let mut pacing = connector.subscribe_pacing();
+            if let Some(chunk_size) = std::env::var(format!("{}_CHUNK", prefix))
+                .ok()
+                .and_then(|v| v.parse().ok())
+            {
+                pacing.chunk_size = chunk_size;
+            }
+            if let Some(ms) = std::env::var(format!("{}_DELAY_MS", prefix))
+                .ok()
+                .and_then(|v| v.parse().ok())
+            {
+                pacing.delay = Duration::from_millis(ms);
+            }
+            connector
+                .with_subscribe_pacing(pacing)
+                .with_channel_filter(channel_filter.clone())
+                .with_book_depth(book_depth)
+        })
+        .collect();
 
     tracing::info!("Configured {} exchange(s)", exchange_connectors.len());
     for connector in &exchange_connectors {
         tracing::info!("  • {}", connector.exchange().name());
     }
 
+    // Relayed books keep their origin venue as exchange
+    let exchange_names: Vec<&str> = if upstream_url.is_some() {
+        Exchange::VENUES.iter().map(|e| e.name()).collect()
+    } else {
+        exchange_connectors
+            .iter()
+            .map(|c| match c {
+                // Replayed books keep the exchange they were recorded from
+                ExchangeConnector::Replay(replay) => replay.source().exchange().name(),
+                c => c.exchange().name(),
+            })
+            .collect()
+    };
+    // Trades-only symbols never get a book, so they don't count towards coverage
+    let symbol_names: Vec<&str> = symbols
+        .iter()
+        .map(String::as_str)
+        .filter(|symbol| channel_filter.wants(symbol, Channel::Book))
+        .collect();
+    // Levels sent to clients, configured separately from the levels kept
+    let mut display_depth: usize = std::env::var("FLOWRS_DISPLAY_DEPTH")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(ORDERBOOK_DISPLAY_DEPTH);
+    if display_depth > book_depth {
+        tracing::warn!(
+            "Display depth {} exceeds stored depth {}, capping it",
+            display_depth,
+            book_depth
+        );
+        display_depth = book_depth;
+    }
+    tracing::info!(
+        "Storing {} levels per side, sending {}",
+        book_depth,
+        display_depth
+    );
+    // Period of the per-client book poll, e.g. FLOWRS_BOOK_POLL_MS=100 (10ms to 5s)
+    let book_poll_interval = match std::env::var("FLOWRS_BOOK_POLL_MS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+    {
+        Some(ms) => {
+            let requested = Duration::from_millis(ms);
+            let interval = requested.clamp(
+                server::MIN_BOOK_POLL_INTERVAL,
+                server::MAX_BOOK_POLL_INTERVAL,
+            );
+            if interval != requested {
+                tracing::warn!(
+                    "Book poll interval {:?} out of range, using {:?}",
+                    requested,
+                    interval
+                );
+            }
+            interval
+        }
+        None => server::DEFAULT_BOOK_POLL_INTERVAL,
+    };
+    // Per-exchange dust threshold in base units, e.g. FLOWRS_BINANCE_MIN_QTY=0.0001
+    // (unset or 0 keeps every level; ignored for Kraken, whose checksum covers dust)
+    let mut orderbook_manager =
+        OrderBookManager::with_streams(&exchange_names, &symbol_names).with_max_depth(book_depth);
+    // Levels past a feed's subscribed depth would never be updated
+    for connector in &exchange_connectors {
+        if let Some(depth) = connector.book_depth().filter(|&depth| depth < book_depth) {
+            let exchange = connector.exchange().name();
+            tracing::warn!(
+                "[{}] Streams {} levels per side at most, storing that many",
+                exchange,
+                depth
+            );
+            orderbook_manager = orderbook_manager.with_exchange_max_depth(exchange, depth);
+        }
+    }
+    for exchange in &exchange_names {
+        let var = format!("FLOWRS_{}_MIN_QTY", exchange.to_uppercase());
+        let Some(min_qty) = std::env::var(&var)
+            .ok()
+            .and_then(|v| v.parse::<Decimal>().ok())
+            .and_then(|qty| (qty * Decimal::from(QTY_FACTOR)).trunc().to_u64())
+        else {
+            continue;
+        };
+        orderbook_manager = orderbook_manager.with_min_qty(exchange, min_qty);
+    }
+    // Quote assets to keep books for, e.g. FLOWRS_QUOTE_CURRENCIES=USDT,USDC
+    // (unset: any); updates for other symbols are dropped on arrival
+    let allowed_quotes: Vec<String> = std::env::var("FLOWRS_QUOTE_CURRENCIES")
+        .unwrap_or_default()
+        .split(',')
+        .map(|quote| quote.trim().to_ascii_uppercase())
+        .filter(|quote| !quote.is_empty())
+        .collect();
+    if !allowed_quotes.is_empty() {
+        tracing::info!("Only tracking symbols quoted in {:?}", allowed_quotes);
+        for symbol in symbol_names.iter().filter(|s| {
+            quote_asset(s).is_none_or(|quote| !allowed_quotes.iter().any(|q| q == quote))
+        }) {
+            tracing::warn!("{} is configured but its quote is not allowed", symbol);
+        }
+        orderbook_manager = orderbook_manager.with_allowed_quotes(allowed_quotes);
+    }
+    let orderbook_manager = Arc::new(orderbook_manager);
+
+    // Books saved on the last run, to serve something during the cold start (unset: off)
+    let book_snapshot_path: Option<PathBuf> = std::env::var("FLOWRS_BOOK_SNAPSHOT_PATH")
+        .ok()
+        .map(Into::into);
+    if let Some(path) = &book_snapshot_path {
+        let max_age = Duration::from_secs(
+            std::env::var("FLOWRS_BOOK_SNAPSHOT_MAX_AGE_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(snapshot::DEFAULT_MAX_AGE_SECS),
+        );
+        match snapshot::load(&orderbook_manager, path, max_age) {
+            Ok(restored) => {
+                tracing::info!("Restored {} book(s) from {}", restored, path.display())
+            }
+            Err(e) => tracing::warn!("Failed to load books from {}: {}", path.display(), e),
+        }
+    }
+    let metrics = create_shared_metrics();
+    // Trades and metrics a client may fall behind by before it skips some (see the
+    // broadcast_lagged metric), e.g. FLOWRS_BROADCAST_CAPACITY=65536
+    let broadcast_capacity = match std::env::var("FLOWRS_BROADCAST_CAPACITY")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+    {
+        Some(requested) => {
+            // The channel rounds up to a power of two anyway
+            let capacity = requested
+                .clamp(1, MAX_BROADCAST_CAPACITY)
+                .next_power_of_two();
+            if capacity != requested {
+                tracing::warn!(
+                    "Broadcast capacity {} must be a power of two in 1..={}, using {}",
+                    requested,
+                    MAX_BROADCAST_CAPACITY,
+                    capacity
+                );
+            }
+            capacity
+        }
+        None => DEFAULT_BROADCAST_CAPACITY,
+    };
+    tracing::info!(
+        "Broadcast channel capacity: {} messages",
+        broadcast_capacity
+    );
+    let (client_broadcast_tx, _) = broadcast::channel::<ClientMessage>(broadcast_capacity);
+
+    // Optional parallel parsing per exchange (0 = parse inline on the read task)
+    let parse_workers: usize = std::env::var("FLOWRS_PARSE_WORKERS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+    if parse_workers > 0 {
+        tracing::info!(
+            "Parsing up to {} messages in parallel per exchange",
+            parse_workers
+        );
+    }
+
+    // Runtime add/remove of symbols from clients and HTTP heap profile dumps
+    // (off by default), only for clients presenting FLOWRS_ADMIN_TOKEN as
+    // `?admin_token=` (or a Bearer header over HTTP)
+    let admin_enabled = std::env::var("FLOWRS_ADMIN").is_ok_and(|v| v == "1");
+    let admin_token = std::env::var("FLOWRS_ADMIN_TOKEN")
+        .ok()
+        .filter(|token| !token.is_empty() && admin_enabled);
+    match (admin_enabled, &admin_token) {
+        (true, Some(_)) => {
+            tracing::info!(
+                "Admin commands enabled: clients with the admin token may add/remove symbols and dump heap profiles"
+            )
+        }
+        (true, None) => {
+            tracing::warn!(
+                "FLOWRS_ADMIN is set without FLOWRS_ADMIN_TOKEN: admin commands stay disabled"
+            )
+        }
+        (false, _) => {}
+    }
+    let symbol_registry = SymbolRegistry::new(symbols);
+
+    // Periodic REST resnapshot against slow drift (0 = disabled)
+    let snapshot_refresh = Duration::from_secs(
+        std::env::var("FLOWRS_SNAPSHOT_REFRESH_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_SNAPSHOT_REFRESH_SECS),
+    );
+
+    // Connect (WebSocket + REST) and REST read timeouts, in milliseconds
+    let mut timeouts = NetworkTimeouts::default();
+    if let Some(ms) = std::env::var("FLOWRS_CONNECT_TIMEOUT_MS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+    {
+        timeouts.connect = Duration::from_millis(ms);
+    }
+    if let Some(ms) = std::env::var("FLOWRS_READ_TIMEOUT_MS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+    {
+        timeouts.read = Duration::from_millis(ms);
+    }
+    if let Some(ms) = std::env::var("FLOWRS_IDLE_TIMEOUT_MS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+    {
+        timeouts.idle = Duration::from_millis(ms);
+    }
+
+    // Fan-out of books and trades to Redis pub/sub and/or Kafka (unset: off)
+    let mut publisher = MarketPublisher::new(metrics.clone());
+    if let Ok(url) = std::env::var("FLOWRS_REDIS_URL") {
+        if let Err(e) = publisher.add_redis(&url) {
+            tracing::error!("Not publishing to Redis: {}", e);
+        }
+    }
+    if let Ok(brokers) = std::env::var("FLOWRS_KAFKA_BROKERS") {
+        let mut kafka = KafkaConfig::new(&brokers);
+        if let Ok(topic) = std::env::var("FLOWRS_KAFKA_BOOK_TOPIC") {
+            kafka.book_topic = topic;
+        }
+        if let Ok(topic) = std::env::var("FLOWRS_KAFKA_TRADE_TOPIC") {
+            kafka.trade_topic = topic;
+        }
+        if let Some(len) = std::env::var("FLOWRS_KAFKA_QUEUE_LEN")
+            .ok()
+            .and_then(|v| v.parse().ok())
+        {
+            kafka.queue_len = len;
+        }
+        if let Err(e) = publisher.add_kafka(&kafka) {
+            tracing::error!("Not producing to Kafka: {}", e);
+        }
+    }
+    // Trades written to daily CSV files in this directory (unset: off)
+    let mut trade_recorder_thread = None;
+    if let Ok(dir) = std::env::var("FLOWRS_TRADE_RECORD_DIR") {
+        let flush_interval = std::env::var("FLOWRS_TRADE_RECORD_FLUSH_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .map(Duration::from_millis)
+            .unwrap_or(RECORDER_FLUSH_INTERVAL);
+        match TradeRecorder::start(dir.clone().into(), flush_interval) {
+            Ok((recorder, thread)) => {
+                publisher.add_trade_recorder(recorder);
+                trade_recorder_thread = Some(thread);
+            }
+            Err(e) => tracing::error!("Not recording trades to {}: {}", dir, e),
+        }
+    }
+
     let exchange_manager = ExchangeManager::new(
         exchange_connectors,
+        symbol_registry.clone(),
         orderbook_manager.clone(),
         metrics.clone(),
-    );
+    )
+    .with_parse_workers(parse_workers)
+    .with_snapshot_refresh(snapshot_refresh)
+    .with_timeouts(timeouts)
+    .with_publisher(publisher);
 
     // Broadcast metrics every 3 seconds (reduced from 1s for better P99 latency)
     let _metrics_ticker = {
-        let _orderbook_manager = orderbook_manager.clone();
+        let orderbook_manager = orderbook_manager.clone();
         let metrics = metrics.clone();
         let broadcast_tx = client_broadcast_tx.clone();
 
@@ -73,8 +451,90 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
             let mut interval = tokio::time::interval(Duration::from_secs(1));
             loop {
                 interval.tick().await;
+                metrics.set_book_coverage(orderbook_manager.coverage());
                 let current_metrics = metrics.compute_metrics();
                 let _ = broadcast_tx.send(ClientMessage::Metrics(current_metrics));
+                // Per-symbol breakdown, only forwarded to clients that asked for it
+                let _ = broadcast_tx.send(ClientMessage::QuoteFlicker(metrics.quote_flicker()));
+            }
+        })
+    };
+
+    // Flag exchanges whose mid diverges from the cross-exchange median
+    let _consistency_monitor = {
+        let orderbook_manager = orderbook_manager.clone();
+        let metrics = metrics.clone();
+        let broadcast_tx = client_broadcast_tx.clone();
+        let threshold_bps: f64 = std::env::var("FLOWRS_ANOMALY_BPS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_ANOMALY_BPS);
+        let grace_secs: u64 = std::env::var("FLOWRS_ANOMALY_GRACE_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_ANOMALY_GRACE_SECS);
+
+        tokio::spawn(async move {
+            let mut monitor =
+                ConsistencyMonitor::new(threshold_bps, Duration::from_secs(grace_secs));
+            let mut interval = tokio::time::interval(Duration::from_secs(1));
+            loop {
+                interval.tick().await;
+                for anomaly in monitor.check(&orderbook_manager, std::time::Instant::now()) {
+                    metrics.record_price_anomaly();
+                    let _ = broadcast_tx.send(anomaly);
+                }
+            }
+        })
+    };
+
+    // Flag symbols whose books cross between exchanges, at the client book poll rate
+    let _arbitrage_scanner = {
+        let orderbook_manager = orderbook_manager.clone();
+        let broadcast_tx = client_broadcast_tx.clone();
+        let threshold_bps: f64 = std::env::var("FLOWRS_ARBITRAGE_BPS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_ARBITRAGE_BPS);
+
+        tokio::spawn(async move {
+            let mut scanner = ArbitrageScanner::new(threshold_bps);
+            let mut interval = tokio::time::interval(book_poll_interval);
+            loop {
+                interval.tick().await;
+                for opportunity in scanner.scan(&orderbook_manager) {
+                    let _ = broadcast_tx.send(opportunity);
+                }
+            }
+        })
+    };
+
+    // Aggregate trades into candles and keep the recent tape (both for client backfill)
+    // FLOWRS_CANDLE_FILL_GAPS=1 closes quiet intervals as flat candles instead of skipping them
+    let fill_gaps = std::env::var("FLOWRS_CANDLE_FILL_GAPS").is_ok_and(|v| v != "0");
+    let candle_store = create_shared_candle_store(fill_gaps);
+    let trade_history = create_shared_trade_history();
+    let _candle_aggregator = {
+        let candle_store = candle_store.clone();
+        let trade_history = trade_history.clone();
+        let candle_tx = client_broadcast_tx.clone();
+        let mut trade_rx = client_broadcast_tx.subscribe();
+
+        tokio::spawn(async move {
+            loop {
+                match trade_rx.recv().await {
+                    Ok(ClientMessage::Trade(trade)) => {
+                        for candle in candle_store.record_trade(&trade) {
+                            let _ = candle_tx.send(ClientMessage::Candle(candle));
+                        }
+                        trade_history.record(&trade);
+                    }
+                    Ok(_) => {}
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        tracing::warn!("Candle aggregator lagged, skipped {} messages", n);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
             }
         })
     };
@@ -87,7 +547,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
             let mut interval = tokio::time::interval(Duration::from_secs(10));
             loop {
                 interval.tick().await;
-                metrics.update_system_metrics();
+                metrics.update_system_metrics().await;
             }
         })
     };
@@ -105,16 +565,225 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         })
     };
 
+    // Save books periodically for the next restart
+    if let Some(path) = book_snapshot_path.clone() {
+        let orderbook_manager = orderbook_manager.clone();
+        let period = Duration::from_secs(
+            std::env::var("FLOWRS_BOOK_SNAPSHOT_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(snapshot::DEFAULT_SAVE_INTERVAL_SECS),
+        );
+
+        tokio::spawn(async move {
+            let mut interval =
+                tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+            loop {
+                interval.tick().await;
+                let orderbook_manager = orderbook_manager.clone();
+                let path = path.clone();
+                let saved =
+                    tokio::task::spawn_blocking(move || snapshot::save(&orderbook_manager, &path))
+                        .await;
+                if let Ok(Err(e)) = saved {
+                    tracing::warn!("Failed to save books: {}", e);
+                }
+            }
+        });
+    }
+
     let exchange_handles = exchange_manager
         .start_all(client_broadcast_tx.clone())
         .await;
 
+    // SIGINT/SIGTERM: stop accepting, close clients, then stop the feeds
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        let _ = shutdown_tx.send(true);
+    });
+
     tracing::info!("Starting WebSocket server on {}", SERVER_ADDR);
-    let server_result =
-        server::start_server(SERVER_ADDR, orderbook_manager, metrics, client_broadcast_tx).await;
+    // Send-time hysteresis for switching slow clients to snapshot-only mode
+    let mut degrade_policy = server::DegradePolicy::default();
+    if let Some(ms) = std::env::var("FLOWRS_DEGRADE_ENTER_MS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+    {
+        degrade_policy.enter_above = Duration::from_millis(ms);
+    }
+    if let Some(ms) = std::env::var("FLOWRS_DEGRADE_EXIT_MS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+    {
+        degrade_policy.exit_below = Duration::from_millis(ms);
+    }
+    // Slow-consumer cut-off: FLOWRS_SEND_TIMEOUT_MS per send, FLOWRS_MAX_SEND_TIMEOUTS in a row
+    if let Some(ms) = std::env::var("FLOWRS_SEND_TIMEOUT_MS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+    {
+        degrade_policy.send_timeout = Duration::from_millis(ms);
+    }
+    if let Some(max) = std::env::var("FLOWRS_MAX_SEND_TIMEOUTS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+    {
+        degrade_policy.max_send_timeouts = max;
+    }
 
-    // Keep exchange handles alive
-    drop(exchange_handles);
+    // Per-client caps on inbound commands and outbound book updates
+    let mut client_limits = server::ClientLimits::default();
+    if let Some(rate) = std::env::var("FLOWRS_CLIENT_COMMANDS_PER_SEC")
+        .ok()
+        .and_then(|v| v.parse().ok())
+    {
+        client_limits.commands_per_sec = rate;
+    }
+    if let Some(ms) = std::env::var("FLOWRS_CLIENT_MIN_BOOK_INTERVAL_MS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+    {
+        client_limits.min_book_interval = Duration::from_millis(ms);
+    }
+    // Most clients connected at once, later ones are closed with 1013 (unset or 0: no cap)
+    let client_slots = server::ClientSlots::new(
+        std::env::var("FLOWRS_MAX_CLIENTS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0),
+    );
+
+    // Browser origins allowed to connect, comma-separated ("*" or unset: any)
+    let origin_policy = std::env::var("FLOWRS_ALLOWED_ORIGINS")
+        .map(|v| server::OriginPolicy::parse(&v))
+        .unwrap_or_default();
+
+    // Shared secret WebSocket clients and HTTP book requests must present
+    // (unset or empty: no auth)
+    let auth_token = std::env::var("FLOWRS_AUTH_TOKEN")
+        .ok()
+        .filter(|token| !token.is_empty());
+    if auth_token.is_some() {
+        tracing::info!("WebSocket clients and HTTP book requests must present the auth token");
+    }
+
+    // Debug/monitoring HTTP endpoints (books dump, Prometheus metrics, health probes,
+    // jemalloc stats and heap profiles)
+    let _http_server = {
+        let http_addr =
+            std::env::var("FLOWRS_HTTP_ADDR").unwrap_or_else(|_| DEFAULT_HTTP_ADDR.to_string());
+        let http_state = http::HttpState {
+            orderbook_manager: orderbook_manager.clone(),
+            metrics: metrics.clone(),
+            connection_health: exchange_manager.connection_health(),
+            display_depth,
+            admin_token: admin_token.clone(),
+            heap_profile_dir: std::env::var("FLOWRS_HEAP_PROFILE_DIR")
+                .map(Into::into)
+                .unwrap_or_else(|_| std::env::temp_dir()),
+            // e.g. FLOWRS_READY_MIN_COVERAGE=0.8 (unset: one book is enough)
+            min_ready_coverage: std::env::var("FLOWRS_READY_MIN_COVERAGE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0.0),
+            auth_token: auth_token.clone(),
+        };
+        tokio::spawn(async move {
+            if let Err(e) = http::start_http_server(&http_addr, http_state).await {
+                tracing::error!("HTTP server error: {}", e);
+            }
+        })
+    };
+
+    // Client listener socket options (defaults: backlog 1024, nodelay, reuseaddr)
+    let mut listener_options = server::ListenerOptions::default();
+    if let Some(backlog) = std::env::var("FLOWRS_LISTEN_BACKLOG")
+        .ok()
+        .and_then(|v| v.parse().ok())
+    {
+        listener_options.backlog = backlog;
+    }
+    if let Ok(v) = std::env::var("FLOWRS_TCP_NODELAY") {
+        listener_options.nodelay = v != "0";
+    }
+    if let Ok(v) = std::env::var("FLOWRS_REUSE_ADDR") {
+        listener_options.reuse_addr = v != "0";
+    }
+
+    let server_state = server::ServerState {
+        orderbook_manager: orderbook_manager.clone(),
+        metrics,
+        client_broadcast_tx,
+        symbol_registry,
+        candle_store,
+        trade_history,
+        admin_token,
+        degrade_policy,
+        client_limits,
+        client_slots,
+        origin_policy,
+        auth_token,
+        display_depth,
+        book_poll_interval,
+        shutdown: shutdown_rx,
+    };
+    let server_result = server::start_server(SERVER_ADDR, server_state, listener_options).await;
+
+    // Clients are closed; stop the exchange feeds before returning
+    for handle in exchange_handles {
+        handle.abort();
+    }
+    // The recorder thread writes its last rows once every publisher is gone
+    drop(exchange_manager);
+    if let Some(thread) = trade_recorder_thread {
+        let joined = tokio::time::timeout(
+            RECORDER_SHUTDOWN_TIMEOUT,
+            tokio::task::spawn_blocking(move || thread.join()),
+        )
+        .await;
+        if joined.is_err() {
+            tracing::warn!("Trade recorder did not finish flushing in time");
+        }
+    }
+    if let Some(path) = &book_snapshot_path {
+        match snapshot::save(&orderbook_manager, path) {
+            Ok(saved) => tracing::info!("Saved {} book(s) to {}", saved, path.display()),
+            Err(e) => tracing::warn!("Failed to save books to {}: {}", path.display(), e),
+        }
+    }
+    tracing::info!("Shutdown complete");
 
     server_result
 }
+
+/// Resolve on Ctrl-C (SIGINT) or, on Unix, SIGTERM (`docker stop`, Kubernetes)
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Failed to listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => tracing::info!("Received SIGINT"),
+        _ = terminate => tracing::info!("Received SIGTERM"),
+    }
+}
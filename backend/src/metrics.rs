@@ -1,7 +1,11 @@
-use crate::types::Metrics;
+use crate::exchanges::Exchange;
+use crate::orderbook::TopOfBookChange;
+use crate::types::{LifetimeLatency, Metrics, QuoteFlicker, StreamMetrics};
+use dashmap::DashMap;
+use std::collections::BTreeMap;
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use sysinfo::System;
 
 /// Number of latency samples to keep for percentile calculations
@@ -18,6 +22,10 @@ pub struct LockFreeLatencyBuffer {
     cached_p50: AtomicU64,
     cached_p95: AtomicU64,
     cached_p99: AtomicU64,
+    // Spread of the same window; stddev stored as f64 bits
+    cached_min: AtomicU64,
+    cached_max: AtomicU64,
+    cached_stddev_bits: AtomicU64,
     // Pre-allocated buffer for percentile calculation (avoids allocation each time)
     scratch_buffer: std::sync::Mutex<Vec<u64>>,
 }
@@ -40,6 +48,9 @@ impl LockFreeLatencyBuffer {
             cached_p50: AtomicU64::new(0),
             cached_p95: AtomicU64::new(0),
             cached_p99: AtomicU64::new(0),
+            cached_min: AtomicU64::new(0),
+            cached_max: AtomicU64::new(0),
+            cached_stddev_bits: AtomicU64::new(0f64.to_bits()),
             // Pre-allocate buffer once, reuse for each percentile calculation
             scratch_buffer: std::sync::Mutex::new(Vec::with_capacity(LATENCY_SAMPLE_SIZE)),
         }
@@ -64,6 +75,27 @@ impl LockFreeLatencyBuffer {
         )
     }
 
+    /// Get cached (min, max, stddev) - O(1), no allocation
+    #[inline(always)]
+    pub fn get_cached_stats(&self) -> (u64, u64, f64) {
+        (
+            self.cached_min.load(Ordering::Relaxed),
+            self.cached_max.load(Ordering::Relaxed),
+            f64::from_bits(self.cached_stddev_bits.load(Ordering::Relaxed)),
+        )
+    }
+
+    /// Samples recorded since write index `from`, oldest first, and the index
+    /// to resume from. Only the last `LATENCY_SAMPLE_SIZE` are still in the ring.
+    #[cfg(feature = "hdr-latency")]
+    fn samples_since(&self, from: usize) -> (impl Iterator<Item = u64> + '_, usize) {
+        let to = self.write_index.load(Ordering::Relaxed);
+        let start = from.max(to.saturating_sub(LATENCY_SAMPLE_SIZE));
+        let samples =
+            (start..to).map(|i| self.samples[i & LATENCY_SAMPLE_MASK].load(Ordering::Relaxed));
+        (samples, to)
+    }
+
     /// Update cached percentiles - called periodically in background
     /// Uses partial selection (O(n)) instead of full sort (O(n log n))
     /// Reuses pre-allocated buffer to avoid allocation
@@ -80,6 +112,20 @@ impl LockFreeLatencyBuffer {
         scratch.clear();
         scratch.extend((0..len).map(|i| self.samples[i].load(Ordering::Relaxed)));
 
+        // Min, max and stddev over the same window, before selection reorders it
+        let (mut min, mut max, mut sum) = (u64::MAX, 0u64, 0f64);
+        for &sample in scratch.iter() {
+            min = min.min(sample);
+            max = max.max(sample);
+            sum += sample as f64;
+        }
+        let mean = sum / len as f64;
+        let variance = scratch
+            .iter()
+            .map(|&sample| (sample as f64 - mean).powi(2))
+            .sum::<f64>()
+            / len as f64;
+
         // Use partial selection - O(n) instead of O(n log n)
         // P99 first (highest index), then P95, then P50
         // This order is more efficient because select_nth_unstable partially sorts
@@ -98,6 +144,56 @@ impl LockFreeLatencyBuffer {
         self.cached_p50.store(p50_val, Ordering::Relaxed);
         self.cached_p95.store(p95_val, Ordering::Relaxed);
         self.cached_p99.store(p99_val, Ordering::Relaxed);
+        self.cached_min.store(min, Ordering::Relaxed);
+        self.cached_max.store(max, Ordering::Relaxed);
+        self.cached_stddev_bits
+            .store(variance.sqrt().to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// Every latency since startup, fed from the ring buffer in the background
+///
+/// The hot path only ever writes the ring; each `update_latency_percentiles`
+/// moves the samples written since the previous one into the histogram. Past
+/// `LATENCY_SAMPLE_SIZE` samples between two updates, the older ones have been
+/// overwritten and only the last `LATENCY_SAMPLE_SIZE` are counted.
+#[cfg(feature = "hdr-latency")]
+struct LifetimeHistogram {
+    /// 1µs to 60s, 3 significant digits; slower samples count as 60s
+    histogram: hdrhistogram::Histogram<u64>,
+    /// Ring buffer write index the next drain resumes from
+    drained_to: usize,
+}
+
+#[cfg(feature = "hdr-latency")]
+impl LifetimeHistogram {
+    const MAX_LATENCY_US: u64 = 60_000_000;
+
+    fn new() -> Self {
+        Self {
+            histogram: hdrhistogram::Histogram::new_with_bounds(1, Self::MAX_LATENCY_US, 3)
+                .expect("valid histogram bounds"),
+            drained_to: 0,
+        }
+    }
+
+    fn drain(&mut self, buffer: &LockFreeLatencyBuffer) {
+        let (samples, drained_to) = buffer.samples_since(self.drained_to);
+        for sample in samples {
+            self.histogram.saturating_record(sample.max(1));
+        }
+        self.drained_to = drained_to;
+    }
+
+    fn report(&self) -> Option<LifetimeLatency> {
+        let histogram = &self.histogram;
+        (!histogram.is_empty()).then(|| LifetimeLatency {
+            count: histogram.len(),
+            p50_us: histogram.value_at_quantile(0.5),
+            p99_us: histogram.value_at_quantile(0.99),
+            p999_us: histogram.value_at_quantile(0.999),
+            max_us: histogram.max(),
+        })
     }
 }
 
@@ -109,6 +205,9 @@ impl std::fmt::Debug for LockFreeLatencyBuffer {
     }
 }
 
+/// Gap between the two process refreshes a CPU usage sample needs
+const CPU_SAMPLE_INTERVAL: Duration = Duration::from_millis(200);
+
 /// Cache for system metrics to avoid expensive syscalls every second
 /// Updated every 10 seconds in a background task
 /// Uses AtomicU64 with f64::to_bits/from_bits for lock-free access
@@ -117,6 +216,8 @@ pub struct SystemMetricsCache {
     memory_used_mb_bits: AtomicU64,
     memory_rss_mb_bits: AtomicU64,
     cpu_usage_percent_bits: AtomicU64,
+    // Logical cores: a process using all of them reports 100% × cpu_cores
+    cpu_cores: u32,
 }
 
 impl SystemMetricsCache {
@@ -125,9 +226,16 @@ impl SystemMetricsCache {
             memory_used_mb_bits: AtomicU64::new(0.0_f64.to_bits()),
             memory_rss_mb_bits: AtomicU64::new(0.0_f64.to_bits()),
             cpu_usage_percent_bits: AtomicU64::new(0.0_f64.to_bits()),
+            cpu_cores: std::thread::available_parallelism().map_or(1, |n| n.get() as u32),
         }
     }
 
+    /// Logical cores available to the process, to normalize `cpu_usage_percent`
+    #[inline]
+    pub fn cpu_cores(&self) -> u32 {
+        self.cpu_cores
+    }
+
     #[inline]
     pub fn get(&self) -> (f64, f64, f64) {
         let mem = f64::from_bits(self.memory_used_mb_bits.load(Ordering::Relaxed));
@@ -136,38 +244,80 @@ impl SystemMetricsCache {
         (mem, rss, cpu)
     }
 
-    pub fn update(&self) {
-        tokio::task::block_in_place(|| {
-            let mut system = System::new();
-
-            // Get current process memory (RSS = Resident Set Size)
-            let pid = sysinfo::Pid::from_u32(std::process::id());
-            system.refresh_processes_specifics(
-                sysinfo::ProcessesToUpdate::Some(&[pid]),
-                true,
-                sysinfo::ProcessRefreshKind::nothing().with_memory(),
-            );
-
-            let (process_mem_mb, process_virt_mb) = system
-                .process(pid)
-                .map(|p| {
-                    let rss = p.memory() as f64 / 1024.0 / 1024.0; // bytes -> MB
-                    let virt = p.virtual_memory() as f64 / 1024.0 / 1024.0;
-                    (rss, virt)
-                })
-                .unwrap_or((0.0, 0.0));
+    /// Sample memory and CPU usage
+    ///
+    /// Waits between the two CPU refreshes without blocking the runtime
+    /// thread, so it works on any runtime flavor.
+    pub async fn update(&self) {
+        let mut system = System::new();
+
+        let pid = sysinfo::Pid::from_u32(std::process::id());
+
+        // CPU usage is a delta between two refreshes: prime the counters,
+        // wait, then take the second sample along with memory. The first
+        // refresh only asks for CPU so memory is read exactly once.
+        system.refresh_processes_specifics(
+            sysinfo::ProcessesToUpdate::Some(&[pid]),
+            true,
+            sysinfo::ProcessRefreshKind::nothing().with_cpu(),
+        );
+        tokio::time::sleep(CPU_SAMPLE_INTERVAL).await;
+
+        // Get current process memory (RSS = Resident Set Size)
+        system.refresh_processes_specifics(
+            sysinfo::ProcessesToUpdate::Some(&[pid]),
+            true,
+            sysinfo::ProcessRefreshKind::nothing()
+                .with_cpu()
+                .with_memory(),
+        );
+
+        let (process_mem_mb, process_virt_mb) = system
+            .process(pid)
+            .map(|p| {
+                let rss = p.memory() as f64 / 1024.0 / 1024.0; // bytes -> MB
+                let virt = p.virtual_memory() as f64 / 1024.0 / 1024.0;
+                (rss, virt)
+            })
+            .unwrap_or((0.0, 0.0));
+
+        // Percent of one core, so up to 100% × cpu_cores
+        let cpu = system.process(pid).map_or(0.0, |p| p.cpu_usage() as f64);
+
+        self.memory_used_mb_bits
+            .store(process_mem_mb.to_bits(), Ordering::Relaxed);
+        self.memory_rss_mb_bits
+            .store(process_virt_mb.to_bits(), Ordering::Relaxed);
+        self.cpu_usage_percent_bits
+            .store(cpu.to_bits(), Ordering::Relaxed);
+    }
+}
 
-            // CPU usage requires two refreshes with a delay, skip for now
-            // Just use 0.0 as placeholder (CPU tracking adds overhead)
-            let cpu: f64 = 0.0;
+/// Top-of-book change counters of one symbol
+#[derive(Debug, Default)]
+struct QuoteChangeCounters {
+    price: AtomicU64,
+    size_only: AtomicU64,
+}
 
-            self.memory_used_mb_bits
-                .store(process_mem_mb.to_bits(), Ordering::Relaxed);
-            self.memory_rss_mb_bits
-                .store(process_virt_mb.to_bits(), Ordering::Relaxed);
-            self.cpu_usage_percent_bits
-                .store(cpu.to_bits(), Ordering::Relaxed);
-        });
+/// Counters of one exchange:symbol stream
+struct StreamCounters {
+    messages: AtomicU64,
+    bytes: AtomicU64,
+    last_messages: AtomicU64,
+    last_bytes: AtomicU64,
+    latency: LockFreeLatencyBuffer,
+}
+
+impl StreamCounters {
+    fn new() -> Self {
+        Self {
+            messages: AtomicU64::new(0),
+            bytes: AtomicU64::new(0),
+            last_messages: AtomicU64::new(0),
+            last_bytes: AtomicU64::new(0),
+            latency: LockFreeLatencyBuffer::new(),
+        }
     }
 }
 
@@ -175,6 +325,10 @@ impl SystemMetricsCache {
 pub struct MetricsCollector {
     /// Global message count (all incoming messages)
     global_message_count: AtomicU64,
+    /// Message counts by type: depth updates, trades, ignored control messages
+    depth_count: AtomicU64,
+    trade_count: AtomicU64,
+    ignored_count: AtomicU64,
     /// Global latency samples for percentile calculations - NOW LOCK-FREE
     global_latency_buffer: LockFreeLatencyBuffer,
     /// Global latency sum
@@ -187,44 +341,116 @@ pub struct MetricsCollector {
     ws_reconnects: AtomicU64,
     /// Active WebSocket connections
     active_connections: AtomicU64,
+    /// Clients in snapshot-only (degraded) mode
+    degraded_clients: AtomicU64,
+    /// Clients disconnected because their sends kept timing out
+    slow_consumer_drops: AtomicU64,
+    /// Market data messages a publisher sink (Redis, Kafka) had to drop
+    publish_drops: AtomicU64,
+    /// WebSocket handshakes refused for a missing or wrong token
+    auth_rejections: AtomicU64,
+    /// Broadcast messages (trades, metrics...) clients missed by lagging behind
+    broadcast_lagged: AtomicU64,
     /// Start time for uptime calculation
     start_time: Instant,
     /// Last reset time for per-second calculations
     last_reset: Arc<std::sync::Mutex<Instant>>,
+    /// Bytes received per exchange, indexed by `Exchange as usize`
+    exchange_bytes: [AtomicU64; Exchange::ALL.len()],
     /// Previous counts for rate calculation
     last_message_count: AtomicU64,
+    last_depth_count: AtomicU64,
+    last_trade_count: AtomicU64,
+    last_ignored_count: AtomicU64,
     last_bytes_received: AtomicU64,
+    last_exchange_bytes: [AtomicU64; Exchange::ALL.len()],
     /// System metrics cache (updated every 10s)
     system_cache: SystemMetricsCache,
+    /// Order book coverage (f64 bits), pushed by the metrics ticker
+    book_coverage_bits: AtomicU64,
+    /// Price anomalies flagged by the consistency monitor
+    price_anomalies: AtomicU64,
+    /// Trades dropped for an implausible timestamp
+    rejected_trades: AtomicU64,
+    /// Top-of-book price vs size-only changes per symbol
+    quote_changes: DashMap<String, QuoteChangeCounters>,
+    /// Per-symbol stream counters, indexed by `Exchange as usize`
+    streams: [DashMap<String, StreamCounters>; Exchange::ALL.len()],
+    /// Last report built by `compute_metrics`, for readers that must not reset it
+    last_report: std::sync::Mutex<Metrics>,
+    /// Every latency since startup, for percentiles the sample window is too short for
+    #[cfg(feature = "hdr-latency")]
+    lifetime_latency: std::sync::Mutex<LifetimeHistogram>,
 }
 
 impl MetricsCollector {
     pub fn new() -> Self {
         Self {
             global_message_count: AtomicU64::new(0),
+            depth_count: AtomicU64::new(0),
+            trade_count: AtomicU64::new(0),
+            ignored_count: AtomicU64::new(0),
             global_latency_buffer: LockFreeLatencyBuffer::new(),
             global_latency_sum_us: AtomicU64::new(0),
             global_latency_count: AtomicU64::new(0),
             bytes_received: AtomicU64::new(0),
             ws_reconnects: AtomicU64::new(0),
             active_connections: AtomicU64::new(0),
+            degraded_clients: AtomicU64::new(0),
+            slow_consumer_drops: AtomicU64::new(0),
+            publish_drops: AtomicU64::new(0),
+            auth_rejections: AtomicU64::new(0),
+            broadcast_lagged: AtomicU64::new(0),
             start_time: Instant::now(),
             last_reset: Arc::new(std::sync::Mutex::new(Instant::now())),
+            exchange_bytes: Default::default(),
             last_message_count: AtomicU64::new(0),
+            last_depth_count: AtomicU64::new(0),
+            last_trade_count: AtomicU64::new(0),
+            last_ignored_count: AtomicU64::new(0),
             last_bytes_received: AtomicU64::new(0),
+            last_exchange_bytes: Default::default(),
             system_cache: SystemMetricsCache::new(),
+            book_coverage_bits: AtomicU64::new(0.0_f64.to_bits()),
+            price_anomalies: AtomicU64::new(0),
+            rejected_trades: AtomicU64::new(0),
+            quote_changes: DashMap::new(),
+            streams: Default::default(),
+            last_report: std::sync::Mutex::new(Metrics::default()),
+            #[cfg(feature = "hdr-latency")]
+            lifetime_latency: std::sync::Mutex::new(LifetimeHistogram::new()),
         }
     }
 
-    /// Record a message received (all types)
+    /// Record `count` messages received (all types), decoded from one frame
+    #[inline]
+    pub fn record_messages(&self, count: u64) {
+        self.global_message_count
+            .fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Record an order book snapshot or delta
     #[inline]
-    pub fn record_message(&self) {
-        self.global_message_count.fetch_add(1, Ordering::Relaxed);
+    pub fn record_depth(&self) {
+        self.depth_count.fetch_add(1, Ordering::Relaxed);
     }
 
-    /// Record bytes received
-    pub fn record_bytes(&self, bytes: u64) {
+    /// Record a trade
+    #[inline]
+    pub fn record_trade(&self) {
+        self.trade_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a control message carrying no market data (heartbeat, ack...)
+    #[inline]
+    pub fn record_ignored(&self) {
+        self.ignored_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record bytes received from an exchange (also counted in the global total)
+    pub fn record_bytes(&self, exchange: Exchange, bytes: u64) {
         self.bytes_received.fetch_add(bytes, Ordering::Relaxed);
+        self.exchange_bytes[exchange as usize].fetch_add(bytes, Ordering::Relaxed);
     }
 
     /// Record a WebSocket reconnection
@@ -242,6 +468,105 @@ impl MetricsCollector {
         self.active_connections.fetch_sub(1, Ordering::Relaxed);
     }
 
+    /// A client switched to snapshot-only mode
+    pub fn increment_degraded_clients(&self) {
+        self.degraded_clients.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A degraded client caught up or disconnected
+    pub fn decrement_degraded_clients(&self) {
+        self.degraded_clients.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// A client was disconnected for not reading its frames
+    pub fn record_slow_consumer_drop(&self) {
+        self.slow_consumer_drops.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A sink's queue was full or it failed to deliver a message
+    pub fn record_publish_drop(&self) {
+        self.publish_drops.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A client was refused for not presenting the auth token
+    pub fn record_auth_rejection(&self) {
+        self.auth_rejections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A client fell `missed` broadcast messages behind and skipped them
+    pub fn record_broadcast_lag(&self, missed: u64) {
+        self.broadcast_lagged.fetch_add(missed, Ordering::Relaxed);
+    }
+
+    /// Store the latest order book coverage (see `OrderBookManager::coverage`)
+    pub fn set_book_coverage(&self, coverage: f64) {
+        self.book_coverage_bits
+            .store(coverage.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Record a price anomaly flagged by the consistency monitor
+    pub fn record_price_anomaly(&self) {
+        self.price_anomalies.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a trade dropped for an implausible timestamp
+    pub fn record_rejected_trade(&self) {
+        self.rejected_trades.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record how a book update moved the top of the book
+    #[inline]
+    pub fn record_top_of_book_change(&self, symbol: &str, change: TopOfBookChange) {
+        if change == TopOfBookChange::Unchanged {
+            return;
+        }
+        // Lookup by &str first: the symbol key is only allocated once
+        let counters = match self.quote_changes.get(symbol) {
+            Some(counters) => counters,
+            None => self
+                .quote_changes
+                .entry(symbol.to_string())
+                .or_default()
+                .downgrade(),
+        };
+        let counter = match change {
+            TopOfBookChange::Price => &counters.price,
+            _ => &counters.size_only,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Top-of-book change counts per symbol since startup
+    pub fn quote_flicker(&self) -> BTreeMap<String, QuoteFlicker> {
+        self.quote_changes
+            .iter()
+            .map(|entry| {
+                let flicker = QuoteFlicker {
+                    price_changes: entry.price.load(Ordering::Relaxed),
+                    size_changes: entry.size_only.load(Ordering::Relaxed),
+                };
+                (entry.key().clone(), flicker)
+            })
+            .collect()
+    }
+
+    /// Record a message of one exchange:symbol stream, `bytes` long and received at `start`
+    #[inline]
+    pub fn record_stream(&self, exchange: Exchange, symbol: &str, bytes: u64, start: Instant) {
+        let streams = &self.streams[exchange as usize];
+        // Lookup by &str first: the symbol key is only allocated once
+        let counters = match streams.get(symbol) {
+            Some(counters) => counters,
+            None => streams
+                .entry(symbol.to_string())
+                .or_insert_with(StreamCounters::new)
+                .downgrade(),
+        };
+        counters.messages.fetch_add(1, Ordering::Relaxed);
+        counters.bytes.fetch_add(bytes, Ordering::Relaxed);
+        counters.latency.record(start.elapsed().as_micros() as u64);
+    }
+
     /// Record latency from Instant (micro_sec)
     #[inline]
     pub fn record_latency(&self, start: Instant) {
@@ -280,6 +605,61 @@ impl MetricsCollector {
             0
         };
 
+        let type_rate = |counter: &AtomicU64, last: &AtomicU64| {
+            let current = counter.load(Ordering::Relaxed);
+            let prev = last.swap(current, Ordering::Relaxed);
+            if elapsed_secs > 0.0 {
+                ((current - prev) as f64 / elapsed_secs) as u64
+            } else {
+                0
+            }
+        };
+        let depth_per_second = type_rate(&self.depth_count, &self.last_depth_count);
+        let trades_per_second = type_rate(&self.trade_count, &self.last_trade_count);
+        let ignored_per_second = type_rate(&self.ignored_count, &self.last_ignored_count);
+
+        // Only exchanges that ever sent data show up
+        let exchange_bytes_per_second = Exchange::ALL
+            .iter()
+            .filter_map(|exchange| {
+                let i = *exchange as usize;
+                let current = self.exchange_bytes[i].load(Ordering::Relaxed);
+                let prev = self.last_exchange_bytes[i].swap(current, Ordering::Relaxed);
+                if current == 0 {
+                    return None;
+                }
+                let rate = if elapsed_secs > 0.0 {
+                    ((current - prev) as f64 / elapsed_secs) as u64
+                } else {
+                    0
+                };
+                Some((exchange.name().to_string(), rate))
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        let mut streams = Exchange::ALL
+            .iter()
+            .flat_map(|exchange| {
+                self.streams[*exchange as usize].iter().map(|entry| {
+                    let (latency_p50_us, latency_p95_us, latency_p99_us) =
+                        entry.latency.get_cached_percentiles();
+                    StreamMetrics {
+                        exchange: exchange.name().to_string(),
+                        symbol: entry.key().clone(),
+                        messages_per_second: type_rate(&entry.messages, &entry.last_messages),
+                        bytes_per_second: type_rate(&entry.bytes, &entry.last_bytes),
+                        total_messages: entry.messages.load(Ordering::Relaxed),
+                        latency_p50_us,
+                        latency_p95_us,
+                        latency_p99_us,
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        streams.sort_by(|a, b| (&a.exchange, &a.symbol).cmp(&(&b.exchange, &b.symbol)));
+
+        let (latency_min_us, latency_max_us, latency_stddev_us) =
+            self.global_latency_buffer.get_cached_stats();
         let (latency_avg_us, latency_p50_us, latency_p95_us, latency_p99_us) = {
             let latency_sum = self.global_latency_sum_us.swap(0, Ordering::Relaxed);
             let latency_count = self.global_latency_count.swap(0, Ordering::Relaxed);
@@ -296,37 +676,137 @@ impl MetricsCollector {
 
         let (memory_used_mb, memory_rss_mb, cpu_usage_percent) = self.system_cache.get();
 
+        let (top_price_changes, top_size_changes) = self.top_of_book_changes();
+
         let active_connections = self.active_connections.load(Ordering::Relaxed) as u32;
         let websocket_reconnects = self.ws_reconnects.load(Ordering::Relaxed);
 
         *last_reset = now;
 
-        Metrics {
+        let metrics = Metrics {
             messages_per_second,
+            depth_per_second,
+            trades_per_second,
+            ignored_per_second,
             bytes_per_second,
             latency_avg_us,
             latency_p50_us,
             latency_p95_us,
             latency_p99_us,
+            latency_min_us,
+            latency_max_us,
+            latency_stddev_us,
             total_messages: current_messages,
             uptime_seconds: self.start_time.elapsed().as_secs(),
             memory_used_mb,
             memory_rss_mb,
             cpu_usage_percent,
+            cpu_cores: self.system_cache.cpu_cores(),
             active_connections,
+            degraded_clients: self.degraded_clients.load(Ordering::Relaxed),
+            slow_consumer_drops: self.slow_consumer_drops.load(Ordering::Relaxed),
+            publish_drops: self.publish_drops.load(Ordering::Relaxed),
+            auth_rejections: self.auth_rejections.load(Ordering::Relaxed),
+            broadcast_lagged: self.broadcast_lagged.load(Ordering::Relaxed),
             websocket_reconnects,
+            book_coverage: f64::from_bits(self.book_coverage_bits.load(Ordering::Relaxed)),
+            price_anomalies: self.price_anomalies.load(Ordering::Relaxed),
+            rejected_trades: self.rejected_trades.load(Ordering::Relaxed),
+            top_price_changes,
+            top_size_changes,
             bytes_received: current_bytes,
-        }
+            exchange_bytes_per_second,
+            streams,
+            latency_lifetime: self.lifetime_latency(),
+        };
+        *self.last_report.lock().unwrap() = metrics.clone();
+        metrics
+    }
+
+    /// Latest report with live totals and gauges, resetting nothing
+    ///
+    /// Rates and the average latency are those of the last `compute_metrics`
+    /// call: only that one owns the per-interval accounting, so scrapers
+    /// (Prometheus) can read as often as they like without skewing it.
+    pub fn peek_metrics(&self) -> Metrics {
+        let mut metrics = self.last_report.lock().unwrap().clone();
+        let (latency_p50_us, latency_p95_us, latency_p99_us) =
+            self.global_latency_buffer.get_cached_percentiles();
+        let (memory_used_mb, memory_rss_mb, cpu_usage_percent) = self.system_cache.get();
+        let (top_price_changes, top_size_changes) = self.top_of_book_changes();
+
+        metrics.total_messages = self.global_message_count.load(Ordering::Relaxed);
+        metrics.bytes_received = self.bytes_received.load(Ordering::Relaxed);
+        metrics.latency_p50_us = latency_p50_us;
+        metrics.latency_p95_us = latency_p95_us;
+        metrics.latency_p99_us = latency_p99_us;
+        (
+            metrics.latency_min_us,
+            metrics.latency_max_us,
+            metrics.latency_stddev_us,
+        ) = self.global_latency_buffer.get_cached_stats();
+        metrics.uptime_seconds = self.start_time.elapsed().as_secs();
+        metrics.memory_used_mb = memory_used_mb;
+        metrics.memory_rss_mb = memory_rss_mb;
+        metrics.cpu_usage_percent = cpu_usage_percent;
+        metrics.cpu_cores = self.system_cache.cpu_cores();
+        metrics.active_connections = self.active_connections.load(Ordering::Relaxed) as u32;
+        metrics.degraded_clients = self.degraded_clients.load(Ordering::Relaxed);
+        metrics.slow_consumer_drops = self.slow_consumer_drops.load(Ordering::Relaxed);
+        metrics.publish_drops = self.publish_drops.load(Ordering::Relaxed);
+        metrics.auth_rejections = self.auth_rejections.load(Ordering::Relaxed);
+        metrics.broadcast_lagged = self.broadcast_lagged.load(Ordering::Relaxed);
+        metrics.websocket_reconnects = self.ws_reconnects.load(Ordering::Relaxed);
+        metrics.book_coverage = f64::from_bits(self.book_coverage_bits.load(Ordering::Relaxed));
+        metrics.price_anomalies = self.price_anomalies.load(Ordering::Relaxed);
+        metrics.rejected_trades = self.rejected_trades.load(Ordering::Relaxed);
+        metrics.top_price_changes = top_price_changes;
+        metrics.top_size_changes = top_size_changes;
+        metrics.latency_lifetime = self.lifetime_latency();
+        metrics
+    }
+
+    /// Lifetime latency percentiles, as of the last background update
+    #[cfg(feature = "hdr-latency")]
+    fn lifetime_latency(&self) -> Option<LifetimeLatency> {
+        self.lifetime_latency.lock().unwrap().report()
+    }
+
+    #[cfg(not(feature = "hdr-latency"))]
+    fn lifetime_latency(&self) -> Option<LifetimeLatency> {
+        None
+    }
+
+    /// Top-of-book price and size-only changes, all symbols combined
+    fn top_of_book_changes(&self) -> (u64, u64) {
+        self.quote_changes
+            .iter()
+            .fold((0, 0), |(price, size), entry| {
+                (
+                    price + entry.price.load(Ordering::Relaxed),
+                    size + entry.size_only.load(Ordering::Relaxed),
+                )
+            })
     }
 
     /// Update system metrics (called every 10 seconds)
-    pub fn update_system_metrics(&self) {
-        self.system_cache.update();
+    pub async fn update_system_metrics(&self) {
+        self.system_cache.update().await;
     }
 
     /// Update latency percentiles (called periodically in background)
     pub fn update_latency_percentiles(&self) {
         self.global_latency_buffer.update_percentiles();
+        #[cfg(feature = "hdr-latency")]
+        self.lifetime_latency
+            .lock()
+            .unwrap()
+            .drain(&self.global_latency_buffer);
+        for streams in &self.streams {
+            for entry in streams.iter() {
+                entry.latency.update_percentiles();
+            }
+        }
     }
 }
 
@@ -341,3 +821,179 @@ pub type SharedMetrics = Arc<MetricsCollector>;
 pub fn create_shared_metrics() -> SharedMetrics {
     Arc::new(MetricsCollector::new())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bytes_attributed_per_exchange() {
+        let metrics = MetricsCollector::new();
+        metrics.record_bytes(Exchange::Binance, 300);
+        metrics.record_bytes(Exchange::Kraken, 100);
+        metrics.record_bytes(Exchange::Binance, 200);
+
+        let computed = metrics.compute_metrics();
+        assert_eq!(computed.bytes_received, 600);
+        let exchanges: Vec<&str> = computed
+            .exchange_bytes_per_second
+            .keys()
+            .map(|k| k.as_str())
+            .collect();
+        assert_eq!(exchanges, vec!["Binance", "Kraken"]);
+        assert!(computed.exchange_bytes_per_second["Binance"] > 0);
+    }
+
+    #[test]
+    fn test_streams_broken_down_per_exchange_and_symbol() {
+        let metrics = MetricsCollector::new();
+        let start = Instant::now();
+        metrics.record_stream(Exchange::Kraken, "BTCUSDT", 100, start);
+        metrics.record_stream(Exchange::Binance, "ETHUSDT", 50, start);
+        metrics.record_stream(Exchange::Binance, "BTCUSDT", 200, start);
+        metrics.record_stream(Exchange::Binance, "BTCUSDT", 300, start);
+
+        let computed = metrics.compute_metrics();
+        let streams: Vec<(&str, &str, u64)> = computed
+            .streams
+            .iter()
+            .map(|s| (s.exchange.as_str(), s.symbol.as_str(), s.total_messages))
+            .collect();
+        assert_eq!(
+            streams,
+            vec![
+                ("Binance", "BTCUSDT", 2),
+                ("Binance", "ETHUSDT", 1),
+                ("Kraken", "BTCUSDT", 1)
+            ]
+        );
+        assert!(computed.streams[0].bytes_per_second > computed.streams[1].bytes_per_second);
+
+        // Rates are per interval, totals are not
+        let computed = metrics.compute_metrics();
+        assert_eq!(computed.streams[0].messages_per_second, 0);
+        assert_eq!(computed.streams[0].total_messages, 2);
+    }
+
+    #[test]
+    fn test_latency_spread_cached_with_percentiles() {
+        let buffer = LockFreeLatencyBuffer::new();
+        // 2, 4, 4, 4, 5, 5, 7, 9 repeated: mean 5, population stddev 2
+        for _ in 0..4 {
+            for latency in [2, 4, 4, 4, 5, 5, 7, 9] {
+                buffer.record(latency);
+            }
+        }
+        assert_eq!(buffer.get_cached_stats(), (0, 0, 0.0));
+
+        buffer.update_percentiles();
+        let (min, max, stddev) = buffer.get_cached_stats();
+        assert_eq!((min, max), (2, 9));
+        assert!((stddev - 2.0).abs() < 1e-9);
+        assert_eq!(buffer.get_cached_percentiles().0, 5);
+    }
+
+    #[cfg(feature = "hdr-latency")]
+    #[test]
+    fn test_lifetime_latency_outlives_sample_window() {
+        let metrics = MetricsCollector::new();
+        assert_eq!(metrics.compute_metrics().latency_lifetime, None);
+
+        // One slow message, then enough fast ones to push it out of the ring
+        metrics.global_latency_buffer.record(50_000);
+        metrics.update_latency_percentiles();
+        for _ in 0..3 {
+            for latency in 1..=1000 {
+                metrics.global_latency_buffer.record(latency);
+            }
+            metrics.update_latency_percentiles();
+        }
+        assert_eq!(metrics.global_latency_buffer.get_cached_stats().1, 1000);
+
+        let lifetime = metrics.peek_metrics().latency_lifetime.unwrap();
+        assert_eq!(lifetime.count, 3001);
+        assert_eq!(lifetime.p50_us, 501);
+        assert_eq!(lifetime.p999_us, 1000);
+        assert!(lifetime.max_us >= 50_000, "{:?}", lifetime);
+
+        // Past the ring size between two updates, only the newest samples count
+        for _ in 0..3000 {
+            metrics.global_latency_buffer.record(10);
+        }
+        metrics.update_latency_percentiles();
+        let lifetime = metrics.peek_metrics().latency_lifetime.unwrap();
+        assert_eq!(lifetime.count, 3001 + LATENCY_SAMPLE_SIZE as u64);
+    }
+
+    #[test]
+    fn test_message_types_counted_separately() {
+        let metrics = MetricsCollector::new();
+        for _ in 0..5 {
+            metrics.record_depth();
+        }
+        metrics.record_trade();
+        metrics.record_trade();
+        metrics.record_ignored();
+
+        let computed = metrics.compute_metrics();
+        assert!(computed.depth_per_second > computed.trades_per_second);
+        assert!(computed.trades_per_second > computed.ignored_per_second);
+        assert!(computed.ignored_per_second > 0);
+
+        // Rates are per interval: nothing new since the last computation
+        let computed = metrics.compute_metrics();
+        assert_eq!(computed.depth_per_second, 0);
+    }
+
+    #[test]
+    fn test_quote_flicker_counted_per_symbol() {
+        let metrics = MetricsCollector::new();
+        metrics.record_top_of_book_change("BTCUSDT", TopOfBookChange::Price);
+        metrics.record_top_of_book_change("BTCUSDT", TopOfBookChange::SizeOnly);
+        metrics.record_top_of_book_change("BTCUSDT", TopOfBookChange::SizeOnly);
+        metrics.record_top_of_book_change("ETHUSDT", TopOfBookChange::SizeOnly);
+        metrics.record_top_of_book_change("SOLUSDT", TopOfBookChange::Unchanged);
+
+        let flicker = metrics.quote_flicker();
+        assert_eq!(flicker.len(), 2);
+        assert_eq!(
+            flicker["BTCUSDT"],
+            QuoteFlicker {
+                price_changes: 1,
+                size_changes: 2
+            }
+        );
+
+        let computed = metrics.compute_metrics();
+        assert_eq!(
+            (computed.top_price_changes, computed.top_size_changes),
+            (1, 3)
+        );
+    }
+
+    // Default current_thread runtime: sampling must not block it
+    #[tokio::test]
+    async fn test_system_metrics_sample_cpu_and_cores() {
+        let metrics = MetricsCollector::new();
+        // Keep a core busy across the sampling window. It has to start
+        // before it: sysinfo skips a process with no CPU ticks at all yet.
+        let stop = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let spinner = {
+            let stop = stop.clone();
+            std::thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    std::hint::spin_loop();
+                }
+            })
+        };
+        std::thread::sleep(Duration::from_millis(100));
+        metrics.update_system_metrics().await;
+        stop.store(true, Ordering::Relaxed);
+        spinner.join().unwrap();
+
+        let computed = metrics.compute_metrics();
+        assert!(computed.cpu_cores >= 1);
+        assert!(computed.cpu_usage_percent > 0.0);
+        assert!(computed.memory_used_mb > 0.0);
+    }
+}
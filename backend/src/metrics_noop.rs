@@ -0,0 +1,103 @@
+//! No-op metrics collector, used when the `metrics` feature is disabled
+//!
+//! Mirrors the `MetricsCollector` API with empty inline bodies so every
+//! recording call on the hot path compiles away. Reports are all zeros.
+
+use crate::exchanges::Exchange;
+use crate::orderbook::TopOfBookChange;
+use crate::types::{Metrics, QuoteFlicker};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Metrics collector that records nothing
+#[derive(Debug, Default)]
+pub struct MetricsCollector;
+
+impl MetricsCollector {
+    pub fn new() -> Self {
+        Self
+    }
+
+    #[inline(always)]
+    pub fn record_messages(&self, _count: u64) {}
+
+    #[inline(always)]
+    pub fn record_depth(&self) {}
+
+    #[inline(always)]
+    pub fn record_trade(&self) {}
+
+    #[inline(always)]
+    pub fn record_ignored(&self) {}
+
+    #[inline(always)]
+    pub fn record_bytes(&self, _exchange: Exchange, _bytes: u64) {}
+
+    #[inline(always)]
+    pub fn record_reconnect(&self) {}
+
+    #[inline(always)]
+    pub fn increment_connections(&self) {}
+
+    #[inline(always)]
+    pub fn decrement_connections(&self) {}
+
+    #[inline(always)]
+    pub fn increment_degraded_clients(&self) {}
+
+    #[inline(always)]
+    pub fn decrement_degraded_clients(&self) {}
+
+    #[inline(always)]
+    pub fn record_slow_consumer_drop(&self) {}
+
+    #[inline(always)]
+    pub fn record_publish_drop(&self) {}
+
+    #[inline(always)]
+    pub fn record_auth_rejection(&self) {}
+
+    #[inline(always)]
+    pub fn record_broadcast_lag(&self, _missed: u64) {}
+
+    #[inline(always)]
+    pub fn set_book_coverage(&self, _coverage: f64) {}
+
+    #[inline(always)]
+    pub fn record_price_anomaly(&self) {}
+
+    #[inline(always)]
+    pub fn record_rejected_trade(&self) {}
+
+    #[inline(always)]
+    pub fn record_top_of_book_change(&self, _symbol: &str, _change: TopOfBookChange) {}
+
+    pub fn quote_flicker(&self) -> BTreeMap<String, QuoteFlicker> {
+        BTreeMap::new()
+    }
+
+    #[inline(always)]
+    pub fn record_stream(&self, _exchange: Exchange, _symbol: &str, _bytes: u64, _start: Instant) {}
+
+    #[inline(always)]
+    pub fn record_latency(&self, _start: Instant) {}
+
+    pub fn compute_metrics(&self) -> Metrics {
+        Metrics::default()
+    }
+
+    pub fn peek_metrics(&self) -> Metrics {
+        Metrics::default()
+    }
+
+    pub async fn update_system_metrics(&self) {}
+
+    pub fn update_latency_percentiles(&self) {}
+}
+
+pub type SharedMetrics = Arc<MetricsCollector>;
+
+pub fn create_shared_metrics() -> SharedMetrics {
+    Arc::new(MetricsCollector::new())
+}
@@ -0,0 +1,188 @@
+//! Cross-exchange price consistency monitor
+//!
+//! Per symbol, compares each fresh exchange's mid against the median mid of all
+//! fresh exchanges and flags those that stay too far away for too long.
+
+use crate::orderbook::OrderBookManager;
+use crate::types::{ClientMessage, BOOK_STALE_AFTER_SECS};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Default deviation from the median mid that counts as divergent
+pub const DEFAULT_ANOMALY_BPS: f64 = 50.0;
+
+/// Default time an exchange must stay divergent before it is flagged
+pub const DEFAULT_ANOMALY_GRACE_SECS: u64 = 5;
+
+/// Fewer fresh exchanges than this give no meaningful consensus
+const MIN_EXCHANGES_FOR_CONSENSUS: usize = 3;
+
+/// Divergence state of one exchange:symbol
+#[derive(Debug, Clone, Copy)]
+struct Divergence {
+    since: Instant,
+    reported: bool,
+}
+
+/// Flags exchanges whose mid diverges from the cross-exchange median
+#[derive(Debug)]
+pub struct ConsistencyMonitor {
+    threshold_bps: f64,
+    grace: Duration,
+    /// Key format: "exchange:symbol"
+    divergent: HashMap<String, Divergence>,
+}
+
+impl ConsistencyMonitor {
+    pub fn new(threshold_bps: f64, grace: Duration) -> Self {
+        Self {
+            threshold_bps,
+            grace,
+            divergent: HashMap::new(),
+        }
+    }
+
+    /// Compare all fresh books and return newly flagged anomalies
+    ///
+    /// An exchange is reported once per divergence episode; it must come back
+    /// within the threshold before it can be reported again.
+    pub fn check(
+        &mut self,
+        orderbook_manager: &OrderBookManager,
+        now: Instant,
+    ) -> Vec<ClientMessage> {
+        let max_age = Duration::from_secs(BOOK_STALE_AFTER_SECS);
+
+        // symbol -> [(exchange, mid)]
+        let mut mids: HashMap<String, Vec<(String, u64)>> = HashMap::new();
+        for entry in orderbook_manager.iter() {
+            let book = entry.value();
+            if !book.is_fresh(max_age) {
+                continue;
+            }
+            if let Some(mid) = book.mid() {
+                mids.entry(book.symbol().to_string())
+                    .or_default()
+                    .push((book.exchange().to_string(), mid));
+            }
+        }
+
+        let mut anomalies = Vec::new();
+        let mut still_divergent = HashMap::new();
+
+        for (symbol, exchange_mids) in mids {
+            if exchange_mids.len() < MIN_EXCHANGES_FOR_CONSENSUS {
+                continue;
+            }
+            let median = median(exchange_mids.iter().map(|(_, mid)| *mid).collect());
+            if median == 0.0 {
+                continue;
+            }
+
+            for (exchange, mid) in exchange_mids {
+                let deviation_bps = (mid as f64 - median) / median * 10_000.0;
+                if deviation_bps.abs() <= self.threshold_bps {
+                    continue;
+                }
+
+                let key = format!("{}:{}", exchange, symbol);
+                let mut divergence = self.divergent.get(&key).copied().unwrap_or(Divergence {
+                    since: now,
+                    reported: false,
+                });
+                if !divergence.reported && now.duration_since(divergence.since) >= self.grace {
+                    divergence.reported = true;
+                    tracing::warn!(
+                        "[{}] {} mid deviates {:.1} bps from the cross-exchange median",
+                        exchange,
+                        symbol,
+                        deviation_bps
+                    );
+                    anomalies.push(ClientMessage::PriceAnomaly {
+                        exchange,
+                        symbol: symbol.clone(),
+                        deviation_bps,
+                    });
+                }
+                still_divergent.insert(key, divergence);
+            }
+        }
+
+        // Anything not divergent this round (recovered or stale) starts over
+        self.divergent = still_divergent;
+        anomalies
+    }
+}
+
+/// Median of a non-empty list of scaled prices
+fn median(mut values: Vec<u64>) -> f64 {
+    values.sort_unstable();
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] as f64 + values[mid] as f64) / 2.0
+    } else {
+        values[mid] as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook::PRICE_FACTOR;
+
+    const ONE: u64 = PRICE_FACTOR;
+
+    fn set_mid(manager: &OrderBookManager, exchange: &str, mid: u64) {
+        manager
+            .get_or_create(exchange, "BTCUSDT")
+            .initialize_from_snapshot(
+                vec![(mid * ONE - ONE / 100, ONE)],
+                vec![(mid * ONE + ONE / 100, ONE)],
+                1,
+            );
+    }
+
+    #[test]
+    fn test_flags_divergent_exchange_after_grace_once() {
+        let manager = OrderBookManager::with_streams(&[], &[]);
+        set_mid(&manager, "Binance", 100_000);
+        set_mid(&manager, "Bybit", 100_010);
+        set_mid(&manager, "Coinbase", 99_995);
+        // 1% away from the others
+        set_mid(&manager, "Kraken", 101_000);
+
+        let mut monitor = ConsistencyMonitor::new(50.0, Duration::from_secs(5));
+        let start = Instant::now();
+
+        assert!(monitor.check(&manager, start).is_empty());
+
+        let anomalies = monitor.check(&manager, start + Duration::from_secs(5));
+        assert_eq!(anomalies.len(), 1);
+        match &anomalies[0] {
+            ClientMessage::PriceAnomaly {
+                exchange,
+                deviation_bps,
+                ..
+            } => {
+                assert_eq!(exchange, "Kraken");
+                assert!(*deviation_bps > 90.0);
+            }
+            other => panic!("expected PriceAnomaly, got {:?}", other),
+        }
+
+        // Same episode is not reported twice
+        assert!(monitor
+            .check(&manager, start + Duration::from_secs(10))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_needs_three_exchanges_for_consensus() {
+        let manager = OrderBookManager::with_streams(&[], &[]);
+        set_mid(&manager, "Binance", 100_000);
+        set_mid(&manager, "Kraken", 110_000);
+
+        let mut monitor = ConsistencyMonitor::new(50.0, Duration::ZERO);
+        assert!(monitor.check(&manager, Instant::now()).is_empty());
+    }
+}
@@ -1,8 +1,19 @@
-use crate::types::{ClientMessage, PriceLevel, ORDERBOOK_DEPTH, TRADING_PAIRS};
-use dashmap::DashMap;
+use crate::exchanges::symbols::{self, quote_asset};
+use crate::exchanges::Exchange;
+use crate::types::{
+    BookPricing, BookUpdate, ClientMessage, ConsolidatedBookUpdate, FillEstimate, PriceLevel,
+    StreamInfo, SymbolInfo, TradeSide, VenueLevel, BOOK_STALE_AFTER_SECS, ORDERBOOK_DEPTH,
+    ORDERBOOK_DISPLAY_DEPTH, TRADING_PAIRS,
+};
+use dashmap::{DashMap, DashSet};
+use rust_decimal::prelude::ToPrimitive;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Write as _;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 
 // Facteurs de précision pour conversion Decimal -> u64
 // 8 décimales de précision (suffisant pour crypto)
@@ -10,10 +21,103 @@ pub const PRICE_FACTOR: u64 = 100_000_000; // 10^8
 pub const QTY_FACTOR: u64 = 100_000_000; // 10^8
 
 /// Structure optimisée pour le cache CPU (16 bytes exactement)
+///
+/// With the `level-age` feature each level also records when it was last touched,
+/// which widens it beyond 16 bytes - keep it off unless the UI needs level ages.
 #[derive(Debug, Clone, Copy)]
 pub struct Level {
     pub price: u64, // Prix * PRICE_FACTOR
     pub qty: u64,   // Quantité * QTY_FACTOR
+    #[cfg(feature = "level-age")]
+    pub touched_at: Instant,
+}
+
+impl Level {
+    #[inline(always)]
+    pub fn new(price: u64, qty: u64) -> Self {
+        Self {
+            price,
+            qty,
+            #[cfg(feature = "level-age")]
+            touched_at: Instant::now(),
+        }
+    }
+
+    /// Update the quantity (and the last-touched time with `level-age`)
+    #[inline(always)]
+    fn set_qty(&mut self, qty: u64) {
+        self.qty = qty;
+        #[cfg(feature = "level-age")]
+        {
+            self.touched_at = Instant::now();
+        }
+    }
+}
+
+/// CRC-32 (IEEE) lookup table
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                0xEDB8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, &byte| {
+        CRC32_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+/// How an update moved the top of the book (either side)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TopOfBookChange {
+    Unchanged,
+    /// Best bid or best ask price moved
+    Price,
+    /// Best prices held but the size quoted at one of them changed (quote flicker)
+    SizeOnly,
+}
+
+/// Where a sequenced delta falls relative to the book (see `OrderBook::check_sequence`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateSequence {
+    /// Continues the book: apply it
+    Apply,
+    /// Already covered by the snapshot or an earlier delta: drop it
+    Stale,
+    /// Updates were missed: the book must be rebuilt from a snapshot
+    Gap,
+}
+
+/// (price, qty) of the best bid and best ask
+type TopOfBook = (Option<(u64, u64)>, Option<(u64, u64)>);
+
+/// Outcome of `OrderBook::apply_update`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BookChange {
+    /// Any level was inserted, removed or resized
+    pub changed: bool,
+    pub top_of_book: TopOfBookChange,
+}
+
+/// Side of the book to price against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Bid,
+    Ask,
 }
 
 #[derive(Debug)]
@@ -25,8 +129,20 @@ pub struct OrderBook {
     /// Asks: Trié ASC (Plus bas prix en premier) -> [101, 102, 103]
     asks: Vec<Level>,
     last_update_id: u64,
+    /// Bumped by every snapshot and every delta that changes a level (unlike
+    /// `last_update_id`, which no-op deltas advance too)
+    content_version: u64,
     initialized: bool,
+    /// A delta has been applied on top of the last snapshot
+    bridged: bool,
     max_depth: usize,
+    /// Levels below this quantity (scaled) are dropped as dust (0 = keep all)
+    min_qty: u64,
+    /// Instant of the last snapshot or delta applied (None until the first
+    /// one, and for a book restored from disk until its first live update)
+    last_update_at: Option<Instant>,
+    /// Last traded price, fed from the trade stream (survives snapshots)
+    last_trade_price: Option<u64>,
 }
 
 impl OrderBook {
@@ -39,8 +155,40 @@ impl OrderBook {
             bids: Vec::with_capacity(capacity),
             asks: Vec::with_capacity(capacity),
             last_update_id: 0,
+            content_version: 0,
             initialized: false,
+            bridged: false,
             max_depth: ORDERBOOK_DEPTH,
+            min_qty: 0,
+            last_update_at: None,
+            last_trade_price: None,
+        }
+    }
+
+    /// Store up to `max_depth` levels per side (display depth is chosen per send)
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        let capacity = max_depth + 10;
+        self.bids.reserve(capacity.saturating_sub(self.bids.len()));
+        self.asks.reserve(capacity.saturating_sub(self.asks.len()));
+        self
+    }
+
+    /// Ignore levels under `min_qty` (scaled by `QTY_FACTOR`)
+    ///
+    /// A level shrinking under it is removed as if deleted. 0 keeps every level.
+    pub fn with_min_qty(mut self, min_qty: u64) -> Self {
+        self.min_qty = min_qty;
+        self
+    }
+
+    /// Quantity to store for an incoming level: 0 (absent) when it is dust
+    #[inline(always)]
+    fn filter_dust(&self, qty: u64) -> u64 {
+        if qty < self.min_qty {
+            0
+        } else {
+            qty
         }
     }
 
@@ -56,24 +204,41 @@ impl OrderBook {
         Decimal::from(qty) / Decimal::from(QTY_FACTOR)
     }
 
+    /// Replace the book with a snapshot and mark it initialized
+    ///
+    /// A snapshot without a level on either side is rejected (returns false)
+    /// and leaves the book untouched: an empty or malformed response must not
+    /// pass for a live book.
     pub fn initialize_from_snapshot(
         &mut self,
         bids: Vec<(u64, u64)>,
         asks: Vec<(u64, u64)>,
         last_update_id: u64,
-    ) {
+    ) -> bool {
+        let has_levels = |levels: &[(u64, u64)]| levels.iter().any(|&(_, qty)| qty > 0);
+        if !has_levels(&bids) || !has_levels(&asks) {
+            tracing::warn!(
+                "[{}] Rejected snapshot for {} with {} bid(s) and {} ask(s)",
+                self.exchange,
+                self.symbol,
+                bids.len(),
+                asks.len()
+            );
+            return false;
+        }
+
         self.bids.clear();
         self.asks.clear();
 
         // Remplissage optimisé - données déjà en u64
         for (price, qty) in bids {
-            if qty > 0 {
-                self.bids.push(Level { price, qty });
+            if self.filter_dust(qty) > 0 {
+                self.bids.push(Level::new(price, qty));
             }
         }
         for (price, qty) in asks {
-            if qty > 0 {
-                self.asks.push(Level { price, qty });
+            if self.filter_dust(qty) > 0 {
+                self.asks.push(Level::new(price, qty));
             }
         }
 
@@ -87,7 +252,42 @@ impl OrderBook {
         self.truncate_books();
 
         self.last_update_id = last_update_id;
+        self.content_version += 1;
         self.initialized = true;
+        self.bridged = false;
+        self.last_update_at = Some(Instant::now());
+        true
+    }
+
+    /// Check a delta's ids against the last one applied (Binance's rules)
+    ///
+    /// The first delta after a snapshot must straddle the snapshot's id
+    /// (`first <= last + 1 <= final`). Later ones must chain on: `prev`, the
+    /// previous delta's final id when the exchange sends it, must equal ours,
+    /// otherwise `first` must be exactly `last + 1`.
+    pub fn check_sequence(
+        &self,
+        first_update_id: u64,
+        prev_update_id: Option<u64>,
+        final_update_id: u64,
+    ) -> UpdateSequence {
+        let last = self.last_update_id;
+        if final_update_id <= last {
+            return UpdateSequence::Stale;
+        }
+        let continues = if !self.bridged {
+            first_update_id <= last + 1
+        } else {
+            match prev_update_id {
+                Some(prev) => prev == last,
+                None => first_update_id == last + 1,
+            }
+        };
+        if continues {
+            UpdateSequence::Apply
+        } else {
+            UpdateSequence::Gap
+        }
     }
 
     /// Application optimisée des updates WebSocket
@@ -97,11 +297,13 @@ impl OrderBook {
         asks: Vec<(u64, u64)>,
         _first_update_id: u64,
         final_update_id: u64,
-    ) -> bool {
+    ) -> BookChange {
         let mut changed = false;
+        let top_before = self.top_of_book();
 
         // --- GESTION DES BIDS (Tri DESC) ---
         for (p_int, q_int) in bids {
+            let q_int = self.filter_dust(q_int);
             // Bids sont triés DESC, donc on inverse la comparaison pour binary_search
             // On cherche où 'p_int' se trouve par rapport aux éléments existants
             let idx_res = self
@@ -117,7 +319,7 @@ impl OrderBook {
                     } else {
                         // Update quantité
                         if self.bids[idx].qty != q_int {
-                            self.bids[idx].qty = q_int;
+                            self.bids[idx].set_qty(q_int);
                             changed = true;
                         }
                     }
@@ -127,13 +329,7 @@ impl OrderBook {
                     if q_int > 0 {
                         // Optimisation: ne pas insérer si c'est au-delà de la profondeur max
                         if idx < self.max_depth {
-                            self.bids.insert(
-                                idx,
-                                Level {
-                                    price: p_int,
-                                    qty: q_int,
-                                },
-                            );
+                            self.bids.insert(idx, Level::new(p_int, q_int));
                             changed = true;
                             // Si on dépasse, on retire le dernier (le moins bon bid)
                             if self.bids.len() > self.max_depth {
@@ -147,6 +343,7 @@ impl OrderBook {
 
         // --- GESTION DES ASKS (Tri ASC) ---
         for (p_int, q_int) in asks {
+            let q_int = self.filter_dust(q_int);
             // Asks sont triés ASC, comparaison standard
             let idx_res = self.asks.binary_search_by(|level| level.price.cmp(&p_int));
 
@@ -157,7 +354,7 @@ impl OrderBook {
                         changed = true;
                     } else {
                         if self.asks[idx].qty != q_int {
-                            self.asks[idx].qty = q_int;
+                            self.asks[idx].set_qty(q_int);
                             changed = true;
                         }
                     }
@@ -165,13 +362,7 @@ impl OrderBook {
                 Err(idx) => {
                     if q_int > 0 {
                         if idx < self.max_depth {
-                            self.asks.insert(
-                                idx,
-                                Level {
-                                    price: p_int,
-                                    qty: q_int,
-                                },
-                            );
+                            self.asks.insert(idx, Level::new(p_int, q_int));
                             changed = true;
                             if self.asks.len() > self.max_depth {
                                 self.asks.pop();
@@ -183,7 +374,35 @@ impl OrderBook {
         }
 
         self.last_update_id = final_update_id;
-        changed
+        if changed {
+            self.content_version += 1;
+        }
+        self.bridged = true;
+        self.last_update_at = Some(Instant::now());
+
+        let top_after = self.top_of_book();
+        let top_of_book = if top_before == top_after {
+            TopOfBookChange::Unchanged
+        } else if (top_before.0.map(|l| l.0), top_before.1.map(|l| l.0))
+            != (top_after.0.map(|l| l.0), top_after.1.map(|l| l.0))
+        {
+            TopOfBookChange::Price
+        } else {
+            TopOfBookChange::SizeOnly
+        };
+
+        BookChange {
+            changed,
+            top_of_book,
+        }
+    }
+
+    #[inline(always)]
+    fn top_of_book(&self) -> TopOfBook {
+        (
+            self.bids.first().map(|l| (l.price, l.qty)),
+            self.asks.first().map(|l| (l.price, l.qty)),
+        )
     }
 
     /// Garde la taille fixe (redondance de sécurité)
@@ -196,6 +415,24 @@ impl OrderBook {
         }
     }
 
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+
+    pub fn exchange(&self) -> &str {
+        &self.exchange
+    }
+
+    /// Mid price scaled by PRICE_FACTOR (None for one-sided or crossed books)
+    pub fn mid(&self) -> Option<u64> {
+        match (self.bids.first(), self.asks.first()) {
+            (Some(bid), Some(ask)) if ask.price >= bid.price => {
+                Some(bid.price / 2 + ask.price / 2 + (bid.price % 2 + ask.price % 2) / 2)
+            }
+            _ => None,
+        }
+    }
+
     pub fn best_bid(&self) -> Option<Decimal> {
         self.bids.first().map(|l| Self::to_external_price(l.price))
     }
@@ -207,33 +444,30 @@ impl OrderBook {
     // ... Le reste (spread, to_client_message) doit juste être adapté pour convertir
     // les u64/f64 internes en Decimal/PriceLevel externes.
 
-    pub fn get_top_levels(&self, n: usize) -> (Vec<PriceLevel>, Vec<PriceLevel>) {
-        let bids: Vec<PriceLevel> = self
-            .bids
-            .iter()
-            .take(n)
-            .map(|l| PriceLevel {
-                price: Self::to_external_price(l.price),
-                quantity: Self::to_external_qty(l.qty),
-            })
-            .collect();
+    /// Helper pour convertir un Level interne -> PriceLevel externe
+    #[inline(always)]
+    fn to_price_level(level: &Level) -> PriceLevel {
+        PriceLevel {
+            price: Self::to_external_price(level.price),
+            quantity: Self::to_external_qty(level.qty),
+            #[cfg(feature = "level-age")]
+            age_ms: level.touched_at.elapsed().as_millis() as u64,
+        }
+    }
 
-        let asks: Vec<PriceLevel> = self
-            .asks
-            .iter()
-            .take(n)
-            .map(|l| PriceLevel {
-                price: Self::to_external_price(l.price),
-                quantity: Self::to_external_qty(l.qty),
-            })
-            .collect();
+    pub fn get_top_levels(&self, n: usize) -> (Vec<PriceLevel>, Vec<PriceLevel>) {
+        let bids: Vec<PriceLevel> = self.bids.iter().take(n).map(Self::to_price_level).collect();
+        let asks: Vec<PriceLevel> = self.asks.iter().take(n).map(Self::to_price_level).collect();
 
         (bids, asks)
     }
 
+    /// Absolute spread and spread as a percent of mid price
+    ///
+    /// None for one-sided or crossed books (best ask below best bid).
     pub fn spread(&self) -> Option<(Decimal, Decimal)> {
         match (self.best_bid(), self.best_ask()) {
-            (Some(bid), Some(ask)) => {
+            (Some(bid), Some(ask)) if ask >= bid => {
                 let spread = ask - bid;
                 let mid_price = (bid + ask) / dec!(2);
                 let spread_percent = if mid_price > dec!(0) {
@@ -247,27 +481,329 @@ impl OrderBook {
         }
     }
 
-    pub fn to_client_message(&self, levels: usize) -> ClientMessage {
+    /// Average price of filling `quantity` against one side (None if the side is too thin)
+    ///
+    /// Sums price × size over the levels walked in u128 and converts once at the end.
+    pub fn vwap(&self, side: Side, quantity: Decimal) -> Option<Decimal> {
+        let target = (quantity * Decimal::from(QTY_FACTOR)).trunc().to_u64()?;
+        if target == 0 {
+            return None;
+        }
+        let levels = match side {
+            Side::Bid => &self.bids,
+            Side::Ask => &self.asks,
+        };
+
+        let mut remaining = target;
+        let mut notional: u128 = 0;
+        for level in levels {
+            let take = remaining.min(level.qty);
+            notional += level.price as u128 * take as u128;
+            remaining -= take;
+            if remaining == 0 {
+                return Some(Self::to_external_price(Self::div_round(
+                    notional,
+                    target as u128,
+                )));
+            }
+        }
+        None
+    }
+
+    /// Walk one side with a market order of `quantity` (scaled by 1e8)
+    ///
+    /// Side::Ask buys from the asks, Side::Bid sells into the bids. A side too
+    /// thin for the whole size gives the partial fill, flagged
+    /// `insufficient_liquidity`. None for a zero size or an empty side.
+    pub fn market_order_impact(&self, side: Side, quantity: u64) -> Option<FillEstimate> {
+        let levels = match side {
+            Side::Bid => &self.bids,
+            Side::Ask => &self.asks,
+        };
+        let best = levels.first()?.price;
+        if quantity == 0 {
+            return None;
+        }
+
+        let mut remaining = quantity;
+        let mut notional: u128 = 0;
+        let mut worst = best;
+        for level in levels {
+            let take = remaining.min(level.qty);
+            notional += level.price as u128 * take as u128;
+            remaining -= take;
+            worst = level.price;
+            if remaining == 0 {
+                break;
+            }
+        }
+
+        let filled = quantity - remaining;
+        let average = Self::div_round(notional, filled as u128);
+        let slippage = average.abs_diff(best);
+        Some(FillEstimate {
+            exchange: self.exchange.clone(),
+            symbol: self.symbol.clone(),
+            side: match side {
+                Side::Bid => TradeSide::Sell,
+                Side::Ask => TradeSide::Buy,
+            },
+            quantity: Self::to_external_qty(quantity),
+            filled_quantity: Self::to_external_qty(filled),
+            average_price: Self::to_external_price(average),
+            worst_price: Self::to_external_price(worst),
+            best_price: Self::to_external_price(best),
+            slippage: Self::to_external_price(slippage),
+            slippage_bps: slippage as f64 / best as f64 * 10_000.0,
+            insufficient_liquidity: remaining > 0,
+        })
+    }
+
+    /// Mid of the top `depth` levels, each side's average price weighted by the
+    /// other side's size (None for a one-sided book)
+    ///
+    /// With depth 1 this is the micro-price: it leans toward the side with less
+    /// size, where the price is more likely to move next.
+    pub fn weighted_mid(&self, depth: usize) -> Option<Decimal> {
+        let side_totals = |levels: &[Level]| -> (u128, u128) {
+            levels
+                .iter()
+                .take(depth)
+                .fold((0, 0), |(notional, qty), l| {
+                    (
+                        notional + l.price as u128 * l.qty as u128,
+                        qty + l.qty as u128,
+                    )
+                })
+        };
+        let (bid_notional, bid_qty) = side_totals(&self.bids);
+        let (ask_notional, ask_qty) = side_totals(&self.asks);
+        if bid_qty == 0 || ask_qty == 0 {
+            return None;
+        }
+
+        let bid_px = Self::div_round(bid_notional, bid_qty) as u128;
+        let ask_px = Self::div_round(ask_notional, ask_qty) as u128;
+        let weighted = Self::div_round(bid_px * ask_qty + ask_px * bid_qty, bid_qty + ask_qty);
+        Some(Self::to_external_price(weighted))
+    }
+
+    /// Size imbalance of the top `depth` levels: (bid - ask) / (bid + ask)
+    ///
+    /// From -1 (asks only) to 1 (bids only), rounded to 8 decimals; None for
+    /// an empty book.
+    pub fn imbalance(&self, depth: usize) -> Option<Decimal> {
+        let volume =
+            |levels: &[Level]| -> i128 { levels.iter().take(depth).map(|l| l.qty as i128).sum() };
+        let (bid, ask) = (volume(&self.bids), volume(&self.asks));
+        if bid + ask == 0 {
+            return None;
+        }
+        let imbalance = Decimal::from_i128_with_scale(bid - ask, 0)
+            / Decimal::from_i128_with_scale(bid + ask, 0);
+        Some(imbalance.round_dp(8))
+    }
+
+    /// `numerator / denominator` rounded to nearest, as a scaled price
+    #[inline]
+    fn div_round(numerator: u128, denominator: u128) -> u64 {
+        ((numerator + denominator / 2) / denominator).min(u64::MAX as u128) as u64
+    }
+
+    /// VWAPs for `quantity` on both sides and the weighted mid of the top `depth` levels
+    pub fn pricing(&self, depth: usize, quantity: Decimal) -> BookPricing {
+        BookPricing {
+            quantity,
+            bid_vwap: self.vwap(Side::Bid, quantity),
+            ask_vwap: self.vwap(Side::Ask, quantity),
+            weighted_mid: self.weighted_mid(depth),
+        }
+    }
+
+    pub fn to_book_update(&self, levels: usize) -> BookUpdate {
         let (bids, asks) = self.get_top_levels(levels);
         let (spread, spread_percent) = self.spread().unwrap_or((dec!(0), dec!(0)));
+        let spread_ticks =
+            symbols::tick_size(&self.symbol).map(|tick| spread / Self::to_external_price(tick));
 
-        ClientMessage::BookUpdate {
+        BookUpdate {
             exchange: self.exchange.clone(),
             symbol: self.symbol.clone(),
             bids,
             asks,
             spread,
             spread_percent,
+            spread_bps: spread_percent * dec!(100),
+            spread_ticks,
+            last_trade_price: self.last_trade_price(),
+            update_id: self.last_update_id,
+            imbalance: self.imbalance(levels),
+            pricing: None,
+            is_stale: self.is_stale(),
         }
     }
 
+    pub fn to_client_message(&self, levels: usize) -> ClientMessage {
+        ClientMessage::BookUpdate(self.to_book_update(levels))
+    }
+
+    /// Largest increment dividing every stored price (None for an empty book)
+    ///
+    /// Converges to the exchange tick size as the book fills up.
+    pub fn tick_size(&self) -> Option<u64> {
+        fn gcd(a: u64, b: u64) -> u64 {
+            if b == 0 {
+                a
+            } else {
+                gcd(b, a % b)
+            }
+        }
+        let tick = self
+            .bids
+            .iter()
+            .chain(&self.asks)
+            .fold(0, |tick, level| gcd(level.price, tick));
+        (tick > 0).then_some(tick)
+    }
+
+    /// CRC32 of the top `levels` asks then bids, Kraken's book checksum
+    ///
+    /// Each level adds its price then its quantity, printed at the given
+    /// decimals without the point or leading zeros. None when the book
+    /// doesn't store that many levels, as the result couldn't match.
+    pub fn checksum(&self, levels: usize, price_decimals: u32, qty_decimals: u32) -> Option<u32> {
+        if self.max_depth < levels {
+            return None;
+        }
+        let price_div = 10u64.pow(8 - price_decimals.min(8));
+        let qty_div = 10u64.pow(8 - qty_decimals.min(8));
+        let mut input = String::with_capacity(levels * 2 * 24);
+        for level in self
+            .asks
+            .iter()
+            .take(levels)
+            .chain(self.bids.iter().take(levels))
+        {
+            let _ = write!(input, "{}{}", level.price / price_div, level.qty / qty_div);
+        }
+        Some(crc32(input.as_bytes()))
+    }
+
+    /// Stop serving the book until the next snapshot (it no longer matches the exchange)
+    pub fn mark_desynced(&mut self) {
+        self.initialized = false;
+    }
+
+    /// Serve the levels but report the book stale until a live update lands
+    ///
+    /// For books loaded from disk: they are minutes old and may be far off.
+    pub fn mark_restored(&mut self) {
+        self.last_update_at = None;
+    }
+
     pub fn is_initialized(&self) -> bool {
         self.initialized
     }
 
+    /// Stored bids and asks, best first
+    pub fn levels(&self) -> (&[Level], &[Level]) {
+        (&self.bids, &self.asks)
+    }
+
     pub fn last_update_id(&self) -> u64 {
         self.last_update_id
     }
+
+    /// Changes whenever the levels do: compare two readings to skip resending a book
+    pub fn content_version(&self) -> u64 {
+        self.content_version
+    }
+
+    /// Record the price of a trade on this exchange:symbol
+    #[inline]
+    pub fn record_trade_price(&mut self, price: u64) {
+        self.last_trade_price = Some(price);
+    }
+
+    pub fn last_trade_price(&self) -> Option<Decimal> {
+        self.last_trade_price.map(Self::to_external_price)
+    }
+
+    /// Render the top `n` levels as a price ladder (debugging aid)
+    ///
+    /// Asks are printed above bids, both from highest to lowest price, so the
+    /// best ask and best bid meet around the spread line in the middle.
+    pub fn pretty(&self, n: usize) -> String {
+        let (bids, asks) = self.get_top_levels(n);
+        let mut out = String::new();
+
+        let _ = writeln!(
+            out,
+            "{}:{} (last_update_id {})",
+            self.exchange, self.symbol, self.last_update_id
+        );
+        let _ = writeln!(out, "     {:>20} {:>20}", "price", "qty");
+        for level in asks.iter().rev() {
+            let _ = writeln!(
+                out,
+                "ASK  {:>20} {:>20}",
+                level.price.to_string(),
+                level.quantity.to_string()
+            );
+        }
+        match self.spread() {
+            Some((spread, spread_percent)) => {
+                let _ = writeln!(
+                    out,
+                    "---- spread {} ({}%) ----",
+                    spread,
+                    spread_percent.round_dp(4)
+                );
+            }
+            None => {
+                let _ = writeln!(out, "---- spread n/a ----");
+            }
+        }
+        for level in &bids {
+            let _ = writeln!(
+                out,
+                "BID  {:>20} {:>20}",
+                level.price.to_string(),
+                level.quantity.to_string()
+            );
+        }
+
+        out
+    }
+
+    /// Number of stored (bid, ask) levels
+    pub fn level_counts(&self) -> (usize, usize) {
+        (self.bids.len(), self.asks.len())
+    }
+
+    /// Time since the last snapshot or delta (None until the first one)
+    pub fn last_update_age(&self) -> Option<Duration> {
+        self.last_update_at.map(|at| at.elapsed())
+    }
+
+    /// Initialized and updated within `max_age`
+    pub fn is_fresh(&self, max_age: Duration) -> bool {
+        self.initialized
+            && self
+                .last_update_at
+                .is_some_and(|at| at.elapsed() <= max_age)
+    }
+
+    /// Not fresh within `BOOK_STALE_AFTER_SECS` (uninitialized books included)
+    pub fn is_stale(&self) -> bool {
+        !self.is_fresh(Duration::from_secs(BOOK_STALE_AFTER_SECS))
+    }
+}
+
+impl fmt::Display for OrderBook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.pretty(ORDERBOOK_DISPLAY_DEPTH))
+    }
 }
 
 // OrderBookManager reste identique car il utilise juste OrderBook comme une boîte noire.
@@ -277,6 +813,20 @@ impl OrderBook {
 pub struct OrderBookManager {
     /// Key format: "exchange:symbol" (e.g., "Binance:BTCUSDT")
     books: DashMap<String, OrderBook>,
+    /// Enabled exchanges, used to expand runtime symbol changes into book keys
+    exchanges: Vec<String>,
+    /// Keys of every enabled exchange × symbol, used for coverage reporting
+    expected_keys: RwLock<Vec<String>>,
+    /// Levels stored per side in each book (storage, not display)
+    max_depth: usize,
+    /// Per-exchange cap on `max_depth`, for feeds streaming fewer levels
+    exchange_max_depth: HashMap<String, usize>,
+    /// Dust threshold per exchange (scaled qty), for books created from now on
+    min_qty: HashMap<String, u64>,
+    /// Quote assets updates are accepted for (None: any)
+    allowed_quotes: Option<Vec<String>>,
+    /// Symbols refused so far, to log each one once
+    refused_symbols: DashSet<String>,
 }
 
 impl OrderBookManager {
@@ -292,11 +842,154 @@ impl OrderBookManager {
         key
     }
 
-    pub fn with_symbols(_symbols: &[&str]) -> Self {
+    pub fn with_symbols(symbols: &[&str]) -> Self {
+        Self::with_streams(&[], symbols)
+    }
+
+    /// Create a manager expecting one book per enabled exchange × symbol
+    pub fn with_streams(exchanges: &[&str], symbols: &[&str]) -> Self {
+        let expected_keys = exchanges
+            .iter()
+            .flat_map(|exchange| {
+                symbols
+                    .iter()
+                    .map(|symbol| Self::book_key(exchange, symbol))
+            })
+            .collect();
+
         // Start with empty books - they'll be created on-demand per exchange
         Self {
             books: DashMap::new(),
+            exchanges: exchanges.iter().map(|e| e.to_string()).collect(),
+            expected_keys: RwLock::new(expected_keys),
+            max_depth: ORDERBOOK_DEPTH,
+            exchange_max_depth: HashMap::new(),
+            min_qty: HashMap::new(),
+            allowed_quotes: None,
+            refused_symbols: DashSet::new(),
+        }
+    }
+
+    /// Store up to `max_depth` levels per side in every book created from now on
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Store at most `max_depth` levels per side in `exchange`'s books
+    ///
+    /// For feeds subscribed at a fixed depth: levels past it never get
+    /// updated, and would go stale. Applies to books created from now on.
+    pub fn with_exchange_max_depth(mut self, exchange: &str, max_depth: usize) -> Self {
+        self.exchange_max_depth
+            .insert(exchange.to_string(), max_depth);
+        self
+    }
+
+    /// Drop `exchange`'s levels under `min_qty` (see `OrderBook::with_min_qty`)
+    ///
+    /// Per exchange since lot sizes differ. Applies to books created from now on.
+    /// Refused for exchanges whose checksum covers every level: a book missing
+    /// its dust would never match it, reconnecting forever.
+    pub fn with_min_qty(mut self, exchange: &str, min_qty: u64) -> Self {
+        if Exchange::from_name(exchange).is_some_and(|venue| venue.checksums_books()) {
+            tracing::warn!(
+                "[{}] Keeping dust levels: the book checksum covers them",
+                exchange
+            );
+            return self;
+        }
+        tracing::info!("[{}] Ignoring levels under {}", exchange, min_qty);
+        self.min_qty.insert(exchange.to_string(), min_qty);
+        self
+    }
+
+    /// Only accept updates for symbols quoted in one of `quotes` ("USDT", "USDC"...)
+    ///
+    /// Keeps an exchange pushing unexpected instruments from growing the book
+    /// map. Symbols with no recognized quote asset are refused too.
+    pub fn with_allowed_quotes(mut self, quotes: Vec<String>) -> Self {
+        self.allowed_quotes = Some(quotes);
+        self
+    }
+
+    /// Whether updates for `symbol` should reach a book; logs a refused symbol once
+    pub fn accepts_symbol(&self, exchange: &str, symbol: &str) -> bool {
+        let Some(allowed) = &self.allowed_quotes else {
+            return true;
+        };
+        let accepted = quote_asset(symbol).is_some_and(|quote| allowed.iter().any(|q| q == quote));
+        if !accepted && self.refused_symbols.insert(symbol.to_string()) {
+            tracing::debug!(
+                "[{}] Dropping {} updates: quote not in {:?}",
+                exchange,
+                symbol,
+                allowed
+            );
         }
+        accepted
+    }
+
+    fn new_book(&self, exchange: &str, symbol: &str) -> OrderBook {
+        let max_depth = self
+            .exchange_max_depth
+            .get(exchange)
+            .map_or(self.max_depth, |&cap| cap.min(self.max_depth));
+        OrderBook::new(symbol, exchange)
+            .with_max_depth(max_depth)
+            .with_min_qty(self.min_qty.get(exchange).copied().unwrap_or(0))
+    }
+
+    /// Levels stored per side in each book
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// Whether a book is expected for this exchange × symbol
+    pub fn expects(&self, exchange: &str, symbol: &str) -> bool {
+        let key = Self::book_key(exchange, symbol);
+        self.expected_keys.read().unwrap().contains(&key)
+    }
+
+    /// Create empty books for a new symbol on every enabled exchange and expect them
+    pub fn add_symbol(&self, symbol: &str) {
+        let mut expected_keys = self.expected_keys.write().unwrap();
+        for exchange in &self.exchanges {
+            let key = Self::book_key(exchange, symbol);
+            if !expected_keys.contains(&key) {
+                expected_keys.push(key.clone());
+            }
+            self.books
+                .entry(key)
+                .or_insert_with(|| self.new_book(exchange, symbol));
+        }
+    }
+
+    /// Stop serving `exchange`'s books of `symbols` until their next snapshot
+    ///
+    /// For feeds sequenced across the whole connection (Coinbase), where a
+    /// gap can't be pinned on one book. Returns how many were initialized.
+    pub fn mark_desynced(&self, exchange: &str, symbols: &[String]) -> usize {
+        let mut desynced = 0;
+        for symbol in symbols {
+            if let Some(mut book) = self.books.get_mut(&Self::book_key(exchange, symbol)) {
+                if book.is_initialized() {
+                    book.mark_desynced();
+                    desynced += 1;
+                }
+            }
+        }
+        desynced
+    }
+
+    /// Drop every book of a symbol and stop expecting it
+    pub fn remove_symbol(&self, symbol: &str) {
+        let suffix = Self::book_key("", symbol);
+        self.books.retain(|key, _| !key.ends_with(&suffix));
+        self.expected_keys
+            .write()
+            .unwrap()
+            .retain(|key| !key.ends_with(&suffix));
     }
 
     /// Get or create an order book for the given exchange and symbol
@@ -308,7 +1001,7 @@ impl OrderBookManager {
         let key = Self::book_key(exchange, symbol);
         self.books
             .entry(key)
-            .or_insert_with(|| OrderBook::new(symbol, exchange))
+            .or_insert_with(|| self.new_book(exchange, symbol))
     }
 
     pub fn get(
@@ -325,6 +1018,140 @@ impl OrderBookManager {
     ) -> dashmap::iter::Iter<'_, String, OrderBook, std::collections::hash_map::RandomState> {
         self.books.iter()
     }
+
+    /// Metadata of a symbol: finest tick seen across its initialized books
+    pub fn symbol_info(&self, symbol: &str) -> SymbolInfo {
+        let tick_size = self
+            .exchanges
+            .iter()
+            .filter_map(|exchange| {
+                let book = self.get(exchange, symbol)?;
+                book.is_initialized().then(|| book.tick_size()).flatten()
+            })
+            .min()
+            .map(OrderBook::to_external_price);
+
+        SymbolInfo {
+            symbol: symbol.to_string(),
+            exchanges: self.exchanges.clone(),
+            tick_size,
+            quote: quote_asset(symbol).map(str::to_string),
+        }
+    }
+
+    /// Status of every book held, sorted by exchange then symbol
+    pub fn stream_list(&self) -> Vec<StreamInfo> {
+        let mut streams: Vec<StreamInfo> = self
+            .books
+            .iter()
+            .map(|entry| {
+                let book = entry.value();
+                StreamInfo {
+                    exchange: book.exchange().to_string(),
+                    symbol: book.symbol().to_string(),
+                    initialized: book.is_initialized(),
+                    is_stale: book.is_stale(),
+                }
+            })
+            .collect();
+        streams.sort_by(|a, b| (&a.exchange, &a.symbol).cmp(&(&b.exchange, &b.symbol)));
+        streams
+    }
+
+    /// Top `levels` per side of a symbol merged across its initialized books
+    ///
+    /// Exchanges whose book isn't initialized yet are left out; None until at
+    /// least one is. Equal prices keep the exchange order of the manager.
+    pub fn consolidated_book(&self, symbol: &str, levels: usize) -> Option<ConsolidatedBookUpdate> {
+        // Copy levels out so no book stays locked while merging
+        let books: Vec<_> = self
+            .exchanges
+            .iter()
+            .filter_map(|exchange| {
+                let book = self.get(exchange, symbol)?;
+                book.is_initialized().then(|| {
+                    let top = |side: &[Level]| -> Vec<(u64, u64)> {
+                        side.iter().take(levels).map(|l| (l.price, l.qty)).collect()
+                    };
+                    (exchange.as_str(), top(&book.bids), top(&book.asks))
+                })
+            })
+            .collect();
+        if books.is_empty() {
+            return None;
+        }
+
+        let mut exchanges = Vec::with_capacity(books.len());
+        let mut bids: Vec<(u64, u64, &str)> = Vec::new();
+        let mut asks: Vec<(u64, u64, &str)> = Vec::new();
+        for (exchange, book_bids, book_asks) in &books {
+            exchanges.push(exchange.to_string());
+            bids.extend(
+                book_bids
+                    .iter()
+                    .map(|&(price, qty)| (price, qty, *exchange)),
+            );
+            asks.extend(
+                book_asks
+                    .iter()
+                    .map(|&(price, qty)| (price, qty, *exchange)),
+            );
+        }
+
+        // Stable sorts: ties stay in exchange order
+        bids.sort_by_key(|&(price, _, _)| std::cmp::Reverse(price));
+        asks.sort_by_key(|&(price, _, _)| price);
+        let to_levels = |side: Vec<(u64, u64, &str)>| -> Vec<VenueLevel> {
+            side.into_iter()
+                .take(levels)
+                .map(|(price, qty, exchange)| VenueLevel {
+                    exchange: exchange.to_string(),
+                    price: OrderBook::to_external_price(price),
+                    quantity: OrderBook::to_external_qty(qty),
+                })
+                .collect()
+        };
+        let bids = to_levels(bids);
+        let asks = to_levels(asks);
+        let best_bid = bids.first().cloned();
+        let best_ask = asks.first().cloned();
+        let spread = best_bid
+            .as_ref()
+            .zip(best_ask.as_ref())
+            .map(|(bid, ask)| ask.price - bid.price);
+
+        Some(ConsolidatedBookUpdate {
+            symbol: symbol.to_string(),
+            bids,
+            asks,
+            best_bid,
+            best_ask,
+            spread,
+            exchanges,
+        })
+    }
+
+    /// Fraction of expected books that are initialized and fresh (0.0 - 1.0)
+    ///
+    /// Returns 0.0 when no exchange × symbol pair is expected.
+    pub fn coverage(&self) -> f64 {
+        let expected_keys = self.expected_keys.read().unwrap();
+        if expected_keys.is_empty() {
+            return 0.0;
+        }
+
+        let max_age = Duration::from_secs(BOOK_STALE_AFTER_SECS);
+        let covered = expected_keys
+            .iter()
+            .filter(|key| {
+                self.books
+                    .get(key.as_str())
+                    .is_some_and(|book| book.is_fresh(max_age))
+            })
+            .count();
+
+        covered as f64 / expected_keys.len() as f64
+    }
 }
 
 impl Default for OrderBookManager {
@@ -336,6 +1163,532 @@ impl Default for OrderBookManager {
 /// Shared multi-symbol order book manager
 pub type SharedOrderBookManager = Arc<OrderBookManager>;
 
-pub fn create_shared_orderbook_manager() -> SharedOrderBookManager {
-    Arc::new(OrderBookManager::default())
+// The server builds its own to set per-exchange dust thresholds
+#[cfg_attr(not(test), allow(dead_code))]
+pub fn create_shared_orderbook_manager(
+    exchanges: &[&str],
+    symbols: &[&str],
+    max_depth: usize,
+) -> SharedOrderBookManager {
+    Arc::new(OrderBookManager::with_streams(exchanges, symbols).with_max_depth(max_depth))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ONE: u64 = PRICE_FACTOR;
+
+    #[test]
+    fn test_apply_update_reports_top_of_book_change() {
+        let mut book = OrderBook::new("BTCUSDT", "Binance");
+        book.initialize_from_snapshot(
+            vec![(100 * ONE, ONE), (99 * ONE, ONE)],
+            vec![(101 * ONE, ONE), (102 * ONE, ONE)],
+            1,
+        );
+
+        // Deeper level only
+        let change = book.apply_update(vec![(99 * ONE, 2 * ONE)], vec![], 0, 2);
+        assert!(change.changed);
+        assert_eq!(change.top_of_book, TopOfBookChange::Unchanged);
+
+        // Same best ask, new size
+        let change = book.apply_update(vec![], vec![(101 * ONE, 3 * ONE)], 0, 3);
+        assert_eq!(change.top_of_book, TopOfBookChange::SizeOnly);
+
+        // Best bid pulled
+        let change = book.apply_update(vec![(100 * ONE, 0)], vec![], 0, 4);
+        assert_eq!(change.top_of_book, TopOfBookChange::Price);
+
+        // Repeat of the current state
+        let change = book.apply_update(vec![(99 * ONE, 2 * ONE)], vec![], 0, 5);
+        assert!(!change.changed);
+        assert_eq!(change.top_of_book, TopOfBookChange::Unchanged);
+    }
+
+    #[test]
+    fn test_book_keeps_configured_depth() {
+        let mut book = OrderBook::new("BTCUSDT", "Binance").with_max_depth(50);
+        book.initialize_from_snapshot(
+            (1..=80).map(|i| ((1_000 - i) * ONE, ONE)).collect(),
+            (1..=80).map(|i| ((1_000 + i) * ONE, ONE)).collect(),
+            1,
+        );
+        assert_eq!((book.bids.len(), book.asks.len()), (50, 50));
+
+        // A new best level pushes the 50th out instead of growing the side
+        book.apply_update(vec![], vec![(1_000 * ONE, ONE)], 0, 2);
+        assert_eq!(book.asks.len(), 50);
+        assert_eq!(book.asks.last().unwrap().price, 1_049 * ONE);
+    }
+
+    #[test]
+    fn test_dust_levels_filtered() {
+        let manager = OrderBookManager::with_streams(&[], &[]).with_min_qty("Binance", ONE / 100);
+        let mut book = manager.get_or_create("Binance", "BTCUSDT");
+        book.initialize_from_snapshot(
+            vec![(100 * ONE, ONE), (99 * ONE, ONE / 1_000)],
+            vec![(101 * ONE, ONE / 1_000), (102 * ONE, ONE)],
+            1,
+        );
+        let (bids, asks) = book.get_top_levels(10);
+        assert_eq!(bids.len(), 1);
+        assert_eq!(asks.len(), 1);
+        assert_eq!(asks[0].price, dec!(102));
+
+        // Dust is not inserted, and a level shrinking to dust is removed
+        let change = book.apply_update(vec![(100 * ONE, ONE / 1_000), (98 * ONE, 1)], vec![], 0, 2);
+        assert!(change.changed);
+        let (bids, _) = book.get_top_levels(10);
+        assert!(bids.is_empty());
+        drop(book);
+
+        // Other exchanges keep every level
+        let mut bybit = manager.get_or_create("Bybit", "BTCUSDT");
+        bybit.initialize_from_snapshot(vec![(100 * ONE, 1)], vec![(101 * ONE, 1)], 1);
+        assert_eq!(bybit.level_counts(), (1, 1));
+    }
+
+    #[test]
+    #[cfg(feature = "level-age")]
+    fn test_level_age_round_trips_and_defaults() {
+        let mut book = OrderBook::new("BTCUSDT", "Binance");
+        book.initialize_from_snapshot(vec![(100 * ONE, ONE)], vec![(101 * ONE, ONE)], 1);
+        std::thread::sleep(Duration::from_millis(5));
+        let (bids, _) = book.get_top_levels(1);
+        assert!(bids[0].age_ms >= 5);
+
+        let json = serde_json::to_string(&bids[0]).unwrap();
+        let level: PriceLevel = serde_json::from_str(&json).unwrap();
+        assert_eq!(level.age_ms, bids[0].age_ms);
+        assert_eq!(level.price, dec!(100));
+
+        // Levels relayed by an upstream built without the feature
+        let level: PriceLevel = serde_json::from_str(r#"{"price":"100","quantity":"1"}"#).unwrap();
+        assert_eq!(level.age_ms, 0);
+    }
+
+    #[test]
+    fn test_storage_depth_independent_of_display_depth() {
+        let manager = OrderBookManager::with_streams(&[], &[]).with_max_depth(100);
+        let mut book = manager.get_or_create("Binance", "BTCUSDT");
+        book.initialize_from_snapshot(
+            (1..=150).map(|i| ((1_000 - i) * ONE, ONE)).collect(),
+            (1..=150).map(|i| ((1_000 + i) * ONE, ONE)).collect(),
+            1,
+        );
+        assert_eq!(book.bids.len(), 100);
+
+        // Freeing the 100th slot lets a level far below the display depth in
+        book.apply_update(vec![(901 * ONE, 0), (850 * ONE, ONE)], vec![], 0, 2);
+        assert_eq!(book.bids.len(), 100);
+        assert_eq!(book.bids.last().unwrap().price, 850 * ONE);
+
+        // Beyond the stored depth: dropped
+        book.apply_update(vec![(800 * ONE, ONE)], vec![], 0, 3);
+        assert_eq!(book.bids.last().unwrap().price, 850 * ONE);
+
+        let update = book.to_book_update(ORDERBOOK_DISPLAY_DEPTH);
+        assert_eq!(update.bids.len(), ORDERBOOK_DISPLAY_DEPTH);
+        drop(book);
+
+        // A feed streaming fewer levels keeps its books cut at its depth
+        let manager = manager.with_exchange_max_depth("Kraken", 25);
+        let mut book = manager.get_or_create("Kraken", "BTCUSDT");
+        book.initialize_from_snapshot(
+            (1..=150).map(|i| ((1_000 - i) * ONE, ONE)).collect(),
+            (1..=150).map(|i| ((1_000 + i) * ONE, ONE)).collect(),
+            1,
+        );
+        assert_eq!((book.bids.len(), book.asks.len()), (25, 25));
+    }
+
+    #[test]
+    fn test_pretty_prints_asks_above_bids() {
+        let mut book = OrderBook::new("BTCUSDT", "Binance");
+        book.initialize_from_snapshot(
+            vec![(100 * ONE, ONE), (99 * ONE, 2 * ONE)],
+            vec![(101 * ONE, 3 * ONE), (102 * ONE, 4 * ONE)],
+            7,
+        );
+
+        let ladder = book.pretty(2);
+        let lines: Vec<&str> = ladder.lines().collect();
+
+        assert_eq!(lines[0], "Binance:BTCUSDT (last_update_id 7)");
+        assert!(lines[2].starts_with("ASK") && lines[2].contains("102"));
+        assert!(lines[3].starts_with("ASK") && lines[3].contains("101"));
+        assert!(lines[4].contains("spread 1"));
+        assert!(lines[5].starts_with("BID") && lines[5].contains("100"));
+        assert!(lines[6].starts_with("BID") && lines[6].contains("99"));
+    }
+
+    fn client_spread(book: &OrderBook) -> (Decimal, Decimal) {
+        match book.to_client_message(3) {
+            ClientMessage::BookUpdate(update) => (update.spread, update.spread_percent),
+            other => panic!("expected BookUpdate, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_client_spread_two_sided_book() {
+        let mut book = OrderBook::new("BTCUSDT", "Binance");
+        book.initialize_from_snapshot(vec![(99 * ONE, ONE)], vec![(101 * ONE, ONE)], 1);
+
+        let (spread, spread_percent) = client_spread(&book);
+        assert_eq!(spread, book.best_ask().unwrap() - book.best_bid().unwrap());
+        assert_eq!(spread, dec!(2));
+        // mid = 100 -> 2 / 100 * 100 = 2%
+        assert_eq!(spread_percent, dec!(2));
+    }
+
+    #[test]
+    fn test_client_spread_empty_book() {
+        let book = OrderBook::new("BTCUSDT", "Binance");
+        assert_eq!(client_spread(&book), (dec!(0), dec!(0)));
+    }
+
+    #[test]
+    fn test_client_spread_of_one_tick() {
+        // BTCUSDT ticks by 0.01
+        let mut book = OrderBook::new("BTCUSDT", "Binance");
+        book.initialize_from_snapshot(
+            vec![(99_990 * ONE / 1000, ONE)],
+            vec![(100_000 * ONE / 1000, ONE)],
+            1,
+        );
+        let update = book.to_book_update(3);
+        assert_eq!(update.spread, dec!(0.01));
+        assert_eq!(update.spread_ticks, Some(dec!(1)));
+        assert_eq!(update.spread_bps, update.spread_percent * dec!(100));
+        assert!(update.spread_bps > dec!(0.99) && update.spread_bps < dec!(1.01));
+
+        // Unknown tick size, and a zero mid like any empty book
+        let mut unlisted = OrderBook::new("FOOUSDT", "Binance");
+        unlisted.initialize_from_snapshot(vec![(0, ONE)], vec![(0, ONE)], 1);
+        let update = unlisted.to_book_update(3);
+        assert_eq!((update.spread_bps, update.spread_ticks), (dec!(0), None));
+    }
+
+    #[test]
+    fn test_client_spread_one_sided_books() {
+        // A side can still empty out through deltas
+        let mut bid_only = OrderBook::new("BTCUSDT", "Binance");
+        bid_only.initialize_from_snapshot(vec![(99 * ONE, ONE)], vec![(101 * ONE, ONE)], 1);
+        bid_only.apply_update(vec![], vec![(101 * ONE, 0)], 0, 2);
+        assert_eq!(client_spread(&bid_only), (dec!(0), dec!(0)));
+
+        let mut ask_only = OrderBook::new("BTCUSDT", "Binance");
+        ask_only.initialize_from_snapshot(vec![(99 * ONE, ONE)], vec![(101 * ONE, ONE)], 1);
+        ask_only.apply_update(vec![(99 * ONE, 0)], vec![], 0, 2);
+        assert_eq!(client_spread(&ask_only), (dec!(0), dec!(0)));
+    }
+
+    #[test]
+    fn test_empty_snapshot_leaves_book_uninitialized() {
+        let mut book = OrderBook::new("BTCUSDT", "Binance");
+        assert!(!book.initialize_from_snapshot(vec![], vec![], 5));
+        assert!(!book.initialize_from_snapshot(vec![(99 * ONE, ONE)], vec![], 5));
+        // Zero quantities are removals, not levels
+        assert!(!book.initialize_from_snapshot(vec![(99 * ONE, ONE)], vec![(101 * ONE, 0)], 5));
+        assert!(!book.is_initialized());
+        assert_eq!(book.last_update_id(), 0);
+
+        // A rejected snapshot doesn't wipe an initialized book either
+        assert!(book.initialize_from_snapshot(vec![(99 * ONE, ONE)], vec![(101 * ONE, ONE)], 6));
+        assert!(!book.initialize_from_snapshot(vec![], vec![], 7));
+        assert!(book.is_initialized());
+        assert_eq!(book.last_update_id(), 6);
+        assert_eq!(book.best_bid(), Some(dec!(99)));
+    }
+
+    #[test]
+    fn test_content_version_ignores_noop_deltas() {
+        let mut book = OrderBook::new("BTCUSDT", "Binance");
+        book.initialize_from_snapshot(vec![(99 * ONE, ONE)], vec![(101 * ONE, ONE)], 1);
+        let version = book.content_version();
+
+        // Same quantity, and a removal of a level that isn't there
+        assert!(
+            !book
+                .apply_update(vec![(99 * ONE, ONE)], vec![(105 * ONE, 0)], 2, 2)
+                .changed
+        );
+        assert_eq!(book.last_update_id(), 2);
+        assert_eq!(book.content_version(), version);
+
+        assert!(
+            book.apply_update(vec![(99 * ONE, 2 * ONE)], vec![], 3, 3)
+                .changed
+        );
+        assert_eq!(book.content_version(), version + 1);
+    }
+
+    #[test]
+    fn test_client_spread_crossed_book() {
+        let mut book = OrderBook::new("BTCUSDT", "Binance");
+        book.initialize_from_snapshot(vec![(101 * ONE, ONE)], vec![(99 * ONE, ONE)], 1);
+
+        assert!(book.spread().is_none());
+        assert_eq!(client_spread(&book), (dec!(0), dec!(0)));
+    }
+
+    #[test]
+    fn test_last_trade_price_overlaid_on_book_update() {
+        let mut book = OrderBook::new("BTCUSDT", "Binance");
+        assert_eq!(book.to_book_update(3).last_trade_price, None);
+
+        book.record_trade_price(100 * ONE + ONE / 2);
+        // A fresh snapshot replaces the levels but keeps the last trade
+        book.initialize_from_snapshot(vec![(99 * ONE, ONE)], vec![(101 * ONE, ONE)], 1);
+        assert_eq!(book.to_book_update(3).last_trade_price, Some(dec!(100.5)));
+        assert_eq!(book.last_trade_price(), Some(dec!(100.5)));
+    }
+
+    #[test]
+    fn test_symbol_info_reports_finest_tick_seen() {
+        let manager = OrderBookManager::with_streams(&["Binance", "Kraken"], &["BTCUSDT"]);
+        let info = manager.symbol_info("BTCUSDT");
+        assert_eq!(info.exchanges, vec!["Binance", "Kraken"]);
+        assert_eq!(info.tick_size, None);
+        assert_eq!(info.quote.as_deref(), Some("USDT"));
+
+        manager
+            .get_or_create("Kraken", "BTCUSDT")
+            .initialize_from_snapshot(vec![(97_000 * ONE, ONE)], vec![(97_001 * ONE, ONE)], 1);
+        assert_eq!(manager.symbol_info("BTCUSDT").tick_size, Some(dec!(1)));
+
+        manager
+            .get_or_create("Binance", "BTCUSDT")
+            .initialize_from_snapshot(
+                vec![(97_000 * ONE + ONE / 10, ONE)],
+                vec![(97_000 * ONE + 3 * ONE / 10, ONE)],
+                1,
+            );
+        assert_eq!(manager.symbol_info("BTCUSDT").tick_size, Some(dec!(0.1)));
+    }
+
+    #[test]
+    fn test_symbols_filtered_by_quote() {
+        let manager = OrderBookManager::with_symbols(&["BTCUSDT"]);
+        assert!(manager.accepts_symbol("Kraken", "BTCEUR"));
+
+        let manager = manager.with_allowed_quotes(vec!["USDT".to_string(), "USDC".to_string()]);
+        assert!(manager.accepts_symbol("Kraken", "BTCUSDT"));
+        assert!(manager.accepts_symbol("Kraken", "ETHUSDC"));
+        assert!(!manager.accepts_symbol("Kraken", "BTCEUR"));
+        assert!(!manager.accepts_symbol("Kraken", "BTCEUR"));
+        // No recognizable quote
+        assert!(!manager.accepts_symbol("Kraken", "XYZ"));
+        assert_eq!(manager.refused_symbols.len(), 2);
+    }
+
+    #[test]
+    fn test_stream_list_reports_book_status() {
+        let manager = OrderBookManager::with_streams(&["Binance", "Kraken"], &["BTCUSDT"]);
+        manager
+            .get_or_create("Kraken", "BTCUSDT")
+            .initialize_from_snapshot(vec![(97_000 * ONE, ONE)], vec![(97_001 * ONE, ONE)], 1);
+        manager.get_or_create("Binance", "ETHUSDT");
+        manager.get_or_create("Binance", "BTCUSDT");
+
+        let streams = manager.stream_list();
+        let streams: Vec<(&str, &str, bool)> = streams
+            .iter()
+            .map(|s| (s.exchange.as_str(), s.symbol.as_str(), s.initialized))
+            .collect();
+        assert_eq!(
+            streams,
+            vec![
+                ("Binance", "BTCUSDT", false),
+                ("Binance", "ETHUSDT", false),
+                ("Kraken", "BTCUSDT", true)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_runtime_symbol_add_remove_updates_coverage() {
+        let manager = OrderBookManager::with_streams(&["Binance", "Bybit"], &["BTCUSDT"]);
+        for exchange in ["Binance", "Bybit"] {
+            manager
+                .get_or_create(exchange, "BTCUSDT")
+                .initialize_from_snapshot(vec![(99 * ONE, ONE)], vec![(101 * ONE, ONE)], 1);
+        }
+        assert_eq!(manager.coverage(), 1.0);
+
+        // New symbol: empty books on every exchange, not yet covered
+        manager.add_symbol("PEPEUSDT");
+        assert!(manager.get("Bybit", "PEPEUSDT").is_some());
+        assert_eq!(manager.coverage(), 0.5);
+
+        manager.remove_symbol("BTCUSDT");
+        assert!(manager.get("Binance", "BTCUSDT").is_none());
+        assert_eq!(manager.coverage(), 0.0);
+    }
+
+    #[test]
+    fn test_consolidated_book_merges_initialized_venues() {
+        let manager = OrderBookManager::with_streams(&["Binance", "Kraken", "Bybit"], &["BTCUSDT"]);
+        assert!(manager.consolidated_book("BTCUSDT", 5).is_none());
+
+        manager
+            .get_or_create("Binance", "BTCUSDT")
+            .initialize_from_snapshot(
+                vec![(100 * ONE, ONE), (99 * ONE, 2 * ONE)],
+                vec![(102 * ONE, ONE), (103 * ONE, ONE)],
+                1,
+            );
+        manager
+            .get_or_create("Kraken", "BTCUSDT")
+            .initialize_from_snapshot(
+                vec![(101 * ONE, 3 * ONE), (99 * ONE, ONE)],
+                vec![(102 * ONE, 5 * ONE)],
+                1,
+            );
+        // Bybit has an empty book, not initialized yet: left out
+        manager.get_or_create("Bybit", "BTCUSDT");
+
+        let book = manager.consolidated_book("BTCUSDT", 3).unwrap();
+        assert_eq!(book.exchanges, vec!["Binance", "Kraken"]);
+        let bids: Vec<(&str, Decimal)> = book
+            .bids
+            .iter()
+            .map(|l| (l.exchange.as_str(), l.price))
+            .collect();
+        assert_eq!(
+            bids,
+            vec![
+                ("Kraken", dec!(101)),
+                ("Binance", dec!(100)),
+                ("Binance", dec!(99))
+            ]
+        );
+        let asks: Vec<(&str, Decimal)> = book
+            .asks
+            .iter()
+            .map(|l| (l.exchange.as_str(), l.price))
+            .collect();
+        assert_eq!(
+            asks,
+            vec![
+                ("Binance", dec!(102)),
+                ("Kraken", dec!(102)),
+                ("Binance", dec!(103))
+            ]
+        );
+        assert_eq!(book.best_bid.unwrap().exchange, "Kraken");
+        assert_eq!(book.spread, Some(dec!(1)));
+
+        // Kraken bidding above Binance's ask: venues cross
+        manager
+            .get_or_create("Kraken", "BTCUSDT")
+            .initialize_from_snapshot(vec![(104 * ONE, ONE)], vec![(105 * ONE, ONE)], 2);
+        assert_eq!(
+            manager.consolidated_book("BTCUSDT", 3).unwrap().spread,
+            Some(dec!(-2))
+        );
+    }
+
+    #[test]
+    fn test_vwap_and_weighted_mid_on_hand_computed_book() {
+        let mut book = OrderBook::new("BTCUSDT", "Binance");
+        book.initialize_from_snapshot(
+            vec![(100 * ONE, ONE), (99 * ONE, 2 * ONE), (98 * ONE, 5 * ONE)],
+            vec![(101 * ONE, 3 * ONE), (102 * ONE, ONE)],
+            1,
+        );
+
+        // Selling 2.5: 1 @ 100 + 1.5 @ 99 = 248.5 -> 99.4
+        assert_eq!(book.vwap(Side::Bid, dec!(2.5)), Some(dec!(99.4)));
+        // Inside the first level: its price
+        assert_eq!(book.vwap(Side::Ask, dec!(0.5)), Some(dec!(101)));
+        // Buying 4: 3 @ 101 + 1 @ 102 = 405 -> 101.25
+        assert_eq!(book.vwap(Side::Ask, dec!(4)), Some(dec!(101.25)));
+        // Selling 1 / 3: rounded to the nearest 1e-8
+        assert_eq!(book.vwap(Side::Bid, dec!(3)), Some(dec!(99.33333333)));
+        // More than the side holds, or nothing at all
+        assert_eq!(book.vwap(Side::Ask, dec!(4.1)), None);
+        assert_eq!(book.vwap(Side::Bid, dec!(0)), None);
+
+        // Top 1: 100 × 3 + 101 × 1 over 4 = 100.25 (leans to the thin bid)
+        assert_eq!(book.weighted_mid(1), Some(dec!(100.25)));
+        // Top 2: bid_px = 298 / 3, ask_px = 101.25, weights 4 and 3
+        // (99.33333333 × 4 + 101.25 × 3) / 7 = 100.15476190
+        assert_eq!(book.weighted_mid(2), Some(dec!(100.15476190)));
+
+        let pricing = book.pricing(1, dec!(2.5));
+        assert_eq!(pricing.bid_vwap, Some(dec!(99.4)));
+        assert_eq!(pricing.ask_vwap, Some(dec!(101)));
+        assert_eq!(book.to_book_update(5).pricing, None);
+    }
+
+    #[test]
+    fn test_market_order_impact_walks_levels() {
+        let mut book = OrderBook::new("BTCUSDT", "Binance");
+        assert_eq!(book.market_order_impact(Side::Ask, ONE), None);
+        book.initialize_from_snapshot(
+            vec![(100 * ONE, ONE), (99 * ONE, 2 * ONE), (98 * ONE, 5 * ONE)],
+            vec![(101 * ONE, 3 * ONE), (102 * ONE, ONE)],
+            1,
+        );
+
+        // Buying 4: 3 @ 101 + 1 @ 102 = 405 -> 101.25, 0.25 above the best ask
+        let buy = book.market_order_impact(Side::Ask, 4 * ONE).unwrap();
+        assert_eq!(buy.side, TradeSide::Buy);
+        assert_eq!(buy.filled_quantity, dec!(4));
+        assert_eq!(buy.average_price, dec!(101.25));
+        assert_eq!(buy.worst_price, dec!(102));
+        assert_eq!(buy.best_price, dec!(101));
+        assert_eq!(buy.slippage, dec!(0.25));
+        assert!((buy.slippage_bps - 24.752475).abs() < 1e-6);
+        assert!(!buy.insufficient_liquidity);
+
+        // Selling 2.5: 1 @ 100 + 1.5 @ 99 = 248.5 -> 99.4, 0.6 below the best bid
+        let sell = book.market_order_impact(Side::Bid, 25 * ONE / 10).unwrap();
+        assert_eq!(sell.side, TradeSide::Sell);
+        assert_eq!(sell.average_price, dec!(99.4));
+        assert_eq!(sell.worst_price, dec!(99));
+        assert_eq!(sell.slippage, dec!(0.6));
+
+        // Inside the first level: no slippage
+        let small = book.market_order_impact(Side::Ask, ONE).unwrap();
+        assert_eq!(small.slippage, dec!(0));
+        assert_eq!(small.slippage_bps, 0.0);
+
+        // Larger than the asks: the 4 available, flagged
+        let thin = book.market_order_impact(Side::Ask, 10 * ONE).unwrap();
+        assert_eq!(thin.quantity, dec!(10));
+        assert_eq!(thin.filled_quantity, dec!(4));
+        assert_eq!(thin.average_price, dec!(101.25));
+        assert!(thin.insufficient_liquidity);
+
+        assert_eq!(book.market_order_impact(Side::Bid, 0), None);
+    }
+
+    #[test]
+    fn test_imbalance_over_top_levels() {
+        let mut book = OrderBook::new("BTCUSDT", "Binance");
+        assert_eq!(book.imbalance(5), None);
+
+        book.initialize_from_snapshot(
+            vec![(100 * ONE, 3 * ONE), (99 * ONE, 4 * ONE)],
+            vec![(101 * ONE, ONE), (102 * ONE, 2 * ONE)],
+            1,
+        );
+        // Top 1: (3 - 1) / 4
+        assert_eq!(book.imbalance(1), Some(dec!(0.5)));
+        // Top 2: (7 - 3) / 10
+        assert_eq!(book.imbalance(2), Some(dec!(0.4)));
+        assert_eq!(book.to_book_update(2).imbalance, Some(dec!(0.4)));
+        assert_eq!(book.imbalance(0), None);
+
+        // One-sided books sit at the bounds
+        book.apply_update(vec![], vec![(101 * ONE, 0), (102 * ONE, 0)], 2, 2);
+        assert_eq!(book.imbalance(5), Some(dec!(1)));
+        book.initialize_from_snapshot(vec![(100 * ONE, ONE)], vec![(101 * ONE, ONE)], 3);
+        book.apply_update(vec![(100 * ONE, 0)], vec![], 4, 4);
+        assert_eq!(book.imbalance(5), Some(dec!(-1)));
+    }
 }
@@ -0,0 +1,453 @@
+//! Optional fan-out of normalized market data to Redis pub/sub, Kafka and disk
+//!
+//! Lets other services consume FlowRS books and trades without speaking the
+//! WebSocket protocol. Both depth updates and trades are published as JSON,
+//! for every exchange:
+//! - Redis: channels `flowrs.book.<SYMBOL>` and `flowrs.trade.<SYMBOL>`
+//! - Kafka: one topic per kind (see `KafkaConfig`), keyed by symbol so a
+//!   symbol's messages stay ordered within a partition
+//! - Trades only: daily CSV files (see `recorder`)
+//!
+//! Exchange tasks only encode and enqueue into bounded queues, drained and
+//! batched off the read loop (a background task for Redis, librdkafka's own
+//! threads for Kafka, a writer thread for files). When a queue is full, or a sink fails to deliver, the
+//! message is dropped and counted in `publish_drops` rather than retried:
+//! delivery is at-most-once, and the exchange tasks never wait on a sink.
+
+use crate::exchanges::Exchange;
+use crate::metrics::SharedMetrics;
+use crate::orderbook::{PRICE_FACTOR, QTY_FACTOR};
+use crate::recorder::TradeRecorder;
+use crate::types::Trade;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::error::Error;
+use tokio::sync::mpsc;
+
+/// Messages waiting to be published to Redis before new ones are dropped
+#[cfg_attr(not(feature = "redis"), allow(dead_code))]
+const PUBLISH_QUEUE_LEN: usize = 8192;
+
+/// Most messages sent to Redis in one pipeline
+#[cfg(feature = "redis")]
+const PUBLISH_BATCH: usize = 256;
+
+/// Wait between two connection attempts while Redis is unreachable
+#[cfg(feature = "redis")]
+const RECONNECT_DELAY: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Kind of market data, which picks the Redis channel and Kafka topic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Feed {
+    Book,
+    Trade,
+}
+
+impl Feed {
+    fn name(self) -> &'static str {
+        match self {
+            Feed::Book => "book",
+            Feed::Trade => "trade",
+        }
+    }
+}
+
+/// An encoded message queued for Redis
+#[derive(Debug)]
+#[cfg_attr(not(feature = "redis"), allow(dead_code))]
+struct Publication {
+    feed: Feed,
+    symbol: String,
+    payload: String,
+}
+
+/// Depth update as published: prices and sizes as decimal strings
+#[derive(Debug, Serialize)]
+struct DepthPayload<'a> {
+    exchange: &'static str,
+    symbol: &'a str,
+    /// (price, quantity), quantity 0 removes the level
+    bids: Vec<(Decimal, Decimal)>,
+    asks: Vec<(Decimal, Decimal)>,
+    update_id: u64,
+    /// Replaces the whole book rather than patching it
+    is_snapshot: bool,
+}
+
+/// Where and how to produce to Kafka
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "kafka"), allow(dead_code))]
+pub struct KafkaConfig {
+    /// Comma-separated `host:port` bootstrap servers
+    pub brokers: String,
+    pub book_topic: String,
+    pub trade_topic: String,
+    /// Messages librdkafka buffers before new ones are dropped
+    pub queue_len: usize,
+}
+
+impl KafkaConfig {
+    pub fn new(brokers: &str) -> Self {
+        Self {
+            brokers: brokers.to_string(),
+            book_topic: "flowrs.books".to_string(),
+            trade_topic: "flowrs.trades".to_string(),
+            queue_len: 100_000,
+        }
+    }
+}
+
+/// Handle exchange tasks publish through; does nothing until a sink is added
+#[derive(Clone)]
+pub struct MarketPublisher {
+    redis: Option<mpsc::Sender<Publication>>,
+    #[cfg(feature = "kafka")]
+    kafka: Option<std::sync::Arc<kafka::KafkaSink>>,
+    recorder: Option<TradeRecorder>,
+    metrics: SharedMetrics,
+}
+
+impl MarketPublisher {
+    /// Publisher without sinks, counting drops in `metrics` once some are added
+    pub fn new(metrics: SharedMetrics) -> Self {
+        Self {
+            redis: None,
+            #[cfg(feature = "kafka")]
+            kafka: None,
+            recorder: None,
+            metrics,
+        }
+    }
+
+    /// Also write every trade to disk
+    pub fn add_trade_recorder(&mut self, recorder: TradeRecorder) {
+        self.recorder = Some(recorder);
+    }
+
+    /// Also publish to the Redis server at `url` (e.g. "redis://127.0.0.1:6379")
+    ///
+    /// Only validates the URL: the connection is made (and remade) in the
+    /// background, so a Redis that is down at startup is not an error.
+    #[cfg(feature = "redis")]
+    pub fn add_redis(&mut self, url: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let client = redis::Client::open(url)?;
+        let (tx, rx) = mpsc::channel(PUBLISH_QUEUE_LEN);
+        tokio::spawn(run_redis(client, rx, self.metrics.clone()));
+        self.redis = Some(tx);
+        Ok(())
+    }
+
+    #[cfg(not(feature = "redis"))]
+    pub fn add_redis(&mut self, _url: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        Err("built without the `redis` feature".into())
+    }
+
+    /// Also produce to Kafka
+    ///
+    /// Brokers are contacted in the background: unreachable ones only show up
+    /// as undelivered (dropped) messages.
+    #[cfg(feature = "kafka")]
+    pub fn add_kafka(&mut self, config: &KafkaConfig) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let sink = kafka::KafkaSink::new(config, self.metrics.clone())?;
+        self.kafka = Some(std::sync::Arc::new(sink));
+        Ok(())
+    }
+
+    #[cfg(not(feature = "kafka"))]
+    pub fn add_kafka(&mut self, _config: &KafkaConfig) -> Result<(), Box<dyn Error + Send + Sync>> {
+        Err("built without the `kafka` feature".into())
+    }
+
+    /// A Redis or Kafka sink is configured (the recorder writes its own format)
+    fn is_enabled(&self) -> bool {
+        #[cfg(feature = "kafka")]
+        if self.kafka.is_some() {
+            return true;
+        }
+        self.redis.is_some()
+    }
+
+    /// Publish a depth update (levels scaled by 1e8, as in `MarketMessage`)
+    pub fn publish_depth(
+        &self,
+        exchange: Exchange,
+        symbol: &str,
+        bids: &[(u64, u64)],
+        asks: &[(u64, u64)],
+        update_id: u64,
+        is_snapshot: bool,
+    ) {
+        if !self.is_enabled() {
+            return;
+        }
+        let payload = DepthPayload {
+            exchange: exchange.name(),
+            symbol,
+            bids: external_levels(bids),
+            asks: external_levels(asks),
+            update_id,
+            is_snapshot,
+        };
+        self.publish(Feed::Book, symbol, &payload);
+    }
+
+    pub fn publish_trade(&self, trade: &Trade) {
+        if let Some(recorder) = &self.recorder {
+            if !recorder.record(trade) {
+                self.metrics.record_publish_drop();
+            }
+        }
+        if self.is_enabled() {
+            self.publish(Feed::Trade, &trade.symbol, trade);
+        }
+    }
+
+    /// Encode once and hand the message to every sink, counting those that drop it
+    fn publish(&self, feed: Feed, symbol: &str, payload: &impl Serialize) {
+        let payload = match serde_json::to_string(payload) {
+            Ok(json) => json,
+            Err(e) => {
+                tracing::warn!("Failed to encode {} {} message: {}", symbol, feed.name(), e);
+                return;
+            }
+        };
+
+        #[cfg(feature = "kafka")]
+        if let Some(kafka) = &self.kafka {
+            if !kafka.produce(feed, symbol, &payload) {
+                self.metrics.record_publish_drop();
+            }
+        }
+
+        if let Some(tx) = &self.redis {
+            let publication = Publication {
+                feed,
+                symbol: symbol.to_string(),
+                payload,
+            };
+            if tx.try_send(publication).is_err() {
+                self.metrics.record_publish_drop();
+            }
+        }
+    }
+}
+
+fn external_levels(levels: &[(u64, u64)]) -> Vec<(Decimal, Decimal)> {
+    levels
+        .iter()
+        .map(|&(price, qty)| {
+            (
+                Decimal::from(price) / Decimal::from(PRICE_FACTOR),
+                Decimal::from(qty) / Decimal::from(QTY_FACTOR),
+            )
+        })
+        .collect()
+}
+
+#[cfg_attr(not(feature = "redis"), allow(dead_code))]
+fn redis_channel(feed: Feed, symbol: &str) -> String {
+    format!("flowrs.{}.{}", feed.name(), symbol)
+}
+
+/// Publish queued messages until every `MarketPublisher` is dropped
+#[cfg(feature = "redis")]
+async fn run_redis(
+    client: redis::Client,
+    mut rx: mpsc::Receiver<Publication>,
+    metrics: SharedMetrics,
+) {
+    let mut batch = Vec::with_capacity(PUBLISH_BATCH);
+    loop {
+        let mut conn = match client.get_multiplexed_async_connection().await {
+            Ok(conn) => {
+                tracing::info!("Publishing market data to Redis");
+                conn
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "Redis unavailable ({}), retrying in {}s",
+                    e,
+                    RECONNECT_DELAY.as_secs()
+                );
+                // Discard what queues up meanwhile: it would be stale on reconnect
+                let retry = tokio::time::sleep(RECONNECT_DELAY);
+                tokio::pin!(retry);
+                loop {
+                    tokio::select! {
+                        _ = &mut retry => break,
+                        received = rx.recv() => match received {
+                            Some(_) => metrics.record_publish_drop(),
+                            None => return,
+                        },
+                    }
+                }
+                continue;
+            }
+        };
+
+        loop {
+            if rx.recv_many(&mut batch, PUBLISH_BATCH).await == 0 {
+                return;
+            }
+            let mut pipe = redis::pipe();
+            for publication in batch.drain(..) {
+                pipe.publish(
+                    redis_channel(publication.feed, &publication.symbol),
+                    publication.payload,
+                )
+                .ignore();
+            }
+            if let Err(e) = pipe.query_async::<()>(&mut conn).await {
+                tracing::warn!("Redis publish failed ({}), reconnecting", e);
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(feature = "kafka")]
+mod kafka {
+    use super::{Feed, KafkaConfig};
+    use crate::metrics::SharedMetrics;
+    use rdkafka::config::ClientConfig;
+    use rdkafka::error::KafkaResult;
+    use rdkafka::producer::{
+        BaseRecord, DeliveryResult, Producer, ProducerContext, ThreadedProducer,
+    };
+    use rdkafka::ClientContext;
+    use std::time::Duration;
+
+    /// How long a closing sink waits for buffered messages to be delivered
+    const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Counts messages the brokers never acknowledged
+    pub(super) struct DeliveryReport {
+        metrics: SharedMetrics,
+    }
+
+    impl ClientContext for DeliveryReport {}
+
+    impl ProducerContext for DeliveryReport {
+        type DeliveryOpaque = ();
+
+        fn delivery(&self, result: &DeliveryResult<'_>, _opaque: ()) {
+            if result.is_err() {
+                self.metrics.record_publish_drop();
+            }
+        }
+    }
+
+    /// Kafka producer; librdkafka batches and sends from its own threads
+    pub(super) struct KafkaSink {
+        producer: ThreadedProducer<DeliveryReport>,
+        book_topic: String,
+        trade_topic: String,
+    }
+
+    impl KafkaSink {
+        pub(super) fn new(config: &KafkaConfig, metrics: SharedMetrics) -> KafkaResult<Self> {
+            let producer = ClientConfig::new()
+                .set("bootstrap.servers", &config.brokers)
+                .set("queue.buffering.max.messages", config.queue_len.to_string())
+                // Give up on a message rather than buffer it for minutes
+                .set("message.timeout.ms", "30000")
+                .set("linger.ms", "20")
+                .create_with_context(DeliveryReport { metrics })?;
+            tracing::info!(
+                "Producing market data to Kafka ({}, topics {} and {})",
+                config.brokers,
+                config.book_topic,
+                config.trade_topic
+            );
+            Ok(Self {
+                producer,
+                book_topic: config.book_topic.clone(),
+                trade_topic: config.trade_topic.clone(),
+            })
+        }
+
+        /// Queue a message, false if librdkafka's queue is full (or refused it)
+        pub(super) fn produce(&self, feed: Feed, symbol: &str, payload: &str) -> bool {
+            let topic = match feed {
+                Feed::Book => &self.book_topic,
+                Feed::Trade => &self.trade_topic,
+            };
+            self.producer
+                .send(BaseRecord::to(topic).key(symbol).payload(payload))
+                .is_ok()
+        }
+    }
+
+    impl Drop for KafkaSink {
+        fn drop(&mut self) {
+            let _ = self.producer.flush(FLUSH_TIMEOUT);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::create_shared_metrics;
+    use crate::types::TradeSide;
+
+    const ONE: u64 = 100_000_000;
+
+    fn capture(queue_len: usize) -> (MarketPublisher, mpsc::Receiver<Publication>) {
+        let (tx, rx) = mpsc::channel(queue_len);
+        let mut publisher = MarketPublisher::new(create_shared_metrics());
+        publisher.redis = Some(tx);
+        (publisher, rx)
+    }
+
+    #[test]
+    fn test_messages_published_per_symbol_channel() {
+        let (publisher, mut rx) = capture(PUBLISH_QUEUE_LEN);
+        publisher.publish_depth(
+            Exchange::Binance,
+            "BTCUSDT",
+            &[(100 * ONE, 2 * ONE)],
+            &[(101 * ONE, 0)],
+            42,
+            false,
+        );
+        let book = rx.try_recv().unwrap();
+        assert_eq!(
+            redis_channel(book.feed, &book.symbol),
+            "flowrs.book.BTCUSDT"
+        );
+        let depth: serde_json::Value = serde_json::from_str(&book.payload).unwrap();
+        assert_eq!(depth["exchange"], "Binance");
+        assert_eq!(depth["bids"], serde_json::json!([["100", "2"]]));
+        assert_eq!(depth["asks"], serde_json::json!([["101", "0"]]));
+        assert_eq!(depth["update_id"], 42);
+
+        publisher.publish_trade(&Trade {
+            exchange: "Bybit".to_string(),
+            symbol: "ETHUSDT".to_string(),
+            price: 2_000 * ONE,
+            quantity: ONE,
+            notional: Trade::notional_of(2_000 * ONE, ONE),
+            side: TradeSide::Sell,
+            timestamp: 1,
+        });
+        let trade = rx.try_recv().unwrap();
+        assert_eq!(
+            redis_channel(trade.feed, &trade.symbol),
+            "flowrs.trade.ETHUSDT"
+        );
+        let trade: Trade = serde_json::from_str(&trade.payload).unwrap();
+        assert_eq!((trade.price, trade.side), (2_000 * ONE, TradeSide::Sell));
+    }
+
+    #[test]
+    fn test_full_queue_drops_instead_of_blocking() {
+        let (publisher, mut rx) = capture(1);
+        publisher.publish_depth(Exchange::Okx, "BTCUSDT", &[], &[], 1, true);
+        publisher.publish_depth(Exchange::Okx, "BTCUSDT", &[], &[], 2, true);
+        assert!(rx.try_recv().unwrap().payload.contains("\"update_id\":1"));
+        assert!(rx.try_recv().is_err());
+        #[cfg(feature = "metrics")]
+        assert_eq!(publisher.metrics.peek_metrics().publish_drops, 1);
+    }
+}
@@ -0,0 +1,63 @@
+//! Token-bucket rate limiting for per-client push paths
+
+use std::time::Instant;
+
+/// Token bucket allowing `rate` events per second with a one-second burst
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// Create a full bucket refilling at `rate_per_sec` tokens per second
+    pub fn new(rate_per_sec: u32) -> Self {
+        Self {
+            rate: rate_per_sec as f64,
+            tokens: rate_per_sec as f64,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Take one token if available
+    pub fn try_take(&mut self) -> bool {
+        self.try_take_at(Instant::now())
+    }
+
+    /// Take one token if available, refilling up to `now`
+    pub fn try_take_at(&mut self, now: Instant) -> bool {
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_bucket_caps_burst_and_refills() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(20);
+
+        let allowed = (0..50).filter(|_| bucket.try_take_at(start)).count();
+        assert_eq!(allowed, 20);
+
+        // 100ms at 20/s refills two tokens
+        let later = start + Duration::from_millis(100);
+        let allowed = (0..50).filter(|_| bucket.try_take_at(later)).count();
+        assert_eq!(allowed, 2);
+    }
+}
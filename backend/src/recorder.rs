@@ -0,0 +1,212 @@
+//! Trades captured to disk for offline analysis
+//!
+//! One CSV file per UTC day (`trades-YYYY-MM-DD.csv`, by trade timestamp) with
+//! the columns of `CSV_HEADER`. A dedicated thread owns the files: rows are
+//! buffered in memory and flushed every `flush_interval`, at rotation and when
+//! the recorder shuts down, so a busy session never syncs per trade.
+
+use crate::types::{Trade, TradeSide};
+use chrono::{DateTime, NaiveDate};
+use rust_decimal::Decimal;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError, SyncSender};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Trades waiting to be written before new ones are dropped
+const RECORDER_QUEUE_LEN: usize = 65_536;
+
+/// Default period between two flushes of the buffered rows
+pub const RECORDER_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+const CSV_HEADER: &str = "exchange,symbol,price,quantity,side,timestamp";
+
+/// Handle exchange tasks record trades through
+#[derive(Debug, Clone)]
+pub struct TradeRecorder {
+    tx: SyncSender<Trade>,
+}
+
+impl TradeRecorder {
+    /// Start recording into `dir` (created if missing)
+    ///
+    /// The writer thread flushes and exits once every `TradeRecorder` clone is
+    /// dropped; join the returned handle to wait for the last rows.
+    pub fn start(dir: PathBuf, flush_interval: Duration) -> io::Result<(Self, JoinHandle<()>)> {
+        std::fs::create_dir_all(&dir)?;
+        let (tx, rx) = mpsc::sync_channel(RECORDER_QUEUE_LEN);
+        let handle = std::thread::Builder::new()
+            .name("trade-recorder".to_string())
+            .spawn(move || {
+                let mut files = DailyCsv::new(dir);
+                let mut last_flush = Instant::now();
+                loop {
+                    match rx.recv_timeout(flush_interval.saturating_sub(last_flush.elapsed())) {
+                        Ok(trade) => {
+                            if let Err(e) = files.write(&trade) {
+                                tracing::warn!("Failed to record trade: {}", e);
+                            }
+                        }
+                        Err(RecvTimeoutError::Timeout) => {}
+                        Err(RecvTimeoutError::Disconnected) => break,
+                    }
+                    if last_flush.elapsed() >= flush_interval {
+                        if let Err(e) = files.flush() {
+                            tracing::warn!("Failed to flush recorded trades: {}", e);
+                        }
+                        last_flush = Instant::now();
+                    }
+                }
+                if let Err(e) = files.close() {
+                    tracing::warn!("Failed to flush recorded trades: {}", e);
+                }
+            })?;
+        Ok((Self { tx }, handle))
+    }
+
+    /// Queue a trade for writing, false if the queue is full (the trade is dropped)
+    pub fn record(&self, trade: &Trade) -> bool {
+        self.tx.try_send(trade.clone()).is_ok()
+    }
+}
+
+/// The current day's file, replaced when a trade of a later day comes in
+struct DailyCsv {
+    dir: PathBuf,
+    current: Option<(NaiveDate, BufWriter<File>)>,
+}
+
+impl DailyCsv {
+    fn new(dir: PathBuf) -> Self {
+        Self { dir, current: None }
+    }
+
+    fn write(&mut self, trade: &Trade) -> io::Result<()> {
+        let day = DateTime::from_timestamp_millis(trade.timestamp)
+            .unwrap_or_default()
+            .date_naive();
+        // Late trades of the previous day stay in the current file
+        if !matches!(&self.current, Some((current, _)) if day <= *current) {
+            self.close()?;
+            self.current = Some((day, open_day(&self.dir, day)?));
+        }
+        let Some((_, writer)) = &mut self.current else {
+            unreachable!("a file was just opened");
+        };
+
+        let side = match trade.side {
+            TradeSide::Buy => "buy",
+            TradeSide::Sell => "sell",
+        };
+        writeln!(
+            writer,
+            "{},{},{},{},{},{}",
+            trade.exchange,
+            trade.symbol,
+            scaled(trade.price),
+            scaled(trade.quantity),
+            side,
+            trade.timestamp
+        )
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.current {
+            Some((_, writer)) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Flush and sync the current file, if any
+    fn close(&mut self) -> io::Result<()> {
+        match self.current.take() {
+            Some((_, mut writer)) => {
+                writer.flush()?;
+                writer.get_ref().sync_data()
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// Open (or reopen, after a restart) the file of `day`, with a header if new
+fn open_day(dir: &Path, day: NaiveDate) -> io::Result<BufWriter<File>> {
+    let path = dir.join(format!("trades-{day}.csv"));
+    let file = OpenOptions::new().create(true).append(true).open(&path)?;
+    let is_new = file.metadata()?.len() == 0;
+    let mut writer = BufWriter::with_capacity(64 * 1024, file);
+    if is_new {
+        writeln!(writer, "{CSV_HEADER}")?;
+    }
+    tracing::info!("Recording trades to {}", path.display());
+    Ok(writer)
+}
+
+/// Fixed-point value (scaled by 1e8) as a plain decimal, e.g. 150000000 -> "1.5"
+fn scaled(value: u64) -> Decimal {
+    Decimal::from_i128_with_scale(value as i128, 8).normalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ONE: u64 = 100_000_000;
+    /// 2024-01-01T23:59:59Z
+    const NEW_YEAR_EVE: i64 = 1_704_153_599_000;
+
+    fn trade(exchange: &str, price: u64, side: TradeSide, timestamp: i64) -> Trade {
+        Trade {
+            exchange: exchange.to_string(),
+            symbol: "BTCUSDT".to_string(),
+            price,
+            quantity: ONE / 4,
+            notional: Trade::notional_of(price, ONE / 4),
+            side,
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn test_trades_written_to_daily_files() {
+        let dir = std::env::temp_dir().join(format!("flowrs-recorder-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let (recorder, handle) =
+            TradeRecorder::start(dir.clone(), RECORDER_FLUSH_INTERVAL).unwrap();
+
+        let trades = [
+            trade("Binance", 42_000 * ONE, TradeSide::Buy, NEW_YEAR_EVE),
+            trade(
+                "Kraken",
+                42_001 * ONE / 2,
+                TradeSide::Sell,
+                NEW_YEAR_EVE + 1_000,
+            ),
+            // Late print of the previous day: kept with the current file
+            trade("Bybit", 42_000 * ONE, TradeSide::Buy, NEW_YEAR_EVE - 1),
+        ];
+        for trade in &trades {
+            assert!(recorder.record(trade));
+        }
+        drop(recorder);
+        handle.join().unwrap();
+
+        let first = std::fs::read_to_string(dir.join("trades-2024-01-01.csv")).unwrap();
+        assert_eq!(
+            first,
+            format!("{CSV_HEADER}\nBinance,BTCUSDT,42000,0.25,buy,{NEW_YEAR_EVE}\n")
+        );
+        let second = std::fs::read_to_string(dir.join("trades-2024-01-02.csv")).unwrap();
+        assert_eq!(
+            second,
+            format!(
+                "{CSV_HEADER}\nKraken,BTCUSDT,21000.5,0.25,sell,{}\nBybit,BTCUSDT,42000,0.25,buy,{}\n",
+                NEW_YEAR_EVE + 1_000,
+                NEW_YEAR_EVE - 1
+            )
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
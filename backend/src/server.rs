@@ -1,135 +1,1124 @@
 //! WebSocket server for frontend clients with per-client throttling
 
+use crate::candles::{CandleStore, SharedCandleStore};
+use crate::exchanges::symbols::{normalize_symbol, SymbolRegistry};
+use crate::exchanges::Exchange;
 use crate::metrics::SharedMetrics;
-use crate::orderbook::SharedOrderBookManager;
-use crate::types::{ClientMessage, ORDERBOOK_DISPLAY_DEPTH, TRADING_PAIRS};
-use futures_util::{SinkExt, StreamExt};
-use std::collections::HashMap;
+use crate::orderbook::{SharedOrderBookManager, Side, QTY_FACTOR};
+use crate::rate_limit::TokenBucket;
+use crate::tape::{ConsolidatedTape, DEFAULT_REORDER_WINDOW};
+use crate::trade_history::SharedTradeHistory;
+use crate::types::{
+    BookUpdate, CandleInterval, ClientCommand, ClientMessage, PriceLevel, StreamInfo, TradeSide,
+    CONSOLIDATED_EXCHANGE,
+};
+use futures_util::{Sink, SinkExt, StreamExt};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::collections::{HashMap, HashSet};
 use std::net::SocketAddr;
-use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::broadcast;
-use tokio::time::{interval, Duration};
-use tokio_tungstenite::tungstenite::Message;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::net::{TcpListener, TcpSocket, TcpStream};
+use tokio::sync::{broadcast, watch};
+use tokio::task::JoinSet;
+use tokio::time::{interval, Duration, Instant};
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::header::{AUTHORIZATION, ORIGIN, WWW_AUTHENTICATE};
+use tokio_tungstenite::tungstenite::http::StatusCode;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::{self, Message};
+use tokio_tungstenite::WebSocketStream;
 
-const BOOK_POLL_MS: u64 = 200;
+/// Default period of each client's book poll
+pub const DEFAULT_BOOK_POLL_INTERVAL: Duration = Duration::from_millis(200);
+/// Shortest and longest book poll periods accepted from the configuration
+pub const MIN_BOOK_POLL_INTERVAL: Duration = Duration::from_millis(10);
+pub const MAX_BOOK_POLL_INTERVAL: Duration = Duration::from_secs(5);
+const DROPPED_SUMMARY_INTERVAL: Duration = Duration::from_secs(1);
+/// How often a consolidated tape is checked for trades to release
+const TAPE_FLUSH_MS: u64 = 50;
+/// How long shutdown waits for client handlers to send their Close frames
+const CLIENT_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+/// Most recent trades (all symbols) sent to a client right after the books
+const INITIAL_TAPE_LEN: usize = 100;
+
+/// Why the server closed a client connection, sent in the Close frame
+///
+/// Clients can use the code to decide whether (and how soon) to reconnect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseReason {
+    /// Server is shutting down or lost its data feed (1001, reconnect later)
+    Shutdown,
+    /// Client broke the WebSocket protocol (1002, don't retry blindly)
+    ProtocolError,
+    /// Client origin is not in the allow-list (1008, retrying won't help)
+    PolicyViolation,
+    /// Client stopped reading its frames (4000, reconnect once it can keep up)
+    SlowConsumer,
+    /// Server already serves its maximum of clients (1013, retry later)
+    AtCapacity,
+}
+
+impl CloseReason {
+    pub fn code(self) -> CloseCode {
+        match self {
+            CloseReason::Shutdown => CloseCode::Away,
+            CloseReason::ProtocolError => CloseCode::Protocol,
+            CloseReason::PolicyViolation => CloseCode::Policy,
+            // Application range: no standard code means "you fell behind"
+            CloseReason::SlowConsumer => CloseCode::Library(4000),
+            CloseReason::AtCapacity => CloseCode::Again,
+        }
+    }
+
+    pub fn reason(self) -> &'static str {
+        match self {
+            CloseReason::Shutdown => "server shutting down",
+            CloseReason::ProtocolError => "protocol error",
+            CloseReason::PolicyViolation => "origin not allowed",
+            CloseReason::SlowConsumer => "client too slow",
+            CloseReason::AtCapacity => "server at capacity",
+        }
+    }
+}
+
+/// Which browser origins may open a WebSocket to the feed
+///
+/// Browsers always send `Origin` on WebSocket handshakes, so this stops other
+/// sites from embedding the feed. Non-browser clients (relays, scripts) send
+/// none and are let through: they could forge the header anyway.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum OriginPolicy {
+    /// Accept every origin
+    #[default]
+    Any,
+    /// Accept only these origins (e.g. "https://flowrs.example.com")
+    AllowList(Vec<String>),
+}
+
+impl OriginPolicy {
+    /// Parse a comma-separated list of origins, "*" meaning any
+    pub fn parse(value: &str) -> Self {
+        let origins: Vec<String> = value
+            .split(',')
+            .map(|origin| origin.trim().trim_end_matches('/').to_ascii_lowercase())
+            .filter(|origin| !origin.is_empty())
+            .collect();
+
+        if origins.is_empty() || origins.iter().any(|origin| origin == "*") {
+            OriginPolicy::Any
+        } else {
+            OriginPolicy::AllowList(origins)
+        }
+    }
+
+    /// True if a handshake with this `Origin` header may proceed
+    pub fn allows(&self, origin: Option<&str>) -> bool {
+        match (self, origin) {
+            (OriginPolicy::Any, _) | (_, None) => true,
+            (OriginPolicy::AllowList(allowed), Some(origin)) => {
+                let origin = origin.trim_end_matches('/');
+                allowed.iter().any(|a| a.eq_ignore_ascii_case(origin))
+            }
+        }
+    }
+}
+
+/// Best-effort Close frame; the connection is dropped right after either way
+async fn send_close(
+    client_ws_write: &mut (impl Sink<Message> + Unpin),
+    client_addr: SocketAddr,
+    reason: CloseReason,
+) {
+    tracing::debug!("Closing client {}: {}", client_addr, reason.reason());
+    let frame = CloseFrame {
+        code: reason.code(),
+        reason: reason.reason().into(),
+    };
+    let _ = client_ws_write.send(Message::Close(Some(frame))).await;
+}
+
+/// When a client's sends back up, switch it to snapshot-only mode
+///
+/// A client enters degraded mode once its smoothed send time exceeds
+/// `enter_above` and only leaves it below `exit_below` (hysteresis). While
+/// degraded, trades are dropped and books go out every `snapshot_interval`.
+/// A client that stops reading altogether is disconnected once
+/// `max_send_timeouts` sends in a row took longer than `send_timeout`.
+#[derive(Debug, Clone, Copy)]
+pub struct DegradePolicy {
+    pub enter_above: Duration,
+    pub exit_below: Duration,
+    pub snapshot_interval: Duration,
+    pub send_timeout: Duration,
+    pub max_send_timeouts: u32,
+}
+
+impl Default for DegradePolicy {
+    fn default() -> Self {
+        Self {
+            enter_above: Duration::from_millis(100),
+            exit_below: Duration::from_millis(20),
+            snapshot_interval: Duration::from_secs(1),
+            send_timeout: Duration::from_secs(2),
+            max_send_timeouts: 3,
+        }
+    }
+}
+
+/// Per-connection caps protecting the server from one client
+///
+/// Commands (text frames) over `commands_per_sec` are dropped unanswered;
+/// WebSocket pings and closes are not counted. Books go out at most every
+/// `min_book_interval`, on top of the book poll (zero: every poll).
+#[derive(Debug, Clone, Copy)]
+pub struct ClientLimits {
+    /// Zero disables the command cap
+    pub commands_per_sec: u32,
+    pub min_book_interval: Duration,
+}
+
+impl Default for ClientLimits {
+    fn default() -> Self {
+        Self {
+            commands_per_sec: 20,
+            min_book_interval: Duration::ZERO,
+        }
+    }
+}
+
+/// Cap on concurrent clients, shared by every connection handler
+#[derive(Debug, Clone, Default)]
+pub struct ClientSlots {
+    /// Zero disables the cap
+    max: usize,
+    connected: Arc<AtomicUsize>,
+}
+
+impl ClientSlots {
+    pub fn new(max: usize) -> Self {
+        Self {
+            max,
+            connected: Arc::default(),
+        }
+    }
+
+    /// Take a slot for one client, None when all of them are in use
+    fn acquire(&self) -> Option<ClientSlot> {
+        self.connected
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |connected| {
+                (self.max == 0 || connected < self.max).then_some(connected + 1)
+            })
+            .ok()
+            .map(|_| ClientSlot(self.connected.clone()))
+    }
+}
+
+/// Frees its slot when the client's handler returns
+struct ClientSlot(Arc<AtomicUsize>);
+
+impl Drop for ClientSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Why a frame could not be handed to a client
+#[derive(Debug, PartialEq, Eq)]
+enum SendError {
+    /// The connection is gone
+    Closed,
+    /// Too many sends in a row timed out: the client stopped reading
+    SlowConsumer,
+}
+
+impl std::fmt::Display for SendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SendError::Closed => write!(f, "connection closed"),
+            SendError::SlowConsumer => write!(f, "slow consumer, sends kept timing out"),
+        }
+    }
+}
+
+impl std::error::Error for SendError {}
+
+/// Smoothed send latency of one client and its degraded state
+struct SendPressure {
+    policy: DegradePolicy,
+    metrics: SharedMetrics,
+    client_addr: SocketAddr,
+    /// Exponentially weighted send time (µs)
+    ewma_us: f64,
+    degraded: bool,
+    /// Sends in a row that hit `send_timeout`
+    timeouts: u32,
+}
+
+impl SendPressure {
+    /// Weight of the newest sample in the moving average
+    const ALPHA: f64 = 0.2;
+
+    fn new(policy: DegradePolicy, metrics: SharedMetrics, client_addr: SocketAddr) -> Self {
+        Self {
+            policy,
+            metrics,
+            client_addr,
+            ewma_us: 0.0,
+            degraded: false,
+            timeouts: 0,
+        }
+    }
+
+    /// Send a frame, giving up on it after `send_timeout`
+    ///
+    /// A timed-out frame may still go out later with the next one, or be
+    /// lost; the client catches up on books at the next poll either way.
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(
+            name = "client_send",
+            skip_all,
+            fields(client = %self.client_addr, bytes = frame.len())
+        )
+    )]
+    async fn send(
+        &mut self,
+        client_ws_write: &mut (impl Sink<Message> + Unpin),
+        frame: Message,
+    ) -> Result<(), SendError> {
+        match tokio::time::timeout(self.policy.send_timeout, client_ws_write.send(frame)).await {
+            Ok(Ok(())) => {
+                self.timeouts = 0;
+                Ok(())
+            }
+            Ok(Err(_)) => Err(SendError::Closed),
+            Err(_) => {
+                self.timeouts += 1;
+                if self.timeouts < self.policy.max_send_timeouts {
+                    return Ok(());
+                }
+                tracing::warn!(
+                    "Disconnecting client {}: {} sends in a row took over {:?}",
+                    self.client_addr,
+                    self.timeouts,
+                    self.policy.send_timeout
+                );
+                self.metrics.record_slow_consumer_drop();
+                // Bounded too: the client may not read this frame either
+                let _ = tokio::time::timeout(
+                    self.policy.send_timeout,
+                    send_close(client_ws_write, self.client_addr, CloseReason::SlowConsumer),
+                )
+                .await;
+                Err(SendError::SlowConsumer)
+            }
+        }
+    }
+
+    /// Record how long a send took; returns the new state on a transition
+    fn record(&mut self, send_time: Duration) -> Option<bool> {
+        let sample_us = send_time.as_micros() as f64;
+        self.ewma_us = Self::ALPHA * sample_us + (1.0 - Self::ALPHA) * self.ewma_us;
+
+        let enter_us = self.policy.enter_above.as_micros() as f64;
+        let exit_us = self.policy.exit_below.as_micros() as f64;
+        if !self.degraded && self.ewma_us > enter_us {
+            self.degraded = true;
+            self.metrics.increment_degraded_clients();
+            Some(true)
+        } else if self.degraded && self.ewma_us < exit_us {
+            self.degraded = false;
+            self.metrics.decrement_degraded_clients();
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
+impl Drop for SendPressure {
+    fn drop(&mut self) {
+        if self.degraded {
+            self.metrics.decrement_degraded_clients();
+        }
+    }
+}
+
+/// Socket options for the client listener and accepted connections
+///
+/// Defaults: a backlog of 1024 pending connections (the kernel caps it at
+/// `net.core.somaxconn`), `TCP_NODELAY` on every client so small pushes go
+/// out without Nagle's delay, and `SO_REUSEADDR` so a restart can rebind
+/// while old connections sit in TIME_WAIT.
+#[derive(Debug, Clone, Copy)]
+pub struct ListenerOptions {
+    pub backlog: u32,
+    pub nodelay: bool,
+    pub reuse_addr: bool,
+}
+
+impl Default for ListenerOptions {
+    fn default() -> Self {
+        Self {
+            backlog: 1024,
+            nodelay: true,
+            reuse_addr: true,
+        }
+    }
+}
+
+impl ListenerOptions {
+    /// Bind a listener on `addr` with these options
+    pub fn bind(&self, addr: SocketAddr) -> std::io::Result<TcpListener> {
+        let socket = if addr.is_ipv4() {
+            TcpSocket::new_v4()?
+        } else {
+            TcpSocket::new_v6()?
+        };
+        socket.set_reuseaddr(self.reuse_addr)?;
+        socket.bind(addr)?;
+        socket.listen(self.backlog)
+    }
+}
+
+/// Shared state handed to every client connection
+#[derive(Clone)]
+pub struct ServerState {
+    pub orderbook_manager: SharedOrderBookManager,
+    pub metrics: SharedMetrics,
+    pub client_broadcast_tx: broadcast::Sender<ClientMessage>,
+    pub symbol_registry: SymbolRegistry,
+    pub candle_store: SharedCandleStore,
+    pub trade_history: SharedTradeHistory,
+    /// Secret clients must present to add/remove tracked symbols at runtime
+    /// (None: admin commands disabled), see `presents_admin_token`
+    pub admin_token: Option<String>,
+    pub degrade_policy: DegradePolicy,
+    pub client_limits: ClientLimits,
+    pub client_slots: ClientSlots,
+    pub origin_policy: OriginPolicy,
+    /// Secret clients must present to connect (None: no auth), see `presents_token`
+    pub auth_token: Option<String>,
+    /// Levels per side sent to clients until they ask for another depth
+    /// (storage depth is set on the book manager)
+    pub display_depth: usize,
+    /// How often each client's books are checked for changes to send: lower is
+    /// snappier, higher costs less CPU
+    pub book_poll_interval: Duration,
+    /// Flips to true on shutdown: stop accepting and close every client
+    pub shutdown: watch::Receiver<bool>,
+}
 
 /// Start the WebSocket server for frontend clients
 pub async fn start_server(
     addr: &str,
-    orderbook_manager: SharedOrderBookManager,
-    metrics: SharedMetrics,
-    client_broadcast_tx: broadcast::Sender<ClientMessage>,
+    state: ServerState,
+    options: ListenerOptions,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let listener = TcpListener::bind(addr).await?;
-    tracing::info!("WebSocket server listening on {}", addr);
+    let listener = options.bind(addr.parse()?)?;
+    tracing::info!(
+        "WebSocket server listening on {} (backlog {}, nodelay {})",
+        addr,
+        options.backlog,
+        options.nodelay
+    );
+
+    let mut shutdown = state.shutdown.clone();
+    let mut clients = JoinSet::new();
+    loop {
+        let (client_stream, client_addr) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(_) => break,
+            },
+            _ = shutting_down(&mut shutdown) => break,
+            // Reap finished handlers so the set only holds live clients
+            Some(_) = clients.join_next(), if !clients.is_empty() => continue,
+        };
+
+        if let Err(e) = client_stream.set_nodelay(options.nodelay) {
+            tracing::warn!("Failed to set TCP_NODELAY for {}: {}", client_addr, e);
+        }
 
-    while let Ok((client_stream, client_addr)) = listener.accept().await {
         // Clone shared state for this client
-        let orderbook_manager = orderbook_manager.clone();
-        let metrics = metrics.clone();
-        let client_broadcast_rx = client_broadcast_tx.subscribe();
+        let state = state.clone();
+        let metrics = state.metrics.clone();
 
         metrics.increment_connections();
 
         // Spawn handler for this client
-        tokio::spawn(async move {
-            if let Err(e) = handle_client(
-                client_stream,
-                client_addr,
-                orderbook_manager,
-                metrics.clone(),
-                client_broadcast_rx,
-            )
-            .await
-            {
+        clients.spawn(async move {
+            if let Err(e) = handle_client(client_stream, client_addr, state).await {
                 tracing::error!("Client {} error: {}", client_addr, e);
             }
             metrics.decrement_connections();
         });
     }
 
+    // No new connections from here; handlers see the same flag and close
+    drop(listener);
+    tracing::info!("Shutting down, closing {} client(s)", clients.len());
+    let drained = tokio::time::timeout(CLIENT_DRAIN_TIMEOUT, async {
+        while clients.join_next().await.is_some() {}
+    })
+    .await;
+    if drained.is_err() {
+        tracing::warn!(
+            "{} client(s) still open after {:?}, dropping them",
+            clients.len(),
+            CLIENT_DRAIN_TIMEOUT
+        );
+    }
+
     Ok(())
 }
 
-async fn handle_client(
+/// Resolve once shutdown is signalled (never, if the sender is gone)
+async fn shutting_down(shutdown: &mut watch::Receiver<bool>) {
+    if shutdown.wait_for(|&stop| stop).await.is_err() {
+        std::future::pending::<()>().await;
+    }
+}
+
+/// Whether a client with `filter` streams `symbol` (an empty filter streams all)
+fn wants_symbol(filter: &HashSet<String>, symbol: &str) -> bool {
+    filter.is_empty() || filter.contains(symbol)
+}
+
+/// Reply to a client ping, stamped with the server clock (epoch millis)
+fn pong(client_time: i64) -> ClientMessage {
+    ClientMessage::Pong {
+        client_time,
+        server_time: chrono::Utc::now().timestamp_millis(),
+    }
+}
+
+/// How messages to one client are serialized, picked at connect time
+///
+/// JSON text frames by default. `?encoding=cbor` on the WebSocket URL switches
+/// to CBOR binary frames: same serde layout (tags, field names), smaller on the
+/// wire. CBOR rather than bincode because it is self-describing, so tagged enums
+/// and skipped fields still round-trip.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WireEncoding {
+    #[default]
+    Json,
+    Cbor,
+}
+
+impl WireEncoding {
+    /// Encoding requested by the handshake query string (JSON if absent or unknown)
+    pub fn from_query(query: Option<&str>) -> Self {
+        let requested = query
+            .unwrap_or_default()
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("encoding="));
+        match requested {
+            Some(value) if value.eq_ignore_ascii_case("cbor") => WireEncoding::Cbor,
+            _ => WireEncoding::Json,
+        }
+    }
+
+    /// Serialize `client_msg` into a text (JSON) or binary (CBOR) frame
+    pub fn encode(
+        self,
+        client_msg: &ClientMessage,
+    ) -> Result<Message, Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            WireEncoding::Json => Ok(Message::Text(serde_json::to_string(client_msg)?.into())),
+            WireEncoding::Cbor => {
+                let mut bytes = Vec::new();
+                ciborium::into_writer(client_msg, &mut bytes)?;
+                Ok(Message::Binary(bytes.into()))
+            }
+        }
+    }
+}
+
+/// What a client streams, picked by the handshake path
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ClientFeed {
+    /// Books, trades, metrics and commands (any path but the ones below)
+    #[default]
+    Market,
+    /// `/metrics-stream`: `Metrics` reports only, for monitoring clients
+    MetricsOnly,
+}
+
+impl ClientFeed {
+    pub fn from_path(path: &str) -> Self {
+        match path.trim_end_matches('/') {
+            "/metrics-stream" => ClientFeed::MetricsOnly,
+            _ => ClientFeed::Market,
+        }
+    }
+}
+
+/// Whether a handshake (or an HTTP request for book data) carries `token`, as
+/// `Authorization: Bearer <token>` or a `token=<token>` query parameter
+///
+/// The query form is for browsers, which can't set headers on a WebSocket; the
+/// token is compared as is (no percent-decoding), so keep it URL-safe.
+pub fn presents_token<B>(request: &tungstenite::http::Request<B>, token: &str) -> bool {
+    let bearer = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim);
+    let query = request
+        .uri()
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("token="));
+    [bearer, query]
+        .into_iter()
+        .flatten()
+        .any(|presented| constant_time_eq(presented.as_bytes(), token.as_bytes()))
+}
+
+/// Whether a request carries the admin `token` as an `admin_token=<token>`
+/// query parameter, or as `Authorization: Bearer <token>` (HTTP admin endpoints)
+///
+/// Separate from `presents_token`: that one lets a client read, this one lets
+/// it change what every client reads.
+pub fn presents_admin_token<B>(request: &tungstenite::http::Request<B>, token: &str) -> bool {
+    let bearer = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim);
+    let query = request
+        .uri()
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("admin_token="));
+    [bearer, query]
+        .into_iter()
+        .flatten()
+        .any(|presented| constant_time_eq(presented.as_bytes(), token.as_bytes()))
+}
+
+/// Byte comparison whose time doesn't depend on where the inputs differ
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// 401 sent instead of the upgrade when the token is missing or wrong
+fn unauthorized() -> ErrorResponse {
+    let mut response = ErrorResponse::new(Some("missing or invalid token".to_string()));
+    *response.status_mut() = StatusCode::UNAUTHORIZED;
+    response
+        .headers_mut()
+        .insert(WWW_AUTHENTICATE, "Bearer".parse().unwrap());
+    response
+}
+
+/// Bids and asks last sent to a client for one book
+type SentLevels = (Vec<PriceLevel>, Vec<PriceLevel>);
+
+/// `BookDelta` taking a client from the levels it was `sent` to `update`'s
+///
+/// None when none of them moved (the change was deeper than the client's depth).
+fn book_delta(update: &BookUpdate, sent: &SentLevels, version: u64) -> Option<ClientMessage> {
+    let (changed_bids, removed_bids) = diff_levels(&sent.0, &update.bids);
+    let (changed_asks, removed_asks) = diff_levels(&sent.1, &update.asks);
+    if changed_bids.is_empty()
+        && removed_bids.is_empty()
+        && changed_asks.is_empty()
+        && removed_asks.is_empty()
+    {
+        return None;
+    }
+    Some(ClientMessage::BookDelta {
+        exchange: update.exchange.clone(),
+        symbol: update.symbol.clone(),
+        changed_bids,
+        removed_bids,
+        changed_asks,
+        removed_asks,
+        version,
+    })
+}
+
+/// Levels of `current` new or resized since `sent`, and prices of `sent` gone from it
+fn diff_levels(sent: &[PriceLevel], current: &[PriceLevel]) -> (Vec<PriceLevel>, Vec<Decimal>) {
+    let sent_qty: HashMap<Decimal, Decimal> = sent
+        .iter()
+        .map(|level| (level.price, level.quantity))
+        .collect();
+    let current_prices: HashSet<Decimal> = current.iter().map(|level| level.price).collect();
+    let changed = current
+        .iter()
+        .filter(|level| sent_qty.get(&level.price) != Some(&level.quantity))
+        .cloned()
+        .collect();
+    let removed = sent
+        .iter()
+        .map(|level| level.price)
+        .filter(|price| !current_prices.contains(price))
+        .collect();
+    (changed, removed)
+}
+
+/// Whether the handshake query string turns `flag` on (`flag=1` or `flag=true`)
+fn query_flag(query: Option<&str>, flag: &str) -> bool {
+    query
+        .unwrap_or_default()
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .any(|(name, value)| name == flag && (value == "1" || value.eq_ignore_ascii_case("true")))
+}
+
+/// A client past the handshake, with what it asked for
+struct Handshake {
+    ws_stream: WebSocketStream<TcpStream>,
+    origin: Option<String>,
+    encoding: WireEncoding,
+    feed: ClientFeed,
+    /// `?book_deltas=1`: books go out in full once, then as `BookDelta`s
+    book_deltas: bool,
+    /// Presented the admin token: may send admin commands
+    admin: bool,
+}
+
+/// Why a client didn't get past the handshake
+enum HandshakeError {
+    /// `auth_token` is set and the client didn't present it (401 sent)
+    Unauthorized,
+    WebSocket(tungstenite::Error),
+}
+
+/// Complete the WebSocket handshake, capturing the client's `Origin` header,
+/// the options of its query string and the feed its path selects
+///
+/// With an `auth_token`, clients without it get a 401 and no upgrade. Clients
+/// without the `admin_token` connect, but not as admins.
+// The callback's error type is tungstenite's full HTTP response
+#[allow(clippy::result_large_err)]
+async fn accept_handshake(
     client_tcp_stream: TcpStream,
+    auth_token: Option<&str>,
+    admin_token: Option<&str>,
+) -> Result<Handshake, HandshakeError> {
+    let mut authorized = true;
+    let mut admin = false;
+    let mut origin = None;
+    let mut encoding = WireEncoding::Json;
+    let mut feed = ClientFeed::Market;
+    let mut book_deltas = false;
+    let client_ws_stream = tokio_tungstenite::accept_hdr_async(
+        client_tcp_stream,
+        |request: &Request, response: Response| {
+            if auth_token.is_some_and(|token| !presents_token(request, token)) {
+                authorized = false;
+                return Err(unauthorized());
+            }
+            origin = request
+                .headers()
+                .get(ORIGIN)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string);
+            encoding = WireEncoding::from_query(request.uri().query());
+            feed = ClientFeed::from_path(request.uri().path());
+            book_deltas = query_flag(request.uri().query(), "book_deltas");
+            admin = admin_token.is_some_and(|token| presents_admin_token(request, token));
+            Ok(response)
+        },
+    )
+    .await
+    .map_err(|e| {
+        if authorized {
+            HandshakeError::WebSocket(e)
+        } else {
+            HandshakeError::Unauthorized
+        }
+    })?;
+    Ok(Handshake {
+        ws_stream: client_ws_stream,
+        origin,
+        encoding,
+        feed,
+        book_deltas,
+        admin,
+    })
+}
+
+/// Serve a `/metrics-stream` client: the `Metrics` broadcasts and nothing else
+///
+/// No book polling, trades or commands; the client only has to read.
+async fn stream_metrics(
+    mut client_ws_write: impl Sink<Message> + Unpin,
+    mut client_ws_read: impl futures_util::Stream<Item = Result<Message, tungstenite::Error>> + Unpin,
     client_addr: SocketAddr,
-    orderbook_manager: SharedOrderBookManager,
-    metrics: SharedMetrics,
     mut client_broadcast_rx: broadcast::Receiver<ClientMessage>,
+    encoding: WireEncoding,
+    mut send_pressure: SendPressure,
+    mut shutdown: watch::Receiver<bool>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let client_msg = ClientMessage::Metrics(send_pressure.metrics.compute_metrics());
+    send_pressure
+        .send(&mut client_ws_write, encoding.encode(&client_msg)?)
+        .await?;
+
+    loop {
+        tokio::select! {
+            broadcast_result = client_broadcast_rx.recv() => match broadcast_result {
+                Ok(client_msg @ ClientMessage::Metrics(_)) => {
+                    let frame = encoding.encode(&client_msg)?;
+                    if let Err(e) = send_pressure.send(&mut client_ws_write, frame).await {
+                        tracing::debug!("Failed to send to client {}: {}", client_addr, e);
+                        break;
+                    }
+                }
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    send_pressure.metrics.record_broadcast_lag(missed);
+                }
+                Err(broadcast::error::RecvError::Closed) => {
+                    send_close(&mut client_ws_write, client_addr, CloseReason::Shutdown).await;
+                    break;
+                }
+            },
+            _ = shutting_down(&mut shutdown) => {
+                send_close(&mut client_ws_write, client_addr, CloseReason::Shutdown).await;
+                break;
+            }
+            client_ws_msg = client_ws_read.next() => match client_ws_msg {
+                Some(Ok(Message::Close(_))) | None => break,
+                Some(Ok(Message::Ping(data))) => {
+                    let _ = send_pressure.send(&mut client_ws_write, Message::Pong(data)).await;
+                }
+                Some(Ok(_)) => {}
+                Some(Err(e)) => {
+                    tracing::debug!("Client {} WebSocket error: {}", client_addr, e);
+                    send_close(&mut client_ws_write, client_addr, CloseReason::ProtocolError).await;
+                    break;
+                }
+            },
+        }
+    }
+
+    tracing::info!("Metrics client {} handler finished", client_addr);
+    Ok(())
+}
+
+async fn handle_client(
+    client_tcp_stream: TcpStream,
+    client_addr: SocketAddr,
+    state: ServerState,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let ServerState {
+        orderbook_manager,
+        metrics,
+        client_broadcast_tx,
+        symbol_registry,
+        candle_store,
+        trade_history,
+        admin_token,
+        degrade_policy,
+        client_limits,
+        client_slots,
+        origin_policy,
+        auth_token,
+        mut display_depth,
+        book_poll_interval,
+        mut shutdown,
+    } = state;
     tracing::info!("New client connected: {}", client_addr);
+    let mut client_broadcast_rx = client_broadcast_tx.subscribe();
 
-    let client_ws_stream = tokio_tungstenite::accept_async(client_tcp_stream).await?;
+    let Handshake {
+        ws_stream: client_ws_stream,
+        origin,
+        encoding,
+        feed,
+        book_deltas,
+        admin,
+    } = match accept_handshake(
+        client_tcp_stream,
+        auth_token.as_deref(),
+        admin_token.as_deref(),
+    )
+    .await
+    {
+        Ok(handshake) => handshake,
+        Err(HandshakeError::Unauthorized) => {
+            tracing::warn!("Rejecting client {}: missing or invalid token", client_addr);
+            metrics.record_auth_rejection();
+            return Ok(());
+        }
+        Err(HandshakeError::WebSocket(e)) => return Err(e.into()),
+    };
     let (mut client_ws_write, mut client_ws_read) = client_ws_stream.split();
 
+    // Finish the handshake first so the client gets a close code it can act on
+    if !origin_policy.allows(origin.as_deref()) {
+        tracing::warn!(
+            "Rejecting client {}: origin {:?} not allowed",
+            client_addr,
+            origin
+        );
+        send_close(
+            &mut client_ws_write,
+            client_addr,
+            CloseReason::PolicyViolation,
+        )
+        .await;
+        return Ok(());
+    }
+
+    // Held until this handler returns
+    let Some(_slot) = client_slots.acquire() else {
+        tracing::warn!("Rejecting client {}: server at capacity", client_addr);
+        send_close(&mut client_ws_write, client_addr, CloseReason::AtCapacity).await;
+        return Ok(());
+    };
+
+    // Snapshot-only fallback when this client can't keep up, and a disconnect
+    // once it stops reading
+    let mut send_pressure = SendPressure::new(degrade_policy, metrics.clone(), client_addr);
+
+    if feed == ClientFeed::MetricsOnly {
+        return stream_metrics(
+            client_ws_write,
+            client_ws_read,
+            client_addr,
+            client_broadcast_rx,
+            encoding,
+            send_pressure,
+            shutdown,
+        )
+        .await;
+    }
+
     // Send initial snapshot
-    let symbols: Vec<String> = TRADING_PAIRS.iter().map(|s| s.to_string()).collect();
-    let client_msg = ClientMessage::SymbolList(symbols);
-    let json = serde_json::to_string(&client_msg)?;
-    client_ws_write.send(Message::Text(json.into())).await?;
+    for client_msg in symbol_messages(&symbol_registry, &orderbook_manager) {
+        let frame = encoding.encode(&client_msg)?;
+        send_pressure.send(&mut client_ws_write, frame).await?;
+    }
+
+    // Which exchange:symbol books have data, sent again as more initialize
+    let streams = orderbook_manager.stream_list();
+    let mut announced_streams = initialized_streams(&streams);
+    let frame = encoding.encode(&ClientMessage::StreamList(streams))?;
+    send_pressure.send(&mut client_ws_write, frame).await?;
 
     for entry in orderbook_manager.iter() {
         let book = entry.value();
         if book.is_initialized() {
-            let client_msg = book.to_client_message(ORDERBOOK_DISPLAY_DEPTH);
-            let json = serde_json::to_string(&client_msg)?;
-            client_ws_write.send(Message::Text(json.into())).await?;
+            let client_msg = book.to_client_message(display_depth);
+            let frame = encoding.encode(&client_msg)?;
+            send_pressure.send(&mut client_ws_write, frame).await?;
         }
     }
 
+    // Then the recent tape, so a late client doesn't start from an empty trade list
+    let recent_trades = trade_history.latest(&[], INITIAL_TAPE_LEN);
+    if !recent_trades.is_empty() {
+        let frame = encoding.encode(&ClientMessage::TradeHistory(recent_trades))?;
+        send_pressure.send(&mut client_ws_write, frame).await?;
+    }
+
     let current_metrics = metrics.compute_metrics();
     let client_msg = ClientMessage::Metrics(current_metrics);
-    let json = serde_json::to_string(&client_msg)?;
-    client_ws_write.send(Message::Text(json.into())).await?;
+    let frame = encoding.encode(&client_msg)?;
+    send_pressure.send(&mut client_ws_write, frame).await?;
+
+    // Track last sent content version per orderbook to avoid redundant sends
+    // Key -> (content version, staleness) last sent, so a book freezing is sent once more
+    let mut last_sent_version: HashMap<String, (u64, bool)> = HashMap::new();
+    // Levels last sent per book, to diff against (book_deltas clients only)
+    let mut last_sent_levels: HashMap<String, SentLevels> = HashMap::new();
 
-    // Track last sent update_id per orderbook to avoid redundant sends
-    let mut last_sent_update_id: HashMap<String, u64> = HashMap::new();
+    // Optional trade-tape cap, set by the client's subscribe request
+    let mut trade_limiter: Option<TokenBucket> = None;
+    let mut summarize_dropped = false;
+    let mut quote_flicker = false;
+    // Opt-in cross-exchange book per changed symbol, after each book batch
+    let mut consolidated_books = false;
+    // Opt-in stream of candles as they close
+    let mut candles = false;
+    // Opt-in size for the VWAPs added to each book update
+    let mut vwap_quantity: Option<Decimal> = None;
+    // Opt-in merged tape replacing the per-exchange trade stream
+    let mut consolidated_tape: Option<ConsolidatedTape> = None;
+    // Symbols this client streams (empty: all of them)
+    let mut symbol_filter: HashSet<String> = HashSet::new();
+    let mut dropped_trades: u64 = 0;
+    let mut dropped_since_summary: u64 = 0;
+    let mut last_dropped_summary = Instant::now();
+    let mut command_limiter = (client_limits.commands_per_sec > 0)
+        .then(|| TokenBucket::new(client_limits.commands_per_sec));
+    let mut dropped_commands: u64 = 0;
+    // Broadcast messages skipped because this client fell behind the channel
+    let mut lagged_messages: u64 = 0;
+    let mut lag_events: u32 = 0;
+
+    let mut last_book_send = Instant::now();
 
     // Poll orderbooks periodically and send only if changed
-    let mut book_poll_ticker = interval(Duration::from_millis(BOOK_POLL_MS));
+    let mut book_poll_ticker = interval(book_poll_interval);
     book_poll_ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let mut tape_ticker = interval(Duration::from_millis(TAPE_FLUSH_MS));
+    tape_ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
 
-    let mut messages_buffer = Vec::with_capacity(TRADING_PAIRS.len());
+    // Sized for the runtime symbol list (grows if symbols are added later)
+    let mut messages_buffer = Vec::with_capacity(symbol_registry.current().len());
     loop {
         tokio::select! {
             // Poll orderbooks and send updates if changed
             _ = book_poll_ticker.tick() => {
+                if summarize_dropped
+                    && dropped_since_summary > 0
+                    && last_dropped_summary.elapsed() >= DROPPED_SUMMARY_INTERVAL
+                {
+                    let frame = encoding.encode(&ClientMessage::TradesDropped(dropped_since_summary))?;
+                    if send_pressure.send(&mut client_ws_write, frame).await.is_err() {
+                        return Ok(());
+                    }
+                    dropped_since_summary = 0;
+                    last_dropped_summary = Instant::now();
+                }
+
+                // Degraded: only a coarse periodic snapshot
+                let min_interval = if send_pressure.degraded {
+                    degrade_policy.snapshot_interval
+                } else {
+                    client_limits.min_book_interval
+                };
+                if last_book_send.elapsed() < min_interval {
+                    continue;
+                }
+
                 messages_buffer.clear();
+                let mut new_stream = false;
                 for entry in orderbook_manager.iter() {
                     let book = entry.value();
 
                     if !book.is_initialized() {
                         continue;
                     }
+                    new_stream |= !announced_streams.contains(entry.key());
+                    if !wants_symbol(&symbol_filter, book.symbol()) {
+                        continue;
+                    }
 
                     let key = entry.key().clone();
-                    let current_update_id = book.last_update_id();
+                    let current = (book.content_version(), book.is_stale());
 
-                    // Check if this orderbook has been updated since last send
-                    let should_send = match last_sent_update_id.get(&key) {
-                        Some(&last_id) => current_update_id != last_id,
+                    // Check if this orderbook has been updated (or gone stale) since last send
+                    let should_send = match last_sent_version.get(&key) {
+                        Some(&last) => current != last,
                         None => true, // First time seeing this book
                     };
 
                     if should_send {
-                        // On construit le message (copie mémoire)
-                        let client_msg = book.to_client_message(ORDERBOOK_DISPLAY_DEPTH);
+                        // On construit l'update (copie mémoire)
+                        let mut update = book.to_book_update(display_depth);
+                        if let Some(quantity) = vwap_quantity {
+                            update.pricing = Some(book.pricing(display_depth, quantity));
+                        }
 
-                        // On stocke le message et la clé pour mettre à jour l'ID après
-                        messages_buffer.push((key, current_update_id, client_msg));
+                        // On stocke l'update et la clé pour mettre à jour l'ID après
+                        messages_buffer.push((key, current, update));
                     }
                 }
+                // Announced before its first update so the client knows where it fits
+                if new_stream {
+                    let streams = orderbook_manager.stream_list();
+                    announced_streams = initialized_streams(&streams);
+                    let frame = encoding.encode(&ClientMessage::StreamList(streams))?;
+                    if send_pressure.send(&mut client_ws_write, frame).await.is_err() {
+                        return Ok(());
+                    }
+                }
+                if messages_buffer.is_empty() {
+                    continue;
+                }
+
                 // PHASE 2: Envoi Réseau (Lent, Async, sans verrou)
-                for (key, update_id, client_msg) in messages_buffer.drain(..) {
-                    if let Ok(json) = serde_json::to_string(&client_msg) {
-                        if let Err(e) = client_ws_write.send(Message::Text(json.into())).await {
-                            tracing::debug!("Failed to send book update to client {}: {}", client_addr, e);
-                            // Si le client est déconnecté, on arrête tout
-                            return Ok(());
+                // Un seul frame BookBatch par tick au lieu d'un message par book
+                let mut sent_versions = Vec::with_capacity(messages_buffer.len());
+                let mut sent_levels = Vec::new();
+                let mut updates = Vec::with_capacity(messages_buffer.len());
+                let mut book_messages = Vec::new();
+                let mut changed_symbols = Vec::new();
+                for (key, sent, update) in messages_buffer.drain(..) {
+                    if consolidated_books && !changed_symbols.contains(&update.symbol) {
+                        changed_symbols.push(update.symbol.clone());
+                    }
+                    if book_deltas {
+                        // A book freezing or thawing is sent in full with its flag
+                        let previous = last_sent_levels
+                            .get(&key)
+                            .filter(|_| last_sent_version.get(&key).is_some_and(|last| last.1 == sent.1));
+                        let is_delta = previous.is_some();
+                        if let Some(levels) = previous {
+                            book_messages.extend(book_delta(&update, levels, sent.0));
                         }
-                        // On ne met à jour l'ID que si l'envoi a réussi
-                        last_sent_update_id.insert(key, update_id);
+                        sent_levels.push((key.clone(), (update.bids.clone(), update.asks.clone())));
+                        if is_delta {
+                            sent_versions.push((key, sent));
+                            continue;
+                        }
+                    }
+                    sent_versions.push((key, sent));
+                    updates.push(update);
+                }
+                if !updates.is_empty() {
+                    book_messages.insert(0, ClientMessage::BookBatch(updates));
+                }
+                let send_start = Instant::now();
+                for client_msg in &book_messages {
+                    let frame = encoding.encode(client_msg)?;
+                    if let Err(e) = send_pressure.send(&mut client_ws_write, frame).await {
+                        tracing::debug!("Failed to send books to client {}: {}", client_addr, e);
+                        // Si le client est déconnecté, on arrête tout
+                        return Ok(());
+                    }
+                }
+                if !book_messages.is_empty() {
+                    last_book_send = Instant::now();
+                    if let Some(degraded) = send_pressure.record(send_start.elapsed()) {
+                        tracing::info!("Client {} snapshot-only mode: {}", client_addr, degraded);
+                    }
+                }
+                // On ne met à jour les IDs que si l'envoi a réussi
+                last_sent_version.extend(sent_versions);
+                last_sent_levels.extend(sent_levels);
+
+                for symbol in changed_symbols {
+                    let Some(book) = orderbook_manager.consolidated_book(&symbol, display_depth) else {
+                        continue;
+                    };
+                    let frame = encoding.encode(&ClientMessage::ConsolidatedBookUpdate(book))?;
+                    if send_pressure.send(&mut client_ws_write, frame).await.is_err() {
+                        return Ok(());
+                    }
+                }
+            }
+
+            // Release trades the consolidated tape no longer needs to reorder
+            _ = tape_ticker.tick(), if consolidated_tape.is_some() => {
+                let released = consolidated_tape
+                    .as_mut()
+                    .map(|tape| tape.drain_ready(Instant::now()))
+                    .unwrap_or_default();
+                for trade in released {
+                    // Unsubscribed while buffered
+                    if !wants_symbol(&symbol_filter, &trade.trade.symbol) {
+                        continue;
+                    }
+                    let frame = encoding.encode(&ClientMessage::ConsolidatedTrade(trade))?;
+                    if send_pressure.send(&mut client_ws_write, frame).await.is_err() {
+                        return Ok(());
                     }
                 }
             }
@@ -139,29 +1128,75 @@ async fn handle_client(
                 match broadcast_result {
                     Ok(client_msg) => {
                         match &client_msg {
-                            ClientMessage::BookUpdate { .. } => {
+                            ClientMessage::BookUpdate(_) | ClientMessage::BookBatch(_) => {
                                 // BookUpdates are no longer sent via broadcast - ignore
                             }
+                            ClientMessage::Trade(trade) if !wants_symbol(&symbol_filter, &trade.symbol) => {
+                                // Not subscribed by this client
+                            }
+                            ClientMessage::QuoteFlicker(_) if !quote_flicker || send_pressure.degraded => {
+                                // Opt-in only, and not worth sending to a client that can't keep up
+                            }
+                            ClientMessage::Candle(candle)
+                                if !candles || send_pressure.degraded || !wants_symbol(&symbol_filter, &candle.symbol) =>
+                            {
+                                // Opt-in only, for the subscribed symbols
+                            }
+                            ClientMessage::Trade(_)
+                                if send_pressure.degraded
+                                    || trade_limiter.as_mut().is_some_and(|bucket| !bucket.try_take()) =>
+                            {
+                                // Degraded or over the client's trade cap - drop it
+                                dropped_trades += 1;
+                                dropped_since_summary += 1;
+                            }
+                            ClientMessage::Trade(trade) if consolidated_tape.is_some() => {
+                                if let Some(tape) = consolidated_tape.as_mut() {
+                                    tape.push(trade.clone(), Instant::now());
+                                }
+                            }
                             _ => {
                                 // Send trades and metrics immediately (no throttling)
-                                let json = serde_json::to_string(&client_msg)?;
-                                if let Err(e) = client_ws_write.send(Message::Text(json.into())).await {
+                                let frame = encoding.encode(&client_msg)?;
+                                let send_start = Instant::now();
+                                if let Err(e) = send_pressure.send(&mut client_ws_write, frame).await {
                                     tracing::debug!("Failed to send to client {}: {}", client_addr, e);
                                     break;
                                 }
+                                if let Some(degraded) = send_pressure.record(send_start.elapsed()) {
+                                    tracing::info!("Client {} snapshot-only mode: {}", client_addr, degraded);
+                                }
                             }
                         }
                     }
-                    Err(broadcast::error::RecvError::Lagged(_n)) => {
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
                         // Client lagged on Trades/Metrics - not critical, just skip
+                        metrics.record_broadcast_lag(missed);
+                        lagged_messages += missed;
+                        lag_events += 1;
+                        if lag_events > 1 {
+                            tracing::debug!(
+                                "Client {} lagged again, {} broadcast messages missed ({} so far)",
+                                client_addr,
+                                missed,
+                                lagged_messages
+                            );
+                        }
                     }
                     Err(broadcast::error::RecvError::Closed) => {
                         tracing::info!("Broadcast channel closed");
+                        send_close(&mut client_ws_write, client_addr, CloseReason::Shutdown).await;
                         break;
                     }
                 }
             }
 
+            // Process shutting down: tell the client to come back later
+            _ = shutting_down(&mut shutdown) => {
+                send_close(&mut client_ws_write, client_addr, CloseReason::Shutdown).await;
+                break;
+            }
+
             // Handle messages from client (ping/pong, close, etc.)
             client_ws_msg = client_ws_read.next() => {
                 match client_ws_msg {
@@ -170,10 +1205,174 @@ async fn handle_client(
                         break;
                     }
                     Some(Ok(Message::Ping(data))) => {
-                        let _ = client_ws_write.send(Message::Pong(data)).await;
+                        let _ = send_pressure.send(&mut client_ws_write, Message::Pong(data)).await;
+                    }
+                    Some(Ok(Message::Text(_)))
+                        if command_limiter.as_mut().is_some_and(|bucket| !bucket.try_take()) =>
+                    {
+                        if dropped_commands == 0 {
+                            tracing::warn!(
+                                "Client {} over {} commands/s, dropping the excess",
+                                client_addr,
+                                client_limits.commands_per_sec
+                            );
+                        }
+                        dropped_commands += 1;
+                    }
+                    Some(Ok(Message::Text(text))) => {
+                        let mut pending_error = None;
+                        match serde_json::from_str::<ClientCommand>(&text) {
+                            Ok(ClientCommand::Subscribe {
+                                max_trades_per_sec,
+                                summarize_dropped: summarize,
+                                quote_flicker: flicker,
+                                consolidated_trades,
+                                reorder_window_ms,
+                                consolidated_books: merged_books,
+                                candles: closed_candles,
+                                vwap_quantity: quantity,
+                                symbols,
+                            }) => {
+                                // An unknown symbol rejects the whole message, so a typo can
+                                // neither drop a symbol silently nor widen the filter to all
+                                let resolved = symbols.map(|symbols| {
+                                    let (known, unknown): (Vec<_>, Vec<_>) = symbols
+                                        .iter()
+                                        .map(|s| symbol_registry.resolve(s))
+                                        .partition(Result::is_ok);
+                                    if unknown.is_empty() {
+                                        Ok(known.into_iter().filter_map(Result::ok).collect::<HashSet<_>>())
+                                    } else {
+                                        let errors: Vec<_> = unknown.into_iter().filter_map(Result::err).collect();
+                                        Err(format!("subscribe rejected: {}", errors.join(", ")))
+                                    }
+                                });
+                                match resolved.transpose() {
+                                    Err(e) => pending_error = Some(e),
+                                    Ok(resolved) => {
+                                        if let Some(limit) = max_trades_per_sec {
+                                            trade_limiter = (limit > 0).then(|| TokenBucket::new(limit));
+                                        }
+                                        if let Some(summarize) = summarize {
+                                            summarize_dropped = summarize;
+                                        }
+                                        if let Some(flicker) = flicker {
+                                            quote_flicker = flicker;
+                                        }
+                                        if let Some(merged_books) = merged_books {
+                                            consolidated_books = merged_books;
+                                        }
+                                        if let Some(closed_candles) = closed_candles {
+                                            candles = closed_candles;
+                                        }
+                                        if let Some(quantity) = quantity {
+                                            vwap_quantity = Some(quantity).filter(|q| q.is_sign_positive() && !q.is_zero());
+                                        }
+                                        if consolidated_trades.is_some() || reorder_window_ms.is_some() {
+                                            // Trades still buffered in a dropped tape are lost, like a resubscribe
+                                            consolidated_tape = consolidated_trades
+                                                .unwrap_or(consolidated_tape.is_some())
+                                                .then(|| {
+                                                    ConsolidatedTape::new(
+                                                        reorder_window_ms
+                                                            .map(Duration::from_millis)
+                                                            .unwrap_or(DEFAULT_REORDER_WINDOW),
+                                                    )
+                                                });
+                                        }
+                                        if let Some(symbols) = resolved {
+                                            symbol_filter = symbols;
+                                        }
+                                        // Newly subscribed books go out on the next poll, changed or not
+                                        last_sent_version.clear();
+                                        last_sent_levels.clear();
+                                    }
+                                }
+                            }
+                            Ok(ClientCommand::SetDepth { levels }) => {
+                                display_depth = levels.clamp(1, orderbook_manager.max_depth());
+                                // Resend every book at the new depth on the next poll
+                                last_sent_version.clear();
+                                last_sent_levels.clear();
+                            }
+                            Ok(ClientCommand::Ping { client_time }) => {
+                                let frame = encoding.encode(&pong(client_time))?;
+                                if send_pressure.send(&mut client_ws_write, frame).await.is_err() {
+                                    break;
+                                }
+                            }
+                            Ok(ClientCommand::Candles { exchange, symbol, interval, count }) => {
+                                let reply = candle_history(
+                                    &symbol_registry,
+                                    &candle_store,
+                                    exchange.as_deref(),
+                                    &symbol,
+                                    interval,
+                                    count,
+                                );
+                                let frame = encoding.encode(&reply)?;
+                                if send_pressure.send(&mut client_ws_write, frame).await.is_err() {
+                                    break;
+                                }
+                            }
+                            Ok(ClientCommand::GetTrades { symbol, limit }) => {
+                                let reply = match symbol_registry.resolve(&symbol) {
+                                    Ok(symbol) => ClientMessage::TradeHistory(trade_history.recent(&symbol, limit)),
+                                    Err(e) => ClientMessage::Error(e),
+                                };
+                                let frame = encoding.encode(&reply)?;
+                                if send_pressure.send(&mut client_ws_write, frame).await.is_err() {
+                                    break;
+                                }
+                            }
+                            Ok(ClientCommand::EstimateFill { exchange, symbol, side, quantity }) => {
+                                let reply = estimate_fill(
+                                    &symbol_registry,
+                                    &orderbook_manager,
+                                    &exchange,
+                                    &symbol,
+                                    side,
+                                    quantity,
+                                );
+                                let frame = encoding.encode(&reply)?;
+                                if send_pressure.send(&mut client_ws_write, frame).await.is_err() {
+                                    break;
+                                }
+                            }
+                            Ok(command) if admin => {
+                                if let Err(e) = apply_command(
+                                    command,
+                                    &symbol_registry,
+                                    &orderbook_manager,
+                                    &client_broadcast_tx,
+                                ) {
+                                    tracing::warn!("Client {} command rejected: {}", client_addr, e);
+                                    pending_error = Some(e);
+                                }
+                            }
+                            Ok(_) if admin_token.is_some() => {
+                                tracing::warn!("Client {} sent an admin command without the admin token", client_addr);
+                                pending_error = Some("admin commands require the admin token".to_string());
+                            }
+                            Ok(_) => {
+                                tracing::warn!("Client {} sent an admin command but admin is disabled", client_addr);
+                                pending_error = Some("admin commands are disabled".to_string());
+                            }
+                            Err(e) => {
+                                tracing::debug!("Client {} sent an invalid command: {}", client_addr, e);
+                                pending_error = Some(format!("invalid command: {}", e));
+                            }
+                        }
+                        if let Some(e) = pending_error.take() {
+                            let frame = encoding.encode(&ClientMessage::Error(e))?;
+                            if send_pressure.send(&mut client_ws_write, frame).await.is_err() {
+                                break;
+                            }
+                        }
                     }
                     Some(Err(e)) => {
                         tracing::debug!("Client {} WebSocket error: {}", client_addr, e);
+                        send_close(&mut client_ws_write, client_addr, CloseReason::ProtocolError).await;
                         break;
                     }
                     None => {
@@ -188,6 +1387,1267 @@ async fn handle_client(
         }
     }
 
+    if dropped_trades > 0 {
+        tracing::info!(
+            "Client {} dropped {} trades over its rate limit",
+            client_addr,
+            dropped_trades
+        );
+    }
+    if dropped_commands > 0 {
+        tracing::info!(
+            "Client {} had {} commands dropped over the command limit",
+            client_addr,
+            dropped_commands
+        );
+    }
+    if lagged_messages > 0 {
+        tracing::info!(
+            "Client {} lagged {} time(s), missing {} broadcast messages",
+            client_addr,
+            lag_events,
+            lagged_messages
+        );
+    }
     tracing::info!("Client {} handler finished", client_addr);
     Ok(())
 }
+
+/// Apply an admin command and announce the new symbol list to every client
+///
+/// Exchange connections watch the registry and resubscribe on their own.
+fn apply_command(
+    command: ClientCommand,
+    symbol_registry: &SymbolRegistry,
+    orderbook_manager: &SharedOrderBookManager,
+    client_broadcast_tx: &broadcast::Sender<ClientMessage>,
+) -> Result<(), String> {
+    let changed = match command {
+        ClientCommand::AddSymbol { symbol } => {
+            let symbol =
+                normalize_symbol(&symbol).ok_or_else(|| format!("invalid symbol {:?}", symbol))?;
+            orderbook_manager.add_symbol(&symbol);
+            let added = symbol_registry.add(&symbol);
+            if added {
+                tracing::info!("Now tracking {}", symbol);
+            }
+            added
+        }
+        ClientCommand::RemoveSymbol { symbol } => {
+            let symbol = symbol_registry.resolve(&symbol)?;
+            symbol_registry.remove(&symbol);
+            orderbook_manager.remove_symbol(&symbol);
+            tracing::info!("Stopped tracking {}", symbol);
+            true
+        }
+        // Per-connection requests, handled in `handle_client`
+        ClientCommand::Subscribe { .. }
+        | ClientCommand::SetDepth { .. }
+        | ClientCommand::Candles { .. }
+        | ClientCommand::GetTrades { .. }
+        | ClientCommand::EstimateFill { .. }
+        | ClientCommand::Ping { .. } => false,
+    };
+
+    if changed {
+        for client_msg in symbol_messages(symbol_registry, orderbook_manager) {
+            let _ = client_broadcast_tx.send(client_msg);
+        }
+    }
+    Ok(())
+}
+
+/// Book keys ("exchange:symbol") of the initialized streams of a `StreamList`
+fn initialized_streams(streams: &[StreamInfo]) -> HashSet<String> {
+    streams
+        .iter()
+        .filter(|stream| stream.initialized)
+        .map(|stream| format!("{}:{}", stream.exchange, stream.symbol))
+        .collect()
+}
+
+/// Answer to `candles`: the series' closed candles, or why there are none
+fn candle_history(
+    symbol_registry: &SymbolRegistry,
+    candle_store: &CandleStore,
+    exchange: Option<&str>,
+    symbol: &str,
+    interval: CandleInterval,
+    count: usize,
+) -> ClientMessage {
+    let exchange = match exchange {
+        None => CONSOLIDATED_EXCHANGE,
+        Some(name) if name.eq_ignore_ascii_case(CONSOLIDATED_EXCHANGE) => CONSOLIDATED_EXCHANGE,
+        Some(name) => match Exchange::VENUES
+            .into_iter()
+            .find(|e| e.name().eq_ignore_ascii_case(name))
+        {
+            Some(exchange) => exchange.name(),
+            None => return ClientMessage::Error(format!("unknown exchange: {name}")),
+        },
+    };
+    match symbol_registry.resolve(symbol) {
+        Ok(symbol) => {
+            ClientMessage::CandleHistory(candle_store.history(exchange, &symbol, interval, count))
+        }
+        Err(e) => ClientMessage::Error(e),
+    }
+}
+
+/// Answer to `estimate_fill`: the fill estimate, or why there is none
+fn estimate_fill(
+    symbol_registry: &SymbolRegistry,
+    orderbook_manager: &SharedOrderBookManager,
+    exchange: &str,
+    symbol: &str,
+    side: TradeSide,
+    quantity: Decimal,
+) -> ClientMessage {
+    let Some(exchange) = Exchange::ALL
+        .into_iter()
+        .find(|e| e.name().eq_ignore_ascii_case(exchange))
+    else {
+        return ClientMessage::Error(format!("unknown exchange: {exchange}"));
+    };
+    let symbol = match symbol_registry.resolve(symbol) {
+        Ok(symbol) => symbol,
+        Err(e) => return ClientMessage::Error(e),
+    };
+    let Some(quantity) = (quantity * Decimal::from(QTY_FACTOR))
+        .trunc()
+        .to_u64()
+        .filter(|&q| q > 0)
+    else {
+        return ClientMessage::Error(format!("invalid quantity: {quantity}"));
+    };
+    let side = match side {
+        TradeSide::Buy => Side::Ask,
+        TradeSide::Sell => Side::Bid,
+    };
+
+    match orderbook_manager.get(exchange.name(), &symbol) {
+        Some(book) if book.is_initialized() => match book.market_order_impact(side, quantity) {
+            Some(estimate) => ClientMessage::FillEstimate(estimate),
+            None => ClientMessage::Error(format!("{} {} side is empty", exchange.name(), symbol)),
+        },
+        _ => ClientMessage::Error(format!(
+            "no initialized {} {} book",
+            exchange.name(),
+            symbol
+        )),
+    }
+}
+
+/// Tracked symbols as a bare `SymbolList` followed by their `SymbolInfo`
+fn symbol_messages(
+    symbol_registry: &SymbolRegistry,
+    orderbook_manager: &SharedOrderBookManager,
+) -> [ClientMessage; 2] {
+    let symbols = symbol_registry.current();
+    let infos = symbols
+        .iter()
+        .map(|symbol| orderbook_manager.symbol_info(symbol))
+        .collect();
+    [
+        ClientMessage::SymbolList(symbols),
+        ClientMessage::SymbolInfo(infos),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::create_shared_metrics;
+
+    #[test]
+    fn test_send_pressure_degrades_with_hysteresis() {
+        let metrics = create_shared_metrics();
+        let mut pressure =
+            SendPressure::new(DegradePolicy::default(), metrics.clone(), test_addr());
+
+        // One slow send is smoothed out, a sustained backlog is not
+        assert_eq!(pressure.record(Duration::from_millis(300)), None);
+        let mut transition = None;
+        for _ in 0..10 {
+            transition = transition.or(pressure.record(Duration::from_millis(300)));
+        }
+        assert_eq!(transition, Some(true));
+        #[cfg(feature = "metrics")]
+        assert_eq!(metrics.compute_metrics().degraded_clients, 1);
+
+        // Between the thresholds: stays degraded (no flapping)
+        for _ in 0..20 {
+            assert_eq!(pressure.record(Duration::from_millis(50)), None);
+        }
+
+        let mut transition = None;
+        for _ in 0..20 {
+            transition = transition.or(pressure.record(Duration::from_millis(1)));
+        }
+        assert_eq!(transition, Some(false));
+        #[cfg(feature = "metrics")]
+        assert_eq!(metrics.compute_metrics().degraded_clients, 0);
+    }
+
+    fn test_addr() -> SocketAddr {
+        "127.0.0.1:9".parse().unwrap()
+    }
+
+    /// Sink of a client that never reads: nothing is ever accepted
+    struct StuckSink;
+
+    impl Sink<Message> for StuckSink {
+        type Error = tungstenite::Error;
+
+        fn poll_ready(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<Result<(), Self::Error>> {
+            std::task::Poll::Pending
+        }
+
+        fn start_send(self: std::pin::Pin<&mut Self>, _item: Message) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn poll_flush(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<Result<(), Self::Error>> {
+            std::task::Poll::Pending
+        }
+
+        fn poll_close(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<Result<(), Self::Error>> {
+            std::task::Poll::Pending
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client_that_stops_reading_is_dropped() {
+        let metrics = create_shared_metrics();
+        let policy = DegradePolicy {
+            send_timeout: Duration::from_millis(10),
+            max_send_timeouts: 3,
+            ..DegradePolicy::default()
+        };
+        let mut pressure = SendPressure::new(policy, metrics.clone(), test_addr());
+
+        // A successful send resets the count of timeouts in a row
+        for _ in 0..2 {
+            assert_eq!(
+                pressure.send(&mut StuckSink, Message::text("a")).await,
+                Ok(())
+            );
+        }
+        let mut drain = futures_util::sink::drain();
+        assert_eq!(pressure.send(&mut drain, Message::text("b")).await, Ok(()));
+
+        for _ in 0..2 {
+            assert_eq!(
+                pressure.send(&mut StuckSink, Message::text("c")).await,
+                Ok(())
+            );
+        }
+        assert_eq!(
+            pressure.send(&mut StuckSink, Message::text("d")).await,
+            Err(SendError::SlowConsumer)
+        );
+        #[cfg(feature = "metrics")]
+        assert_eq!(metrics.compute_metrics().slow_consumer_drops, 1);
+    }
+
+    /// Sink whose data frames never flush, while a Close frame goes straight out
+    #[derive(Default)]
+    struct CloseOnlySink(Vec<Message>);
+
+    impl Sink<Message> for CloseOnlySink {
+        type Error = tungstenite::Error;
+
+        fn poll_ready(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<Result<(), Self::Error>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+
+        fn start_send(
+            mut self: std::pin::Pin<&mut Self>,
+            item: Message,
+        ) -> Result<(), Self::Error> {
+            self.0.push(item);
+            Ok(())
+        }
+
+        fn poll_flush(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<Result<(), Self::Error>> {
+            match self.0.last() {
+                Some(Message::Close(_)) => std::task::Poll::Ready(Ok(())),
+                _ => std::task::Poll::Pending,
+            }
+        }
+
+        fn poll_close(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<Result<(), Self::Error>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slow_client_gets_a_close_code() {
+        let policy = DegradePolicy {
+            send_timeout: Duration::from_millis(10),
+            max_send_timeouts: 2,
+            ..DegradePolicy::default()
+        };
+        let mut pressure = SendPressure::new(policy, create_shared_metrics(), test_addr());
+        let mut sink = CloseOnlySink::default();
+
+        assert_eq!(pressure.send(&mut sink, Message::text("a")).await, Ok(()));
+        assert_eq!(
+            pressure.send(&mut sink, Message::text("b")).await,
+            Err(SendError::SlowConsumer)
+        );
+        let Some(Message::Close(Some(frame))) = sink.0.last() else {
+            panic!("expected a Close frame, got {:?}", sink.0.last());
+        };
+        assert_eq!(frame.code, CloseCode::Library(4000));
+        assert_eq!(u16::from(frame.code), 4000);
+        assert_eq!(frame.reason, "client too slow");
+    }
+
+    #[test]
+    fn test_client_slots_cap_concurrent_clients() {
+        let slots = ClientSlots::new(2);
+        let first = slots.acquire().unwrap();
+        let _second = slots.acquire().unwrap();
+        assert!(slots.acquire().is_none());
+
+        // A disconnect frees its slot
+        drop(first);
+        assert!(slots.acquire().is_some());
+
+        let unlimited = ClientSlots::new(0);
+        let held: Vec<_> = (0..100).map(|_| unlimited.acquire().unwrap()).collect();
+        assert_eq!(held.len(), 100);
+    }
+
+    #[test]
+    #[cfg(feature = "metrics")]
+    fn test_dropping_degraded_client_releases_gauge() {
+        let metrics = create_shared_metrics();
+        let mut pressure =
+            SendPressure::new(DegradePolicy::default(), metrics.clone(), test_addr());
+        while pressure.record(Duration::from_secs(1)).is_none() {}
+        assert_eq!(metrics.compute_metrics().degraded_clients, 1);
+
+        drop(pressure);
+        assert_eq!(metrics.compute_metrics().degraded_clients, 0);
+    }
+
+    #[test]
+    fn test_ping_echoes_client_time_with_server_time() {
+        let command: ClientCommand =
+            serde_json::from_str(r#"{"action":"ping","client_time":1700000000123}"#).unwrap();
+        let ClientCommand::Ping { client_time } = command else {
+            panic!("expected Ping, got {:?}", command);
+        };
+
+        let before = chrono::Utc::now().timestamp_millis();
+        let reply = serde_json::to_value(pong(client_time)).unwrap();
+        assert_eq!(reply["type"], "pong");
+        assert_eq!(reply["data"]["client_time"], 1_700_000_000_123_i64);
+        assert!(reply["data"]["server_time"].as_i64().unwrap() >= before);
+    }
+
+    #[test]
+    fn test_subscribe_takes_vwap_quantity_as_decimal_string() {
+        let command: ClientCommand =
+            serde_json::from_str(r#"{"action":"subscribe","vwap_quantity":"0.5"}"#).unwrap();
+        let ClientCommand::Subscribe { vwap_quantity, .. } = command else {
+            panic!("expected Subscribe, got {:?}", command);
+        };
+        assert_eq!(vwap_quantity, Some(Decimal::new(5, 1)));
+    }
+
+    #[test]
+    fn test_origin_policy_allow_list() {
+        assert_eq!(OriginPolicy::parse("*"), OriginPolicy::Any);
+        assert_eq!(OriginPolicy::parse(" "), OriginPolicy::Any);
+        assert!(OriginPolicy::Any.allows(Some("https://evil.example")));
+
+        let policy = OriginPolicy::parse("https://flowrs.example.com/, http://localhost:5173");
+        assert!(policy.allows(Some("https://flowrs.example.com")));
+        assert!(policy.allows(Some("HTTP://LOCALHOST:5173")));
+        assert!(!policy.allows(Some("https://evil.example")));
+        assert!(!policy.allows(Some("http://localhost:8080")));
+        // Non-browser clients send no Origin
+        assert!(policy.allows(None));
+    }
+
+    #[tokio::test]
+    async fn test_listener_options_bind_and_accept() {
+        let options = ListenerOptions::default();
+        let listener = options.bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = TcpStream::connect(addr).await.unwrap();
+        let (accepted, _) = listener.accept().await.unwrap();
+        accepted.set_nodelay(options.nodelay).unwrap();
+        assert!(accepted.nodelay().unwrap());
+        drop(client);
+    }
+
+    /// Next message the server sends, failing the test after 2s without one
+    async fn next_client_message(
+        client: &mut (impl futures_util::Stream<Item = Result<Message, tungstenite::Error>> + Unpin),
+    ) -> ClientMessage {
+        let frame = tokio::time::timeout(Duration::from_secs(2), client.next())
+            .await
+            .expect("no message from the server")
+            .unwrap()
+            .unwrap();
+        serde_json::from_str(frame.to_text().unwrap()).unwrap()
+    }
+
+    /// Serve one client connection on a local port and connect to it
+    async fn connect_test_client(
+        orderbook_manager: SharedOrderBookManager,
+        client_broadcast_tx: broadcast::Sender<ClientMessage>,
+        shutdown: watch::Receiver<bool>,
+    ) -> WebSocketStream<tokio_tungstenite::MaybeTlsStream<TcpStream>> {
+        serve_test_client(test_state(orderbook_manager, client_broadcast_tx, shutdown)).await
+    }
+
+    fn test_state(
+        orderbook_manager: SharedOrderBookManager,
+        client_broadcast_tx: broadcast::Sender<ClientMessage>,
+        shutdown: watch::Receiver<bool>,
+    ) -> ServerState {
+        ServerState {
+            orderbook_manager,
+            metrics: create_shared_metrics(),
+            client_broadcast_tx,
+            symbol_registry: SymbolRegistry::new(vec![
+                "BTCUSDT".to_string(),
+                "ETHUSDT".to_string(),
+            ]),
+            candle_store: crate::candles::create_shared_candle_store(false),
+            trade_history: crate::trade_history::create_shared_trade_history(),
+            admin_token: None,
+            degrade_policy: DegradePolicy::default(),
+            client_limits: ClientLimits::default(),
+            client_slots: ClientSlots::default(),
+            origin_policy: OriginPolicy::Any,
+            auth_token: None,
+            display_depth: 10,
+            book_poll_interval: DEFAULT_BOOK_POLL_INTERVAL,
+            shutdown,
+        }
+    }
+
+    async fn serve_test_client(
+        state: ServerState,
+    ) -> WebSocketStream<tokio_tungstenite::MaybeTlsStream<TcpStream>> {
+        serve_test_client_at(state, "/").await
+    }
+
+    /// Like `serve_test_client`, with the client asking for `path` in its handshake
+    async fn serve_test_client_at(
+        state: ServerState,
+        path: &str,
+    ) -> WebSocketStream<tokio_tungstenite::MaybeTlsStream<TcpStream>> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, client_addr) = listener.accept().await.unwrap();
+            let _ = handle_client(stream, client_addr, state).await;
+        });
+        let (client, _) = tokio_tungstenite::connect_async(format!("ws://{}{}", addr, path))
+            .await
+            .unwrap();
+        client
+    }
+
+    #[tokio::test]
+    async fn test_subscribed_symbols_filter_books_and_trades() {
+        use crate::orderbook::create_shared_orderbook_manager;
+        use crate::types::{Trade, TradeSide};
+
+        let one = crate::orderbook::PRICE_FACTOR;
+        let orderbook_manager =
+            create_shared_orderbook_manager(&["Binance"], &["BTCUSDT", "ETHUSDT"], 20);
+        for (symbol, price) in [("BTCUSDT", 97_000), ("ETHUSDT", 3_000)] {
+            orderbook_manager
+                .get_or_create("Binance", symbol)
+                .initialize_from_snapshot(
+                    vec![(price * one, one)],
+                    vec![((price + 1) * one, one)],
+                    1,
+                );
+        }
+        let (client_broadcast_tx, _) = broadcast::channel(16);
+        let mut client = connect_test_client(
+            orderbook_manager,
+            client_broadcast_tx.clone(),
+            watch::channel(false).1,
+        )
+        .await;
+
+        // Everything flows until the client narrows it down
+        loop {
+            if let ClientMessage::BookBatch(updates) = next_client_message(&mut client).await {
+                assert_eq!(updates.len(), 2);
+                break;
+            }
+        }
+        client
+            .send(Message::Text(
+                r#"{"action":"subscribe","symbols":["btcusdt"]}"#.into(),
+            ))
+            .await
+            .unwrap();
+
+        loop {
+            if let ClientMessage::BookBatch(updates) = next_client_message(&mut client).await {
+                let symbols: Vec<&str> = updates.iter().map(|u| u.symbol.as_str()).collect();
+                assert_eq!(symbols, ["BTCUSDT"]);
+                break;
+            }
+        }
+
+        for symbol in ["ETHUSDT", "BTCUSDT"] {
+            let _ = client_broadcast_tx.send(ClientMessage::Trade(Trade {
+                exchange: "Binance".to_string(),
+                symbol: symbol.to_string(),
+                price: one,
+                quantity: one,
+                notional: one,
+                side: TradeSide::Buy,
+                timestamp: 1,
+            }));
+        }
+        loop {
+            if let ClientMessage::Trade(trade) = next_client_message(&mut client).await {
+                assert_eq!(trade.symbol, "BTCUSDT");
+                break;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_rejects_unknown_symbols_and_keeps_unset_options() {
+        use crate::orderbook::create_shared_orderbook_manager;
+
+        let one = crate::orderbook::PRICE_FACTOR;
+        let orderbook_manager =
+            create_shared_orderbook_manager(&["Binance"], &["BTCUSDT", "ETHUSDT"], 20);
+        for (symbol, price) in [("BTCUSDT", 97_000), ("ETHUSDT", 3_000)] {
+            orderbook_manager
+                .get_or_create("Binance", symbol)
+                .initialize_from_snapshot(
+                    vec![(price * one, one)],
+                    vec![((price + 1) * one, one)],
+                    1,
+                );
+        }
+        let mut client = connect_test_client(
+            orderbook_manager,
+            broadcast::channel(16).0,
+            watch::channel(false).1,
+        )
+        .await;
+
+        client
+            .send(Message::Text(
+                r#"{"action":"subscribe","vwap_quantity":"0.5","symbols":["btcusdt"]}"#.into(),
+            ))
+            .await
+            .unwrap();
+        loop {
+            if let ClientMessage::BookBatch(updates) = next_client_message(&mut client).await {
+                if updates.iter().all(|u| u.pricing.is_some()) {
+                    let symbols: Vec<&str> = updates.iter().map(|u| u.symbol.as_str()).collect();
+                    assert_eq!(symbols, ["BTCUSDT"]);
+                    break;
+                }
+            }
+        }
+
+        // Nothing valid in the list: an error, not the firehose
+        client
+            .send(Message::Text(
+                r#"{"action":"subscribe","symbols":["DOGEUSDT"]}"#.into(),
+            ))
+            .await
+            .unwrap();
+        loop {
+            match next_client_message(&mut client).await {
+                ClientMessage::Error(e) => {
+                    assert!(e.contains("DOGEUSDT"), "{}", e);
+                    break;
+                }
+                ClientMessage::BookBatch(updates) => {
+                    assert!(updates.iter().all(|u| u.symbol == "BTCUSDT"));
+                }
+                _ => {}
+            }
+        }
+
+        // Changing only the symbols keeps the VWAPs asked for earlier
+        client
+            .send(Message::Text(
+                r#"{"action":"subscribe","symbols":["ethusdt"]}"#.into(),
+            ))
+            .await
+            .unwrap();
+        loop {
+            if let ClientMessage::BookBatch(updates) = next_client_message(&mut client).await {
+                let symbols: Vec<&str> = updates.iter().map(|u| u.symbol.as_str()).collect();
+                assert_eq!(symbols, ["ETHUSDT"]);
+                assert!(updates[0].pricing.is_some());
+                break;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_zero_trade_limit_removes_the_cap() {
+        use crate::orderbook::create_shared_orderbook_manager;
+        use crate::types::{Trade, TradeSide};
+
+        let (client_broadcast_tx, _) = broadcast::channel(16);
+        let mut client = connect_test_client(
+            create_shared_orderbook_manager(&["Binance"], &["BTCUSDT"], 20),
+            client_broadcast_tx.clone(),
+            watch::channel(false).1,
+        )
+        .await;
+
+        // Commands are handled in order: the pong means the subscribe is applied
+        for limit in [1, 0] {
+            let subscribe = format!(r#"{{"action":"subscribe","max_trades_per_sec":{}}}"#, limit);
+            client.send(Message::Text(subscribe.into())).await.unwrap();
+            client
+                .send(Message::Text(r#"{"action":"ping","client_time":1}"#.into()))
+                .await
+                .unwrap();
+            while !matches!(
+                next_client_message(&mut client).await,
+                ClientMessage::Pong { .. }
+            ) {}
+        }
+
+        for timestamp in 1..=3 {
+            let _ = client_broadcast_tx.send(ClientMessage::Trade(Trade {
+                exchange: "Binance".to_string(),
+                symbol: "BTCUSDT".to_string(),
+                price: 1,
+                quantity: 1,
+                notional: 1,
+                side: TradeSide::Buy,
+                timestamp,
+            }));
+        }
+        for expected in 1..=3 {
+            loop {
+                if let ClientMessage::Trade(trade) = next_client_message(&mut client).await {
+                    assert_eq!(trade.timestamp, expected);
+                    break;
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_recent_tape_sent_on_connect_and_on_request() {
+        use crate::orderbook::create_shared_orderbook_manager;
+        use crate::types::{Trade, TradeSide};
+
+        let state = test_state(
+            create_shared_orderbook_manager(&["Binance"], &["BTCUSDT", "ETHUSDT"], 20),
+            broadcast::channel(16).0,
+            watch::channel(false).1,
+        );
+        for (symbol, timestamp) in [("BTCUSDT", 1), ("ETHUSDT", 2), ("BTCUSDT", 3)] {
+            state.trade_history.record(&Trade {
+                exchange: "Binance".to_string(),
+                symbol: symbol.to_string(),
+                price: 100,
+                quantity: 1,
+                notional: Trade::notional_of(100, 1),
+                side: TradeSide::Sell,
+                timestamp,
+            });
+        }
+        let mut client = serve_test_client(state).await;
+
+        let tape = loop {
+            if let ClientMessage::TradeHistory(trades) = next_client_message(&mut client).await {
+                break trades;
+            }
+        };
+        let timestamps: Vec<i64> = tape.iter().map(|t| t.timestamp).collect();
+        assert_eq!(timestamps, [1, 2, 3]);
+
+        client
+            .send(Message::Text(
+                r#"{"action":"get_trades","symbol":"btc-usdt","limit":1}"#.into(),
+            ))
+            .await
+            .unwrap();
+        loop {
+            if let ClientMessage::TradeHistory(trades) = next_client_message(&mut client).await {
+                assert_eq!(trades.len(), 1);
+                assert_eq!(
+                    (trades[0].symbol.as_str(), trades[0].timestamp),
+                    ("BTCUSDT", 3)
+                );
+                break;
+            }
+        }
+
+        client
+            .send(Message::Text(
+                r#"{"action":"get_trades","symbol":"DOGEUSDT"}"#.into(),
+            ))
+            .await
+            .unwrap();
+        loop {
+            if let ClientMessage::Error(_) = next_client_message(&mut client).await {
+                break;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_set_depth_is_clamped_and_bad_input_keeps_connection() {
+        use crate::orderbook::create_shared_orderbook_manager;
+
+        let one = crate::orderbook::PRICE_FACTOR;
+        let orderbook_manager = create_shared_orderbook_manager(&["Binance"], &["BTCUSDT"], 20);
+        orderbook_manager
+            .get_or_create("Binance", "BTCUSDT")
+            .initialize_from_snapshot(
+                (1..=30).map(|i| ((1_000 - i) * one, one)).collect(),
+                (1..=30).map(|i| ((1_000 + i) * one, one)).collect(),
+                1,
+            );
+        let (client_broadcast_tx, _) = broadcast::channel(16);
+        let mut client = connect_test_client(
+            orderbook_manager,
+            client_broadcast_tx,
+            watch::channel(false).1,
+        )
+        .await;
+
+        // Default display depth first
+        loop {
+            if let ClientMessage::BookBatch(updates) = next_client_message(&mut client).await {
+                assert_eq!(updates[0].bids.len(), 10);
+                break;
+            }
+        }
+
+        client
+            .send(Message::Text(
+                r#"{"action":"set_depth","levels":"ten"}"#.into(),
+            ))
+            .await
+            .unwrap();
+        loop {
+            if let ClientMessage::Error(e) = next_client_message(&mut client).await {
+                assert!(e.contains("invalid command"));
+                break;
+            }
+        }
+
+        // More than the book stores is clamped to the stored depth
+        client
+            .send(Message::Text(
+                r#"{"action":"set_depth","levels":50}"#.into(),
+            ))
+            .await
+            .unwrap();
+        loop {
+            if let ClientMessage::BookBatch(updates) = next_client_message(&mut client).await {
+                assert_eq!(updates[0].bids.len(), 20);
+                assert_eq!(updates[0].asks.len(), 20);
+                break;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_commands_over_the_limit_are_dropped() {
+        let mut state = test_state(
+            crate::orderbook::create_shared_orderbook_manager(&[], &[], 20),
+            broadcast::channel(16).0,
+            watch::channel(false).1,
+        );
+        state.client_limits.commands_per_sec = 5;
+        let mut client = serve_test_client(state).await;
+
+        for client_time in 0..20 {
+            client
+                .send(Message::Text(
+                    format!(r#"{{"action":"ping","client_time":{}}}"#, client_time).into(),
+                ))
+                .await
+                .unwrap();
+        }
+        // Burst of one second's worth answered, the rest dropped
+        let mut pongs = Vec::new();
+        while let Ok(Some(Ok(frame))) =
+            tokio::time::timeout(Duration::from_millis(300), client.next()).await
+        {
+            if let Ok(ClientMessage::Pong { client_time, .. }) =
+                serde_json::from_str(frame.to_text().unwrap())
+            {
+                pongs.push(client_time);
+            }
+        }
+        assert_eq!(pongs, [0, 1, 2, 3, 4]);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "metrics")]
+    async fn test_lagging_client_counted() {
+        let (client_broadcast_tx, _) = broadcast::channel(4);
+        let state = test_state(
+            crate::orderbook::create_shared_orderbook_manager(&[], &[], 20),
+            client_broadcast_tx.clone(),
+            watch::channel(false).1,
+        );
+        let metrics = state.metrics.clone();
+        let mut client = serve_test_client(state).await;
+        while !matches!(
+            next_client_message(&mut client).await,
+            ClientMessage::Metrics(_)
+        ) {}
+
+        // The handler can't run between these sends: it falls 6 behind
+        for client_time in 0..10 {
+            let _ = client_broadcast_tx.send(pong(client_time));
+        }
+        tokio::time::timeout(Duration::from_secs(2), async {
+            while metrics.peek_metrics().broadcast_lagged == 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("lag never recorded");
+        assert_eq!(metrics.peek_metrics().broadcast_lagged, 6);
+    }
+
+    #[tokio::test]
+    async fn test_auth_token_required_when_configured() {
+        use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+        let mut state = test_state(
+            crate::orderbook::create_shared_orderbook_manager(&[], &[], 20),
+            broadcast::channel(16).0,
+            watch::channel(false).1,
+        );
+        state.auth_token = Some("s3cret".to_string());
+        #[cfg(feature = "metrics")]
+        let metrics = state.metrics.clone();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (stream, client_addr) = listener.accept().await.unwrap();
+                tokio::spawn(handle_client(stream, client_addr, state.clone()));
+            }
+        });
+
+        for uri in [
+            format!("ws://{}", addr),
+            format!("ws://{}/?token=wrong", addr),
+        ] {
+            match tokio_tungstenite::connect_async(uri).await {
+                Err(tungstenite::Error::Http(response)) => {
+                    assert_eq!(response.status(), StatusCode::UNAUTHORIZED)
+                }
+                other => panic!("expected a 401, got {:?}", other.map(|(_, r)| r)),
+            }
+        }
+        #[cfg(feature = "metrics")]
+        assert_eq!(metrics.peek_metrics().auth_rejections, 2);
+
+        let (mut client, _) =
+            tokio_tungstenite::connect_async(format!("ws://{}/?encoding=json&token=s3cret", addr))
+                .await
+                .unwrap();
+        assert!(matches!(
+            next_client_message(&mut client).await,
+            ClientMessage::SymbolList(_)
+        ));
+
+        let mut request = format!("ws://{}", addr).into_client_request().unwrap();
+        request
+            .headers_mut()
+            .insert(AUTHORIZATION, "Bearer s3cret".parse().unwrap());
+        assert!(tokio_tungstenite::connect_async(request).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_admin_commands_need_the_admin_token() {
+        let mut state = test_state(
+            crate::orderbook::create_shared_orderbook_manager(&[], &[], 20),
+            broadcast::channel(16).0,
+            watch::channel(false).1,
+        );
+        state.admin_token = Some("adm1n".to_string());
+        let symbol_registry = state.symbol_registry.clone();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (stream, client_addr) = listener.accept().await.unwrap();
+                tokio::spawn(handle_client(stream, client_addr, state.clone()));
+            }
+        });
+        let add_symbol = || Message::Text(r#"{"action":"add_symbol","symbol":"SOLUSDT"}"#.into());
+
+        for path in ["/", "/?admin_token=wrong"] {
+            let (mut client, _) =
+                tokio_tungstenite::connect_async(format!("ws://{}{}", addr, path))
+                    .await
+                    .unwrap();
+            client.send(add_symbol()).await.unwrap();
+            loop {
+                if let ClientMessage::Error(e) = next_client_message(&mut client).await {
+                    assert!(e.contains("admin token"), "{}", e);
+                    break;
+                }
+            }
+        }
+        assert!(!symbol_registry.current().contains(&"SOLUSDT".to_string()));
+
+        let (mut admin, _) =
+            tokio_tungstenite::connect_async(format!("ws://{}/?admin_token=adm1n", addr))
+                .await
+                .unwrap();
+        admin.send(add_symbol()).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(2), async {
+            while !symbol_registry.current().contains(&"SOLUSDT".to_string()) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("admin command not applied");
+    }
+
+    #[tokio::test]
+    async fn test_metrics_stream_sends_metrics_only() {
+        use crate::orderbook::create_shared_orderbook_manager;
+        use crate::types::{Trade, TradeSide};
+
+        let one = crate::orderbook::PRICE_FACTOR;
+        let orderbook_manager = create_shared_orderbook_manager(&["Binance"], &["BTCUSDT"], 20);
+        orderbook_manager
+            .get_or_create("Binance", "BTCUSDT")
+            .initialize_from_snapshot(vec![(99 * one, one)], vec![(101 * one, one)], 1);
+        let (client_broadcast_tx, _) = broadcast::channel(16);
+        let state = test_state(
+            orderbook_manager,
+            client_broadcast_tx.clone(),
+            watch::channel(false).1,
+        );
+        let metrics = state.metrics.clone();
+        let mut client = serve_test_client_at(state, "/metrics-stream").await;
+
+        // The current report right away, no symbols or books
+        assert!(matches!(
+            next_client_message(&mut client).await,
+            ClientMessage::Metrics(_)
+        ));
+
+        let _ = client_broadcast_tx.send(ClientMessage::Trade(Trade {
+            exchange: "Binance".to_string(),
+            symbol: "BTCUSDT".to_string(),
+            price: one,
+            quantity: one,
+            notional: one,
+            side: TradeSide::Buy,
+            timestamp: 1,
+        }));
+        let _ = client_broadcast_tx.send(ClientMessage::Metrics(metrics.peek_metrics()));
+        assert!(matches!(
+            next_client_message(&mut client).await,
+            ClientMessage::Metrics(_)
+        ));
+        // Nothing else is on its way, book polls included
+        assert!(
+            tokio::time::timeout(DEFAULT_BOOK_POLL_INTERVAL * 3, client.next())
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_noop_update_not_resent() {
+        use crate::orderbook::create_shared_orderbook_manager;
+
+        let one = crate::orderbook::PRICE_FACTOR;
+        let orderbook_manager = create_shared_orderbook_manager(&["Binance"], &["BTCUSDT"], 20);
+        orderbook_manager
+            .get_or_create("Binance", "BTCUSDT")
+            .initialize_from_snapshot(vec![(99 * one, one)], vec![(101 * one, one)], 1);
+        let (client_broadcast_tx, _) = broadcast::channel(16);
+        let mut client = connect_test_client(
+            orderbook_manager.clone(),
+            client_broadcast_tx,
+            watch::channel(false).1,
+        )
+        .await;
+
+        loop {
+            if let ClientMessage::BookBatch(updates) = next_client_message(&mut client).await {
+                assert_eq!(updates[0].update_id, 1);
+                break;
+            }
+        }
+
+        // Same quantity again: the id moves on but the levels don't
+        orderbook_manager
+            .get_or_create("Binance", "BTCUSDT")
+            .apply_update(vec![(99 * one, one)], vec![], 2, 2);
+        tokio::time::sleep(DEFAULT_BOOK_POLL_INTERVAL * 3).await;
+        orderbook_manager
+            .get_or_create("Binance", "BTCUSDT")
+            .apply_update(vec![(99 * one, 2 * one)], vec![], 3, 3);
+
+        loop {
+            if let ClientMessage::BookBatch(updates) = next_client_message(&mut client).await {
+                assert_eq!(updates[0].update_id, 3);
+                break;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_single_level_change_sent_as_one_entry_delta() {
+        use crate::orderbook::create_shared_orderbook_manager;
+
+        let one = crate::orderbook::PRICE_FACTOR;
+        let orderbook_manager = create_shared_orderbook_manager(&["Binance"], &["BTCUSDT"], 20);
+        orderbook_manager
+            .get_or_create("Binance", "BTCUSDT")
+            .initialize_from_snapshot(
+                vec![(99 * one, one), (98 * one, one)],
+                vec![(101 * one, one), (102 * one, one)],
+                1,
+            );
+        let (client_broadcast_tx, _) = broadcast::channel(16);
+        let state = test_state(
+            orderbook_manager.clone(),
+            client_broadcast_tx,
+            watch::channel(false).1,
+        );
+        let mut client = serve_test_client_at(state, "/?book_deltas=1").await;
+
+        // Sent in full first
+        loop {
+            if let ClientMessage::BookBatch(updates) = next_client_message(&mut client).await {
+                assert_eq!(updates[0].bids.len(), 2);
+                break;
+            }
+        }
+
+        orderbook_manager
+            .get_or_create("Binance", "BTCUSDT")
+            .apply_update(vec![(98 * one, 3 * one)], vec![], 2, 2);
+        loop {
+            match next_client_message(&mut client).await {
+                ClientMessage::BookDelta {
+                    changed_bids,
+                    removed_bids,
+                    changed_asks,
+                    removed_asks,
+                    ..
+                } => {
+                    assert_eq!(changed_bids.len(), 1);
+                    assert_eq!(changed_bids[0].price, Decimal::from(98));
+                    assert_eq!(changed_bids[0].quantity, Decimal::from(3));
+                    assert!(removed_bids.is_empty());
+                    assert!(changed_asks.is_empty());
+                    assert!(removed_asks.is_empty());
+                    break;
+                }
+                ClientMessage::BookBatch(_) => panic!("full book resent to a delta client"),
+                _ => {}
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_list_resent_when_a_book_initializes() {
+        use crate::orderbook::create_shared_orderbook_manager;
+
+        let one = crate::orderbook::PRICE_FACTOR;
+        let orderbook_manager =
+            create_shared_orderbook_manager(&["Binance"], &["BTCUSDT", "ETHUSDT"], 20);
+        orderbook_manager
+            .get_or_create("Binance", "BTCUSDT")
+            .initialize_from_snapshot(vec![(99 * one, one)], vec![(101 * one, one)], 1);
+        orderbook_manager.get_or_create("Binance", "ETHUSDT");
+        let (client_broadcast_tx, _) = broadcast::channel(16);
+        let state = test_state(
+            orderbook_manager.clone(),
+            client_broadcast_tx,
+            watch::channel(false).1,
+        );
+        let mut client = serve_test_client(state).await;
+
+        let initialized = |streams: &[StreamInfo]| {
+            streams
+                .iter()
+                .filter(|s| s.initialized)
+                .map(|s| s.symbol.clone())
+                .collect::<Vec<_>>()
+        };
+        loop {
+            if let ClientMessage::StreamList(streams) = next_client_message(&mut client).await {
+                assert_eq!(streams.len(), 2);
+                assert_eq!(initialized(&streams), vec!["BTCUSDT"]);
+                break;
+            }
+        }
+
+        orderbook_manager
+            .get_or_create("Binance", "ETHUSDT")
+            .initialize_from_snapshot(vec![(9 * one, one)], vec![(11 * one, one)], 1);
+        loop {
+            match next_client_message(&mut client).await {
+                ClientMessage::StreamList(streams) => {
+                    assert_eq!(initialized(&streams), vec!["BTCUSDT", "ETHUSDT"]);
+                    break;
+                }
+                ClientMessage::BookBatch(updates) => {
+                    assert!(
+                        updates.iter().all(|u| u.symbol == "BTCUSDT"),
+                        "book before its stream"
+                    );
+                }
+                _ => {}
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_closes_clients_with_going_away() {
+        use crate::orderbook::create_shared_orderbook_manager;
+
+        let orderbook_manager =
+            create_shared_orderbook_manager(&["Binance"], &["BTCUSDT", "ETHUSDT"], 20);
+        let (client_broadcast_tx, _) = broadcast::channel(16);
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let mut client =
+            connect_test_client(orderbook_manager, client_broadcast_tx, shutdown_rx).await;
+
+        shutdown_tx.send(true).unwrap();
+        let close = loop {
+            let frame = tokio::time::timeout(Duration::from_secs(2), client.next())
+                .await
+                .expect("no close from the server")
+                .unwrap()
+                .unwrap();
+            if let Message::Close(frame) = frame {
+                break frame.unwrap();
+            }
+        };
+        assert_eq!(close.code, CloseCode::Away);
+    }
+
+    #[test]
+    fn test_encoding_picked_from_handshake_query() {
+        assert_eq!(WireEncoding::from_query(None), WireEncoding::Json);
+        assert_eq!(
+            WireEncoding::from_query(Some("encoding=cbor")),
+            WireEncoding::Cbor
+        );
+        assert_eq!(
+            WireEncoding::from_query(Some("token=x&encoding=CBOR")),
+            WireEncoding::Cbor
+        );
+        assert_eq!(
+            WireEncoding::from_query(Some("encoding=xml")),
+            WireEncoding::Json
+        );
+
+        assert!(query_flag(
+            Some("encoding=cbor&book_deltas=1"),
+            "book_deltas"
+        ));
+        assert!(query_flag(Some("book_deltas=true"), "book_deltas"));
+        assert!(!query_flag(Some("book_deltas=0"), "book_deltas"));
+        assert!(!query_flag(None, "book_deltas"));
+    }
+
+    #[test]
+    fn test_cbor_frames_decode_back_into_client_messages() {
+        use crate::orderbook::create_shared_orderbook_manager;
+        use crate::types::{Metrics, Trade, TradeSide};
+
+        let one = crate::orderbook::PRICE_FACTOR;
+        let orderbook_manager = create_shared_orderbook_manager(&["Binance"], &["BTCUSDT"], 20);
+        let mut book = orderbook_manager.get_or_create("Binance", "BTCUSDT");
+        book.initialize_from_snapshot(
+            vec![(97_000 * one, one / 2)],
+            vec![(97_001 * one, 3 * one)],
+            42,
+        );
+        let messages = [
+            book.to_client_message(10),
+            ClientMessage::Trade(Trade {
+                exchange: "Binance".to_string(),
+                symbol: "BTCUSDT".to_string(),
+                price: 97_000 * one,
+                quantity: one / 4,
+                notional: Trade::notional_of(97_000 * one, one / 4),
+                side: TradeSide::Sell,
+                timestamp: 1_700_000_000_000,
+            }),
+            ClientMessage::Metrics(Metrics {
+                messages_per_second: 1234,
+                cpu_usage_percent: 12.5,
+                ..Metrics::default()
+            }),
+            ClientMessage::SymbolList(vec!["BTCUSDT".to_string(), "ETHUSDT".to_string()]),
+        ];
+
+        for client_msg in &messages {
+            let Message::Binary(bytes) = WireEncoding::Cbor.encode(client_msg).unwrap() else {
+                panic!("CBOR must go out as a binary frame");
+            };
+            let decoded: ClientMessage = ciborium::from_reader(&bytes[..]).unwrap();
+            assert_eq!(
+                serde_json::to_value(&decoded).unwrap(),
+                serde_json::to_value(client_msg).unwrap()
+            );
+        }
+
+        let Message::Binary(bytes) = WireEncoding::Cbor.encode(&messages[0]).unwrap() else {
+            unreachable!()
+        };
+        let Ok(ClientMessage::BookUpdate(update)) = ciborium::from_reader(&bytes[..]) else {
+            panic!("expected a book update");
+        };
+        assert_eq!(update.exchange, "Binance");
+        assert_eq!(update.bids[0].price, Decimal::from(97_000));
+        assert_eq!(update.asks[0].quantity, Decimal::from(3));
+
+        let Message::Text(json) = WireEncoding::Json.encode(&messages[0]).unwrap() else {
+            panic!("JSON stays a text frame");
+        };
+        assert!(bytes.len() < json.len());
+    }
+}
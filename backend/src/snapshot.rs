@@ -0,0 +1,166 @@
+//! Order book persistence across restarts
+//!
+//! Books are saved as JSON (levels kept in their scaled u64 form, so nothing
+//! is lost to rounding) and loaded back before exchange connections start.
+//! A loaded book only bridges the cold window: sequenced feeds see a gap on
+//! their first live delta and re-fetch a snapshot, the others replace it with
+//! the snapshot they send on subscribe. Until then it is reported stale.
+
+use crate::orderbook::OrderBookManager;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+/// Seconds between two saves when persistence is on
+pub const DEFAULT_SAVE_INTERVAL_SECS: u64 = 30;
+/// Older snapshot files are ignored on startup: their books are too far off
+pub const DEFAULT_MAX_AGE_SECS: u64 = 300;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SnapshotFile {
+    /// Milliseconds since the Unix epoch
+    saved_at: i64,
+    books: Vec<SavedBook>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedBook {
+    exchange: String,
+    symbol: String,
+    last_update_id: u64,
+    /// (price, qty) scaled by 1e8, best first
+    bids: Vec<(u64, u64)>,
+    asks: Vec<(u64, u64)>,
+}
+
+/// Write every initialized book to `path`, returning how many were saved
+///
+/// Goes through a temporary file and a rename so a crash mid-write never
+/// leaves a truncated snapshot behind.
+pub fn save(manager: &OrderBookManager, path: &Path) -> io::Result<usize> {
+    let books: Vec<SavedBook> = manager
+        .iter()
+        .filter(|entry| entry.is_initialized())
+        .map(|entry| {
+            let (bids, asks) = entry.levels();
+            let side = |levels: &[crate::orderbook::Level]| {
+                levels.iter().map(|l| (l.price, l.qty)).collect()
+            };
+            SavedBook {
+                exchange: entry.exchange().to_string(),
+                symbol: entry.symbol().to_string(),
+                last_update_id: entry.last_update_id(),
+                bids: side(bids),
+                asks: side(asks),
+            }
+        })
+        .collect();
+    let count = books.len();
+
+    let file = SnapshotFile {
+        saved_at: chrono::Utc::now().timestamp_millis(),
+        books,
+    };
+    let json = serde_json::to_vec(&file)?;
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, json)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(count)
+}
+
+/// Load books saved at `path` into `manager`, returning how many were restored
+///
+/// A missing file or one older than `max_age` restores nothing. Books of
+/// exchange × symbol pairs the manager no longer expects are skipped.
+pub fn load(manager: &OrderBookManager, path: &Path, max_age: Duration) -> io::Result<usize> {
+    let json = match std::fs::read(path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    let file: SnapshotFile = serde_json::from_slice(&json)?;
+
+    let age_ms = chrono::Utc::now().timestamp_millis() - file.saved_at;
+    if age_ms < 0 || age_ms as u128 > max_age.as_millis() {
+        tracing::info!(
+            "Ignoring book snapshot {} saved {}s ago",
+            path.display(),
+            age_ms / 1000
+        );
+        return Ok(0);
+    }
+
+    let mut restored = 0;
+    for saved in file.books {
+        if !manager.expects(&saved.exchange, &saved.symbol) {
+            continue;
+        }
+        let mut book = manager.get_or_create(&saved.exchange, &saved.symbol);
+        if book.initialize_from_snapshot(saved.bids, saved.asks, saved.last_update_id) {
+            book.mark_restored();
+            restored += 1;
+        }
+    }
+    Ok(restored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook::{UpdateSequence, PRICE_FACTOR};
+
+    const ONE: u64 = PRICE_FACTOR;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("flowrs-{}-{}.json", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_saved_books_reload_and_still_check_sequence() {
+        let path = temp_path("snapshot-roundtrip");
+        let manager = OrderBookManager::with_streams(&["Binance", "Kraken"], &["BTCUSDT"]);
+        manager
+            .get_or_create("Binance", "BTCUSDT")
+            .initialize_from_snapshot(
+                vec![(97_000 * ONE + ONE / 10, ONE / 3), (96_999 * ONE, 2 * ONE)],
+                vec![(97_001 * ONE, ONE)],
+                1_000,
+            );
+        // Never initialized: not saved
+        manager.get_or_create("Kraken", "BTCUSDT");
+        assert_eq!(save(&manager, &path).unwrap(), 1);
+
+        // Restored into a manager that no longer runs Kraken
+        let reloaded = OrderBookManager::with_streams(&["Binance"], &["BTCUSDT"]);
+        assert_eq!(load(&reloaded, &path, Duration::from_secs(60)).unwrap(), 1);
+        let book = reloaded.get("Binance", "BTCUSDT").unwrap();
+        assert!(book.is_initialized());
+        assert!(book.is_stale());
+        assert_eq!(book.last_update_id(), 1_000);
+        assert_eq!(
+            book.best_bid(),
+            manager.get("Binance", "BTCUSDT").unwrap().best_bid()
+        );
+        assert_eq!(book.level_counts(), (2, 1));
+
+        // The first live delta still has to bridge the restored id
+        assert_eq!(book.check_sequence(990, None, 1_005), UpdateSequence::Apply);
+        assert_eq!(book.check_sequence(5_000, None, 5_010), UpdateSequence::Gap);
+        drop(book);
+
+        // Live again once the feed updates it
+        let mut book = reloaded.get_or_create("Binance", "BTCUSDT");
+        book.apply_update(vec![(96_998 * ONE, ONE)], vec![], 1_001, 1_001);
+        assert!(!book.is_stale());
+        drop(book);
+
+        // Too old: nothing restored
+        std::thread::sleep(Duration::from_millis(5));
+        let stale = OrderBookManager::with_streams(&["Binance"], &["BTCUSDT"]);
+        assert_eq!(load(&stale, &path, Duration::ZERO).unwrap(), 0);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(load(&stale, &path, Duration::from_secs(60)).unwrap(), 0);
+    }
+}
@@ -0,0 +1,194 @@
+//! Consolidated trade tape: prints from every exchange in timestamp order
+//!
+//! Venues deliver trades with different latencies, so a print from one
+//! exchange can arrive after a later one from another. The tape holds trades
+//! in a short reorder buffer and releases them oldest first. A trade is never
+//! held longer than the window after it arrived, whatever its timestamp says.
+
+use crate::orderbook::PRICE_FACTOR;
+use crate::types::{ConsolidatedTrade, Trade};
+use rust_decimal::Decimal;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap};
+use tokio::time::{Duration, Instant};
+
+/// Reorder window used when the client doesn't pick one
+pub const DEFAULT_REORDER_WINDOW: Duration = Duration::from_millis(250);
+/// Longest window a client may ask for
+pub const MAX_REORDER_WINDOW: Duration = Duration::from_secs(2);
+
+/// A buffered trade, ordered by exchange timestamp then arrival
+#[derive(Debug)]
+struct Pending {
+    seq: u64,
+    arrived: Instant,
+    trade: Trade,
+}
+
+impl Pending {
+    fn key(&self) -> (i64, u64) {
+        (self.trade.timestamp, self.seq)
+    }
+}
+
+impl PartialEq for Pending {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Pending {}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Pending {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+/// Per-client reorder buffer merging every exchange's trades
+#[derive(Debug)]
+pub struct ConsolidatedTape {
+    window: Duration,
+    pending: BinaryHeap<Reverse<Pending>>,
+    next_seq: u64,
+    /// Last released price per symbol, across all exchanges
+    last_price: HashMap<String, u64>,
+}
+
+impl ConsolidatedTape {
+    /// Tape holding trades up to `window` (capped at `MAX_REORDER_WINDOW`)
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window: window.min(MAX_REORDER_WINDOW),
+            pending: BinaryHeap::new(),
+            next_seq: 0,
+            last_price: HashMap::new(),
+        }
+    }
+
+    /// Buffer a trade that arrived at `now`
+    pub fn push(&mut self, trade: Trade, now: Instant) {
+        self.pending.push(Reverse(Pending {
+            seq: self.next_seq,
+            arrived: now,
+            trade,
+        }));
+        self.next_seq += 1;
+    }
+
+    /// Release, oldest first, every trade that can no longer be reordered
+    ///
+    /// Once a trade's window expires it goes out along with every buffered
+    /// trade stamped at or before it; later ones wait for their own window.
+    pub fn drain_ready(&mut self, now: Instant) -> Vec<ConsolidatedTrade> {
+        let watermark = self
+            .pending
+            .iter()
+            .filter(|Reverse(p)| now.saturating_duration_since(p.arrived) >= self.window)
+            .map(|Reverse(p)| p.trade.timestamp)
+            .max();
+        let Some(watermark) = watermark else {
+            return Vec::new();
+        };
+
+        let mut ready = Vec::new();
+        while self
+            .pending
+            .peek()
+            .is_some_and(|Reverse(p)| p.trade.timestamp <= watermark)
+        {
+            let Some(Reverse(pending)) = self.pending.pop() else {
+                break;
+            };
+            let trade = pending.trade;
+            let last_price = self
+                .last_price
+                .insert(trade.symbol.clone(), trade.price)
+                .map(|price| Decimal::from(price) / Decimal::from(PRICE_FACTOR));
+            ready.push(ConsolidatedTrade { trade, last_price });
+        }
+        ready
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::TradeSide;
+
+    const ONE: u64 = PRICE_FACTOR;
+
+    fn trade(exchange: &str, price: u64, timestamp: i64) -> Trade {
+        Trade {
+            exchange: exchange.to_string(),
+            symbol: "BTCUSDT".to_string(),
+            price,
+            quantity: ONE,
+            notional: Trade::notional_of(price, ONE),
+            side: TradeSide::Buy,
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn test_tape_reorders_across_exchanges() {
+        let mut tape = ConsolidatedTape::new(Duration::from_millis(100));
+        let start = Instant::now();
+
+        // Kraken's earlier print arrives after Binance's later one
+        tape.push(trade("Binance", 100 * ONE, 1_000), start);
+        tape.push(
+            trade("Kraken", 99 * ONE, 990),
+            start + Duration::from_millis(20),
+        );
+        assert!(tape
+            .drain_ready(start + Duration::from_millis(50))
+            .is_empty());
+
+        let released = tape.drain_ready(start + Duration::from_millis(100));
+        let order: Vec<&str> = released.iter().map(|t| t.trade.exchange.as_str()).collect();
+        assert_eq!(order, ["Kraken", "Binance"]);
+        assert_eq!(released[0].last_price, None);
+        assert_eq!(released[1].last_price, Some(Decimal::from(99)));
+        assert!(tape.pending.is_empty());
+    }
+
+    #[test]
+    fn test_tape_holds_later_trades_for_their_own_window() {
+        let mut tape = ConsolidatedTape::new(Duration::from_millis(100));
+        let start = Instant::now();
+
+        tape.push(trade("Binance", 100 * ONE, 1_000), start);
+        tape.push(
+            trade("Bybit", 101 * ONE, 1_050),
+            start + Duration::from_millis(60),
+        );
+
+        let released = tape.drain_ready(start + Duration::from_millis(100));
+        assert_eq!(released.len(), 1);
+        assert_eq!(released[0].trade.exchange, "Binance");
+
+        // A straggler goes out with the next expired print, ahead of it
+        tape.push(
+            trade("Coinbase", 99 * ONE, 900),
+            start + Duration::from_millis(110),
+        );
+        let released = tape.drain_ready(start + Duration::from_millis(160));
+        let order: Vec<&str> = released.iter().map(|t| t.trade.exchange.as_str()).collect();
+        assert_eq!(order, ["Coinbase", "Bybit"]);
+        assert_eq!(released[0].last_price, Some(Decimal::from(100)));
+        assert_eq!(released[1].last_price, Some(Decimal::from(99)));
+    }
+
+    #[test]
+    fn test_tape_window_is_capped() {
+        let tape = ConsolidatedTape::new(Duration::from_secs(60));
+        assert_eq!(tape.window, MAX_REORDER_WINDOW);
+    }
+}
@@ -0,0 +1,108 @@
+//! Log output and optional OpenTelemetry trace export
+//!
+//! With the `otel` feature, the hot path carries spans tagged with exchange and
+//! symbol (`handle_text_message` -> `process_market_message`, then each client
+//! send), exported over OTLP/gRPC to `FLOWRS_OTLP_ENDPOINT` (e.g. Jaeger or
+//! Tempo on `http://localhost:4317`). Without the feature the spans are not
+//! compiled in and this only sets up the log output.
+//!
+//! Spans go through the same `RUST_LOG` filter as logs and are emitted at the
+//! info level.
+
+use tracing_subscriber::EnvFilter;
+
+/// Keeps the trace exporter alive; dropping it flushes the spans still queued
+#[must_use = "dropping the guard stops trace export"]
+pub struct TelemetryGuard {
+    #[cfg(feature = "otel")]
+    provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        if let Some(provider) = self.provider.take() {
+            if let Err(e) = provider.shutdown() {
+                tracing::warn!("Failed to flush traces: {}", e);
+            }
+        }
+    }
+}
+
+fn env_filter() -> EnvFilter {
+    EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"))
+}
+
+/// Install the global subscriber: logs to stdout, no trace export
+#[cfg(not(feature = "otel"))]
+pub fn init() -> TelemetryGuard {
+    tracing_subscriber::fmt()
+        .with_env_filter(env_filter())
+        .init();
+    TelemetryGuard {}
+}
+
+/// Install the global subscriber: logs to stdout, plus spans to the OTLP
+/// collector at `FLOWRS_OTLP_ENDPOINT` when set
+///
+/// Must run inside the Tokio runtime (the gRPC exporter needs it).
+#[cfg(feature = "otel")]
+pub fn init() -> TelemetryGuard {
+    use opentelemetry::trace::TracerProvider as _;
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::util::SubscriberInitExt;
+
+    let endpoint = std::env::var("FLOWRS_OTLP_ENDPOINT").ok();
+    let provider = endpoint.as_deref().map(otlp_provider).transpose();
+    let (provider, error) = match provider {
+        Ok(provider) => (provider, None),
+        Err(e) => (None, Some(e)),
+    };
+
+    let otel_layer = provider
+        .as_ref()
+        .map(|provider| tracing_opentelemetry::layer().with_tracer(provider.tracer("flowrs")));
+    tracing_subscriber::registry()
+        .with(env_filter())
+        .with(tracing_subscriber::fmt::layer())
+        .with(otel_layer)
+        .init();
+
+    match (&endpoint, error) {
+        (Some(endpoint), None) => tracing::info!("Exporting traces to {}", endpoint),
+        (_, Some(e)) => tracing::error!("Not exporting traces: {}", e),
+        (None, None) => {}
+    }
+    TelemetryGuard { provider }
+}
+
+#[cfg(feature = "otel")]
+fn otlp_provider(
+    endpoint: &str,
+) -> Result<opentelemetry_sdk::trace::SdkTracerProvider, opentelemetry_otlp::ExporterBuildError> {
+    use opentelemetry_otlp::WithExportConfig;
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(endpoint)
+        .build()?;
+    Ok(opentelemetry_sdk::trace::SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            opentelemetry_sdk::Resource::builder()
+                .with_service_name("flowrs")
+                .build(),
+        )
+        .build())
+}
+
+/// Name a span's `symbol` field once the message is parsed
+///
+/// No-op without the `otel` feature.
+#[inline(always)]
+pub fn record_symbol(symbol: &str) {
+    #[cfg(feature = "otel")]
+    tracing::Span::current().record("symbol", symbol);
+    #[cfg(not(feature = "otel"))]
+    let _ = symbol;
+}
@@ -0,0 +1,142 @@
+//! Recent trades per symbol, replayed to clients that connect late
+//!
+//! Every exchange's prints for a symbol share one bounded ring, in arrival
+//! order. Rings sit behind their own mutex so recording a BTC trade never
+//! waits on a client reading the ETH tape.
+
+use crate::types::Trade;
+use dashmap::DashMap;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Trades retained per symbol
+pub const TRADE_HISTORY_LEN: usize = 500;
+
+/// Bounded trade tape of every tracked symbol
+#[derive(Debug)]
+pub struct TradeHistory {
+    trades: DashMap<String, Mutex<VecDeque<Trade>>>,
+    capacity: usize,
+}
+
+impl TradeHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            trades: DashMap::new(),
+            capacity,
+        }
+    }
+
+    /// Append a trade, evicting the oldest one of its symbol when full
+    pub fn record(&self, trade: &Trade) {
+        // Read-locks the shard on the hot path; only a new symbol takes the write lock
+        let ring = match self.trades.get(&trade.symbol) {
+            Some(ring) => ring,
+            None => self
+                .trades
+                .entry(trade.symbol.clone())
+                .or_insert_with(|| Mutex::new(VecDeque::with_capacity(self.capacity)))
+                .downgrade(),
+        };
+        let mut ring = ring.lock().unwrap();
+        if ring.len() == self.capacity {
+            ring.pop_front();
+        }
+        ring.push_back(trade.clone());
+    }
+
+    /// Last `limit` trades of a symbol, oldest first
+    pub fn recent(&self, symbol: &str, limit: usize) -> Vec<Trade> {
+        self.trades
+            .get(symbol)
+            .map(|ring| tail(&ring, limit))
+            .unwrap_or_default()
+    }
+
+    /// Last `limit` trades across `symbols` (every symbol when empty), oldest first
+    pub fn latest(&self, symbols: &[String], limit: usize) -> Vec<Trade> {
+        let mut trades: Vec<Trade> = self
+            .trades
+            .iter()
+            .filter(|entry| symbols.is_empty() || symbols.contains(entry.key()))
+            .flat_map(|entry| tail(entry.value(), limit))
+            .collect();
+        trades.sort_by_key(|trade| trade.timestamp);
+        let skip = trades.len().saturating_sub(limit);
+        trades.drain(..skip);
+        trades
+    }
+}
+
+fn tail(ring: &Mutex<VecDeque<Trade>>, limit: usize) -> Vec<Trade> {
+    let ring = ring.lock().unwrap();
+    let skip = ring.len().saturating_sub(limit);
+    ring.iter().skip(skip).cloned().collect()
+}
+
+impl Default for TradeHistory {
+    fn default() -> Self {
+        Self::new(TRADE_HISTORY_LEN)
+    }
+}
+
+/// Shared trade tape
+pub type SharedTradeHistory = Arc<TradeHistory>;
+
+pub fn create_shared_trade_history() -> SharedTradeHistory {
+    Arc::new(TradeHistory::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::TradeSide;
+
+    fn trade(symbol: &str, timestamp: i64) -> Trade {
+        Trade {
+            exchange: "Binance".to_string(),
+            symbol: symbol.to_string(),
+            price: 100,
+            quantity: 1,
+            notional: Trade::notional_of(100, 1),
+            side: TradeSide::Buy,
+            timestamp,
+        }
+    }
+
+    fn timestamps(trades: &[Trade]) -> Vec<i64> {
+        trades.iter().map(|t| t.timestamp).collect()
+    }
+
+    #[test]
+    fn test_ring_keeps_latest_trades_per_symbol() {
+        let history = TradeHistory::new(3);
+        for timestamp in 1..=5 {
+            history.record(&trade("BTCUSDT", timestamp));
+        }
+        history.record(&trade("ETHUSDT", 10));
+
+        assert_eq!(timestamps(&history.recent("BTCUSDT", 100)), vec![3, 4, 5]);
+        assert_eq!(timestamps(&history.recent("BTCUSDT", 2)), vec![4, 5]);
+        assert!(history.recent("SOLUSDT", 10).is_empty());
+    }
+
+    #[test]
+    fn test_latest_merges_symbols_in_time_order() {
+        let history = TradeHistory::new(10);
+        for (symbol, timestamp) in [
+            ("BTCUSDT", 1),
+            ("ETHUSDT", 2),
+            ("BTCUSDT", 3),
+            ("ETHUSDT", 4),
+        ] {
+            history.record(&trade(symbol, timestamp));
+        }
+
+        assert_eq!(timestamps(&history.latest(&[], 3)), vec![2, 3, 4]);
+        assert_eq!(
+            timestamps(&history.latest(&["BTCUSDT".to_string()], 10)),
+            vec![1, 3]
+        );
+    }
+}
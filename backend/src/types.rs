@@ -1,9 +1,12 @@
+use rust_decimal::prelude::ToPrimitive;
 use rust_decimal::Decimal;
-use serde::{Deserialize, Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
 
 // Scale factors for u64 ↔ Decimal conversion (must match orderbook.rs)
 const PRICE_FACTOR: u64 = 100_000_000; // 1e8
 const QTY_FACTOR: u64 = 100_000_000; // 1e8
+const NOTIONAL_FACTOR: u64 = 100_000_000; // 1e8, quote currency
 
 // Custom serializers to convert u64 → Decimal for JSON output
 fn serialize_price<S>(value: &u64, serializer: S) -> Result<S::Ok, S::Error>
@@ -22,13 +25,65 @@ where
     Serialize::serialize(&decimal, serializer)
 }
 
-/// Number of price levels to store in memory (auto-trimmed after each update)
+fn serialize_notional<S>(value: &u64, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let decimal = Decimal::from(*value) / Decimal::from(NOTIONAL_FACTOR);
+    Serialize::serialize(&decimal, serializer)
+}
+
+// Inverse of the serializers above, for reading client frames back (upstream relay)
+fn deserialize_price<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let decimal = <Decimal as Deserialize>::deserialize(deserializer)?;
+    (decimal * Decimal::from(PRICE_FACTOR))
+        .to_u64()
+        .ok_or_else(|| serde::de::Error::custom("price out of range"))
+}
+
+fn deserialize_quantity<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let decimal = <Decimal as Deserialize>::deserialize(deserializer)?;
+    (decimal * Decimal::from(QTY_FACTOR))
+        .to_u64()
+        .ok_or_else(|| serde::de::Error::custom("quantity out of range"))
+}
+
+fn deserialize_notional<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let decimal = <Decimal as Deserialize>::deserialize(deserializer)?;
+    (decimal * Decimal::from(NOTIONAL_FACTOR))
+        .to_u64()
+        .ok_or_else(|| serde::de::Error::custom("notional out of range"))
+}
+
+/// Default number of price levels to store in memory (auto-trimmed after each update)
+///
+/// Independent of the display depth: depth analytics may keep far more levels
+/// than clients are sent (see `OrderBookManager::with_max_depth`). Overridden
+/// at startup by `FLOWRS_BOOK_DEPTH`.
 pub const ORDERBOOK_DEPTH: usize = 25;
 
-/// Number of price levels to send to clients (optimisation)
+/// Default number of price levels to send to clients (optimisation)
 pub const ORDERBOOK_DISPLAY_DEPTH: usize = 3;
 
-/// Trading pairs supported
+/// A book without any update for this long is no longer considered fresh
+pub const BOOK_STALE_AFTER_SECS: u64 = 10;
+
+/// Trades stamped further ahead of the server clock than this are rejected
+pub const MAX_TRADE_FUTURE_SKEW_MS: i64 = 5_000;
+
+/// Trades older than this (e.g. a 0 from a failed parse) are rejected
+pub const MAX_TRADE_AGE_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// Default trading pairs, replaced at startup by `FLOWRS_SYMBOLS` (comma-separated)
 pub const TRADING_PAIRS: &[&str] = &[
     "BTCUSDT", "ETHUSDT", "SOLUSDT", "XRPUSDT", "DOGEUSDT", "ADAUSDT", "AVAXUSDT", "DOTUSDT",
     "LINKUSDT",
@@ -39,6 +94,11 @@ pub const TRADING_PAIRS: &[&str] = &[
 pub struct PriceLevel {
     pub price: Decimal,
     pub quantity: Decimal,
+    /// Milliseconds since this level was last inserted or resized (`level-age` feature,
+    /// 0 from a server built without it)
+    #[cfg(feature = "level-age")]
+    #[serde(default)]
+    pub age_ms: u64,
 }
 
 /// Trade side
@@ -54,19 +114,132 @@ pub enum TradeSide {
 pub struct Trade {
     pub exchange: String,
     pub symbol: String,
-    #[serde(serialize_with = "serialize_price")]
+    #[serde(
+        serialize_with = "serialize_price",
+        deserialize_with = "deserialize_price"
+    )]
     pub price: u64, // Scaled by PRICE_FACTOR (1e8), converted to Decimal on serialization
-    #[serde(serialize_with = "serialize_quantity")]
+    #[serde(
+        serialize_with = "serialize_quantity",
+        deserialize_with = "deserialize_quantity"
+    )]
     pub quantity: u64, // Scaled by QTY_FACTOR (1e8), converted to Decimal on serialization
+    /// price × quantity in the quote currency, computed once at ingest (see `Trade::notional_of`)
+    #[serde(
+        default,
+        serialize_with = "serialize_notional",
+        deserialize_with = "deserialize_notional"
+    )]
+    pub notional: u64, // Scaled by NOTIONAL_FACTOR (1e8), converted to Decimal on serialization
     pub side: TradeSide,
     pub timestamp: i64,
 }
 
+impl Trade {
+    /// Scaled notional of a scaled price and quantity (saturates past ~1.8e11 quote units)
+    #[inline]
+    pub fn notional_of(price: u64, quantity: u64) -> u64 {
+        let notional = price as u128 * quantity as u128 * NOTIONAL_FACTOR as u128
+            / (PRICE_FACTOR as u128 * QTY_FACTOR as u128);
+        notional.min(u64::MAX as u128) as u64
+    }
+}
+
+/// A trade on the consolidated tape (every exchange, timestamp order)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsolidatedTrade {
+    #[serde(flatten)]
+    pub trade: Trade,
+    /// Price of the previous print for the symbol on the tape, from any exchange
+    pub last_price: Option<Decimal>,
+}
+
+/// Candle interval
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CandleInterval {
+    #[serde(rename = "1s")]
+    S1,
+    #[serde(rename = "1m")]
+    M1,
+    #[serde(rename = "5m")]
+    M5,
+    #[serde(rename = "15m")]
+    M15,
+    #[serde(rename = "1h")]
+    H1,
+}
+
+impl CandleInterval {
+    /// Every aggregated interval
+    pub const ALL: [CandleInterval; 5] = [
+        CandleInterval::S1,
+        CandleInterval::M1,
+        CandleInterval::M5,
+        CandleInterval::M15,
+        CandleInterval::H1,
+    ];
+
+    /// Interval length in milliseconds
+    pub fn millis(&self) -> i64 {
+        match self {
+            CandleInterval::S1 => 1_000,
+            CandleInterval::M1 => 60_000,
+            CandleInterval::M5 => 5 * 60_000,
+            CandleInterval::M15 => 15 * 60_000,
+            CandleInterval::H1 => 60 * 60_000,
+        }
+    }
+}
+
+/// `Candle::exchange` of the series merging every exchange's trades
+pub const CONSOLIDATED_EXCHANGE: &str = "Consolidated";
+
+/// OHLCV candle aggregated from one exchange's trades, or every exchange's
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Candle {
+    /// Exchange the trades come from, `CONSOLIDATED_EXCHANGE` for all of them
+    pub exchange: String,
+    pub symbol: String,
+    pub interval: CandleInterval,
+    /// Candle start (ms since epoch, aligned to the interval)
+    pub open_time: i64,
+    #[serde(
+        serialize_with = "serialize_price",
+        deserialize_with = "deserialize_price"
+    )]
+    pub open: u64,
+    #[serde(
+        serialize_with = "serialize_price",
+        deserialize_with = "deserialize_price"
+    )]
+    pub high: u64,
+    #[serde(
+        serialize_with = "serialize_price",
+        deserialize_with = "deserialize_price"
+    )]
+    pub low: u64,
+    #[serde(
+        serialize_with = "serialize_price",
+        deserialize_with = "deserialize_price"
+    )]
+    pub close: u64,
+    #[serde(
+        serialize_with = "serialize_quantity",
+        deserialize_with = "deserialize_quantity"
+    )]
+    pub volume: u64,
+    pub trade_count: u64,
+}
+
 /// Global performance metrics
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Metrics {
     // Per-second rates
     pub messages_per_second: u64,
+    // Breakdown by type: book churn vs trade activity vs control noise
+    pub depth_per_second: u64,
+    pub trades_per_second: u64,
+    pub ignored_per_second: u64,
     pub bytes_per_second: u64,
 
     // Latency stats (in microseconds for precision)
@@ -74,6 +247,16 @@ pub struct Metrics {
     pub latency_p50_us: u64,
     pub latency_p95_us: u64,
     pub latency_p99_us: u64,
+    // Spread of the same sample window as the percentiles
+    #[serde(default)]
+    pub latency_min_us: u64,
+    #[serde(default)]
+    pub latency_max_us: u64,
+    #[serde(default)]
+    pub latency_stddev_us: f64,
+    // Every sample since startup (HdrHistogram), only with the hdr-latency feature
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_lifetime: Option<LifetimeLatency>,
 
     // Totals
     pub total_messages: u64,
@@ -82,32 +265,377 @@ pub struct Metrics {
     pub uptime_seconds: u64,
     pub memory_used_mb: f64,
     pub memory_rss_mb: f64,
+    // Percent of one core: divide by cpu_cores for a 0-100% machine share
     pub cpu_usage_percent: f64,
+    #[serde(default)]
+    pub cpu_cores: u32,
 
     // Connection stats
     pub active_connections: u32,
+    // Clients currently in snapshot-only mode because their sends back up
+    pub degraded_clients: u64,
+    // Clients disconnected because their sends kept timing out (total)
+    #[serde(default)]
+    pub slow_consumer_drops: u64,
+    // Market data messages dropped by publisher sinks: queue full or undelivered (total)
+    #[serde(default)]
+    pub publish_drops: u64,
+    // WebSocket handshakes refused for a missing or wrong auth token (total)
+    #[serde(default)]
+    pub auth_rejections: u64,
+    // Broadcast messages (trades, metrics...) skipped by clients lagging behind (total)
+    #[serde(default)]
+    pub broadcast_lagged: u64,
     pub websocket_reconnects: u64,
 
+    // Book health: fraction of expected exchange × symbol books initialized and fresh
+    pub book_coverage: f64,
+    // Exchange mids flagged as diverging from the cross-exchange median (total)
+    pub price_anomalies: u64,
+    // Trades dropped for a zero, future or far-past timestamp (total)
+    pub rejected_trades: u64,
+    // Top-of-book changes across all books (totals): best price moved vs only its size
+    pub top_price_changes: u64,
+    pub top_size_changes: u64,
+
     // Throughput
     pub bytes_received: u64,
+    // Raw bytes/sec received per exchange (bandwidth attribution)
+    pub exchange_bytes_per_second: BTreeMap<String, u64>,
+    // Breakdown per exchange:symbol stream, sorted by exchange then symbol
+    #[serde(default)]
+    pub streams: Vec<StreamMetrics>,
+}
+
+/// Latency percentiles over every sample since startup
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LifetimeLatency {
+    pub count: u64,
+    pub p50_us: u64,
+    pub p99_us: u64,
+    pub p999_us: u64,
+    pub max_us: u64,
+}
+
+/// Throughput and latency of one exchange:symbol stream
+///
+/// Latency runs from frame receipt to the parsed message reaching the books,
+/// so a lagging exchange stands out from the others.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StreamMetrics {
+    pub exchange: String,
+    pub symbol: String,
+    pub messages_per_second: u64,
+    pub bytes_per_second: u64,
+    pub total_messages: u64,
+    pub latency_p50_us: u64,
+    pub latency_p95_us: u64,
+    pub latency_p99_us: u64,
 }
 
-/// Messages sent to frontend clients
-#[derive(Debug, Clone, Serialize)]
+/// Top-of-book change counts of one symbol, all exchanges combined
+///
+/// A high `size_changes` to `price_changes` ratio means quotes flicker in size
+/// without the price moving.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuoteFlicker {
+    pub price_changes: u64,
+    pub size_changes: u64,
+}
+
+/// Metadata of one tracked symbol
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SymbolInfo {
+    pub symbol: String,
+    /// Exchanges the symbol is tracked on
+    pub exchanges: Vec<String>,
+    /// Finest price increment seen in the symbol's books (None until one is initialized)
+    pub tick_size: Option<Decimal>,
+    /// Quote asset ("USDT" for "BTCUSDT"), None if not a known one
+    pub quote: Option<String>,
+}
+
+/// Status of one exchange:symbol book held by the server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StreamInfo {
+    pub exchange: String,
+    pub symbol: String,
+    /// Has a snapshot and two sides to show
+    pub initialized: bool,
+    /// No update for `BOOK_STALE_AFTER_SECS`
+    pub is_stale: bool,
+}
+
+/// Size-aware prices of one book, for clients that subscribed with a `vwap_quantity`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BookPricing {
+    /// Size the VWAPs are computed for, in base units
+    pub quantity: Decimal,
+    /// Average price selling `quantity` into the bids (None if they are too thin)
+    pub bid_vwap: Option<Decimal>,
+    /// Average price buying `quantity` from the asks (None if they are too thin)
+    pub ask_vwap: Option<Decimal>,
+    /// Mid of the levels sent, weighted by size (see `OrderBook::weighted_mid`)
+    pub weighted_mid: Option<Decimal>,
+}
+
+/// Cost of filling a market order against one book, answering `estimate_fill`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FillEstimate {
+    pub exchange: String,
+    pub symbol: String,
+    /// Buy walks the asks, sell walks the bids
+    pub side: TradeSide,
+    /// Size asked for, in base units
+    pub quantity: Decimal,
+    /// Size the book can absorb: `quantity` unless `insufficient_liquidity`
+    pub filled_quantity: Decimal,
+    pub average_price: Decimal,
+    /// Price of the deepest level touched
+    pub worst_price: Decimal,
+    /// Best price of the side walked, before the order
+    pub best_price: Decimal,
+    /// Distance from `best_price` to `average_price`, always >= 0
+    pub slippage: Decimal,
+    pub slippage_bps: f64,
+    /// The whole side was consumed before `quantity` filled
+    pub insufficient_liquidity: bool,
+}
+
+/// Top-of-book view of one exchange:symbol order book
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookUpdate {
+    pub exchange: String,
+    pub symbol: String,
+    pub bids: Vec<PriceLevel>,
+    pub asks: Vec<PriceLevel>,
+    pub spread: Decimal,
+    pub spread_percent: Decimal,
+    /// `spread_percent` in basis points
+    #[serde(default)]
+    pub spread_bps: Decimal,
+    /// `spread` in price increments of the symbol (None if its tick size isn't known)
+    #[serde(default)]
+    pub spread_ticks: Option<Decimal>,
+    /// Price of the last trade seen for this exchange:symbol (None before the first)
+    #[serde(default)]
+    pub last_trade_price: Option<Decimal>,
+    /// Exchange-native id of the last snapshot or delta applied, as received:
+    /// - Binance: `u` (final update id) of the depth event, `lastUpdateId` of REST snapshots
+    /// - Bybit: `data.u` of the orderbook message
+    /// - Coinbase: `sequence_num` (counts every message on the connection, not just this book)
+    /// - Kraken: `checksum` (CRC32 of the top 10 levels, v2 has no sequence number)
+    /// - OKX: `seqId` of the books message
+    /// - KuCoin: `sequenceEnd` of the level2 message, `sequence` of REST snapshots
+    ///
+    /// Only inequality is meaningful across exchanges: not all of them increase.
+    /// 0 from a server that predates the field.
+    #[serde(default)]
+    pub update_id: u64,
+    /// Size imbalance of the levels sent, -1 (asks only) to 1 (bids only)
+    #[serde(default)]
+    pub imbalance: Option<Decimal>,
+    /// VWAPs and weighted mid, only for clients that asked for them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pricing: Option<BookPricing>,
+    /// No update for `BOOK_STALE_AFTER_SECS`: the feed may be dead, levels are frozen
+    #[serde(default)]
+    pub is_stale: bool,
+}
+
+/// A price level of a consolidated book, tagged with the exchange quoting it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VenueLevel {
+    pub exchange: String,
+    pub price: Decimal,
+    pub quantity: Decimal,
+}
+
+/// Books of one symbol on every exchange merged into a single ladder
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsolidatedBookUpdate {
+    pub symbol: String,
+    /// Best first; the same price quoted on two exchanges gives two levels
+    pub bids: Vec<VenueLevel>,
+    pub asks: Vec<VenueLevel>,
+    /// Highest bid and lowest ask across venues
+    pub best_bid: Option<VenueLevel>,
+    pub best_ask: Option<VenueLevel>,
+    /// best_ask - best_bid across venues: negative when venues cross (arbitrage)
+    pub spread: Option<Decimal>,
+    /// Exchanges with an initialized book, i.e. those merged in
+    pub exchanges: Vec<String>,
+}
+
+/// Messages sent to frontend clients (also read back by a downstream relay)
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", content = "data")]
 #[serde(rename_all = "snake_case")]
 pub enum ClientMessage {
-    BookUpdate {
+    BookUpdate(BookUpdate),
+    /// All books that changed during one server poll tick, sent as a single frame
+    BookBatch(Vec<BookUpdate>),
+    /// Levels of a book that moved since it was last sent, replacing its
+    /// `BookUpdate` for clients that connected with `?book_deltas=1`
+    ///
+    /// Relative to the top levels the client receives: a level pushed out of
+    /// them is removed. Spread, imbalance and pricing are left to the client.
+    BookDelta {
         exchange: String,
         symbol: String,
-        bids: Vec<PriceLevel>,
-        asks: Vec<PriceLevel>,
-        spread: Decimal,
-        spread_percent: Decimal,
+        /// New or resized levels
+        changed_bids: Vec<PriceLevel>,
+        /// Prices of the levels gone
+        removed_bids: Vec<Decimal>,
+        changed_asks: Vec<PriceLevel>,
+        removed_asks: Vec<Decimal>,
+        /// Content version of the book once applied (increases per book)
+        version: u64,
     },
     Trade(Trade),
+    /// Trade released by the client's consolidated tape, replacing `Trade` once opted in
+    ConsolidatedTrade(ConsolidatedTrade),
+    /// Cross-exchange book of a symbol that changed, sent only to clients that opted in
+    ConsolidatedBookUpdate(ConsolidatedBookUpdate),
     Metrics(Metrics),
     SymbolList(Vec<String>),
+    /// Structured counterpart of `SymbolList`, sent right after it (older clients
+    /// keep reading the bare list and ignore this one)
+    SymbolInfo(Vec<SymbolInfo>),
+    /// Every exchange:symbol book the server holds, sorted by exchange then
+    /// symbol; sent on connect and again whenever a book first initializes
+    StreamList(Vec<StreamInfo>),
+    /// Trades dropped by this client's rate limit since the last summary
+    TradesDropped(u64),
+    /// Closed candles answering a `candles` request, oldest first
+    CandleHistory(Vec<Candle>),
+    /// A candle that just closed, sent only to clients that opted in
+    Candle(Candle),
+    /// Recent trades, oldest first: the tape on connect, or a `get_trades` answer
+    TradeHistory(Vec<Trade>),
+    /// Answer to an `estimate_fill` request
+    FillEstimate(FillEstimate),
+    /// A client request was rejected (unknown symbol, malformed command...)
+    Error(String),
+    /// An exchange's mid stayed away from the cross-exchange median past the grace period
+    PriceAnomaly {
+        exchange: String,
+        symbol: String,
+        deviation_bps: f64,
+    },
+    /// A symbol's best bid on one exchange exceeds its best ask on another by
+    /// more than the threshold; sent once per opportunity
+    Arbitrage {
+        symbol: String,
+        /// Exchange with the lowest ask
+        buy_exchange: String,
+        /// Exchange with the highest bid
+        sell_exchange: String,
+        /// Bid - ask, in quote currency
+        spread: Decimal,
+        /// Spread relative to the ask, in percent
+        spread_percent: Decimal,
+    },
+    /// Per-symbol top-of-book change counts, sent only to clients that opted in
+    QuoteFlicker(BTreeMap<String, QuoteFlicker>),
+    /// Answer to a client `ping`; both times are milliseconds since the Unix epoch
+    ///
+    /// RTT = now - client_time; clock offset ≈ server_time - (client_time + RTT / 2).
+    Pong {
+        client_time: i64,
+        server_time: i64,
+    },
+}
+
+fn default_candle_count() -> usize {
+    100
+}
+
+fn default_trade_limit() -> usize {
+    100
+}
+
+/// Commands received from frontend clients
+///
+/// Symbol management is only honored when the server runs with admin commands enabled.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ClientCommand {
+    /// Per-connection delivery options
+    ///
+    /// Options left out of the message keep their current value.
+    Subscribe {
+        /// Hard cap on trade messages per second (excess trades are dropped,
+        /// 0 removes the cap)
+        #[serde(default)]
+        max_trades_per_sec: Option<u32>,
+        /// Send a `TradesDropped` count at most once per second while dropping
+        #[serde(default)]
+        summarize_dropped: Option<bool>,
+        /// Receive the periodic per-symbol `QuoteFlicker` breakdown
+        #[serde(default)]
+        quote_flicker: Option<bool>,
+        /// Receive one timestamp-ordered tape merging every exchange
+        #[serde(default)]
+        consolidated_trades: Option<bool>,
+        /// How long the tape may hold a trade to reorder it (default 250, max 2000)
+        #[serde(default)]
+        reorder_window_ms: Option<u64>,
+        /// Also receive each symbol's books merged across exchanges
+        #[serde(default)]
+        consolidated_books: Option<bool>,
+        /// Receive every candle of every interval as it closes
+        #[serde(default)]
+        candles: Option<bool>,
+        /// Add VWAPs for this size and a weighted mid to book updates (decimal
+        /// string in base units, e.g. "0.5", like every price on the wire; "0"
+        /// turns them off)
+        #[serde(default)]
+        vwap_quantity: Option<Decimal>,
+        /// Only stream books and trades of these symbols (empty: all of them).
+        /// Rejected as a whole if any symbol is unknown.
+        #[serde(default)]
+        symbols: Option<Vec<String>>,
+    },
+    /// Backfill of the last `count` closed candles
+    Candles {
+        /// One exchange's series (None: the consolidated one)
+        #[serde(default)]
+        exchange: Option<String>,
+        symbol: String,
+        interval: CandleInterval,
+        #[serde(default = "default_candle_count")]
+        count: usize,
+    },
+    /// Last `limit` trades of a symbol, from every exchange
+    GetTrades {
+        symbol: String,
+        #[serde(default = "default_trade_limit")]
+        limit: usize,
+    },
+    /// Average price and slippage of a market order of `quantity` on one book
+    EstimateFill {
+        exchange: String,
+        symbol: String,
+        side: TradeSide,
+        /// Decimal string in base units, e.g. "5"
+        quantity: Decimal,
+    },
+    /// Levels per side this connection receives (clamped to the stored depth)
+    SetDepth {
+        levels: usize,
+    },
+    AddSymbol {
+        symbol: String,
+    },
+    RemoveSymbol {
+        symbol: String,
+    },
+    /// Application-level ping (browsers can't send protocol pings)
+    Ping {
+        /// Client clock in milliseconds since the Unix epoch (`Date.now()`), echoed back
+        client_time: i64,
+    },
 }
 
 /// Binance depth update event
@@ -1,9 +1,11 @@
 import {computed, onMounted, onUnmounted, ref} from 'vue'
-import type {BookUpdate, Metrics, ServerMessage, Trade} from '../types'
+import type {BookUpdate, Metrics, ServerMessage, StreamInfo, Trade} from '../types'
 
 const WS_URL = 'ws://localhost:8080/ws'
 const MAX_TRADES = 50
 const RECONNECT_DELAY = 3000
+const CLOSE_PROTOCOL_ERROR = 1002
+const CLOSE_POLICY_VIOLATION = 1008
 
 export function useWebSocket() {
     // Store books per exchange+symbol (key format: "exchange:symbol")
@@ -15,8 +17,10 @@ export function useWebSocket() {
     const connected = ref(false)
     const error = ref<string | null>(null)
     const symbols = ref<string[]>([])
+    // Exchange:symbol books the server holds, and whether they have data yet
+    const streams = ref<StreamInfo[]>([])
     const selectedSymbol = ref<string>('BTCUSDT')
-    const enabledExchanges = ref<Set<string>>(new Set(['Binance', 'Bybit', 'Coinbase', 'Kraken']))
+    const enabledExchanges = ref<Set<string>>(new Set(['Binance', 'Bybit', 'Coinbase', 'Kraken', 'OKX', 'KuCoin']))
 
     let ws: WebSocket | null = null
     let reconnectTimeout: number | null = null
@@ -49,8 +53,14 @@ export function useWebSocket() {
             error.value = null
         }
 
-        ws.onclose = () => {
+        ws.onclose = (event) => {
             connected.value = false
+            // 1002: the server rejected our frames, 1008: our origin is not allowed.
+            // Retrying would fail the same way (unlike 4000, sent when we fell behind)
+            if (event.code === CLOSE_PROTOCOL_ERROR || event.code === CLOSE_POLICY_VIOLATION) {
+                error.value = `Closed by server: ${event.reason}`
+                return
+            }
             scheduleReconnect()
         }
 
@@ -79,6 +89,16 @@ export function useWebSocket() {
                         tradesBySymbol.value[symbol] = []
                     }
                 }
+                // Drop books of symbols the server stopped tracking
+                for (const key of Object.keys(books.value)) {
+                    if (!message.data.includes(key.split(':')[1])) {
+                        delete books.value[key]
+                    }
+                }
+                break
+
+            case 'stream_list':
+                streams.value = message.data
                 break
 
             case 'book_update':
@@ -87,6 +107,13 @@ export function useWebSocket() {
                 books.value[bookKey] = message.data
                 break
 
+            case 'book_batch':
+                // All books changed during one server tick - apply them together
+                for (const update of message.data) {
+                    books.value[`${update.exchange}:${update.symbol}`] = update
+                }
+                break
+
             case 'trade':
                 // Add to all trades
                 allTrades.value = [message.data, ...allTrades.value].slice(0, MAX_TRADES * 2)
@@ -99,6 +126,18 @@ export function useWebSocket() {
                 tradesBySymbol.value[symbol] = [message.data, ...tradesBySymbol.value[symbol]].slice(0, MAX_TRADES)
                 break
 
+            case 'trade_history':
+                // Recent tape (oldest first) replayed on connect: seed the newest-first lists
+                const history = [...message.data].reverse()
+                allTrades.value = history.slice(0, MAX_TRADES * 2)
+                for (const trade of history) {
+                    const symbolTrades = (tradesBySymbol.value[trade.symbol] ??= [])
+                    if (symbolTrades.length < MAX_TRADES) {
+                        symbolTrades.push(trade)
+                    }
+                }
+                break
+
             case 'metrics':
                 metrics.value = message.data
                 break
@@ -169,6 +208,7 @@ export function useWebSocket() {
         allTrades,
         tradesBySymbol,
         symbols,
+        streams,
         selectedSymbol,
         selectSymbol,
         // Exchange filtering
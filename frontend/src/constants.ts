@@ -8,6 +8,7 @@ export const EXCHANGE_COLORS: Record<string, string> = {
     'OKX': '#00c087',      // Official OKX green
     'Coinbase': '#0052ff', // Official Coinbase blue
     'Kraken': '#5741d9',   // Official Kraken purple
+    'KuCoin': '#23af91',   // Official KuCoin teal
 }
 
 /**
@@ -1,6 +1,31 @@
 export interface PriceLevel {
     price: string
     quantity: string
+    // Only present when the backend is built with the `level-age` feature
+    age_ms?: number
+}
+
+// Size-aware prices, present after {action: 'subscribe', vwap_quantity: '0.5'}
+export interface BookPricing {
+    quantity: string
+    bid_vwap: string | null
+    ask_vwap: string | null
+    weighted_mid: string | null
+}
+
+// Answer to {action: 'estimate_fill', exchange, symbol, side: 'buy', quantity: '5'}
+export interface FillEstimate {
+    exchange: string
+    symbol: string
+    side: 'buy' | 'sell'
+    quantity: string
+    filled_quantity: string
+    average_price: string
+    worst_price: string
+    best_price: string
+    slippage: string
+    slippage_bps: number
+    insufficient_liquidity: boolean
 }
 
 export interface BookUpdate {
@@ -10,8 +35,27 @@ export interface BookUpdate {
     asks: PriceLevel[]
     spread: string
     spread_percent: string
+    spread_bps: string
+    // Spread in price increments of the symbol, null if its tick size isn't known
+    spread_ticks: string | null
+    last_trade_price: string | null
+    // Exchange-native id of the last applied update (source per exchange: BookUpdate in types.rs)
+    update_id: number
+    // (bid - ask) / (bid + ask) size over the levels sent: -1 asks only, 1 bids only
+    imbalance: string | null
+    pricing?: BookPricing
     bid_depth: string
     ask_depth: string
+    // No update for BOOK_STALE_AFTER_SECS: the feed may be dead
+    is_stale: boolean
+}
+
+export interface SymbolInfo {
+    symbol: string
+    exchanges: string[]
+    // Finest price increment seen in the books, null until one is initialized
+    tick_size: string | null
+    quote: string | null
 }
 
 export interface Trade {
@@ -19,13 +63,25 @@ export interface Trade {
     symbol: string
     price: string
     quantity: string
+    // price × quantity in the quote currency
+    notional: string
     side: 'buy' | 'sell'
     timestamp: number
 }
 
+// A trade on the consolidated tape: every exchange, in timestamp order
+export interface ConsolidatedTrade extends Trade {
+    // Previous print for the symbol on the tape, from any exchange
+    last_price: string | null
+}
+
 export interface Metrics {
     // Per-second rates
     messages_per_second: number
+    // Breakdown by type: book churn vs trade activity vs control noise
+    depth_per_second: number
+    trades_per_second: number
+    ignored_per_second: number
     bytes_per_second: number
 
     // Latency stats (in microseconds)
@@ -33,6 +89,18 @@ export interface Metrics {
     latency_p50_us: number
     latency_p95_us: number
     latency_p99_us: number
+    // Spread of the same sample window as the percentiles
+    latency_min_us: number
+    latency_max_us: number
+    latency_stddev_us: number
+    // Every sample since startup, only when the backend has the hdr-latency feature
+    latency_lifetime?: {
+        count: number
+        p50_us: number
+        p99_us: number
+        p999_us: number
+        max_us: number
+    }
 
     // Totals
     total_messages: number
@@ -41,18 +109,137 @@ export interface Metrics {
     uptime_seconds: number
     memory_used_mb: number
     memory_rss_mb: number
+    // Percent of one core: divide by cpu_cores for a 0-100% machine share
     cpu_usage_percent: number
+    cpu_cores: number
 
     // Connection stats
     active_connections: number
+    // Clients currently in snapshot-only mode because their sends back up
+    degraded_clients: number
+    // Clients disconnected because their sends kept timing out (total)
+    slow_consumer_drops: number
+    // Market data messages dropped by publisher sinks: queue full or undelivered (total)
+    publish_drops: number
+    // WebSocket handshakes refused for a missing or wrong auth token (total)
+    auth_rejections: number
+    // Broadcast messages (trades, metrics...) skipped by clients lagging behind (total)
+    broadcast_lagged: number
     websocket_reconnects: number
 
+    // Book health (0.0 - 1.0)
+    book_coverage: number
+    // Exchange mids flagged as diverging from the cross-exchange median (total)
+    price_anomalies: number
+    // Trades dropped for a zero, future or far-past timestamp (total)
+    rejected_trades: number
+    // Top-of-book changes (totals): best price moved vs only its size
+    top_price_changes: number
+    top_size_changes: number
+
     // Throughput
     bytes_received: number
+    // Raw bytes/sec received per exchange (bandwidth attribution)
+    exchange_bytes_per_second: Record<string, number>
+    // Breakdown per exchange:symbol stream, sorted by exchange then symbol
+    streams: StreamMetrics[]
+}
+
+// Throughput and latency of one exchange:symbol stream
+export interface StreamMetrics {
+    exchange: string
+    symbol: string
+    messages_per_second: number
+    bytes_per_second: number
+    total_messages: number
+    latency_p50_us: number
+    latency_p95_us: number
+    latency_p99_us: number
+}
+
+export interface Candle {
+    // One exchange's series, or 'Consolidated' for every exchange's trades
+    exchange: string
+    symbol: string
+    interval: '1s' | '1m' | '5m' | '15m' | '1h'
+    open_time: number
+    open: string
+    high: string
+    low: string
+    close: string
+    volume: string
+    trade_count: number
+}
+
+// Top-of-book change counts of one symbol, all exchanges combined
+export interface QuoteFlicker {
+    price_changes: number
+    size_changes: number
+}
+
+// Price level of a consolidated book and the exchange quoting it
+export interface VenueLevel {
+    exchange: string
+    price: string
+    quantity: string
+}
+
+// Books of one symbol on every exchange merged into a single ladder
+export interface ConsolidatedBookUpdate {
+    symbol: string
+    bids: VenueLevel[]
+    asks: VenueLevel[]
+    best_bid: VenueLevel | null
+    best_ask: VenueLevel | null
+    // best_ask - best_bid across venues: negative when venues cross
+    spread: string | null
+    exchanges: string[]
+}
+
+// Levels that moved since the book was last sent; removed_* are prices
+export interface BookDelta {
+    exchange: string
+    symbol: string
+    changed_bids: PriceLevel[]
+    removed_bids: string[]
+    changed_asks: PriceLevel[]
+    removed_asks: string[]
+    version: number
+}
+
+// An exchange:symbol book the server holds
+export interface StreamInfo {
+    exchange: string
+    symbol: string
+    initialized: boolean
+    is_stale: boolean
 }
 
 export type ServerMessage =
     | { type: 'book_update'; data: BookUpdate }
+    | { type: 'book_batch'; data: BookUpdate[] }
+    // Replaces a book's update once it was sent in full, when connected with ?book_deltas=1
+    | { type: 'book_delta'; data: BookDelta }
     | { type: 'trade'; data: Trade }
+    // Sent instead of 'trade' after {action: 'subscribe', consolidated_trades: true}
+    | { type: 'consolidated_trade'; data: ConsolidatedTrade }
+    // Sent after {action: 'subscribe', consolidated_books: true}
+    | { type: 'consolidated_book_update'; data: ConsolidatedBookUpdate }
     | { type: 'metrics'; data: Metrics }
     | { type: 'symbol_list'; data: string[] }
+    | { type: 'symbol_info'; data: SymbolInfo[] }
+    // Sent on connect and again whenever a book first initializes
+    | { type: 'stream_list'; data: StreamInfo[] }
+    | { type: 'trades_dropped'; data: number }
+    | { type: 'candle_history'; data: Candle[] }
+    // Each candle as it closes, after {action: 'subscribe', candles: true}
+    | { type: 'candle'; data: Candle }
+    // Recent trades, oldest first: sent on connect and after {action: 'get_trades', symbol, limit}
+    | { type: 'trade_history'; data: Trade[] }
+    | { type: 'fill_estimate'; data: FillEstimate }
+    | { type: 'error'; data: string }
+    | { type: 'price_anomaly'; data: { exchange: string; symbol: string; deviation_bps: number } }
+    | { type: 'arbitrage'; data: { symbol: string; buy_exchange: string; sell_exchange: string; spread: string; spread_percent: string } }
+    | { type: 'quote_flicker'; data: Record<string, QuoteFlicker> }
+    // Answer to {action: 'ping', client_time}; both times are epoch milliseconds
+    | { type: 'pong'; data: { client_time: number; server_time: number } }